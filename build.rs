@@ -0,0 +1,64 @@
+//! Records the git revision, the compiler and the Postgres client crate
+//! versions the tool is built with, for `Environment`.
+
+use std::{fs, path::Path, process::Command};
+
+/// Crates whose behaviour shows up in the results.
+const CLIENT_CRATES: [&str; 3] = ["postgres", "tokio-postgres", "postgres-types"];
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    // Only a checkout of this repository; built as a dependency, git would
+    // describe whatever repository encloses it.
+    if Path::new(".git").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+        println!("cargo:rerun-if-changed=.git/index");
+        if let Some(mut revision) = output("git", &["rev-parse", "--short=12", "HEAD"]) {
+            let changes = output("git", &["status", "--porcelain", "--untracked-files=no"]);
+            if changes.is_some_and(|changes| !changes.is_empty()) {
+                revision += "-dirty";
+            }
+            println!("cargo:rustc-env=FAST_GENERATION_GIT_REVISION={}", revision);
+        }
+    }
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Some(version) = output(&rustc, &["--version"]) {
+        println!("cargo:rustc-env=FAST_GENERATION_RUSTC_VERSION={}", version);
+    }
+
+    // Cargo.lock is only next to the manifest in a checkout, too.
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let versions: Vec<_> = lock
+        .split("[[package]]")
+        .filter_map(|package| {
+            let field = |key: &str| {
+                package.lines().find_map(|line| {
+                    line.strip_prefix(key)?
+                        .trim()
+                        .strip_prefix("= ")
+                        .map(|value| value.trim_matches('"'))
+                })
+            };
+            let name = field("name")?;
+            if !CLIENT_CRATES.contains(&name) {
+                return None;
+            }
+            Some(format!("{}={}", name, field("version")?))
+        })
+        .collect();
+    println!(
+        "cargo:rustc-env=FAST_GENERATION_CLIENT_CRATES={}",
+        versions.join(",")
+    );
+}
@@ -0,0 +1,46 @@
+/* C interface to the fast_generation binary COPY encoder, built with the
+ * `ffi` cargo feature into libfast_generation. */
+
+#ifndef FAST_GENERATION_H
+#define FAST_GENERATION_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FG_OK 0
+#define FG_INVALID_ARGUMENT -1
+#define FG_ENCODING_ERROR -2
+#define FG_PANIC -3
+
+#define FG_TIMESTAMPTZ 0
+#define FG_TIMESTAMP 1
+#define FG_BIGINT 2
+
+/* `created` is microseconds since the Unix epoch, with INT64_MAX and
+ * INT64_MIN standing for +-infinity. */
+typedef struct {
+    int64_t created;
+    int32_t sensor_id;
+    double temperature;
+} fg_row;
+
+/* Encodes `count` rows as one complete binary COPY stream for
+ * `COPY ... FROM STDIN WITH BINARY`. A `numeric_scale` of -1 leaves
+ * temperatures unscaled. On FG_OK, release `*out` with fg_free. */
+int32_t fg_encode_batch(const fg_row *rows, size_t count, uint32_t time_column,
+                        int32_t numeric_scale, uint8_t **out, size_t *out_len);
+
+void fg_free(uint8_t *buffer, size_t len);
+
+/* Why the last failing call on this thread failed, or NULL. */
+const char *fg_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
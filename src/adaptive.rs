@@ -0,0 +1,83 @@
+//! Batch sizes picked while loading to hold each batch's latency near a
+//! target. Every committed batch suggests the next size: its own, scaled
+//! by the square root of how far its latency was from the target, so that
+//! one slow commit moves the size less than a consistently slow run.
+
+use crate::generator::{GeneratorConfig, BATCH_SIZE};
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Bounds of the sizes picked where --batch-size-min and --batch-size-max
+/// don't set them.
+pub const MIN_ADAPTIVE_BATCH_SIZE: usize = 100;
+pub const MAX_ADAPTIVE_BATCH_SIZE: usize = 1_000_000;
+
+/// Sizes last picked, whose median is the size converged on.
+const CONVERGED_OVER: usize = 16;
+
+pub struct BatchSizer {
+    target: Duration,
+    sizes: RangeInclusive<usize>,
+    state: Mutex<State>,
+}
+
+struct State {
+    current: usize,
+    recent: VecDeque<usize>,
+}
+
+impl BatchSizer {
+    /// Starts at `BATCH_SIZE`, within the generator's batch size bounds if
+    /// set.
+    pub fn new(target: Duration, generator: &GeneratorConfig) -> BatchSizer {
+        let max = generator
+            .batch_size_max
+            .unwrap_or(MAX_ADAPTIVE_BATCH_SIZE.max(generator.batch_size_min.unwrap_or(0)));
+        let min = generator
+            .batch_size_min
+            .unwrap_or(MIN_ADAPTIVE_BATCH_SIZE.min(max));
+        BatchSizer {
+            target,
+            sizes: min..=max,
+            state: Mutex::new(State {
+                current: BATCH_SIZE.clamp(min, max),
+                recent: VecDeque::with_capacity(CONVERGED_OVER),
+            }),
+        }
+    }
+
+    /// Batches `rows` rows may be split into, at the smallest size picked.
+    pub fn most_batches(&self, rows: usize) -> usize {
+        rows.div_ceil(*self.sizes.start())
+    }
+
+    /// Rows to generate the next batch with.
+    pub fn next_size(&self) -> usize {
+        self.state.lock().unwrap().current
+    }
+
+    /// Adjusts the size to a batch of `rows` having taken `latency`.
+    pub fn observe(&self, rows: usize, latency: Duration) {
+        let ratio = (self.target.as_secs_f64() / latency.as_secs_f64().max(1e-6))
+            .sqrt()
+            .clamp(0.5, 2.0);
+        let size =
+            ((rows as f64 * ratio).round() as usize).clamp(*self.sizes.start(), *self.sizes.end());
+        let mut state = self.state.lock().unwrap();
+        state.current = size;
+        if state.recent.len() == CONVERGED_OVER {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(size);
+    }
+
+    /// The median of the sizes last picked; `None` before any batch was
+    /// committed.
+    pub fn converged(&self) -> Option<usize> {
+        let mut recent: Vec<usize> = self.state.lock().unwrap().recent.iter().copied().collect();
+        recent.sort_unstable();
+        recent.get(recent.len() / 2).copied()
+    }
+}
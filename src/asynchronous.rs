@@ -0,0 +1,85 @@
+//! Async counterparts of loading and running, on tokio-postgres, for
+//! embedding in services that already run a tokio runtime.
+
+use crate::generator::Row;
+use crate::observer::Observer;
+use crate::runner::{BenchmarkRunner, StrategyResult};
+use crate::strategy::{Payload, Strategy, StrategyContext};
+use crate::FastGenError;
+use bytes::Bytes;
+use futures_util::{pin_mut, SinkExt};
+use postgres_types::ToSql;
+use tokio_postgres::Client;
+
+/// Sends a payload encoded by any of the built-in `STRATEGIES` the way its
+/// own `send` would; each payload kind belongs to exactly one of them.
+pub async fn send(
+    client: &mut Client,
+    context: &StrategyContext<'_>,
+    payload: Payload<'_>,
+) -> crate::Result<()> {
+    match payload {
+        Payload::Params(rows) => {
+            let tx = client.transaction().await?;
+            let stmt = tx
+                .prepare(&format!(
+                    "INSERT INTO {} VALUES ($1, $2, $3)",
+                    context.table_name
+                ))
+                .await?;
+            for row in &rows {
+                let params: [&(dyn ToSql + Sync); 3] = [&row.0, &row.1, &row.2];
+                tx.execute(&stmt, &params).await?;
+            }
+            tx.commit().await?;
+        }
+        Payload::Query(query) => {
+            client.execute(&query, &[]).await?;
+        }
+        Payload::Copy(buffer) => {
+            let sink = client
+                .copy_in(&format!(
+                    "COPY {} FROM STDIN WITH BINARY",
+                    context.table_name
+                ))
+                .await?;
+            pin_mut!(sink);
+            // `send` flushes, so every chunk goes as a message of its own.
+            let chunk_size = context.copy_chunk_size.unwrap_or(buffer.len()).max(1);
+            for chunk in buffer.chunks(chunk_size) {
+                sink.send(Bytes::copy_from_slice(chunk)).await?;
+            }
+            sink.finish().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Feeds every batch to a built-in `strategy` over one connection. Batches
+/// are encoded on the calling task, a few milliseconds of CPU each, and sent
+/// one after another.
+pub async fn load(
+    strategy: &Strategy,
+    client: &mut Client,
+    context: &StrategyContext<'_>,
+    batches: impl Iterator<Item = (Vec<Row>, i64)>,
+    observer: &dyn Observer,
+) -> crate::Result<()> {
+    for (batch_data, current_tick) in batches {
+        observer.batch_started(strategy.name, current_tick);
+        let payload = (strategy.encode)(context, &batch_data)?;
+        send(client, context, payload).await?;
+        observer.batch_committed(strategy.name, current_tick, batch_data.len());
+    }
+    Ok(())
+}
+
+impl BenchmarkRunner<'static> {
+    /// `run` on a blocking thread of the runtime, so that the measurements
+    /// are those of the synchronous loaders, without stalling other tasks.
+    pub async fn run_async(self) -> crate::Result<Vec<StrategyResult>> {
+        tokio::task::spawn_blocking(move || self.run())
+            .await
+            .map_err(|e| FastGenError::Interrupted(format!("benchmark run: {}", e)))?
+    }
+}
@@ -0,0 +1,203 @@
+//! Checks of what a strategy left in the table once it finished.
+//!
+//! The duplicate audit compares how many rows of each key the table gained
+//! with how many of them the strategy sent, to catch batches loaded twice,
+//! e.g. by retry logic resending one the server had already committed.
+//! Every row of a batch shares one timestamp, so `(created, sensor_id)`
+//! repeats by design; a key only counts as duplicated when the table holds
+//! more rows of it than were generated.
+//!
+//! The scale check samples the temperatures a strategy loaded and compares
+//! the scale each was stored at with the one it should have, since the
+//! server keeps whatever display scale a binary numeric arrives with.
+
+use crate::generator::{Created, Row, TEMPERATURE_SCALE};
+use crate::schema::SchemaConfig;
+use crate::serialize::temperature_text;
+use crate::sql_log;
+use crate::FastGenError;
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A column a duplicate audit keys rows by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditColumn {
+    Created,
+    SensorId,
+    /// Compared by value, rounded to the numeric scale when one is set
+    Temperature,
+}
+
+/// The values of the audited columns; those not audited are `None`.
+type Key = (Option<Created>, Option<i32>, Option<String>);
+
+/// Rows per key.
+#[derive(Default)]
+pub struct KeyCounts(HashMap<Key, i64>);
+
+impl KeyCounts {
+    /// Counts the rows of a batch sent.
+    pub fn add(&mut self, columns: &[AuditColumn], schema: &SchemaConfig, rows: &[Row]) {
+        for row in rows {
+            let key = (
+                columns.contains(&AuditColumn::Created).then_some(row.0),
+                columns.contains(&AuditColumn::SensorId).then_some(row.1),
+                columns
+                    .contains(&AuditColumn::Temperature)
+                    .then(|| temperature_text(schema, row.2)),
+            );
+            *self.0.entry(key).or_default() += 1;
+        }
+    }
+
+    /// Counts the rows the table holds, by a scan of all of them.
+    pub fn of_table(
+        client: &mut Client,
+        table_name: &str,
+        columns: &[AuditColumn],
+        schema: &SchemaConfig,
+    ) -> crate::Result<KeyCounts> {
+        // Typed even when not audited, so every row decodes alike.
+        let column = |column, expression, null| match columns.contains(&column) {
+            true => expression,
+            false => null,
+        };
+        // insert-str's cast to numeric(10, 2) keeps trailing zeros an
+        // unconstrained column then holds, where the other strategies store
+        // none.
+        let temperature = match schema.numeric_scale {
+            Some(_) => "temperature::text",
+            None => "trim_scale(temperature)::text",
+        };
+        let statement = format!(
+            "SELECT {}, {}, {}, count(*) FROM {} GROUP BY 1, 2, 3",
+            column(AuditColumn::Created, "created::text", "NULL::text"),
+            column(AuditColumn::SensorId, "sensor_id", "NULL::int"),
+            column(AuditColumn::Temperature, temperature, "NULL::text"),
+            table_name
+        );
+        sql_log::statement(&statement);
+        let time_column = schema.time_column;
+        let mut counts = KeyCounts::default();
+        for row in client.query(&statement, &[])? {
+            let created = row
+                .get::<_, Option<&str>>(0)
+                .map(|text| {
+                    time_column.parse(text).ok_or_else(|| {
+                        FastGenError::SchemaMismatch(format!(
+                            "created value {:?} isn't a {}",
+                            text,
+                            time_column.data_type()
+                        ))
+                    })
+                })
+                .transpose()?;
+            counts
+                .0
+                .insert((created, row.get(1), row.get(2)), row.get(3));
+        }
+        Ok(counts)
+    }
+}
+
+/// Keys the table gained more rows of than were sent, and the rows sent it
+/// did not gain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateAudit {
+    pub duplicated_keys: usize,
+    /// Rows beyond those sent, over all duplicated keys.
+    pub extra_rows: i64,
+    pub missing_rows: i64,
+}
+
+impl DuplicateAudit {
+    /// Compares what the table gained between `before` and `after` with
+    /// what was `sent`.
+    pub fn new(before: &KeyCounts, after: &KeyCounts, sent: &KeyCounts) -> DuplicateAudit {
+        let mut audit = DuplicateAudit::default();
+        for (key, &count) in &after.0 {
+            let gained = count - before.0.get(key).copied().unwrap_or(0);
+            let expected = sent.0.get(key).copied().unwrap_or(0);
+            if gained > expected {
+                audit.duplicated_keys += 1;
+                audit.extra_rows += gained - expected;
+            }
+        }
+        for (key, &expected) in &sent.0 {
+            let gained =
+                after.0.get(key).copied().unwrap_or(0) - before.0.get(key).copied().unwrap_or(0);
+            audit.missing_rows += (expected - gained).max(0);
+        }
+        audit
+    }
+}
+
+/// Sampled temperatures a strategy loaded, and those not stored at the
+/// scale they should have.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScaleCheck {
+    pub sampled: i64,
+    pub mismatched: i64,
+    /// What the scale should be, e.g. "scale 2".
+    pub expected: String,
+    /// One temperature as stored at the wrong scale, e.g. "16.5700".
+    pub example: Option<String>,
+}
+
+impl ScaleCheck {
+    /// A transaction id no row the strategy loads afterwards predates.
+    pub fn start(client: &mut Client) -> crate::Result<i64> {
+        Ok(client.query_one("SELECT txid_current()", &[])?.get(0))
+    }
+
+    /// Samples up to `sample` finite temperatures committed since `start`
+    /// and checks their scale: the column's when it has one, the two places
+    /// insert-str casts to otherwise, and for other strategies the fewest
+    /// that hold the value, as its shortest decimal text has.
+    pub fn run(
+        client: &mut Client,
+        table_name: &str,
+        schema: &SchemaConfig,
+        strategy: &str,
+        start: i64,
+        sample: usize,
+    ) -> crate::Result<ScaleCheck> {
+        let (expected, expression) = match (schema.numeric_scale, strategy) {
+            (Some(scale), _) => (format!("scale {}", scale), scale.to_string()),
+            (None, "insert-str") => (
+                format!("scale {}", TEMPERATURE_SCALE),
+                TEMPERATURE_SCALE.to_string(),
+            ),
+            (None, _) => (
+                "their shortest scale".to_string(),
+                "min_scale(temperature)".to_string(),
+            ),
+        };
+        // age() counts back from the transaction's own id once it has one,
+        // so rows loaded after `start` are those younger than the distance
+        // from it.
+        let mut transaction = client.transaction()?;
+        let now: i64 = transaction.query_one("SELECT txid_current()", &[])?.get(0);
+        let statement = format!(
+            "SELECT count(*), \
+                    count(*) FILTER (WHERE scale(temperature) <> {expression}), \
+                    min(temperature::text) FILTER (WHERE scale(temperature) <> {expression}) \
+             FROM (SELECT temperature FROM {table_name} \
+                   WHERE age(xmin) < $1 AND scale(temperature) IS NOT NULL \
+                   ORDER BY random() LIMIT $2) sampled"
+        );
+        sql_log::statement(&statement);
+        let since = (now - start) as i32;
+        let row = transaction.query_one(&statement, &[&since, &(sample as i64)])?;
+        transaction.commit()?;
+        Ok(ScaleCheck {
+            sampled: row.get(0),
+            mismatched: row.get(1),
+            expected,
+            example: row.get(2),
+        })
+    }
+}
@@ -0,0 +1,176 @@
+//! Avro encodings of generated rows, for ingest paths that start at Kafka:
+//! the record schema matching the table's columns, the binary encoding of a
+//! row, object container files, and the framing Confluent's serializers put
+//! around a row so connectors can look its schema up in a registry.
+
+use crate::generator::{Row, Timestamp};
+use crate::schema::{temperature_integer_digits, SchemaConfig, TimeColumn};
+use crate::FastGenError;
+use std::io::Write;
+
+/// The first byte of a message in Confluent's wire format.
+const CONFLUENT_MAGIC: u8 = 0;
+/// Rows per block of an object container file.
+const BLOCK_ROWS: usize = 10_000;
+
+/// The Avro schema of a record named `name` with the table's columns, as
+/// JSON. `created` is a `timestamp-micros` long for a timestamptz column,
+/// `local-timestamp-micros` for a timestamp one and a plain long for bigint;
+/// `temperature` is a double, or a decimal at `--numeric-scale`.
+pub fn schema_json(schema: &SchemaConfig, name: &str) -> String {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => r#"{"type":"long","logicalType":"timestamp-micros"}"#,
+        TimeColumn::Timestamp => r#"{"type":"long","logicalType":"local-timestamp-micros"}"#,
+        TimeColumn::Bigint => r#""long""#,
+    };
+    let temperature = match schema.numeric_scale {
+        Some(scale) => format!(
+            r#"{{"type":"bytes","logicalType":"decimal","precision":{},"scale":{}}}"#,
+            temperature_integer_digits() + scale as i32,
+            scale
+        ),
+        None => r#""double""#.to_string(),
+    };
+    format!(
+        r#"{{"type":"record","name":"{}","fields":[{{"name":"created","type":{}}},{{"name":"sensor_id","type":"int"}},{{"name":"temperature","type":{}}}]}}"#,
+        name, created, temperature
+    )
+}
+
+/// Avro's variable-length zig-zag encoding of `int` and `long`.
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
+
+/// Appends the binary encoding of `row` to `out`. Infinite timestamps are
+/// the extremes of a long, as Postgres stores them; a bigint column can't
+/// hold them and a decimal has no NaN or infinities, so those fail.
+pub fn write_row(out: &mut Vec<u8>, schema: &SchemaConfig, row: &Row) -> crate::Result<()> {
+    let created = match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), _) => datetime.timestamp_micros(),
+        (_, TimeColumn::Bigint) => {
+            return Err(FastGenError::Encoding(
+                "a bigint time column cannot store infinite timestamps".to_string(),
+            ))
+        }
+        (Timestamp::PosInfinity, _) => i64::MAX,
+        (Timestamp::NegInfinity, _) => i64::MIN,
+    };
+    write_long(out, created);
+    write_long(out, row.1 as i64);
+    match schema.numeric_scale {
+        Some(scale) => {
+            if !row.2.is_finite() {
+                return Err(FastGenError::Encoding(format!(
+                    "an Avro decimal cannot store a {} temperature",
+                    row.2
+                )));
+            }
+            // Two's complement, big-endian, in as few bytes as keep the sign.
+            let unscaled = (row.2 * 10f64.powi(scale as i32)).round() as i64;
+            let bytes = unscaled.to_be_bytes();
+            let skip = bytes
+                .windows(2)
+                .take_while(|pair| {
+                    (pair[0] == 0 && pair[1] < 0x80) || (pair[0] == 0xff && pair[1] >= 0x80)
+                })
+                .count();
+            write_bytes(out, &bytes[skip..]);
+        }
+        None => out.extend_from_slice(&row.2.to_le_bytes()),
+    }
+    Ok(())
+}
+
+/// A row framed the way Confluent's Avro serializer frames it: a zero byte,
+/// the schema's registry id and the binary encoding.
+pub fn confluent_message(
+    schema_id: u32,
+    schema: &SchemaConfig,
+    row: &Row,
+) -> crate::Result<Vec<u8>> {
+    let mut message = vec![CONFLUENT_MAGIC];
+    message.extend_from_slice(&schema_id.to_be_bytes());
+    write_row(&mut message, schema, row)?;
+    Ok(message)
+}
+
+/// Writes an Avro object container file, uncompressed: the schema in its
+/// header, then the rows in blocks of up to `BLOCK_ROWS`, each ending in the
+/// file's sync marker.
+pub struct AvroFileWriter<W: Write> {
+    writer: W,
+    schema: SchemaConfig,
+    sync: [u8; 16],
+    block: Vec<u8>,
+    rows: usize,
+    bytes: u64,
+}
+
+impl<W: Write> AvroFileWriter<W> {
+    /// Writes the header, with the schema of a record named `name`.
+    pub fn new(mut writer: W, schema: &SchemaConfig, name: &str) -> crate::Result<Self> {
+        let sync: [u8; 16] = rand::random();
+        let mut header = b"Obj\x01".to_vec();
+        write_long(&mut header, 2);
+        write_bytes(&mut header, b"avro.schema");
+        write_bytes(&mut header, schema_json(schema, name).as_bytes());
+        write_bytes(&mut header, b"avro.codec");
+        write_bytes(&mut header, b"null");
+        write_long(&mut header, 0);
+        header.extend_from_slice(&sync);
+        writer.write_all(&header)?;
+        Ok(AvroFileWriter {
+            writer,
+            schema: *schema,
+            sync,
+            block: Vec::new(),
+            rows: 0,
+            bytes: header.len() as u64,
+        })
+    }
+
+    pub fn write_rows(&mut self, rows: &[Row]) -> crate::Result<()> {
+        for row in rows {
+            write_row(&mut self.block, &self.schema, row)?;
+            self.rows += 1;
+            if self.rows == BLOCK_ROWS {
+                self.flush_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> crate::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut prefix = Vec::new();
+        write_long(&mut prefix, self.rows as i64);
+        write_long(&mut prefix, self.block.len() as i64);
+        self.writer.write_all(&prefix)?;
+        self.writer.write_all(&self.block)?;
+        self.writer.write_all(&self.sync)?;
+        self.bytes += (prefix.len() + self.block.len() + self.sync.len()) as u64;
+        self.block.clear();
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the last block and returns the size of the file.
+    pub fn finish(mut self) -> crate::Result<u64> {
+        self.flush_block()?;
+        self.writer.flush()?;
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::generator::{generate_data, GeneratorConfig, Row, BASE_TEMP};
+use crate::pgcopy::{generate_buffer, temperature_to_postgres_binary};
+use crate::schema::SchemaConfig;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub elapsed: Duration,
+}
+
+/// Times the client-side stages of the COPY path on their own, without a
+/// database: generating the batches, encoding them as binary COPY, and the
+/// numeric encoder the schema selects by itself.
+pub fn bench_gen(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batch_count: usize,
+) -> crate::Result<(usize, Vec<StageTiming>)> {
+    let started = Instant::now();
+    let batches: Vec<Vec<Row>> = generate_data(generator, BASE_TEMP, batch_count)
+        .map(|(batch_data, _)| batch_data)
+        .collect();
+    let generate = started.elapsed();
+    let rows = batches.iter().map(Vec::len).sum();
+
+    let started = Instant::now();
+    for batch_data in &batches {
+        black_box(generate_buffer(schema, batch_data)?);
+    }
+    let encode = started.elapsed();
+
+    let started = Instant::now();
+    for row in batches.iter().flatten() {
+        black_box(temperature_to_postgres_binary(schema, black_box(row.2)));
+    }
+    let numeric = started.elapsed();
+
+    Ok((
+        rows,
+        vec![
+            StageTiming {
+                stage: "generate_batch",
+                elapsed: generate,
+            },
+            StageTiming {
+                stage: "generate_buffer",
+                elapsed: encode,
+            },
+            StageTiming {
+                stage: if schema.numeric_scale.is_some() {
+                    "fixed_numeric_to_postgres_binary"
+                } else {
+                    "numeric_to_postgres_binary"
+                },
+                elapsed: numeric,
+            },
+        ],
+    ))
+}
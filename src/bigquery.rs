@@ -0,0 +1,286 @@
+//! `sink bigquery`: appends each batch to a BigQuery table through the
+//! Storage Write API, as one AppendRows request on the table's default
+//! stream with the rows encoded as the message `protobuf::schema_proto`
+//! declares, and waits for a batch to be appended before sending the next.
+
+use crate::cli::BigqueryArgs;
+use crate::sink::Sink;
+use fast_generation::generator::Row;
+use fast_generation::protobuf;
+use fast_generation::schema::SchemaConfig;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{self, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::client::Grpc;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Channel, ClientTlsConfig};
+use tonic::{Request, Streaming};
+use tonic_prost::ProstCodec;
+
+const APPEND_ROWS: &str = "/google.cloud.bigquery.storage.v1.BigQueryWrite/AppendRows";
+
+/// The parts of the messages of google/cloud/bigquery/storage/v1 used;
+/// fields left out are skipped when decoding.
+#[derive(Clone, PartialEq, prost::Message)]
+struct AppendRowsRequest {
+    #[prost(string, tag = "1")]
+    write_stream: String,
+    /// One of the `rows` oneof, which encodes as a plain field.
+    #[prost(message, optional, tag = "4")]
+    proto_rows: Option<ProtoData>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoData {
+    /// Only needed in the first request on a connection.
+    #[prost(message, optional, tag = "1")]
+    writer_schema: Option<ProtoSchema>,
+    #[prost(message, optional, tag = "2")]
+    rows: Option<ProtoRows>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoSchema {
+    #[prost(message, optional, tag = "1")]
+    proto_descriptor: Option<DescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoRows {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    serialized_rows: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AppendRowsResponse {
+    #[prost(message, optional, tag = "2")]
+    error: Option<RpcStatus>,
+    #[prost(message, repeated, tag = "4")]
+    row_errors: Vec<RowError>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct RowError {
+    #[prost(int64, tag = "1")]
+    index: i64,
+    #[prost(string, tag = "3")]
+    message: String,
+}
+
+/// The descriptor of the message `protobuf::schema_proto` declares, which
+/// BigQuery maps onto the table's columns by name.
+fn descriptor(schema: &SchemaConfig, name: &str) -> DescriptorProto {
+    let field = |name: &str, number: i32, field_type: Type| FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        label: Some(Label::Optional as i32),
+        r#type: Some(field_type as i32),
+        ..Default::default()
+    };
+    let temperature = if schema.numeric_scale.is_some() {
+        Type::String
+    } else {
+        Type::Double
+    };
+    DescriptorProto {
+        name: Some(name.to_string()),
+        field: vec![
+            field("created", 1, Type::Int64),
+            field("sensor_id", 2, Type::Int32),
+            field("temperature", 3, temperature),
+        ],
+        ..Default::default()
+    }
+}
+
+/// An AppendRows stream, opened with the first batch.
+struct Append {
+    requests: Sender<AppendRowsRequest>,
+    responses: Streaming<AppendRowsResponse>,
+}
+
+/// Appends to the default stream of a table.
+struct Writer {
+    grpc: Grpc<Channel>,
+    token: Option<String>,
+    write_stream: String,
+    descriptor: DescriptorProto,
+    append: Option<Append>,
+}
+
+impl Writer {
+    /// Opens the stream, with `first` as its first request: the response
+    /// headers may only come once there is one.
+    async fn open(&mut self, first: AppendRowsRequest) -> anyhow::Result<Append> {
+        let (requests, queue) = mpsc::channel(1);
+        requests.send(first).await?;
+        let mut request = Request::new(ReceiverStream::new(queue));
+        let metadata = request.metadata_mut();
+        if let Some(token) = &self.token {
+            metadata.insert(
+                "authorization",
+                MetadataValue::try_from(format!("Bearer {}", token))?,
+            );
+        }
+        // Routes the stream to the table's region.
+        metadata.insert(
+            "x-goog-request-params",
+            MetadataValue::try_from(format!(
+                "write_stream={}",
+                self.write_stream.replace('/', "%2F")
+            ))?,
+        );
+        self.grpc.ready().await?;
+        let responses = self
+            .grpc
+            .streaming(
+                request,
+                PathAndQuery::from_static(APPEND_ROWS),
+                ProstCodec::default(),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("appending to {}: {}", self.write_stream, e.message()))?
+            .into_inner();
+        Ok(Append {
+            requests,
+            responses,
+        })
+    }
+
+    /// Appends `serialized_rows` and waits for them to be appended.
+    async fn append(&mut self, serialized_rows: Vec<Vec<u8>>) -> anyhow::Result<()> {
+        let rows = Some(ProtoRows { serialized_rows });
+        let append = match &mut self.append {
+            Some(append) => {
+                let request = AppendRowsRequest {
+                    write_stream: String::new(),
+                    proto_rows: Some(ProtoData {
+                        writer_schema: None,
+                        rows,
+                    }),
+                };
+                append.requests.send(request).await.map_err(|_| {
+                    anyhow::anyhow!("appending to {}: stream closed", self.write_stream)
+                })?;
+                append
+            }
+            None => {
+                let request = AppendRowsRequest {
+                    write_stream: self.write_stream.clone(),
+                    proto_rows: Some(ProtoData {
+                        writer_schema: Some(ProtoSchema {
+                            proto_descriptor: Some(self.descriptor.clone()),
+                        }),
+                        rows,
+                    }),
+                };
+                let append = self.open(request).await?;
+                self.append.insert(append)
+            }
+        };
+        let response = append
+            .responses
+            .message()
+            .await
+            .map_err(|e| anyhow::anyhow!("appending to {}: {}", self.write_stream, e.message()))?
+            .ok_or_else(|| anyhow::anyhow!("appending to {}: stream closed", self.write_stream))?;
+        if let Some(error) = response.error {
+            anyhow::bail!(
+                "appending to {}: {} (code {})",
+                self.write_stream,
+                error.message,
+                error.code
+            );
+        }
+        if let Some(error) = response.row_errors.first() {
+            anyhow::bail!(
+                "appending to {}: {} rows rejected, row {} of a batch with: {}",
+                self.write_stream,
+                response.row_errors.len(),
+                error.index,
+                error.message
+            );
+        }
+        Ok(())
+    }
+}
+
+pub struct BigquerySink {
+    runtime: Runtime,
+    writer: Writer,
+    schema: SchemaConfig,
+    bytes: u64,
+}
+
+impl BigquerySink {
+    /// Appends to `--table`, or else `name`.
+    pub fn new(
+        args: &BigqueryArgs,
+        schema: &SchemaConfig,
+        name: &str,
+    ) -> anyhow::Result<BigquerySink> {
+        let table = args.table.as_deref().unwrap_or(name);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let channel = runtime.block_on(async {
+            let mut endpoint = Channel::from_shared(args.endpoint.clone())?;
+            if args.endpoint.starts_with("https://") {
+                endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+            }
+            endpoint.connect().await.map_err(|e| {
+                // A transport error only says what failed in its source.
+                let cause = std::error::Error::source(&e)
+                    .map(|cause| format!(": {}", cause))
+                    .unwrap_or_default();
+                anyhow::anyhow!("connecting to {}: {}{}", args.endpoint, e, cause)
+            })
+        })?;
+        Ok(BigquerySink {
+            runtime,
+            writer: Writer {
+                grpc: Grpc::new(channel),
+                token: args
+                    .token
+                    .clone()
+                    .or_else(|| std::env::var("BIGQUERY_TOKEN").ok()),
+                write_stream: format!(
+                    "projects/{}/datasets/{}/tables/{}/streams/_default",
+                    args.project, args.dataset, table
+                ),
+                descriptor: descriptor(schema, table),
+                append: None,
+            },
+            schema: *schema,
+            bytes: 0,
+        })
+    }
+}
+
+impl Sink for BigquerySink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let mut serialized_rows = Vec::with_capacity(batch.len());
+        for row in batch {
+            let mut payload = Vec::new();
+            protobuf::write_row(&mut payload, &self.schema, row)?;
+            self.bytes += payload.len() as u64;
+            serialized_rows.push(payload);
+        }
+        self.runtime.block_on(self.writer.append(serialized_rows))
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,46 @@
+/// Values an `EncodeCache` keeps at most.
+const CAPACITY: usize = 64;
+
+/// Encoded forms of values a batch repeats, found by linear scan: a batch
+/// holds one timestamp, plus the infinities, and a few dozen sensor ids, so
+/// scanning beats hashing.
+///
+/// At most `CAPACITY` values are kept; once full, the last one is replaced,
+/// so rows read from a file with many distinct values cost a bounded scan.
+pub struct EncodeCache<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> Default for EncodeCache<K, V> {
+    fn default() -> Self {
+        EncodeCache {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<K: PartialEq + Copy, V> EncodeCache<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached encoding of `key`, running `encode` on the first lookup.
+    pub fn get_or_try_insert<E>(
+        &mut self,
+        key: K,
+        encode: impl FnOnce(K) -> Result<V, E>,
+    ) -> Result<&V, E> {
+        let index = match self.entries.iter().position(|(cached, _)| *cached == key) {
+            Some(index) => index,
+            None => {
+                let entry = (key, encode(key)?);
+                if self.entries.len() == CAPACITY {
+                    self.entries.pop();
+                }
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+        Ok(&self.entries[index].1)
+    }
+}
@@ -0,0 +1,81 @@
+//! The `capacity` command: loads with one strategy at a rising target rate,
+//! a fixed time per step, until a step fails, falls short of its target or
+//! gets too slow per batch, and reports the highest rate that held.
+
+use crate::cli::CapacityArgs;
+use fast_generation::shape::{LoadShape, Shape};
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig};
+use postgres::Config;
+use std::time::Duration;
+
+pub fn capacity(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    args: &CapacityArgs,
+) -> anyhow::Result<()> {
+    if !(0.0..=1.0).contains(&args.min_achieved) {
+        anyhow::bail!("--min-achieved must be between 0 and 1");
+    }
+    let max_p99 = Duration::from_millis(args.max_p99_ms);
+    let mean_batch = generator.expected_rows(1).max(1) as u64;
+
+    let mut sustained = None;
+    for step in 0..args.max_steps {
+        let rate = args.start_rate + args.step_rate * step as u64;
+        let run = RunConfig {
+            strategies: vec![args.strategy.clone()],
+            table_name: table_name.to_string(),
+            batch_count: (rate * args.step_secs).div_ceil(mean_batch) as usize,
+            workers: args.workers,
+            shape: Some(LoadShape {
+                shape: Shape::Steady,
+                rate,
+                min_rate: 0,
+                period_secs: args.step_secs,
+            }),
+            ..RunConfig::default()
+        };
+        let results = BenchmarkRunner::new(config.clone())
+            .with_generator(*generator)
+            .with_schema(*schema)
+            .with_config(run)
+            .run()?;
+        let result = &results[0];
+
+        let achieved = result.rows_per_second();
+        let p99 = result.latency.map_or(Duration::ZERO, |latency| latency.p99);
+        let shortfall = if let Some(e) = &result.error {
+            Some(format!("failed: {}", e))
+        } else if achieved < rate as f64 * args.min_achieved {
+            Some("fell behind".to_string())
+        } else if p99 > max_p99 {
+            Some(format!("p99 over {}ms", args.max_p99_ms))
+        } else {
+            None
+        };
+        println!(
+            "Step {}: target {} rows/s, achieved {:.0} rows/s, p99 {:.1}ms, {}",
+            step + 1,
+            rate,
+            achieved,
+            p99.as_secs_f64() * 1000.0,
+            shortfall.as_deref().unwrap_or("sustained")
+        );
+        if shortfall.is_some() {
+            break;
+        }
+        sustained = Some((rate, achieved));
+    }
+
+    println!();
+    match sustained {
+        Some((rate, achieved)) => println!(
+            "Maximum sustainable: {:.0} rows/s (target {}) with {} on {} worker(s)",
+            achieved, rate, args.strategy, args.workers
+        ),
+        None => println!("Not even the first step was sustained"),
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! `run --distributed citus`: makes the table a Citus distributed table
+//! sharded by `sensor_id`, splits every batch into the rows each worker
+//! node holds, so that a COPY or INSERT goes to one node's shards rather
+//! than fanning out from the coordinator to all of them, and reports how
+//! evenly the rows ended up spread over the nodes.
+
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::generator::{Row, MAX_SENSORS};
+use postgres::Client;
+
+/// The distribution column; the only one with few enough distinct values
+/// to co-locate a batch's rows.
+const DISTRIBUTION_COLUMN: &str = "sensor_id";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Distribution {
+    /// create_distributed_table on a Citus coordinator
+    Citus,
+}
+
+pub struct Citus {
+    /// HOST:PORT of each worker node holding shards of the table.
+    nodes: Vec<String>,
+    /// Index into `nodes` of each sensor's shard, by sensor_id.
+    node_of_sensor: Vec<usize>,
+}
+
+impl Citus {
+    /// Distributes `table_name` by sensor_id over `shard_count` shards, or
+    /// citus.shard_count when unset, unless it is distributed already, and looks up the
+    /// node each sensor's rows go to.
+    pub fn distribute(
+        client: &mut Client,
+        table_name: &str,
+        shard_count: Option<u32>,
+    ) -> anyhow::Result<Citus> {
+        let installed = client
+            .query_opt(
+                "SELECT extversion FROM pg_extension WHERE extname = 'citus'",
+                &[],
+            )?
+            .is_some();
+        if !installed {
+            anyhow::bail!("--distributed citus needs the citus extension on the coordinator");
+        }
+        let column: Option<String> = client
+            .query_opt(
+                "SELECT column_to_column_name(logicalrelid, partkey)
+                 FROM pg_dist_partition WHERE logicalrelid = $1::text::regclass",
+                &[&table_name],
+            )?
+            .map(|row| row.get(0));
+        match column.as_deref() {
+            Some(DISTRIBUTION_COLUMN) => {
+                println!(
+                    "{} is distributed by {} already",
+                    table_name, DISTRIBUTION_COLUMN
+                )
+            }
+            Some(column) => anyhow::bail!(
+                "{} is distributed by {} rather than {}",
+                table_name,
+                column,
+                DISTRIBUTION_COLUMN
+            ),
+            None => {
+                client.execute(
+                    "SELECT create_distributed_table($1::text::regclass, $2, shard_count => $3)",
+                    &[
+                        &table_name,
+                        &DISTRIBUTION_COLUMN,
+                        &shard_count.map(|count| count as i32),
+                    ],
+                )?;
+                println!("Distributed {} by {}", table_name, DISTRIBUTION_COLUMN);
+            }
+        }
+
+        // A shard with replicas goes to its first placement's node.
+        let placements = client.query(
+            "SELECT DISTINCT ON (sensor_id) sensor_id, nodename || ':' || nodeport
+             FROM generate_series(1, $2) AS sensor_id
+             JOIN citus_shards ON shardid = get_shard_id_for_distribution_column($1::text::regclass, sensor_id)
+             ORDER BY sensor_id, nodename, nodeport",
+            &[&table_name, &MAX_SENSORS],
+        )?;
+        let placements: Vec<(i32, String)> = placements
+            .into_iter()
+            .map(|placement| (placement.get(0), placement.get(1)))
+            .collect();
+        let mut nodes: Vec<String> = placements.iter().map(|(_, node)| node.clone()).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        let mut node_of_sensor = vec![0; MAX_SENSORS as usize + 1];
+        for (sensor_id, node) in &placements {
+            node_of_sensor[*sensor_id as usize] = nodes.binary_search(node).unwrap();
+        }
+        println!("Shards on {} node(s): {}", nodes.len(), nodes.join(", "));
+        Ok(Citus {
+            nodes,
+            node_of_sensor,
+        })
+    }
+
+    /// The batch's rows grouped by the node they go to, each group in the
+    /// batch's order; nodes without rows are left out.
+    pub fn split(&self, batch: &[Row]) -> Vec<Vec<Row>> {
+        let mut parts = vec![Vec::new(); self.nodes.len()];
+        for &row in batch {
+            parts[self.node_of_sensor[row.1 as usize]].push(row);
+        }
+        parts.retain(|part| !part.is_empty());
+        parts
+    }
+}
+
+/// Prints the rows and shard sizes of each node, a replicated shard counting
+/// on every node holding it, and how far the busiest node is above the
+/// average.
+pub fn print_balance(client: &mut Client, table_name: &str) -> anyhow::Result<()> {
+    let nodes = client.query(
+        "SELECT nodename || ':' || nodeport, count(*), sum(result::bigint)::bigint,
+                sum(shard_size)::bigint
+         FROM run_command_on_shards($1::text::regclass, 'SELECT count(*) FROM %s')
+         JOIN citus_shards USING (shardid)
+         WHERE success
+         GROUP BY 1 ORDER BY 1",
+        &[&table_name],
+    )?;
+    let total: i64 = nodes.iter().map(|node| node.get::<_, i64>(2)).sum();
+    println!();
+    println!(
+        "{:<24} {:>7} {:>12} {:>7} {:>12}",
+        "node", "shards", "rows", "share", "size"
+    );
+    for node in &nodes {
+        let rows: i64 = node.get(2);
+        println!(
+            "{:<24} {:>7} {:>12} {:>6.1}% {:>12}",
+            node.get::<_, String>(0),
+            node.get::<_, i64>(1),
+            rows,
+            100.0 * rows as f64 / total.max(1) as f64,
+            units::bytes(node.get::<_, i64>(3) as f64)
+        );
+    }
+    let busiest = nodes.iter().map(|node| node.get::<_, i64>(2)).max();
+    if let (Some(busiest), false) = (busiest, total == 0) {
+        let mean = total as f64 / nodes.len() as f64;
+        println!(
+            "Busiest node holds {:.1}% more rows than the average",
+            100.0 * (busiest as f64 / mean - 1.0)
+        );
+    }
+    Ok(())
+}
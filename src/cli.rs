@@ -0,0 +1,1523 @@
+use crate::citus::Distribution;
+#[cfg(feature = "cql")]
+use crate::cql::Consistency;
+use crate::foreign_keys::ForeignKey;
+#[cfg(feature = "http")]
+use crate::http::BodyFormat;
+use crate::indexes::IndexSet;
+#[cfg(feature = "mssql")]
+use crate::mssql::LoadMethod;
+#[cfg(feature = "nats")]
+use crate::nats::MessageFormat;
+use crate::overhead::TableFeatures;
+use crate::partitions::Granularity;
+use crate::roles::Privileges;
+#[cfg(feature = "upload")]
+use crate::s3::ObjectFormat;
+#[cfg(all(feature = "upload", feature = "http"))]
+use crate::stage::TokenType;
+use crate::storage::Compression;
+use crate::tables::{parse_table_spec, TableSpec};
+use crate::tenants::Layout;
+use crate::units::Units;
+use clap::builder::PossibleValuesParser;
+use clap::{Args, Parser, Subcommand};
+use fast_generation::audit::AuditColumn;
+use fast_generation::connection::ConnectionConfig;
+use fast_generation::export::{Codec, ExportFormat, Partitioning};
+use fast_generation::generator::GeneratorConfig;
+use fast_generation::read::ResultFormat;
+use fast_generation::runner::StrategyOrder;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::shape::{LoadShape, Shape};
+use fast_generation::{RunConfig, STRATEGIES};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(about = "Benchmarks bulk loading of synthetic sensor data into Postgres")]
+pub struct Cli {
+    #[command(flatten)]
+    pub generator: GeneratorConfig,
+
+    #[command(flatten)]
+    pub schema: SchemaConfig,
+
+    #[command(flatten)]
+    pub connection: ConnectionConfig,
+
+    /// Units byte sizes and rates are reported in, each scaled to its value
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub units: Units,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Load generated data with every strategy and report throughput (default)
+    Run(RunArgs),
+    /// Print a sample of generated rows without touching the database
+    Preview {
+        /// Number of rows to print
+        #[arg(long, default_value_t = 20)]
+        rows: usize,
+        /// Also print the binary COPY encoding of each row
+        #[arg(long)]
+        hex: bool,
+        /// Encode the sample as binary COPY, decode it again and compare
+        #[arg(long)]
+        round_trip: bool,
+    },
+    /// Time COPY ... TO STDOUT of the loaded table in each format
+    Export {
+        /// Formats to export, in order
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [ExportFormat::Text, ExportFormat::Csv, ExportFormat::Binary])]
+        formats: Vec<ExportFormat>,
+        /// Write each export to this directory instead of discarding it
+        #[arg(long, conflicts_with = "decode")]
+        output_dir: Option<PathBuf>,
+        /// Split each export into a Hive-style directory per day or hour of
+        /// `created` under --output-dir, for DuckDB, Athena and the like to
+        /// query in place; CSV and text only
+        #[arg(long, value_enum, requires = "output_dir")]
+        partition_by: Option<Partitioning>,
+        /// Start another file in a partition once the current one reaches
+        /// this size on disk, e.g. 256MB; compressed files run over by what
+        /// the compressor still holds, tens of KB
+        #[arg(long, value_parser = parse_size, requires = "partition_by")]
+        max_file_size: Option<usize>,
+        /// Compress the files written to --output-dir
+        #[arg(long, value_enum, value_name = "CODEC", requires = "output_dir")]
+        compress: Option<Codec>,
+        /// Compression level: 0-9 for gzip, 1-22 for zstd [default: 6 for gzip, 3 for zstd]
+        #[arg(long, requires = "compress")]
+        compression_level: Option<i32>,
+        /// Parse binary exports into rows, timing client-side decoding too
+        #[arg(long)]
+        decode: bool,
+        /// Upload the exported files to this S3 or GCS URI afterwards, e.g. s3://bench-results/exports
+        #[cfg(feature = "upload")]
+        #[arg(long, value_name = "URI", requires = "output_dir")]
+        upload: Option<String>,
+    },
+    /// Time dumping the loaded table to a file and restoring it into a copy
+    DumpRestore {
+        /// COPY format of the dump
+        #[arg(long, value_enum, default_value_t = ExportFormat::Binary)]
+        format: ExportFormat,
+        /// Directory the dump file is written to [default: the system temp directory]
+        #[arg(long)]
+        dump_dir: Option<PathBuf>,
+        /// Table the dump is restored into; it is dropped and recreated
+        #[arg(long, default_value = "metrics_restore")]
+        target_table: String,
+        /// Keep the dump file and the restored table afterwards
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Time reading the loaded table back with binary and text result formats
+    Read {
+        /// Result formats to compare, in order
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [ResultFormat::Binary, ResultFormat::Text])]
+        formats: Vec<ResultFormat>,
+        /// Rows fetched from the cursor per round trip
+        #[arg(long, default_value_t = 10_000)]
+        fetch_size: usize,
+    },
+    /// Time row generation and binary encoding alone, without a database
+    BenchGen {
+        /// Rows to generate, rounded up to whole batches
+        #[arg(long, default_value_t = 5_000_000)]
+        rows: usize,
+    },
+    /// Accept runs over an HTTP JSON API instead of running one now
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+    },
+    /// Run on several hosts running `serve` at once and report their combined throughput
+    #[cfg(feature = "serve")]
+    Coordinate {
+        /// Base URL of a host running `serve`, e.g. http://bench1:7878; repeatable
+        #[arg(long = "node", value_name = "URL", required = true)]
+        nodes: Vec<String>,
+
+        #[command(flatten)]
+        load: LoadArgs,
+    },
+    /// Print Kubernetes ConfigMap and Job YAML running this scenario on several pods at once
+    K8sManifest(ManifestArgs),
+    /// Raise the target rate step by step until loading falls behind or slows
+    /// down, and report the highest rate sustained
+    Capacity(CapacityArgs),
+    /// Load into the primary until a hot standby is promoted, then into the
+    /// standby, and report the gap in ingestion and any rows lost
+    Failover(FailoverArgs),
+    /// Create a copy of the table per tenant and load into all of them at
+    /// once, busier tenants taking more batches
+    Tenants(TenantsArgs),
+    /// Load into several tables at once, each with a layout, strategy and
+    /// workers of its own, and report the throughput of each and of all
+    Tables(TablesArgs),
+    /// Repeat the same load with a different set of indexes each time and
+    /// report the throughput and index size of each
+    Indexes(IndexesArgs),
+    /// Repeat the same load for every combination of fillfactor,
+    /// toast_tuple_target and compression given, and report each
+    Storage(StorageArgs),
+    /// Load the same data into a table partitioned by each interval in turn
+    /// and report the throughput and size of each
+    Partitions(PartitionsArgs),
+    /// Repeat the same load with a generated column, a trigger, both or
+    /// neither on the table and report what each costs
+    Overhead(OverheadArgs),
+    /// Load with a foreign key to a sensors table enforced, dropped or
+    /// validated afterwards and compare the cost of each
+    ForeignKeys(ForeignKeysArgs),
+    /// Load as the connecting user without row-level security, then as a
+    /// non-superuser role with a policy, and compare
+    Rls(RlsArgs),
+    /// Load into a table directly, then through a postgres_fdw foreign
+    /// table pointing at it with each batch_size, and compare
+    Fdw(FdwArgs),
+    /// Replicate a table to a subscriber database with logical replication
+    /// and report how fast it applies what each strategy loads
+    Replication(ReplicationArgs),
+    /// Run the same loads through several connection paths to one cluster,
+    /// e.g. direct, pooled and proxied, and report what each hop adds
+    Paths(PathsArgs),
+    /// Load a few batches under several client TimeZone settings, read them
+    /// back in each and check every timestamp is the UTC one generated
+    TimeZones(TimeZonesArgs),
+    /// Load with several strategies in the same window, alternating batch
+    /// by batch into a table each, so background work hits all of them alike
+    Interleave(InterleaveArgs),
+    /// Load a recorded file with COPY straight from its memory-mapped
+    /// pages, without parsing or encoding its rows, and report throughput
+    Replay(ReplayArgs),
+    /// Sample an existing table and print generator settings producing
+    /// data shaped like it
+    Profile(ProfileArgs),
+    /// Copy related tables with keys replaced by pseudonyms that still match
+    /// across them and chosen columns by fakes, to seed staging environments
+    Fixtures(FixturesArgs),
+    /// Send the rows a run loads somewhere other than Postgres and report
+    /// throughput, to compare other ingest paths on the same data
+    Sink {
+        /// Rows per second to send at most [default: as fast as the sink
+        /// takes them]
+        #[arg(long)]
+        rate: Option<u64>,
+        #[command(subcommand)]
+        target: SinkTarget,
+    },
+    /// Upload the rows to S3 or GCS as `sink s3` does, then load them into a
+    /// cloud warehouse with its COPY, timing both, to compare warehouse
+    /// ingest with Postgres on the same data
+    #[cfg(feature = "upload")]
+    Stage {
+        #[command(subcommand)]
+        warehouse: Warehouse,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SinkTarget {
+    /// Write an Avro object container file
+    Avro {
+        /// File to write
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Produce Avro messages to a Kafka topic, one per row keyed by sensor
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaArgs),
+    /// Write InfluxDB line protocol to a file or a write endpoint
+    Influx(InfluxArgs),
+    /// Add rows to a Redis stream, or to a RedisTimeSeries series per sensor
+    #[cfg(feature = "redis")]
+    Redis(RedisArgs),
+    /// Publish rows to NATS JetStream on a subject per sensor
+    #[cfg(feature = "nats")]
+    Nats(NatsArgs),
+    /// POST batches to an HTTP ingestion API
+    #[cfg(feature = "http")]
+    Http(HttpArgs),
+    /// Stream CSV or Parquet files to S3 or GCS in multipart uploads, with
+    /// nothing staged on disk
+    #[cfg(feature = "upload")]
+    S3(S3Args),
+    /// Append to a BigQuery table through the Storage Write API
+    #[cfg(feature = "bigquery")]
+    Bigquery(BigqueryArgs),
+    /// Insert documents into a MongoDB collection, an insertMany per batch
+    #[cfg(feature = "mongodb")]
+    Mongodb(MongodbArgs),
+    /// Insert into a Cassandra or ScyllaDB table in batches of a prepared
+    /// INSERT, each sent to the node owning its partition
+    #[cfg(feature = "cql")]
+    Cql(CqlArgs),
+    /// Index documents into Elasticsearch or OpenSearch with the _bulk API
+    #[cfg(feature = "http")]
+    Elasticsearch(ElasticsearchArgs),
+    /// Load into a SQL Server table with bulk loads, table-valued
+    /// parameters or INSERTs
+    #[cfg(feature = "mssql")]
+    Mssql(MssqlArgs),
+    /// Load into an Oracle table with SQL*Loader
+    #[cfg(all(unix, feature = "oracle"))]
+    Oracle(OracleArgs),
+}
+
+impl SinkTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SinkTarget::Avro { .. } => "avro",
+            #[cfg(feature = "kafka")]
+            SinkTarget::Kafka(_) => "kafka",
+            SinkTarget::Influx(_) => "influx",
+            #[cfg(feature = "redis")]
+            SinkTarget::Redis(_) => "redis",
+            #[cfg(feature = "nats")]
+            SinkTarget::Nats(_) => "nats",
+            #[cfg(feature = "http")]
+            SinkTarget::Http(_) => "http",
+            #[cfg(feature = "upload")]
+            SinkTarget::S3(_) => "s3",
+            #[cfg(feature = "bigquery")]
+            SinkTarget::Bigquery(_) => "bigquery",
+            #[cfg(feature = "mongodb")]
+            SinkTarget::Mongodb(_) => "mongodb",
+            #[cfg(feature = "cql")]
+            SinkTarget::Cql(_) => "cql",
+            #[cfg(feature = "http")]
+            SinkTarget::Elasticsearch(_) => "elasticsearch",
+            #[cfg(feature = "mssql")]
+            SinkTarget::Mssql(_) => "mssql",
+            #[cfg(all(unix, feature = "oracle"))]
+            SinkTarget::Oracle(_) => "oracle",
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[derive(Args)]
+pub struct KafkaArgs {
+    /// Bootstrap servers of the cluster
+    #[arg(long, default_value = "localhost:9092")]
+    pub brokers: String,
+
+    /// Topic to produce to
+    #[arg(long, default_value = "metrics")]
+    pub topic: String,
+
+    /// Schema registry to register the schema with, e.g.
+    /// http://localhost:8081; messages then carry its id the way
+    /// Confluent's serializers frame them, instead of being bare Avro
+    #[arg(long, value_name = "URL")]
+    pub schema_registry: Option<String>,
+
+    /// Subject to register the schema under [default: <topic>-value]
+    #[arg(long, requires = "schema_registry")]
+    pub subject: Option<String>,
+
+    /// Producer setting passed to librdkafka, e.g. acks=all or
+    /// compression.type=lz4; repeatable
+    #[arg(long = "producer-config", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub producer_config: Vec<(String, String)>,
+}
+
+#[derive(Args)]
+pub struct FixturesArgs {
+    /// Table to copy; repeatable. Foreign keys between the tables given are
+    /// added between their copies
+    #[arg(long = "table", value_name = "TABLE", required = true)]
+    pub tables: Vec<String>,
+
+    /// Column holding keys, integer or text, replaced one-to-one by the
+    /// same pseudonym in every table; repeatable
+    #[arg(long = "key-column", value_name = "COLUMN")]
+    pub key_columns: Vec<String>,
+
+    /// Column replaced by fakes, text by a token and numbers by a value
+    /// within the column's range, the same for the same value; repeatable
+    #[arg(long = "mask-column", value_name = "COLUMN")]
+    pub mask_columns: Vec<String>,
+
+    /// Secret the fakes are derived from; whoever knows it can tell which
+    /// original a pseudonym stands for
+    #[arg(long)]
+    pub secret: String,
+
+    /// Appended to each table's name to name its copy, which is replaced
+    #[arg(long, default_value = "_fixture")]
+    pub suffix: String,
+}
+
+#[derive(Args)]
+pub struct ProfileArgs {
+    /// Table to profile, with the columns the generator produces
+    #[arg(long)]
+    pub table: String,
+
+    /// Rows to sample at most; larger tables are sampled at random, with
+    /// --seed picking the sample
+    #[arg(long, default_value_t = 1_000_000)]
+    pub sample_rows: usize,
+
+    /// Also write the settings as JSON to this file, in the shape the
+    /// generator and schema take in a `serve` run request
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RlsArgs {
+    /// Non-superuser role the policy applies to; created and dropped
+    /// afterwards unless it exists
+    #[arg(long, default_value = "fast_generation_rls")]
+    pub role: String,
+
+    /// Password for the role, if the server asks for one
+    #[arg(long)]
+    pub role_password: Option<String>,
+
+    /// Table created like the loaded one for both loads, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_rls")]
+    pub scratch_table: String,
+
+    /// Strategy to load with; the server refuses COPY FROM into a table
+    /// with row-level security
+    #[arg(long, default_value = "insert-str", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct FdwArgs {
+    /// Server holding the table the foreign table points at [default: the
+    /// connected one, reached over a loopback connection]
+    #[arg(long)]
+    pub remote_dsn: Option<String>,
+
+    /// batch_size server options to load through the foreign table with;
+    /// COPY into a foreign table is batched from PostgreSQL 16 only
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 10, 100, 1000], value_parser = clap::value_parser!(u32).range(1..))]
+    pub batch_size: Vec<u32>,
+
+    /// Table created on the remote server for every load, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_fdw")]
+    pub remote_table: String,
+
+    /// Foreign table created on the connected server, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_fdw_foreign")]
+    pub foreign_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct ReplicationArgs {
+    /// Database the subscription is created on; superuser rights are
+    /// needed there before PostgreSQL 16
+    #[arg(long)]
+    pub subscriber_dsn: String,
+
+    /// Connection string the subscriber reaches the publisher with
+    /// [default: the one loaded through]
+    #[arg(long)]
+    pub publisher_conninfo: Option<String>,
+
+    /// Table created on both sides for the loads, and dropped afterwards
+    #[arg(long, default_value = "metrics_replication")]
+    pub scratch_table: String,
+
+    /// Strategies to load with, in order [default: all of them]
+    #[arg(long = "strategy", value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategies: Vec<String>,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+
+    /// Seconds to wait for the subscriber to apply a load before giving up
+    #[arg(long, default_value_t = 600)]
+    pub apply_timeout: u64,
+}
+
+#[derive(Args)]
+pub struct PathsArgs {
+    /// A path to the cluster as NAME=DSN, e.g. "pgbouncer=host=db port=6432
+    /// user=bench"; repeatable, the first one is compared against and sets
+    /// up the table
+    #[arg(long = "path", value_name = "NAME=DSN", required = true, value_parser = parse_key_value)]
+    pub paths: Vec<(String, String)>,
+
+    /// Table created like the loaded one for the loads, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_paths")]
+    pub scratch_table: String,
+
+    /// Strategies to load with, in order [default: all of them]
+    #[arg(long = "strategy", value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategies: Vec<String>,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct TimeZonesArgs {
+    /// Session TimeZone settings to load and read back under, in order
+    #[arg(long = "time-zone", value_name = "ZONE", value_delimiter = ',', default_values_t = ["UTC", "America/Los_Angeles", "America/St_Johns", "Asia/Kathmandu", "Pacific/Chatham"].map(String::from))]
+    pub time_zones: Vec<String>,
+
+    /// Batches loaded under each; combine with --start to cover a daylight
+    /// saving change
+    #[arg(long, default_value_t = 10)]
+    pub batches: usize,
+
+    /// Table created like the loaded one for the loads, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_time_zones")]
+    pub scratch_table: String,
+
+    /// Strategies to load with, in order [default: all of them]
+    #[arg(long = "strategy", value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategies: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// File to load, as `export --output-dir` writes it: .csv, .tsv (COPY
+    /// text) or .pgcopy (COPY binary), uncompressed. Rows are sent as they
+    /// lie in the file, never parsed
+    #[arg(long, value_name = "PATH")]
+    pub input: PathBuf,
+
+    /// Skip the first line of a .csv or .tsv --input, holding column names
+    #[arg(long)]
+    pub input_header: bool,
+
+    /// Rows sent per COPY
+    #[arg(long, value_name = "ROWS", default_value_t = 10_000)]
+    pub batch_rows: usize,
+}
+
+#[derive(Args)]
+pub struct InterleaveArgs {
+    /// Strategies to alternate between, each loading into a table of its
+    /// own [default: all of them]
+    #[arg(long = "strategy", value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategies: Vec<String>,
+
+    /// Prefix of the tables created like the loaded one, one per strategy
+    /// named <scratch-table>_<strategy>, and dropped afterwards
+    #[arg(long, default_value = "metrics_interleave")]
+    pub scratch_table: String,
+}
+
+#[derive(Args)]
+pub struct ForeignKeysArgs {
+    /// How the foreign key is handled, in order
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [ForeignKey::Enforced, ForeignKey::Dropped, ForeignKey::After])]
+    pub modes: Vec<ForeignKey>,
+
+    /// Table created like the loaded one for each load, next to a
+    /// <scratch-table>_sensors table it references, both dropped afterwards
+    #[arg(long, default_value = "metrics_fk")]
+    pub scratch_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct OverheadArgs {
+    /// Table features to compare, in order
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [TableFeatures::Plain, TableFeatures::Generated, TableFeatures::Trigger, TableFeatures::Both])]
+    pub features: Vec<TableFeatures>,
+
+    /// Table created like the loaded one for each comparison, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_overhead")]
+    pub scratch_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct PartitionsArgs {
+    /// Partition or chunk intervals to compare, in order; a run only spans
+    /// several with a --batch-interval-ms of minutes
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Granularity::Hour, Granularity::Day, Granularity::Week])]
+    pub intervals: Vec<Granularity>,
+
+    /// Partition with TimescaleDB's create_hypertable instead of natively
+    #[arg(long)]
+    pub hypertable: bool,
+
+    /// Table created like the loaded one for each interval, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_partitioned")]
+    pub scratch_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct StorageArgs {
+    /// Table fillfactors to compare, e.g. 100,90,70 [default: the server's]
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(10..=100))]
+    pub fillfactor: Vec<u32>,
+
+    /// toast_tuple_target values to compare, in bytes [default: the server's]
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u32).range(128..=8160))]
+    pub toast_tuple_target: Vec<u32>,
+
+    /// Compression methods of the temperature column to compare [default: the server's]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub compression: Vec<Compression>,
+
+    /// Table created like the loaded one for each combination, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_storage")]
+    pub scratch_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct IndexesArgs {
+    /// Index sets to compare, in order
+    #[arg(long = "sets", value_enum, value_delimiter = ',', default_values_t = [IndexSet::None, IndexSet::Btree, IndexSet::Brin, IndexSet::Hash])]
+    pub sets: Vec<IndexSet>,
+
+    /// Table created like the loaded one for the comparison, and dropped
+    /// afterwards
+    #[arg(long, default_value = "metrics_indexes")]
+    pub scratch_table: String,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct TablesArgs {
+    /// Table to load, created if missing, with optional settings, e.g.
+    /// "events,time-column=bigint,numeric-scale=2,strategy=insert,workers=2";
+    /// repeatable
+    #[arg(long = "table", value_name = "SPEC", required = true, value_parser = parse_table_spec)]
+    pub tables: Vec<TableSpec>,
+
+    /// Strategy of the tables that don't name one
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections per table of the tables that don't set workers
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args)]
+pub struct TenantsArgs {
+    /// Tenants to create
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tenants: u32,
+
+    /// Whether every tenant gets a schema of its own or only a table
+    #[arg(long, value_enum, default_value_t)]
+    pub layout: Layout,
+
+    /// Zipf exponent of how batches spread over tenants: 0 spreads them
+    /// evenly, 1 gives tenant N about 1/N of the first tenant's batches
+    #[arg(long, default_value_t = 1.0)]
+    pub skew: f64,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Connections loading batches in parallel, each into whichever tenant
+    /// its batch belongs to
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+
+    /// Keep the tenants' schemas or tables afterwards
+    #[arg(long)]
+    pub keep: bool,
+}
+
+#[derive(Args)]
+pub struct FailoverArgs {
+    /// Connection string of a hot standby of the server loaded into
+    #[arg(long, value_name = "DSN")]
+    pub standby_dsn: String,
+
+    /// Promote the standby with pg_promote() after loading for this many
+    /// seconds [default: wait for it to be promoted some other way]
+    #[arg(long, value_name = "SECS")]
+    pub promote_after_secs: Option<u64>,
+
+    /// Seconds to wait for the promotion before giving up
+    #[arg(long, default_value_t = 300)]
+    pub timeout_secs: u64,
+
+    /// Seconds to keep loading into the new primary once switched
+    #[arg(long, default_value_t = 10)]
+    pub after_secs: u64,
+
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+}
+
+#[derive(Args)]
+pub struct CapacityArgs {
+    /// Strategy to load with
+    #[arg(long, default_value = "copy", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategy: String,
+
+    /// Target rows per second of the first step
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub start_rate: u64,
+
+    /// Rows per second added with every step
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub step_rate: u64,
+
+    /// Seconds each step loads for
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub step_secs: u64,
+
+    /// Most steps to try
+    #[arg(long, default_value_t = 50)]
+    pub max_steps: usize,
+
+    /// Highest p99 batch latency, in milliseconds, of a sustained step
+    #[arg(long, default_value_t = 1_000)]
+    pub max_p99_ms: u64,
+
+    /// Share of its target rate a step must reach to count as sustained
+    #[arg(long, default_value_t = 0.95)]
+    pub min_achieved: f64,
+
+    /// Connections loading batches in parallel
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+}
+
+#[derive(Args, Default)]
+pub struct RunArgs {
+    /// Check the target table against the generator and print the plan without inserting
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub load: LoadArgs,
+
+    /// Load the rows of this file with every strategy instead of generated
+    /// ones: COPY output as `export` writes it, in .csv, .tsv (COPY text) or
+    /// .pgcopy (COPY binary), optionally compressed as .gz or .zst, or a
+    /// .parquet file with created, sensor_id and temperature columns. It's
+    /// read into memory first and split into batches of --batch-size-max rows
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["dry_run", "node_index", "repro_dir"]
+    )]
+    pub input: Option<PathBuf>,
+
+    /// Skip the first line of a .csv or .tsv --input, holding column names
+    #[arg(long, requires = "input")]
+    pub input_header: bool,
+
+    /// Make the table a distributed table first, unless it is one already,
+    /// split every batch into the rows of each node and report how the rows
+    /// spread over the nodes at the end
+    #[arg(long, value_enum, conflicts_with = "dry_run")]
+    pub distributed: Option<Distribution>,
+
+    /// Shards to distribute the table over [default: citus.shard_count]
+    #[arg(long, requires = "distributed")]
+    pub shard_count: Option<u32>,
+
+    /// Load as node N of several loading the same table at once, with a seed
+    /// derived for it the way `coordinate` derives one; give every node the
+    /// same --seed and --start
+    #[arg(long, value_name = "N")]
+    pub node_index: Option<u64>,
+
+    /// Refuse to run if the database would grow past this size, e.g. 50GB;
+    /// the growth is estimated from a sample batch and the rows to load,
+    /// those of --input if given, with every strategy in every iteration
+    #[arg(long, value_parser = parse_size)]
+    pub disk_limit: Option<usize>,
+
+    /// Write a JSON report of the environment, settings and results to this file
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Write a scenario file with the run's settings and a script repeating
+    /// the run with them, table and all, to this directory at the end
+    #[arg(long, value_name = "DIR")]
+    pub repro_dir: Option<PathBuf>,
+
+    /// Write the statements the run issues to this file, for psql to
+    /// replay: session SQL, hooks and the like in full, and the first batch
+    /// of each strategy as a sample of the rest
+    #[arg(long, value_name = "PATH")]
+    pub dump_sql: Option<PathBuf>,
+
+    /// Compare the results with the report of an earlier run, flagging the
+    /// server settings that changed since
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+
+    /// Upload a JSON report of the settings and results to this S3 or GCS
+    /// URI at the end, e.g. s3://bench-results/nightly
+    #[cfg(feature = "upload")]
+    #[arg(long, value_name = "URI")]
+    pub upload: Option<String>,
+
+    #[command(flatten)]
+    pub slo: SloArgs,
+
+    #[command(flatten)]
+    pub load_role: LoadRoleArgs,
+}
+
+#[derive(Args, Default)]
+pub struct LoadRoleArgs {
+    /// Load as this role, created if missing with no more privileges than
+    /// --load-role-privileges and dropped afterwards; settling between
+    /// strategies, pausing autovacuum and hooks may need more
+    #[arg(long, value_name = "ROLE")]
+    pub load_role: Option<String>,
+
+    /// What the load role may do with the table
+    #[arg(long, value_enum, default_value_t = Privileges::Insert, requires = "load_role")]
+    pub load_role_privileges: Privileges,
+
+    /// Password to create the load role with and connect as it, if the
+    /// server asks for one
+    #[arg(long, requires = "load_role")]
+    pub load_role_password: Option<String>,
+}
+
+/// Objectives the run as a whole passes or fails; any failing one makes
+/// the exit code 3.
+#[derive(Args, Default)]
+pub struct SloArgs {
+    /// Highest p99 batch latency, in milliseconds, of every strategy
+    #[arg(long, value_name = "MS")]
+    pub slo_p99_ms: Option<u64>,
+
+    /// Lowest rows per second of every strategy
+    #[arg(long, value_name = "ROWS")]
+    pub slo_min_rows_per_sec: Option<u64>,
+
+    /// Most strategies that may fail, e.g. 0; failures are then judged by
+    /// this objective alone rather than always failing the run
+    #[arg(long, value_name = "COUNT")]
+    pub slo_max_errors: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ManifestArgs {
+    /// Image with fast_generation on its PATH
+    #[arg(long)]
+    pub image: String,
+
+    /// Pods loading at once, each as a node with a seed of its own
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    pub parallelism: u32,
+
+    /// Secret holding the connection string the pods connect with
+    #[arg(long, value_name = "NAME")]
+    pub dsn_secret: String,
+
+    /// Key of the connection string in --dsn-secret
+    #[arg(long, default_value = "dsn")]
+    pub dsn_key: String,
+
+    /// Name of the ConfigMap and the Job
+    #[arg(long, default_value = "fast-generation")]
+    pub name: String,
+
+    #[command(flatten)]
+    pub load: LoadArgs,
+}
+
+/// How each strategy loads, shared by `run`, the runs `coordinate` starts
+/// and the pods of `k8s-manifest`.
+#[derive(Args, Default)]
+pub struct LoadArgs {
+    /// Strategies to load with, in order [default: all of them]
+    #[arg(long = "strategy", value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub strategies: Vec<String>,
+
+    /// Strategies to leave out, e.g. the per-row insert at large scales
+    #[arg(long, value_delimiter = ',', value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub skip: Vec<String>,
+
+    /// Times to run every strategy, cycling through all of them each time
+    #[arg(long, default_value_t = 1)]
+    pub iterations: usize,
+
+    /// Order the strategies run in each iteration, to keep effects such as
+    /// a cache warmed by the previous strategy from favouring one of them
+    #[arg(long, value_enum, default_value_t)]
+    pub order: StrategyOrder,
+
+    /// Connections loading batches in parallel for each strategy
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+
+    /// Encode each batch only after the previous one was sent, instead of
+    /// overlapping encoding with sending
+    #[arg(long)]
+    pub sequential: bool,
+
+    /// Size of each write into the COPY stream, e.g. 64KB [default: the whole batch at once]
+    #[arg(long, value_parser = parse_size)]
+    pub copy_chunk_size: Option<usize>,
+
+    /// SQL run on a connection of its own before each strategy, e.g.
+    /// "ALTER TABLE metrics SET (autovacuum_enabled = false)"; repeatable
+    #[arg(long, value_name = "SQL")]
+    pub setup_sql: Vec<String>,
+
+    /// SQL run after each strategy, even one that failed, e.g. CHECKPOINT; repeatable
+    #[arg(long, value_name = "SQL")]
+    pub teardown_sql: Vec<String>,
+
+    /// SQL run on every loading connection once opened, e.g. "SET work_mem = '64MB'"; repeatable
+    #[arg(long, value_name = "SQL")]
+    pub session_sql: Vec<String>,
+
+    /// Turn autovacuum off on the table for the run, restoring it afterwards,
+    /// and report any autovacuum or autoanalyze that ran anyway
+    #[arg(long)]
+    pub pause_autovacuum: bool,
+
+    /// Skip counting the table's rows before and after each strategy to
+    /// check it grew by the rows sent, e.g. when other clients load the
+    /// same table or counting it takes too long
+    #[arg(long)]
+    pub no_reconcile: bool,
+
+    /// After each strategy, count the rows of every key the table gained
+    /// beyond those sent, as a batch loaded twice leaves; scans the table
+    /// twice per strategy
+    #[arg(long)]
+    pub audit_duplicates: bool,
+
+    /// Columns keying the duplicate audit
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [AuditColumn::Created, AuditColumn::SensorId], requires = "audit_duplicates")]
+    pub audit_key: Vec<AuditColumn>,
+
+    /// After each strategy, sample the temperatures it loaded and check each
+    /// was stored at the scale it should have, which a binary encoder
+    /// writing the wrong display scale would break unnoticed
+    #[arg(long)]
+    pub check_scale: bool,
+
+    /// Temperatures to sample for the scale check; the sample is drawn
+    /// from a scan of the table
+    #[arg(
+        long,
+        value_name = "ROWS",
+        default_value_t = 1000,
+        requires = "check_scale"
+    )]
+    pub scale_sample: usize,
+
+    /// Most memory the batches generated and not yet sent may take, with
+    /// their encoded payloads, e.g. 512MB; generating waits while over it,
+    /// so many workers with large batches can't exhaust the host. A batch
+    /// larger than this goes alone [default: unbounded]
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_client_memory: Option<usize>,
+
+    /// Size batches while loading to hold each near this many milliseconds,
+    /// from how long those committed took, and report the size converged
+    /// on; loads as many rows as the default batches hold, and
+    /// --batch-size-min and --batch-size-max bound the sizes picked
+    /// [default: 100 to 1000000 rows]
+    #[arg(long, value_name = "MS")]
+    pub target_batch_ms: Option<u64>,
+
+    /// Stop a strategy still loading after this many seconds, cancelling
+    /// the statements it has in flight, record it as timed out and go on
+    /// with the next, e.g. to cap the per-row INSERTs at large scales
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub strategy_timeout: Option<u64>,
+
+    /// Generate every batch before the first strategy starts, holding them
+    /// all in memory, and have each strategy load those, so the times
+    /// measured include no generating, as with tools loading pregenerated
+    /// files; rows read with --input always are
+    #[arg(long)]
+    pub pregenerate: bool,
+
+    /// Pause before sending each batch, in milliseconds, to emulate a
+    /// producer doing other work between batches
+    #[arg(long, default_value_t = 0)]
+    pub think_time_ms: u64,
+
+    /// Up to this many more milliseconds of pause, drawn at random for every
+    /// batch, for bursty producers
+    #[arg(long, default_value_t = 0)]
+    pub think_jitter_ms: u64,
+
+    /// Target rows per second across all workers [default: as fast as they go]
+    #[arg(long)]
+    pub rate: Option<u64>,
+
+    /// How the target rate changes over time
+    #[arg(long, value_enum, default_value_t, requires = "rate")]
+    pub shape: Shape,
+
+    /// Lowest target rate, where ramps start and bursts and sines bottom out
+    #[arg(long, default_value_t = 0, requires = "rate")]
+    pub min_rate: u64,
+
+    /// Seconds a ramp takes, or one period of bursts or a sine
+    #[arg(long, default_value_t = 60, requires = "rate")]
+    pub period_secs: u64,
+
+    /// Strategy to give the speed of the others relative to, e.g. "copy =
+    /// 14.3x insert" [default: relative to the slowest, in the summary only]
+    #[arg(long, value_name = "STRATEGY", value_parser = PossibleValuesParser::new(STRATEGIES.map(|strategy| strategy.name)))]
+    pub reference: Option<String>,
+
+    /// Times to reconnect and resend a batch whose connection was lost, with
+    /// a growing pause between attempts, e.g. to ride out a failover
+    #[arg(long, default_value_t = 0)]
+    pub retries: u32,
+
+    /// Terminate one of the loading backends with pg_terminate_backend this
+    /// often, in turn, and report how long loading took to recover; combine
+    /// with --retries
+    #[arg(long, value_name = "MS")]
+    pub chaos_interval_ms: Option<u64>,
+
+    /// Read a random sample of every Nth batch back once committed and stop
+    /// the strategy at the first row not loaded as generated; each check
+    /// scans the table unless it has an index on created
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub validate_every: Option<u64>,
+
+    /// Rows of each checked batch to read back
+    #[arg(long, default_value_t = 10, requires = "validate_every")]
+    pub validate_sample: usize,
+
+    /// CHECKPOINT before each strategy and, on PostgreSQL 17 with
+    /// pg_buffercache installed, evict shared buffers, so every strategy
+    /// starts from a comparable server state
+    #[arg(long)]
+    pub settle: bool,
+}
+
+impl LoadArgs {
+    pub fn run_config(&self, table_name: &str, batch_count: usize) -> RunConfig {
+        RunConfig {
+            strategies: self.strategies.clone(),
+            skip: self.skip.clone(),
+            order: self.order,
+            table_name: table_name.to_string(),
+            batch_count,
+            iterations: self.iterations,
+            workers: self.workers,
+            sequential: self.sequential,
+            copy_chunk_size: self.copy_chunk_size,
+            setup_sql: self.setup_sql.clone(),
+            teardown_sql: self.teardown_sql.clone(),
+            session_sql: self.session_sql.clone(),
+            pause_autovacuum: self.pause_autovacuum,
+            reconcile: !self.no_reconcile,
+            audit_key: match self.audit_duplicates {
+                true => self.audit_key.clone(),
+                false => Vec::new(),
+            },
+            scale_sample: self.check_scale.then_some(self.scale_sample),
+            max_client_memory: self.max_client_memory,
+            target_batch_ms: self.target_batch_ms,
+            strategy_timeout_secs: self.strategy_timeout,
+            pregenerate: self.pregenerate,
+            settle: self.settle,
+            think_time_ms: self.think_time_ms,
+            think_jitter_ms: self.think_jitter_ms,
+            shape: self.rate.map(|rate| LoadShape {
+                shape: self.shape,
+                rate,
+                min_rate: self.min_rate,
+                period_secs: self.period_secs,
+            }),
+            retries: self.retries,
+            chaos_interval_ms: self.chaos_interval_ms,
+            validate_every: self.validate_every,
+            validate_sample: self.validate_sample,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct InfluxArgs {
+    /// File to write
+    #[cfg_attr(not(feature = "http"), arg(long, required = true))]
+    #[cfg_attr(
+        feature = "http",
+        arg(long, required_unless_present = "url", conflicts_with = "url")
+    )]
+    pub output: Option<PathBuf>,
+
+    /// Write endpoint each batch is POSTed to, e.g.
+    /// http://localhost:8086/api/v2/write?org=bench&bucket=metrics, or
+    /// /write?db=bench on InfluxDB 1.x
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// API token sent with each write [default: $INFLUX_TOKEN]
+    #[cfg(feature = "http")]
+    #[arg(long, requires = "url")]
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "redis")]
+#[derive(Args)]
+pub struct RedisArgs {
+    /// Server to connect to
+    #[arg(long, default_value = "redis://127.0.0.1/")]
+    pub url: String,
+
+    /// Stream to add to, or prefix of the series, named KEY:SENSOR_ID
+    /// [default: the table name]
+    #[arg(long)]
+    pub key: Option<String>,
+
+    /// TS.ADD each temperature to a series per sensor instead, keeping one
+    /// value per sensor and millisecond
+    #[arg(long)]
+    pub timeseries: bool,
+
+    /// Trim the stream to about this many entries as it grows
+    #[arg(long, conflicts_with = "timeseries")]
+    pub max_len: Option<u64>,
+}
+
+#[cfg(feature = "nats")]
+#[derive(Args)]
+pub struct NatsArgs {
+    /// Server to connect to
+    #[arg(long, default_value = "nats://127.0.0.1:4222")]
+    pub url: String,
+
+    /// Rows are published to PREFIX.SENSOR_ID [default: the table name]
+    #[arg(long)]
+    pub subject_prefix: Option<String>,
+
+    /// Encoding of each message
+    #[arg(long, value_enum, default_value_t)]
+    pub format: MessageFormat,
+
+    /// Stream capturing PREFIX.>, created if it doesn't exist; without it
+    /// a stream must capture the subjects already
+    #[arg(long)]
+    pub stream: Option<String>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Args)]
+pub struct HttpArgs {
+    /// Endpoint each batch is POSTed to
+    #[arg(long)]
+    pub url: String,
+
+    /// Encoding of the request bodies
+    #[arg(long, value_enum, default_value_t)]
+    pub format: BodyFormat,
+
+    /// Requests in flight at once
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+
+    /// Times to resend a request that failed to connect or got a 429 or 5xx
+    /// response
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+
+    /// Pause before the first resend, doubling with each further one
+    #[arg(long, default_value_t = 100)]
+    pub retry_backoff_ms: u64,
+
+    /// Header sent with every request, e.g. "Authorization=Bearer $TOKEN";
+    /// repeatable
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_key_value)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Args)]
+pub struct ElasticsearchArgs {
+    /// Cluster to index into
+    #[arg(long, default_value = "http://localhost:9200")]
+    pub url: String,
+
+    /// Index to add the documents to, created on the first request if it
+    /// doesn't exist [default: the table name]
+    #[arg(long)]
+    pub index: Option<String>,
+
+    /// Documents per _bulk request
+    #[arg(long, default_value_t = 5_000)]
+    pub bulk_rows: usize,
+
+    /// The index's refresh_interval while loading, e.g. 30s, or -1 to not
+    /// refresh until the end; the previous one is put back afterwards
+    #[arg(long, value_name = "INTERVAL", allow_hyphen_values = true)]
+    pub refresh_interval: Option<String>,
+
+    /// Header sent with every request, e.g. "Authorization=ApiKey $KEY";
+    /// repeatable
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_key_value)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "upload")]
+#[derive(Args)]
+pub struct S3Args {
+    /// Where to upload the files, e.g. s3://bench-inputs/metrics; each run
+    /// gets a directory of its own under it
+    #[arg(long, value_name = "URI")]
+    pub uri: String,
+
+    /// Format of the files
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ObjectFormat,
+
+    /// Start each CSV file with a line of column names
+    #[arg(long)]
+    pub header: bool,
+
+    /// Compress CSV files, or the pages of Parquet ones
+    #[arg(long, value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+
+    /// Compression level: 0-9 for gzip, 1-22 for zstd [default: 6 for gzip, 3 for zstd]
+    #[arg(long, requires = "compress")]
+    pub compression_level: Option<i32>,
+
+    /// Start another file once the current one reaches this size, e.g.
+    /// 256MB, for loaders that read several files in parallel; files run
+    /// over by up to a batch, and Parquet ones by up to a row group
+    #[arg(long, value_parser = parse_size)]
+    pub max_file_size: Option<usize>,
+}
+
+#[cfg(feature = "bigquery")]
+#[derive(Args)]
+pub struct BigqueryArgs {
+    /// Project of the dataset
+    #[arg(long)]
+    pub project: String,
+
+    /// Dataset of the table
+    #[arg(long)]
+    pub dataset: String,
+
+    /// Table to append to, created beforehand with columns created
+    /// (TIMESTAMP, or INT64 for a bigint time column), sensor_id and
+    /// temperature [default: the table name]
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// OAuth access token, e.g. from `gcloud auth print-access-token`
+    /// [default: $BIGQUERY_TOKEN, or none for an emulator]
+    #[arg(long)]
+    pub token: Option<String>,
+
+    /// Storage API endpoint, or an emulator's
+    #[arg(long, default_value = "https://bigquerystorage.googleapis.com")]
+    pub endpoint: String,
+}
+
+#[cfg(feature = "mongodb")]
+#[derive(Args)]
+pub struct MongodbArgs {
+    /// Deployment to connect to
+    #[arg(long, default_value = "mongodb://127.0.0.1:27017")]
+    pub uri: String,
+
+    /// Database of the collection
+    #[arg(long, default_value = "bench")]
+    pub database: String,
+
+    /// Collection to insert into [default: the table name]
+    #[arg(long)]
+    pub collection: Option<String>,
+
+    /// Members that must acknowledge each insertMany: a count, "majority",
+    /// or a custom write concern's name [default: the deployment's]
+    #[arg(long, value_name = "W")]
+    pub write_concern: Option<String>,
+
+    /// Wait for each insertMany to reach the on-disk journal
+    #[arg(long)]
+    pub journal: bool,
+
+    /// Fail an insertMany not acknowledged as asked within this time
+    #[arg(long, value_name = "MS", requires = "write_concern")]
+    pub wtimeout_ms: Option<u64>,
+
+    /// Let the server insert a batch's documents in any order
+    #[arg(long)]
+    pub unordered: bool,
+}
+
+#[cfg(feature = "cql")]
+#[derive(Args)]
+pub struct CqlArgs {
+    /// Node to discover the others through, as HOST:PORT
+    #[arg(long, default_value = "127.0.0.1:9042")]
+    pub contact_point: String,
+
+    /// Keyspace of the table
+    #[arg(long, default_value = "bench")]
+    pub keyspace: String,
+
+    /// Table to insert into, partitioned by sensor_id [default: the table
+    /// name]
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// Consistency level of the inserts
+    #[arg(long, value_enum, default_value_t)]
+    pub consistency: Consistency,
+
+    /// Rows per batch at most; a batch never spans partitions
+    #[arg(long, default_value_t = 100)]
+    pub batch_rows: usize,
+
+    /// Batches in flight at once on each node
+    #[arg(long, default_value_t = 4)]
+    pub concurrency: usize,
+
+    /// User to authenticate as, with --password
+    #[arg(long, requires = "password")]
+    pub username: Option<String>,
+
+    /// Password of --username
+    #[arg(long, requires = "username")]
+    pub password: Option<String>,
+}
+
+#[cfg(feature = "mssql")]
+#[derive(Args)]
+pub struct MssqlArgs {
+    /// ADO.NET-style connection string, e.g. "server=tcp:HOST,1433;
+    /// user=sa;password=...;database=bench;TrustServerCertificate=true"
+    #[arg(
+        long,
+        default_value = "server=tcp:localhost,1433;TrustServerCertificate=true"
+    )]
+    pub connection: String,
+
+    /// Table to load into, created if it doesn't exist [default: the table
+    /// name]
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// How each batch is sent
+    #[arg(long, value_enum, default_value_t)]
+    pub method: LoadMethod,
+}
+
+#[cfg(all(unix, feature = "oracle"))]
+#[derive(Args)]
+pub struct OracleArgs {
+    /// SQL*Loader connect string, e.g. "bench/PASSWORD@//oracle:1521/FREEPDB1"
+    /// [default: $ORACLE_USERID]
+    #[arg(long)]
+    pub userid: Option<String>,
+
+    /// Table to load into, created if it doesn't exist [default: the table
+    /// name]
+    #[arg(long)]
+    pub table: Option<String>,
+
+    /// Load over SQL*Loader's conventional path, INSERTs, instead of the
+    /// direct path writing blocks
+    #[arg(long)]
+    pub conventional: bool,
+
+    /// SQL*Loader executable
+    #[arg(long, default_value = "sqlldr")]
+    pub sqlldr: String,
+
+    /// SQL*Plus executable, creating the table
+    #[arg(long, default_value = "sqlplus")]
+    pub sqlplus: String,
+}
+
+#[cfg(feature = "upload")]
+#[derive(Subcommand)]
+pub enum Warehouse {
+    /// COPY the files into a Redshift table from S3
+    Redshift(RedshiftArgs),
+    /// COPY INTO a Snowflake table from an external stage over the files,
+    /// through the SQL API
+    #[cfg(feature = "http")]
+    Snowflake(SnowflakeArgs),
+}
+
+#[cfg(feature = "upload")]
+impl Warehouse {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Warehouse::Redshift(_) => "redshift",
+            #[cfg(feature = "http")]
+            Warehouse::Snowflake(_) => "snowflake",
+        }
+    }
+}
+
+#[cfg(feature = "upload")]
+#[derive(Args)]
+pub struct RedshiftArgs {
+    #[command(flatten)]
+    pub files: S3Args,
+
+    /// Connection string of the cluster, e.g.
+    /// postgres://bench@cluster.abc123.eu-west-1.redshift.amazonaws.com:5439/dev
+    #[arg(long, value_name = "DSN")]
+    pub dsn: String,
+
+    /// IAM role the cluster reads the files as
+    #[arg(long, value_name = "ARN")]
+    pub iam_role: String,
+
+    /// Table to load into, created beforehand
+    #[arg(long, default_value = "metrics")]
+    pub table: String,
+}
+
+#[cfg(all(feature = "upload", feature = "http"))]
+#[derive(Args)]
+pub struct SnowflakeArgs {
+    #[command(flatten)]
+    pub files: S3Args,
+
+    /// Account URL, e.g. https://myorg-myaccount.snowflakecomputing.com
+    #[arg(long, value_name = "URL")]
+    pub account_url: String,
+
+    /// Token the statements are authorized with [default: $SNOWFLAKE_TOKEN]
+    #[arg(long)]
+    pub token: Option<String>,
+
+    /// Kind of token given
+    #[arg(long, value_enum, default_value_t)]
+    pub token_type: TokenType,
+
+    /// External stage whose URL is --uri
+    #[arg(long)]
+    pub stage: String,
+
+    /// Table to load into, created beforehand
+    #[arg(long, default_value = "metrics")]
+    pub table: String,
+
+    /// Warehouse running the COPY [default: the user's]
+    #[arg(long)]
+    pub warehouse: Option<String>,
+
+    /// Database of the stage and table [default: the user's]
+    #[arg(long)]
+    pub database: Option<String>,
+
+    /// Schema of the stage and table [default: the user's]
+    #[arg(long)]
+    pub schema: Option<String>,
+
+    /// Role to run the COPY as [default: the user's]
+    #[arg(long)]
+    pub role: Option<String>,
+}
+
+/// Parses `KEY=VALUE`, splitting at the first `=`.
+fn parse_key_value(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", text)),
+    }
+}
+
+/// Parses a byte count with an optional binary suffix: B, KB, MB or GB.
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size {:?}", text))?;
+    let multiplier = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("unknown size unit {:?}", unit)),
+    };
+    match number.checked_mul(multiplier) {
+        Some(0) | None => Err(format!("invalid size {:?}", text)),
+        Some(size) => Ok(size),
+    }
+}
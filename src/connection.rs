@@ -0,0 +1,242 @@
+use crate::FastGenError;
+use clap::{Args, ValueEnum};
+use postgres::config::{Host, SslMode};
+use std::path::{Path, PathBuf};
+use std::{env, fs, time::Duration};
+
+/// Managed Postgres services, whose users are never superusers and whose
+/// connections cross networks the operator doesn't control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Amazon Aurora PostgreSQL, whose storage layer replaces the WAL
+    Aurora,
+    /// Amazon RDS for PostgreSQL
+    Rds,
+    Neon,
+    Supabase,
+    /// Google Cloud SQL for PostgreSQL
+    CloudSql,
+    /// Azure Database for PostgreSQL flexible server
+    Azure,
+}
+
+/// Session and socket settings applied to every connection the tool opens.
+/// The postgres crate always enables TCP_NODELAY and leaves the socket send
+/// buffer at the OS default, so neither can be varied here.
+#[derive(Args, Clone, Debug)]
+pub struct ConnectionConfig {
+    /// Connection string, as key=value pairs or a postgresql:// URL [default: postgres on localhost]
+    #[arg(long, global = true)]
+    pub dsn: Option<String>,
+
+    /// Managed service connected to: sslmode=prefer becomes require, and
+    /// what its users may not do, like CHECKPOINT, is skipped with a note
+    /// rather than failing the run
+    #[arg(long, global = true, value_enum)]
+    pub preset: Option<Preset>,
+
+    /// File holding the password, when the connection string has none;
+    /// otherwise PGPASSWORD, then PGPASSFILE or ~/.pgpass, are tried as
+    /// libpq would
+    #[arg(long, global = true, value_name = "PATH")]
+    pub password_file: Option<PathBuf>,
+
+    /// CA certificate (PEM) to verify the server's certificate and host name
+    /// against; without it, with sslmode=prefer or require, any is accepted
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub ssl_root_cert: Option<PathBuf>,
+
+    /// Client certificate (PEM) for servers that authenticate with
+    /// certificates rather than passwords
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PATH", requires = "ssl_key")]
+    pub ssl_cert: Option<PathBuf>,
+
+    /// PKCS#8 private key (PEM) of --ssl-cert
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PATH", requires = "ssl_cert")]
+    pub ssl_key: Option<PathBuf>,
+
+    /// application_name reported to the server, e.g. to tell runs apart in pg_stat_activity
+    #[arg(long, global = true, default_value = "fast_generation")]
+    pub application_name: String,
+
+    /// Disable TCP keepalives on the client socket
+    #[arg(long, global = true)]
+    pub no_keepalives: bool,
+
+    /// Seconds of inactivity before a keepalive probe is sent [default: 7200]
+    #[arg(long, global = true, conflicts_with = "no_keepalives")]
+    pub keepalives_idle: Option<u64>,
+
+    /// Seconds between keepalive probes [default: the OS setting]
+    #[arg(long, global = true, conflicts_with = "no_keepalives")]
+    pub keepalives_interval: Option<u64>,
+
+    /// Milliseconds sent data may stay unacknowledged before the connection is dropped [default: the OS setting]
+    #[arg(long, global = true)]
+    pub tcp_user_timeout_ms: Option<u64>,
+
+    /// statement_timeout for every session, in milliseconds [default: the server setting]
+    #[arg(long, global = true)]
+    pub statement_timeout_ms: Option<u64>,
+
+    /// lock_timeout for every session, in milliseconds [default: the server setting]
+    #[arg(long, global = true)]
+    pub lock_timeout_ms: Option<u64>,
+
+    /// idle_in_transaction_session_timeout for every session, in milliseconds [default: the server setting]
+    #[arg(long, global = true)]
+    pub idle_in_transaction_timeout_ms: Option<u64>,
+}
+
+impl ConnectionConfig {
+    /// `--dsn`, or `conn_info` without one, with these settings applied on top.
+    pub fn postgres_config(&self, conn_info: &str) -> crate::Result<postgres::Config> {
+        let mut config: postgres::Config = self.dsn.as_deref().unwrap_or(conn_info).parse()?;
+        config
+            .application_name(&self.application_name)
+            .keepalives(!self.no_keepalives);
+        if let Some(seconds) = self.keepalives_idle {
+            config.keepalives_idle(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = self.keepalives_interval {
+            config.keepalives_interval(Duration::from_secs(seconds));
+        }
+        if let Some(millis) = self.tcp_user_timeout_ms {
+            config.tcp_user_timeout(Duration::from_millis(millis));
+        }
+
+        let settings = [
+            ("statement_timeout", self.statement_timeout_ms),
+            ("lock_timeout", self.lock_timeout_ms),
+            (
+                "idle_in_transaction_session_timeout",
+                self.idle_in_transaction_timeout_ms,
+            ),
+        ];
+        let options = settings
+            .iter()
+            .filter_map(|(name, millis)| millis.map(|millis| format!("-c {}={}", name, millis)))
+            .collect::<Vec<_>>();
+        if !options.is_empty() {
+            config.options(&options.join(" "));
+        }
+        if let Some(preset) = self.preset {
+            if !cfg!(feature = "tls") {
+                return Err(FastGenError::InvalidConfig(format!(
+                    "--preset {} needs TLS, which this build lacks; enable the tls feature",
+                    preset.to_possible_value().unwrap().get_name()
+                )));
+            }
+            if config.get_ssl_mode() == SslMode::Prefer {
+                config.ssl_mode(SslMode::Require);
+            }
+        }
+        if config.get_password().is_none() {
+            if let Some(password) = self.password(&config)? {
+                config.password(password);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The certificates every connection is made with.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(&self) -> crate::tls::TlsConfig {
+        crate::tls::TlsConfig {
+            root_cert: self.ssl_root_cert.clone(),
+            cert: self.ssl_cert.clone(),
+            key: self.ssl_key.clone(),
+        }
+    }
+
+    /// The password from --password-file, PGPASSWORD or the password file,
+    /// in that order, so that it stays out of shell history and process
+    /// listings.
+    fn password(&self, config: &postgres::Config) -> crate::Result<Option<String>> {
+        if let Some(path) = &self.password_file {
+            let password = fs::read_to_string(path).map_err(|e| {
+                FastGenError::InvalidConfig(format!(
+                    "reading password file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            return Ok(Some(password.trim_end_matches(['\r', '\n']).to_string()));
+        }
+        if let Ok(password) = env::var("PGPASSWORD") {
+            return Ok(Some(password));
+        }
+        let path = match env::var_os("PGPASSFILE") {
+            Some(path) => PathBuf::from(path),
+            None => match env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".pgpass"),
+                None => return Ok(None),
+            },
+        };
+        Ok(pgpass(&path, config))
+    }
+}
+
+/// The password of the first line of a pgpass file matching the host, port,
+/// database and user connected to, skipping the file if it can't be read or
+/// others may read it, as libpq does.
+fn pgpass(path: &Path, config: &postgres::Config) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(path).ok()?.permissions().mode() & 0o077 != 0 {
+            eprintln!(
+                "password file {} has group or world access; permissions should be u=rw (0600) or less",
+                path.display()
+            );
+            return None;
+        }
+    }
+    let user = config.get_user()?;
+    let dbname = config.get_dbname().unwrap_or(user);
+    let host = match config.get_hosts().first() {
+        Some(Host::Tcp(host)) => host.clone(),
+        // libpq matches a socket by "localhost" too.
+        #[cfg(unix)]
+        Some(Host::Unix(_)) | None => "localhost".to_string(),
+        #[cfg(not(unix))]
+        None => "localhost".to_string(),
+    };
+    let port = config
+        .get_ports()
+        .first()
+        .copied()
+        .unwrap_or(5432)
+        .to_string();
+    let wanted = [host.as_str(), port.as_str(), dbname, user];
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(pgpass_fields)
+        .find(|fields| {
+            fields.len() == 5
+                && wanted
+                    .iter()
+                    .zip(fields)
+                    .all(|(wanted, field)| field == "*" || field == wanted)
+        })
+        .map(|mut fields| fields.remove(4))
+}
+
+/// Splits a pgpass line on unescaped colons, undoing `\:` and `\\`.
+fn pgpass_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().unwrap().extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
@@ -0,0 +1,164 @@
+//! The `coordinate` command: starts the same run on several hosts running
+//! `serve` against one database, each with a seed of its own, waits for all
+//! of them and reports their combined throughput per strategy.
+
+use crate::serve::{RunRequest, RunState, RunStatus};
+use crate::units;
+use fast_generation::{GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use std::{thread, time::Duration};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn get_status(node: &str, id: usize) -> anyhow::Result<RunStatus> {
+    Ok(ureq::get(format!("{}/runs/{}", node, id))
+        .call()?
+        .body_mut()
+        .read_json()?)
+}
+
+pub fn coordinate(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    nodes: &[String],
+    run: RunConfig,
+    reference: Option<&str>,
+) -> anyhow::Result<()> {
+    let nodes: Vec<_> = nodes
+        .iter()
+        .map(|node| node.trim_end_matches('/'))
+        .collect();
+    let generator = GeneratorConfig {
+        start: Some(generator.start_time()),
+        ..*generator
+    };
+
+    // Every host loads the same table at once, so none of their row counts
+    // would add up, nor their keys.
+    let run = RunConfig {
+        reconcile: false,
+        audit_key: Vec::new(),
+        ..run
+    };
+    let mut ids = Vec::new();
+    for (index, node) in nodes.iter().enumerate() {
+        let request = RunRequest {
+            run: run.clone(),
+            generator: Some(generator.for_node(index as u64)),
+            schema: Some(*schema),
+        };
+        let started: serde_json::Value = ureq::post(format!("{}/runs", node))
+            .send_json(&request)
+            .map_err(|e| anyhow::anyhow!("starting the run on {}: {}", node, e))?
+            .body_mut()
+            .read_json()?;
+        let id = started["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("{} did not return a run id", node))?;
+        println!("Started run {} on {}", id, node);
+        ids.push(id as usize);
+    }
+
+    let statuses = loop {
+        thread::sleep(POLL_INTERVAL);
+        let statuses = nodes
+            .iter()
+            .zip(&ids)
+            .map(|(node, &id)| get_status(node, id))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if statuses
+            .iter()
+            .all(|status| status.state != RunState::Running)
+        {
+            break statuses;
+        }
+    };
+
+    let mut failed = false;
+    for (node, status) in nodes.iter().zip(&statuses) {
+        if let Some(error) = &status.error {
+            println!("{}: {}", node, error);
+            failed = true;
+        }
+        for result in &status.results {
+            if let Some(error) = &result.error {
+                println!("{}: {} failed: {}", node, result.strategy, error);
+                failed = true;
+            }
+        }
+    }
+
+    // Results line up by position, as every node ran the same configuration.
+    let rounds = statuses
+        .iter()
+        .map(|status| status.results.len())
+        .min()
+        .unwrap_or(0);
+    let mut reference_speed = None;
+    for round in 0..rounds {
+        let results: Vec<_> = statuses
+            .iter()
+            .map(|status| &status.results[round])
+            .collect();
+        let speed = print_combined(&results);
+        match reference {
+            Some(name) if results[0].strategy == name => reference_speed = Some(speed),
+            Some(name) => {
+                if let Some(reference_speed) = reference_speed {
+                    println!(" Relative: {:.1}x {}", speed / reference_speed, name);
+                }
+            }
+            None => {}
+        }
+    }
+
+    if failed {
+        anyhow::bail!("the run failed on some nodes");
+    }
+    Ok(())
+}
+
+/// One strategy across all nodes, timed from the first node starting to the
+/// last one finishing; clock skew between the hosts adds to the error.
+/// Returns the combined rows per second.
+fn print_combined(results: &[&StrategyResult]) -> f64 {
+    let first = results[0];
+    let started = results
+        .iter()
+        .map(|result| result.started_at)
+        .min()
+        .unwrap();
+    let finished = results
+        .iter()
+        .map(|result| result.started_at + result.elapsed)
+        .max()
+        .unwrap();
+    let seconds = (finished - started)
+        .to_std()
+        .unwrap_or_default()
+        .as_secs_f64();
+    let rows: usize = results.iter().map(|result| result.rows).sum();
+    let before = results
+        .iter()
+        .map(|result| result.before.total)
+        .min()
+        .unwrap();
+    let after = results
+        .iter()
+        .map(|result| result.after.total)
+        .max()
+        .unwrap();
+
+    println!(
+        "fn {} #{} on {} nodes",
+        first.strategy,
+        first.iteration + 1,
+        results.len()
+    );
+    println!(" Rows: {} in {:.2}s", rows, seconds);
+    println!(" Combined: {:.0} rows/s", rows as f64 / seconds);
+    println!(
+        " Combined speed: {}",
+        units::rate((after - before) as f64 / seconds)
+    );
+    rows as f64 / seconds
+}
@@ -0,0 +1,623 @@
+//! `sink cql`: inserts the rows into a Cassandra or ScyllaDB table over the
+//! CQL native protocol, as unlogged batches of a prepared INSERT holding a
+//! single partition each, sent straight to the node owning the partition
+//! by threads of their own for each node. The protocol is spoken directly,
+//! as much of version 4 as that takes: the table's partition key must be
+//! `sensor_id`, as in
+//!
+//! ```text
+//! CREATE TABLE bench.metrics (sensor_id int, created timestamp,
+//!     temperature double, PRIMARY KEY ((sensor_id), created))
+//! ```
+
+use crate::cli::CqlArgs;
+use crate::sink::Sink;
+use clap::ValueEnum;
+use fast_generation::generator::{Row, Timestamp};
+use fast_generation::schema::{SchemaConfig, TimeColumn};
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+const VERSION: u8 = 4;
+
+const ERROR: u8 = 0x00;
+const STARTUP: u8 = 0x01;
+const READY: u8 = 0x02;
+const AUTHENTICATE: u8 = 0x03;
+const QUERY: u8 = 0x07;
+const RESULT: u8 = 0x08;
+const PREPARE: u8 = 0x09;
+const BATCH: u8 = 0x0D;
+const AUTH_RESPONSE: u8 = 0x0F;
+const AUTH_SUCCESS: u8 = 0x10;
+
+/// RESULT kinds.
+const ROWS: i32 = 2;
+const PREPARED: i32 = 4;
+
+const UNLOGGED: u8 = 1;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Consistency {
+    Any,
+    One,
+    Two,
+    Three,
+    Quorum,
+    All,
+    LocalQuorum,
+    EachQuorum,
+    #[default]
+    LocalOne,
+}
+
+impl Consistency {
+    fn code(self) -> u16 {
+        match self {
+            Consistency::Any => 0,
+            Consistency::One => 1,
+            Consistency::Two => 2,
+            Consistency::Three => 3,
+            Consistency::Quorum => 4,
+            Consistency::All => 5,
+            Consistency::LocalQuorum => 6,
+            Consistency::EachQuorum => 7,
+            Consistency::LocalOne => 10,
+        }
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as u16).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+fn put_long_string(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as i32).to_be_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Reads the parts of a response body in order.
+struct Body<'a>(&'a [u8]);
+
+impl<'a> Body<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.0.len() < len {
+            anyhow::bail!("truncated CQL response");
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn short(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn int(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.short()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn short_bytes(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.short()? as usize;
+        self.take(len)
+    }
+
+    /// `None` for a null.
+    fn bytes(&mut self) -> anyhow::Result<Option<&'a [u8]>> {
+        match self.int()? {
+            len if len < 0 => Ok(None),
+            len => Ok(Some(self.take(len as usize)?)),
+        }
+    }
+
+    /// Skips a column type.
+    fn skip_option(&mut self) -> anyhow::Result<()> {
+        match self.short()? {
+            0x0000 => {
+                self.string()?;
+            }
+            0x0020 | 0x0022 => self.skip_option()?,
+            0x0021 => {
+                self.skip_option()?;
+                self.skip_option()?;
+            }
+            0x0031 => {
+                for _ in 0..self.short()? {
+                    self.skip_option()?;
+                }
+            }
+            0x0030 => anyhow::bail!("user-defined types are not supported"),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A connection running one request at a time.
+struct Connection {
+    address: SocketAddr,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn open(address: SocketAddr, credentials: Option<(&str, &str)>) -> anyhow::Result<Connection> {
+        let stream = TcpStream::connect(address)
+            .map_err(|e| anyhow::anyhow!("connecting to {}: {}", address, e))?;
+        stream.set_nodelay(true)?;
+        let mut connection = Connection {
+            address,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        };
+        let mut options = Vec::new();
+        options.extend_from_slice(&1u16.to_be_bytes());
+        put_string(&mut options, "CQL_VERSION");
+        put_string(&mut options, "3.0.0");
+        match connection.request(STARTUP, &options)? {
+            (READY, _) => {}
+            (AUTHENTICATE, _) => {
+                let Some((username, password)) = credentials else {
+                    anyhow::bail!("{} requires --username and --password", address);
+                };
+                let mut token = Vec::new();
+                put_bytes(
+                    &mut token,
+                    format!("\0{}\0{}", username, password).as_bytes(),
+                );
+                if connection.request(AUTH_RESPONSE, &token)?.0 != AUTH_SUCCESS {
+                    anyhow::bail!("{} did not accept the credentials", address);
+                }
+            }
+            (opcode, _) => anyhow::bail!("{} answered STARTUP with opcode {}", address, opcode),
+        }
+        Ok(connection)
+    }
+
+    /// Sends a request and returns the opcode and body of the response, or
+    /// the error the server sent instead.
+    fn request(&mut self, opcode: u8, body: &[u8]) -> anyhow::Result<(u8, Vec<u8>)> {
+        self.writer.write_all(&[VERSION, 0, 0, 0, opcode])?;
+        self.writer.write_all(&(body.len() as u32).to_be_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.flush()?;
+        let mut header = [0u8; 9];
+        self.reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header[5..9].try_into()?) as usize;
+        let mut response = vec![0u8; len];
+        self.reader.read_exact(&mut response)?;
+        if header[4] == ERROR {
+            let mut body = Body(&response);
+            let code = body.int()?;
+            anyhow::bail!(
+                "{}: {} (error 0x{:04x})",
+                self.address,
+                body.string()?,
+                code
+            );
+        }
+        Ok((header[4], response))
+    }
+
+    /// Runs a query and returns the values of its rows.
+    fn query(&mut self, cql: &str) -> anyhow::Result<Vec<Vec<Option<Vec<u8>>>>> {
+        let mut body = Vec::new();
+        put_long_string(&mut body, cql);
+        body.extend_from_slice(&Consistency::One.code().to_be_bytes());
+        body.push(0);
+        let (_, response) = self.request(QUERY, &body)?;
+        let mut body = Body(&response);
+        if body.int()? != ROWS {
+            anyhow::bail!("{}: {:?} returned no rows", self.address, cql);
+        }
+        let flags = body.int()?;
+        let columns = body.int()?;
+        if flags & 0x0002 != 0 {
+            body.bytes()?;
+        }
+        let global = flags & 0x0001 != 0;
+        if global {
+            body.string()?;
+            body.string()?;
+        }
+        if flags & 0x0004 == 0 {
+            for _ in 0..columns {
+                if !global {
+                    body.string()?;
+                    body.string()?;
+                }
+                body.string()?;
+                body.skip_option()?;
+            }
+        }
+        (0..body.int()?)
+            .map(|_| {
+                (0..columns)
+                    .map(|_| Ok(body.bytes()?.map(<[u8]>::to_vec)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Prepares a statement and returns its id.
+    fn prepare(&mut self, cql: &str) -> anyhow::Result<Vec<u8>> {
+        let mut body = Vec::new();
+        put_long_string(&mut body, cql);
+        let (_, response) = self.request(PREPARE, &body)?;
+        let mut body = Body(&response);
+        if body.int()? != PREPARED {
+            anyhow::bail!("{}: preparing {:?} failed", self.address, cql);
+        }
+        Ok(body.short_bytes()?.to_vec())
+    }
+}
+
+/// The tokens in a `set<text>` of them.
+fn tokens(value: &[u8]) -> anyhow::Result<Vec<i64>> {
+    let mut body = Body(value);
+    (0..body.int()?)
+        .map(|_| {
+            let token = body.bytes()?.unwrap_or_default();
+            Ok(std::str::from_utf8(token)?.parse()?)
+        })
+        .collect()
+}
+
+const C1: u64 = 0x87c3_7b91_1142_53d5;
+const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+fn fmix(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+/// The token Murmur3Partitioner gives a partition key: the first half of
+/// its MurmurHash3 x64 128, with the tail bytes sign-extended as Cassandra
+/// does.
+fn token(key: &[u8]) -> i64 {
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let mut blocks = key.chunks_exact(16);
+    for block in &mut blocks {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+    let tail = blocks.remainder();
+    let (mut k1, mut k2) = (0u64, 0u64);
+    for (i, &byte) in tail.iter().enumerate() {
+        let byte = byte as i8 as i64 as u64;
+        if i < 8 {
+            k1 ^= byte << (i * 8);
+        } else {
+            k2 ^= byte << ((i - 8) * 8);
+        }
+    }
+    if tail.len() > 8 {
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+    h1 ^= key.len() as u64;
+    h2 ^= key.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1).wrapping_add(fmix(h2));
+    match h1 as i64 {
+        i64::MIN => i64::MAX,
+        token => token,
+    }
+}
+
+/// An `inet` value.
+fn inet(value: Option<&[u8]>) -> Option<IpAddr> {
+    match value? {
+        &[a, b, c, d] => Some([a, b, c, d].into()),
+        bytes => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+    }
+}
+
+/// Which node owns which tokens: a node owns the tokens after the one
+/// before its own, up to its own.
+struct Ring {
+    nodes: Vec<SocketAddr>,
+    tokens: BTreeMap<i64, usize>,
+}
+
+impl Ring {
+    fn discover(connection: &mut Connection) -> anyhow::Result<Ring> {
+        let mut ring = Ring {
+            nodes: vec![connection.address],
+            tokens: BTreeMap::new(),
+        };
+        for row in connection.query("SELECT tokens FROM system.local")? {
+            for token in tokens(row[0].as_deref().unwrap_or_default())? {
+                ring.tokens.insert(token, 0);
+            }
+        }
+        for row in connection.query("SELECT peer, rpc_address, tokens FROM system.peers")? {
+            // A node whose rpc_address is the wildcard listens on every
+            // interface, the one it talks to its peers on included.
+            let address = match (inet(row[1].as_deref()), inet(row[0].as_deref())) {
+                (Some(rpc_address), _) if !rpc_address.is_unspecified() => rpc_address,
+                (_, Some(peer)) => peer,
+                _ => continue,
+            };
+            let node = ring.nodes.len();
+            ring.nodes
+                .push(SocketAddr::new(address, connection.address.port()));
+            for token in tokens(row[2].as_deref().unwrap_or_default())? {
+                ring.tokens.insert(token, node);
+            }
+        }
+        Ok(ring)
+    }
+
+    fn owner(&self, token: i64) -> usize {
+        self.tokens
+            .range(token..)
+            .next()
+            .or_else(|| self.tokens.iter().next())
+            .map_or(0, |(_, &node)| node)
+    }
+}
+
+/// `value` as a minimal two's complement varint: no leading byte that only
+/// repeats the sign of the next.
+fn varint(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = (0..7)
+        .take_while(|&i| {
+            (bytes[i] == 0 && bytes[i + 1] < 0x80) || (bytes[i] == 0xff && bytes[i + 1] >= 0x80)
+        })
+        .count();
+    bytes[skip..].to_vec()
+}
+
+/// The bound values of a row, in the order of the INSERT's columns.
+fn values(schema: &SchemaConfig, row: &Row) -> anyhow::Result<[Vec<u8>; 3]> {
+    let created = match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), TimeColumn::Bigint) => datetime.timestamp_micros(),
+        (Timestamp::Value(datetime), _) => datetime.timestamp_millis(),
+        (_, TimeColumn::Bigint) => {
+            anyhow::bail!("a bigint time column cannot store infinite timestamps")
+        }
+        (Timestamp::PosInfinity, _) => i64::MAX,
+        (Timestamp::NegInfinity, _) => i64::MIN,
+    };
+    let temperature = match schema.numeric_scale {
+        Some(_) if !row.2.is_finite() => {
+            anyhow::bail!("a CQL decimal cannot store a {} temperature", row.2)
+        }
+        Some(scale) => {
+            // The scale, then the unscaled value.
+            let unscaled = (row.2 * 10f64.powi(scale as i32)).round() as i64;
+            let mut value = (scale as i32).to_be_bytes().to_vec();
+            value.extend_from_slice(&varint(unscaled));
+            value
+        }
+        None => row.2.to_be_bytes().to_vec(),
+    };
+    Ok([
+        created.to_be_bytes().to_vec(),
+        row.1.to_be_bytes().to_vec(),
+        temperature,
+    ])
+}
+
+/// A node's queue of batches, drained by its own threads.
+struct Node {
+    batches: Option<SyncSender<Vec<u8>>>,
+    workers: Vec<JoinHandle<anyhow::Result<usize>>>,
+}
+
+pub struct CqlSink {
+    ring: Ring,
+    nodes: Vec<Node>,
+    prepared: Vec<u8>,
+    consistency: Consistency,
+    batch_rows: usize,
+    schema: SchemaConfig,
+    bytes: u64,
+}
+
+impl CqlSink {
+    /// Inserts into `--table`, or else `name`, in `--keyspace`.
+    pub fn new(args: &CqlArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<CqlSink> {
+        if args.concurrency == 0 || args.batch_rows == 0 {
+            anyhow::bail!("--concurrency and --batch-rows must be positive");
+        }
+        let contact_point = args
+            .contact_point
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{} does not resolve", args.contact_point))?;
+        let credentials = args.username.as_deref().zip(args.password.as_deref());
+        let mut connection = Connection::open(contact_point, credentials)?;
+        let ring = Ring::discover(&mut connection)?;
+        println!(
+            "Nodes: {}",
+            ring.nodes
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let insert = format!(
+            "INSERT INTO {}.{} (created, sensor_id, temperature) VALUES (?, ?, ?)",
+            args.keyspace,
+            args.table.as_deref().unwrap_or(name)
+        );
+        let prepared = connection.prepare(&insert)?;
+        let mut nodes = Vec::with_capacity(ring.nodes.len());
+        for &address in &ring.nodes {
+            // A batch waits for a free connection rather than piling up.
+            let (batches, queue) = mpsc::sync_channel::<Vec<u8>>(0);
+            let queue = Arc::new(Mutex::new(queue));
+            let mut workers = Vec::with_capacity(args.concurrency);
+            for _ in 0..args.concurrency {
+                let mut connection = Connection::open(address, credentials)?;
+                if connection.prepare(&insert)? != prepared {
+                    anyhow::bail!("{} prepared the INSERT as another statement", address);
+                }
+                let queue = queue.clone();
+                workers.push(thread::spawn(move || {
+                    let mut sent = 0;
+                    loop {
+                        let batch = match queue.lock().unwrap().recv() {
+                            Ok(batch) => batch,
+                            Err(_) => return Ok(sent),
+                        };
+                        if connection.request(BATCH, &batch)?.0 != RESULT {
+                            anyhow::bail!("{} answered a BATCH with no result", address);
+                        }
+                        sent += 1;
+                    }
+                }));
+            }
+            nodes.push(Node {
+                batches: Some(batches),
+                workers,
+            });
+        }
+        Ok(CqlSink {
+            ring,
+            nodes,
+            prepared,
+            consistency: args.consistency,
+            batch_rows: args.batch_rows,
+            schema: *schema,
+            bytes: 0,
+        })
+    }
+
+    /// Waits for the threads, returning the batches sent or the first error
+    /// any of them hit.
+    fn join(&mut self) -> anyhow::Result<usize> {
+        let mut sent = 0;
+        for node in &mut self.nodes {
+            node.batches = None;
+            for worker in node.workers.drain(..) {
+                sent += worker
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("a CQL worker panicked"))??;
+            }
+        }
+        Ok(sent)
+    }
+}
+
+impl Sink for CqlSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let mut partitions: BTreeMap<i32, Vec<&Row>> = BTreeMap::new();
+        for row in batch {
+            partitions.entry(row.1).or_default().push(row);
+        }
+        for (sensor_id, rows) in partitions {
+            let node = self.ring.owner(token(&sensor_id.to_be_bytes()));
+            for rows in rows.chunks(self.batch_rows) {
+                let mut body = vec![UNLOGGED];
+                body.extend_from_slice(&(rows.len() as u16).to_be_bytes());
+                for row in rows {
+                    body.push(1);
+                    body.extend_from_slice(&(self.prepared.len() as u16).to_be_bytes());
+                    body.extend_from_slice(&self.prepared);
+                    body.extend_from_slice(&3u16.to_be_bytes());
+                    for value in values(&self.schema, row)? {
+                        self.bytes += value.len() as u64;
+                        put_bytes(&mut body, &value);
+                    }
+                }
+                body.extend_from_slice(&self.consistency.code().to_be_bytes());
+                body.push(0);
+                let sent = self.nodes[node]
+                    .batches
+                    .as_ref()
+                    .map(|batches| batches.send(body));
+                if let Some(Err(_)) = sent {
+                    // Every thread of the node stopped, so at least one failed.
+                    self.join()?;
+                    anyhow::bail!("inserting into {} stopped", self.ring.nodes[node]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<u64> {
+        println!("Batches: {}", self.join()?);
+        Ok(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_match_murmur3_partitioner() {
+        // SELECT token(id) for int partition keys 1 to 3.
+        for (id, expected) in [
+            (1i32, -4_069_959_284_402_364_209),
+            (2, -3_248_873_570_005_575_792),
+            (3, 9_010_454_139_840_013_625),
+        ] {
+            assert_eq!(token(&id.to_be_bytes()), expected, "{}", id);
+        }
+    }
+
+    #[test]
+    fn varints_are_minimal_twos_complement() {
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (127, &[0x7f]),
+            (128, &[0x00, 0x80]),
+            (-127, &[0x81]),
+            (-128, &[0x80]),
+            (-129, &[0xff, 0x7f]),
+            (i64::MAX, &[0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            (i64::MIN, &[0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ] {
+            assert_eq!(varint(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn decimals_carry_their_scale_before_the_varint() {
+        let schema = SchemaConfig {
+            time_column: TimeColumn::Timestamptz,
+            numeric_scale: Some(2),
+        };
+        let row = (Timestamp::PosInfinity, 1, -1.29);
+        assert_eq!(values(&schema, &row).unwrap()[2], [0, 0, 0, 2, 0xff, 0x7f]);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::export::{self, ExportFormat, ExportStats};
+use crate::schema::SchemaConfig;
+use crate::FastGenError;
+use postgres::Client;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    path::Path,
+    time::Instant,
+};
+
+pub struct DumpRestoreStats {
+    pub dump: ExportStats,
+    pub restore: ExportStats,
+    pub rows: i64,
+}
+
+fn count_rows(client: &mut Client, table_name: &str) -> crate::Result<i64> {
+    Ok(client
+        .query_one(&format!("SELECT count(*) FROM {}", table_name), &[])?
+        .get(0))
+}
+
+/// Loads a dump written by `export::export` into `table_name` with
+/// `COPY ... FROM STDIN`, streaming it from disk.
+fn restore(
+    client: &mut Client,
+    table_name: &str,
+    format: ExportFormat,
+    path: &Path,
+) -> crate::Result<ExportStats> {
+    let started = Instant::now();
+    let mut file = BufReader::new(File::open(path)?);
+    let mut writer = client.copy_in(&format!(
+        "COPY {} FROM STDIN WITH (FORMAT {})",
+        table_name,
+        format.name()
+    ))?;
+    let bytes = io::copy(&mut file, &mut writer)?;
+    writer.flush()?;
+    writer.finish()?;
+
+    Ok(ExportStats {
+        bytes,
+        elapsed: started.elapsed(),
+        files: Vec::new(),
+    })
+}
+
+/// Dumps `table_name` to a file in `dump_dir`, then restores the file into a
+/// fresh `target_table` created like the source, timing each direction. The
+/// dump file and the target table are removed afterwards unless `keep` is set.
+pub fn dump_restore(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+    target_table: &str,
+    format: ExportFormat,
+    dump_dir: &Path,
+    keep: bool,
+) -> crate::Result<DumpRestoreStats> {
+    let rows = count_rows(client, table_name)?;
+    let path = export::output_path(dump_dir, table_name, format, None);
+
+    let dump = export::export(
+        client,
+        schema,
+        table_name,
+        format,
+        Some(dump_dir),
+        None,
+        false,
+    )?;
+
+    client.batch_execute(&format!(
+        "DROP TABLE IF EXISTS {target};
+         CREATE TABLE {target} (LIKE {source} INCLUDING ALL)",
+        target = target_table,
+        source = table_name
+    ))?;
+    let restore = restore(client, target_table, format, &path)?;
+
+    let restored = count_rows(client, target_table)?;
+    if !keep {
+        client.batch_execute(&format!("DROP TABLE {}", target_table))?;
+        fs::remove_file(&path)?;
+    }
+    if restored != rows {
+        return Err(FastGenError::Interrupted(format!(
+            "restored {} rows into {}, but {} has {}",
+            restored, target_table, table_name, rows
+        )));
+    }
+
+    Ok(DumpRestoreStats {
+        dump,
+        restore,
+        rows,
+    })
+}
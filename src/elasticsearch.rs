@@ -0,0 +1,219 @@
+//! `sink elasticsearch`: indexes the rows as documents in an Elasticsearch
+//! or OpenSearch index through the `_bulk` API, `--bulk-rows` documents per
+//! request, optionally with the index's refresh interval changed for the
+//! load, so search-engine ingest can be compared with the database paths.
+
+use crate::cli::ElasticsearchArgs;
+use crate::sink::Sink;
+use fast_generation::generator::Row;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use ureq::Agent;
+
+pub struct ElasticsearchSink {
+    agent: Agent,
+    index_url: String,
+    headers: Vec<(String, String)>,
+    bulk_rows: usize,
+    /// The refresh interval to put back after loading, if it was changed.
+    previous_refresh_interval: Option<Value>,
+    schema: SchemaConfig,
+    body: Vec<u8>,
+    documents: usize,
+    requests: usize,
+    took: Duration,
+    bytes: u64,
+}
+
+impl ElasticsearchSink {
+    /// Indexes into `--index`, or else `name`.
+    pub fn new(
+        args: &ElasticsearchArgs,
+        schema: &SchemaConfig,
+        name: &str,
+    ) -> anyhow::Result<ElasticsearchSink> {
+        if args.bulk_rows == 0 {
+            anyhow::bail!("--bulk-rows must be positive");
+        }
+        let mut sink = ElasticsearchSink {
+            agent: Agent::config_builder()
+                .http_status_as_error(false)
+                .build()
+                .into(),
+            index_url: format!(
+                "{}/{}",
+                args.url.trim_end_matches('/'),
+                args.index.as_deref().unwrap_or(name)
+            ),
+            headers: args.headers.clone(),
+            bulk_rows: args.bulk_rows,
+            previous_refresh_interval: None,
+            schema: *schema,
+            body: Vec::new(),
+            documents: 0,
+            requests: 0,
+            took: Duration::ZERO,
+            bytes: 0,
+        };
+        if let Some(interval) = &args.refresh_interval {
+            let (status, settings) = sink.request("GET", "_settings", None)?;
+            let previous = if status == 404 {
+                // A new index gets the interval from the start.
+                sink.expect_success(sink.request(
+                    "PUT",
+                    "",
+                    Some(json!({ "settings": { "index": { "refresh_interval": interval } } })),
+                )?)?;
+                Value::Null
+            } else {
+                let settings = sink.expect_success((status, settings))?;
+                let previous = settings
+                    .as_object()
+                    .and_then(|indexes| indexes.values().next())
+                    .map(|index| index["settings"]["index"]["refresh_interval"].clone())
+                    .unwrap_or(Value::Null);
+                sink.set_refresh_interval(json!(interval))?;
+                previous
+            };
+            sink.previous_refresh_interval = Some(previous);
+        }
+        Ok(sink)
+    }
+
+    /// Sends a request to the index, or to `path` under it, and returns the
+    /// status and the JSON body.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> anyhow::Result<(u16, Value)> {
+        let url = match path {
+            "" => self.index_url.clone(),
+            path => format!("{}/{}", self.index_url, path),
+        };
+        let mut request = ureq::http::Request::builder()
+            .method(method)
+            .uri(&url)
+            .header("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut response = self
+            .agent
+            .run(request.body(body)?)
+            .map_err(|e| anyhow::anyhow!("{} {}: {}", method, url, e))?;
+        let status = response.status().as_u16();
+        let text = response.body_mut().read_to_string()?;
+        Ok((
+            status,
+            serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        ))
+    }
+
+    fn expect_success(&self, (status, body): (u16, Value)) -> anyhow::Result<Value> {
+        if !(200..300).contains(&status) {
+            anyhow::bail!("{}: {}: {}", self.index_url, status, body);
+        }
+        Ok(body)
+    }
+
+    fn set_refresh_interval(&self, interval: Value) -> anyhow::Result<()> {
+        self.expect_success(self.request(
+            "PUT",
+            "_settings",
+            Some(json!({ "index": { "refresh_interval": interval } })),
+        )?)?;
+        Ok(())
+    }
+
+    /// Sends the documents buffered in one `_bulk` request.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.documents == 0 {
+            return Ok(());
+        }
+        let url = format!("{}/_bulk", self.index_url);
+        let mut request = self
+            .agent
+            .post(&url)
+            .header("Content-Type", "application/x-ndjson");
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let mut response = request
+            .send(&self.body[..])
+            .map_err(|e| anyhow::anyhow!("indexing into {}: {}", url, e))?;
+        let status = response.status();
+        let text = response.body_mut().read_to_string()?;
+        if !status.is_success() {
+            anyhow::bail!("indexing into {}: {}: {}", url, status, text);
+        }
+        // A bulk request succeeds as a whole even when documents fail.
+        let result: Value = serde_json::from_str(&text)?;
+        if result["errors"].as_bool() == Some(true) {
+            let items = result["items"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let failed: Vec<_> = items
+                .iter()
+                .filter_map(|item| item["index"]["error"].as_object())
+                .collect();
+            anyhow::bail!(
+                "indexing into {}: {} of {} documents failed, the first with {}: {}",
+                url,
+                failed.len(),
+                self.documents,
+                failed
+                    .first()
+                    .and_then(|error| error.get("type"))
+                    .unwrap_or(&Value::Null),
+                failed
+                    .first()
+                    .and_then(|error| error.get("reason"))
+                    .unwrap_or(&Value::Null)
+            );
+        }
+        self.took += Duration::from_millis(result["took"].as_u64().unwrap_or(0));
+        self.requests += 1;
+        self.bytes += self.body.len() as u64;
+        self.body.clear();
+        self.documents = 0;
+        Ok(())
+    }
+}
+
+impl Sink for ElasticsearchSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        for row in batch {
+            self.body.extend_from_slice(b"{\"index\":{}}\n");
+            self.body
+                .extend_from_slice(serialize::json_object(&self.schema, row).as_bytes());
+            self.body.push(b'\n');
+            self.documents += 1;
+            if self.documents == self.bulk_rows {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what's left and refreshes the index, so that loading counts
+    /// until every document is searchable.
+    fn finish(mut self: Box<Self>) -> anyhow::Result<u64> {
+        self.flush()?;
+        if let Some(previous) = self.previous_refresh_interval.take() {
+            self.set_refresh_interval(previous)?;
+        }
+        self.expect_success(self.request("POST", "_refresh", None)?)?;
+        println!(
+            "Bulk requests: {} ({:.2}s taken server-side)",
+            self.requests,
+            self.took.as_secs_f64()
+        );
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,120 @@
+//! What results depend on besides the run settings: the server, its
+//! configuration, the client build and the machine it runs on, recorded so
+//! that results can still be interpreted months later.
+
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Server settings that bear on loading speed.
+pub const SETTINGS: [&str; 9] = [
+    "server_version",
+    "shared_buffers",
+    "wal_level",
+    "max_wal_size",
+    "synchronous_commit",
+    "fsync",
+    "full_page_writes",
+    "wal_compression",
+    "checkpoint_timeout",
+];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Environment {
+    /// `version()`, e.g. "PostgreSQL 16.2 on x86_64-pc-linux-gnu, ...".
+    pub server_version: String,
+    /// Address and port of the server that took the connection, which of
+    /// several hosts given that was, or the socket directory for a Unix
+    /// socket; marked as a standby when in recovery.
+    #[serde(default)]
+    pub server_address: Option<String>,
+    /// `SETTINGS` as the server shows them, with units.
+    pub settings: BTreeMap<String, String>,
+    pub tool_version: String,
+    /// Commit the tool was built from, with "-dirty" for local changes;
+    /// unknown outside a git checkout.
+    pub git_revision: Option<String>,
+    pub rustc_version: Option<String>,
+    /// Versions of the Postgres client crates, unknown outside a checkout.
+    pub client_crates: BTreeMap<String, String>,
+    /// Of the client; only read on Linux.
+    pub cpu_model: Option<String>,
+    pub cpus: usize,
+}
+
+impl Environment {
+    pub fn capture(client: &mut Client) -> crate::Result<Environment> {
+        let server_version = client.query_one("SELECT version()", &[])?.get(0);
+        let server_address = client
+            .query_one(
+                "SELECT coalesce(host(inet_server_addr()), \
+                         split_part(current_setting('unix_socket_directories'), ',', 1)) \
+                     || ':' || current_setting('port') \
+                     || CASE WHEN pg_is_in_recovery() THEN ' (standby)' ELSE '' END",
+                &[],
+            )?
+            .get(0);
+        let settings = client
+            .query(
+                "SELECT name, current_setting(name) FROM unnest($1::text[]) AS name",
+                &[&&SETTINGS[..]],
+            )?
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
+        Ok(Environment {
+            server_version,
+            server_address,
+            settings,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_revision: option_env!("FAST_GENERATION_GIT_REVISION").map(str::to_string),
+            rustc_version: option_env!("FAST_GENERATION_RUSTC_VERSION").map(str::to_string),
+            client_crates: env!("FAST_GENERATION_CLIENT_CRATES")
+                .split(',')
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(name, version)| (name.to_string(), version.to_string()))
+                .collect(),
+            cpu_model: cpu_model(),
+            cpus: std::thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+        })
+    }
+
+    /// The settings that differ from those of `baseline`, by name.
+    pub fn changed_settings(&self, baseline: &Environment) -> Vec<SettingChange> {
+        let names: BTreeSet<_> = baseline
+            .settings
+            .keys()
+            .chain(self.settings.keys())
+            .collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let before = baseline.settings.get(name);
+                let after = self.settings.get(name);
+                (before != after).then(|| SettingChange {
+                    name: name.clone(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A setting that differs between two environments; `None` where one of
+/// them didn't record it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "model name").then(|| value.trim().to_string())
+    })
+}
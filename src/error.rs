@@ -0,0 +1,98 @@
+use std::io;
+use thiserror::Error;
+
+/// Errors of the library, by kind, so that callers can decide whether a
+/// failure is worth retrying.
+#[derive(Debug, Error)]
+pub enum FastGenError {
+    /// Connecting failed, the connection broke, or the server rejected a
+    /// statement; `postgres::Error::code` tells these apart.
+    #[cfg(feature = "postgres")]
+    #[error(transparent)]
+    Connection(#[from] postgres::Error),
+
+    /// A statement given by the user, such as a setup hook, failed.
+    #[cfg(feature = "postgres")]
+    #[error("running {statement:?}")]
+    Statement {
+        statement: String,
+        #[source]
+        source: postgres::Error,
+    },
+
+    /// A value or stream can't be represented in, or read from, the wire
+    /// format.
+    #[error("{0}")]
+    Encoding(String),
+
+    /// The target table doesn't match the columns the generator produces.
+    #[error("{0}")]
+    SchemaMismatch(String),
+
+    /// Work stopped before it was done, e.g. because a worker panicked.
+    #[error("{0}")]
+    Interrupted(String),
+
+    /// A strategy ran past its deadline; what it was running was cancelled.
+    #[error("{0}")]
+    TimedOut(String),
+
+    /// Reading or writing a file or stream failed.
+    #[error(transparent)]
+    SinkError(io::Error),
+
+    /// A row read back from the table isn't the one that was loaded.
+    #[error("{0}")]
+    Validation(String),
+
+    /// An option is out of range or contradicts another.
+    #[error("{0}")]
+    InvalidConfig(String),
+}
+
+impl FastGenError {
+    /// The message followed by those of its causes, separated by colons, as
+    /// `postgres::Error` alone only says "db error".
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            report.push_str(": ");
+            report.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        report
+    }
+
+    /// Whether the connection broke, or the server ended the session, rather
+    /// than the server rejecting a statement, so a new connection may do.
+    #[cfg(feature = "postgres")]
+    pub fn is_connection_lost(&self) -> bool {
+        let FastGenError::Connection(e) = self else {
+            return false;
+        };
+        match e.as_db_error() {
+            Some(db) => matches!(db.severity(), "FATAL" | "PANIC"),
+            None => e.code().is_none(),
+        }
+    }
+}
+
+impl From<io::Error> for FastGenError {
+    /// The postgres crate's COPY readers and writers report its errors as
+    /// `io::Error`; those are unwrapped into `Connection`.
+    fn from(e: io::Error) -> Self {
+        #[cfg(feature = "postgres")]
+        if e.get_ref()
+            .is_some_and(|inner| inner.is::<postgres::Error>())
+        {
+            if let Ok(inner) = e.into_inner().unwrap().downcast::<postgres::Error>() {
+                return FastGenError::Connection(*inner);
+            }
+            unreachable!("checked to be a postgres::Error");
+        }
+        FastGenError::SinkError(e)
+    }
+}
+
+pub type Result<T, E = FastGenError> = std::result::Result<T, E>;
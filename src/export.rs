@@ -0,0 +1,453 @@
+use crate::generator::{Created, Timestamp};
+use crate::pgcopy::PgCopyBinaryReader;
+use crate::schema::SchemaConfig;
+use crate::FastGenError;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use postgres::Client;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ExportFormat {
+    Text,
+    Csv,
+    Binary,
+}
+
+impl ExportFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Text => "text",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Binary => "binary",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Text => "tsv",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Binary => "pgcopy",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<ExportFormat> {
+        [ExportFormat::Text, ExportFormat::Csv, ExportFormat::Binary]
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gz",
+            Codec::Zstd => "zst",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Codec> {
+        [Codec::Gzip, Codec::Zstd]
+            .into_iter()
+            .find(|codec| codec.extension() == extension)
+    }
+
+    /// Decompresses what's read through it.
+    pub fn decoder<'a, R: Read + 'a>(self, inner: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Codec::Gzip => Box::new(MultiGzDecoder::new(inner)),
+            Codec::Zstd => Box::new(zstd::Decoder::new(inner)?),
+        })
+    }
+
+    fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => 1..=22,
+        }
+    }
+}
+
+/// How exported files are compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    pub codec: Codec,
+    /// Higher is smaller and slower; unset means the codec's default, 6
+    /// for gzip and 3 for zstd.
+    pub level: Option<i32>,
+}
+
+impl Compression {
+    pub fn validate(&self) -> crate::Result<()> {
+        match self.level {
+            Some(level) if !self.codec.levels().contains(&level) => {
+                Err(FastGenError::InvalidConfig(format!(
+                    "{} compression levels run from {} to {}, not {}",
+                    self.codec.name(),
+                    self.codec.levels().start(),
+                    self.codec.levels().end(),
+                    level
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encoder<W: Write>(&self, inner: W) -> io::Result<Encoder<W>> {
+        Ok(match self.codec {
+            Codec::Gzip => Encoder::Gzip(GzEncoder::new(
+                inner,
+                self.level.map_or(flate2::Compression::default(), |level| {
+                    flate2::Compression::new(level as u32)
+                }),
+            )),
+            Codec::Zstd => Encoder::Zstd(zstd::Encoder::new(
+                inner,
+                self.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+            )?),
+        })
+    }
+}
+
+/// Where `export` writes a table's data inside `output_dir`.
+pub fn output_path(
+    output_dir: &Path,
+    table_name: &str,
+    format: ExportFormat,
+    compression: Option<Compression>,
+) -> PathBuf {
+    let mut name = format!("{}.{}", table_name, format.extension());
+    if let Some(compression) = compression {
+        name = format!("{}.{}", name, compression.codec.extension());
+    }
+    output_dir.join(name)
+}
+
+pub struct ExportStats {
+    /// COPY data received, before any compression.
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Files written, none unless an output directory was given.
+    pub files: Vec<WrittenFile>,
+}
+
+/// Counts the bytes read through it, so every sink reports the same figure.
+struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+/// Hashes and counts the bytes written through it, so a file's digest
+/// costs no second read.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The format and compression of a file named the way `output_path` names
+/// them, e.g. `metrics.csv.zst`.
+pub fn file_format(path: &Path) -> Option<(ExportFormat, Option<Codec>)> {
+    let name = path.file_name()?.to_str()?;
+    let (rest, extension) = name.rsplit_once('.')?;
+    match Codec::from_extension(extension) {
+        Some(codec) => {
+            let (_, extension) = rest.rsplit_once('.')?;
+            Some((ExportFormat::from_extension(extension)?, Some(codec)))
+        }
+        None => Some((ExportFormat::from_extension(extension)?, None)),
+    }
+}
+
+/// Compresses what's written through it, if asked to.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes out whatever the codec still holds and returns the writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(inner) => Ok(inner),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(inner) => inner.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(inner) => inner.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A file written by an export.
+pub struct WrittenFile {
+    pub path: PathBuf,
+    /// Size on disk.
+    pub bytes: u64,
+    /// SHA-256 of the contents, in hex.
+    pub sha256: String,
+}
+
+/// An export file being written: compressed if asked to, hashed, and
+/// synced to disk once finished.
+struct FileSink {
+    path: PathBuf,
+    encoder: Encoder<HashingWriter<BufWriter<File>>>,
+}
+
+impl FileSink {
+    fn create(path: PathBuf, compression: Option<Compression>) -> crate::Result<FileSink> {
+        let file = HashingWriter {
+            inner: BufWriter::new(File::create(&path)?),
+            hasher: Sha256::new(),
+            bytes: 0,
+        };
+        let encoder = match &compression {
+            Some(compression) => compression.encoder(file)?,
+            None => Encoder::Plain(file),
+        };
+        Ok(FileSink { path, encoder })
+    }
+
+    /// Bytes that reached the file so far; a compressor may hold more.
+    fn written(&self) -> u64 {
+        match &self.encoder {
+            Encoder::Plain(file) => file.bytes,
+            Encoder::Gzip(encoder) => encoder.get_ref().bytes,
+            Encoder::Zstd(encoder) => encoder.get_ref().bytes,
+        }
+    }
+
+    fn finish(self) -> crate::Result<WrittenFile> {
+        let file = self.encoder.finish()?;
+        file.inner
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(WrittenFile {
+            path: self.path,
+            bytes: file.bytes,
+            sha256: format!("{:x}", file.hasher.finalize()),
+        })
+    }
+}
+
+/// Runs `COPY table TO STDOUT` in the given format and times it end to end.
+/// The data is discarded unless `output_dir` is set; with `decode`, binary
+/// exports are parsed into rows so the client-side cost is included.
+/// Written files are compressed with `compression`, which is timed too.
+pub fn export(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+    format: ExportFormat,
+    output_dir: Option<&Path>,
+    compression: Option<Compression>,
+    decode: bool,
+) -> crate::Result<ExportStats> {
+    if let Some(compression) = &compression {
+        compression.validate()?;
+    }
+    let started = Instant::now();
+    let mut reader = CountingReader {
+        inner: client.copy_out(&format!(
+            "COPY {} TO STDOUT WITH (FORMAT {})",
+            table_name,
+            format.name()
+        ))?,
+        bytes: 0,
+    };
+
+    let mut files = Vec::new();
+    if decode && format == ExportFormat::Binary {
+        for row in PgCopyBinaryReader::new(&mut reader, schema)? {
+            row?;
+        }
+    } else if let Some(dir) = output_dir {
+        let mut file = FileSink::create(
+            output_path(dir, table_name, format, compression),
+            compression,
+        )?;
+        io::copy(&mut reader, &mut file.encoder)?;
+        files.push(file.finish()?);
+    } else {
+        io::copy(&mut reader, &mut io::sink())?;
+    }
+
+    Ok(ExportStats {
+        bytes: reader.bytes,
+        elapsed: started.elapsed(),
+        files,
+    })
+}
+
+/// Directory levels of a Hive-style partitioned export, by `created` in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Partitioning {
+    /// date=YYYY-MM-DD
+    Day,
+    /// date=YYYY-MM-DD/hour=HH
+    Hour,
+}
+
+impl Partitioning {
+    /// The directory rows created at `created` go to, relative to the
+    /// output directory. Infinite timestamps go where Hive puts NULLs.
+    fn directory(self, created: Created) -> String {
+        const DEFAULT: &str = "__HIVE_DEFAULT_PARTITION__";
+        let (date, hour) = match created {
+            Timestamp::Value(datetime) => (
+                datetime.format("%Y-%m-%d").to_string(),
+                datetime.format("%H").to_string(),
+            ),
+            _ => (DEFAULT.to_string(), DEFAULT.to_string()),
+        };
+        match self {
+            Partitioning::Day => format!("date={}", date),
+            Partitioning::Hour => format!("date={}/hour={}", date, hour),
+        }
+    }
+}
+
+/// Like `export` into `output_dir`, but with the rows split into a
+/// directory per partition of `created`, as Hive, DuckDB or Athena read
+/// them, holding files named `part-00000.csv` and on. A partition's rows go
+/// to a new file once the current one reaches `max_file_size` bytes on
+/// disk. The table is read in `created` order, which the server sorts;
+/// binary COPY can't be split by row and isn't supported.
+#[allow(clippy::too_many_arguments)]
+pub fn export_partitioned(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+    format: ExportFormat,
+    output_dir: &Path,
+    compression: Option<Compression>,
+    partitioning: Partitioning,
+    max_file_size: Option<u64>,
+) -> crate::Result<ExportStats> {
+    let separator = match format {
+        ExportFormat::Csv => b',',
+        ExportFormat::Text => b'\t',
+        ExportFormat::Binary => {
+            return Err(FastGenError::InvalidConfig(
+                "partitioned exports are CSV or text, not binary".to_string(),
+            ))
+        }
+    };
+    if let Some(compression) = &compression {
+        compression.validate()?;
+    }
+    let started = Instant::now();
+    let mut reader = BufReader::new(CountingReader {
+        inner: client.copy_out(&format!(
+            "COPY (SELECT * FROM {} ORDER BY created) TO STDOUT WITH (FORMAT {})",
+            table_name,
+            format.name()
+        ))?,
+        bytes: 0,
+    });
+
+    let mut files = Vec::new();
+    // Infinities sort first and last, so their partition comes up twice.
+    let mut parts: HashMap<String, usize> = HashMap::new();
+    let mut current: Option<(String, FileSink)> = None;
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let field = line.split(|&byte| byte == separator).next().unwrap_or(&[]);
+        let field = String::from_utf8_lossy(field);
+        let created = schema
+            .time_column
+            .parse(field.trim_matches('"'))
+            .ok_or_else(|| FastGenError::Encoding(format!("invalid created {:?}", field)))?;
+        let directory = partitioning.directory(created);
+        let full = |(current, file): &(String, FileSink)| {
+            *current != directory || max_file_size.is_some_and(|max| file.written() >= max)
+        };
+        if current.as_ref().is_none_or(full) {
+            if let Some((_, file)) = current.take() {
+                files.push(file.finish()?);
+            }
+            let part = parts.entry(directory.clone()).or_default();
+            let dir = output_dir.join(&directory);
+            fs::create_dir_all(&dir)?;
+            let mut name = format!("part-{:05}.{}", part, format.extension());
+            if let Some(compression) = compression {
+                name = format!("{}.{}", name, compression.codec.extension());
+            }
+            *part += 1;
+            current = Some((directory, FileSink::create(dir.join(name), compression)?));
+        }
+        current.as_mut().unwrap().1.encoder.write_all(&line)?;
+        line.clear();
+    }
+    if let Some((_, file)) = current {
+        files.push(file.finish()?);
+    }
+
+    Ok(ExportStats {
+        bytes: reader.into_inner().bytes,
+        elapsed: started.elapsed(),
+        files,
+    })
+}
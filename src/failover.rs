@@ -0,0 +1,223 @@
+//! The `failover` command, a disaster recovery rehearsal: loads into the
+//! primary until a hot standby is promoted, by this command or by whatever
+//! manages the cluster, switches to the standby, and reports how long
+//! ingestion stalled and whether acknowledged rows went missing.
+
+use crate::cli::FailoverArgs;
+use fast_generation::generator::{generate_data, GeneratorConfig, BASE_TEMP};
+use fast_generation::pool::BufferPool;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::strategy::{StrategyContext, STRATEGIES};
+use fast_generation::tls;
+use fast_generation::FastGenError;
+use postgres::error::SqlState;
+use postgres::{Client, Config};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Batches generated at most; loading stops long before at any real rate.
+const MAX_BATCHES: usize = 10_000_000;
+/// Between checks whether the standby was promoted, and between attempts to
+/// get a batch in while no primary takes it.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn in_recovery(client: &mut Client) -> Result<bool, postgres::Error> {
+    Ok(client.query_one("SELECT pg_is_in_recovery()", &[])?.get(0))
+}
+
+fn count_rows(client: &mut Client, table_name: &str) -> Result<i64, postgres::Error> {
+    let query = format!("SELECT count(*) FROM {}", table_name);
+    Ok(client.query_one(&query, &[])?.get(0))
+}
+
+/// Whether the server loaded into has gone away or stopped taking writes,
+/// as an old primary demoted to a standby does.
+fn primary_lost(e: &FastGenError) -> bool {
+    match e {
+        FastGenError::Connection(inner)
+            if inner.code() == Some(&SqlState::READ_ONLY_SQL_TRANSACTION) =>
+        {
+            true
+        }
+        e => e.is_connection_lost(),
+    }
+}
+
+/// Promotes the standby after `promote_after_secs`, or waits for it to be
+/// promoted otherwise, and records when it left recovery.
+fn watch(
+    mut standby: Client,
+    args: &FailoverArgs,
+    started: Instant,
+    promoted: &Mutex<Option<Instant>>,
+    done: &AtomicBool,
+) -> anyhow::Result<()> {
+    if let Some(secs) = args.promote_after_secs {
+        let due = started + Duration::from_secs(secs);
+        while Instant::now() < due {
+            if done.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        println!("Promoting the standby");
+        if !standby
+            .query_one("SELECT pg_promote()", &[])?
+            .get::<_, bool>(0)
+        {
+            anyhow::bail!("the standby was not promoted within pg_promote()'s 60 seconds");
+        }
+    } else {
+        while in_recovery(&mut standby)? {
+            if done.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    *promoted.lock().unwrap() = Some(Instant::now());
+    Ok(())
+}
+
+pub fn failover(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    args: &FailoverArgs,
+) -> anyhow::Result<()> {
+    generator.validate(MAX_BATCHES)?;
+    let standby_config: Config = args.standby_dsn.parse()?;
+    let mut standby = tls::connect(&standby_config)?;
+    if !in_recovery(&mut standby)? {
+        anyhow::bail!("--standby-dsn points to a server that is not in recovery");
+    }
+    let rows_before = count_rows(&mut standby, table_name)?;
+    let strategy = STRATEGIES
+        .iter()
+        .find(|strategy| strategy.name == args.strategy)
+        .unwrap();
+    let buffers = BufferPool::new();
+    let context = StrategyContext {
+        table_name,
+        schema,
+        buffers: &buffers,
+        copy_chunk_size: None,
+    };
+    let mut client = Some(tls::connect(config)?);
+
+    let started = Instant::now();
+    let deadline =
+        started + Duration::from_secs(args.promote_after_secs.unwrap_or(0) + args.timeout_secs);
+    let promoted = Mutex::new(None);
+    let done = AtomicBool::new(false);
+    println!(
+        "Loading with {} into the primary until the standby is promoted",
+        strategy.name
+    );
+
+    let mut switched = None;
+    let mut last_old_commit = None;
+    let mut first_new_commit = None;
+    let (mut old_rows, mut new_rows, mut failed_attempts) = (0, 0, 0);
+    thread::scope(|scope| -> anyhow::Result<()> {
+        let watcher = scope.spawn(|| watch(standby, args, started, &promoted, &done));
+        let loaded = (|| -> anyhow::Result<()> {
+            for (batch_data, _) in generate_data(generator, BASE_TEMP, MAX_BATCHES) {
+                loop {
+                    if switched.is_none() {
+                        if let Some(at) = *promoted.lock().unwrap() {
+                            println!(
+                                "Standby promoted after {:.2}s, switching to it",
+                                (at - started).as_secs_f64()
+                            );
+                            switched = Some(at);
+                            client = None;
+                        } else if watcher.is_finished() {
+                            return Ok(());
+                        } else if Instant::now() > deadline {
+                            anyhow::bail!(
+                                "the standby was not promoted within {} seconds",
+                                args.timeout_secs
+                            );
+                        }
+                    }
+                    let target = if switched.is_some() {
+                        &standby_config
+                    } else {
+                        config
+                    };
+                    let Some(connection) = &mut client else {
+                        client = tls::connect(target).ok();
+                        if client.is_none() {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        continue;
+                    };
+
+                    match (strategy.encode)(&context, &batch_data)
+                        .and_then(|payload| (strategy.send)(connection, &context, payload))
+                    {
+                        Ok(()) if switched.is_some() => {
+                            first_new_commit.get_or_insert_with(Instant::now);
+                            new_rows += batch_data.len();
+                            break;
+                        }
+                        Ok(()) => {
+                            last_old_commit = Some(Instant::now());
+                            old_rows += batch_data.len();
+                            break;
+                        }
+                        Err(e) if primary_lost(&e) => {
+                            failed_attempts += 1;
+                            client = None;
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                if switched.is_some_and(|at| at.elapsed() >= Duration::from_secs(args.after_secs)) {
+                    break;
+                }
+            }
+            Ok(())
+        })();
+        done.store(true, Ordering::Relaxed);
+        let watched = watcher.join().unwrap();
+        loaded.and(watched)
+    })?;
+    if switched.is_none() {
+        anyhow::bail!("loading stopped before the standby was promoted");
+    }
+
+    let present = count_rows(&mut tls::connect(&standby_config)?, table_name)? - rows_before;
+    let acknowledged = (old_rows + new_rows) as i64;
+    println!();
+    match (last_old_commit, first_new_commit) {
+        (Some(last), Some(first)) => println!(
+            "Ingestion gap: {:.2}s from the last commit on the old primary to the first on the new one",
+            (first - last).as_secs_f64()
+        ),
+        _ => println!("Ingestion gap: unknown, as no batch was committed on one of the two"),
+    }
+    println!(" Failed attempts: {}", failed_attempts);
+    println!(
+        " Rows: {} acknowledged by the old primary, {} by the new one, {} on the new primary",
+        old_rows, new_rows, present
+    );
+    if acknowledged > present {
+        println!(
+            " Lost: {} acknowledged rows are missing from the new primary",
+            acknowledged - present
+        );
+    } else {
+        println!(" Lost: none of the acknowledged rows");
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! The `fdw` command: loads straight into a table, then through a
+//! postgres_fdw foreign table pointing at it with each `batch_size` server
+//! option given, to measure FDW-based ingestion against a direct
+//! connection on the same data.
+
+use crate::cli::FdwArgs;
+use crate::tables;
+use crate::units;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::config::Host;
+use postgres::{Client, Config};
+
+/// The foreign server created on the connected database, and dropped with
+/// its user mapping and foreign table afterwards.
+const SERVER_NAME: &str = "fast_generation_fdw";
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The libpq settings reaching the server `config` connects to, as
+/// keyword and value pairs.
+pub fn libpq_settings(config: &Config) -> anyhow::Result<Vec<(&'static str, String)>> {
+    let host = match config.get_hosts().first() {
+        Some(Host::Tcp(host)) => host.clone(),
+        #[cfg(unix)]
+        Some(Host::Unix(path)) => path.to_string_lossy().into_owned(),
+        None => "localhost".to_string(),
+    };
+    let mut settings = vec![("host", host)];
+    if let Some(port) = config.get_ports().first() {
+        settings.push(("port", port.to_string()));
+    }
+    if let Some(dbname) = config.get_dbname() {
+        settings.push(("dbname", dbname.to_string()));
+    }
+    if let Some(user) = config.get_user() {
+        settings.push(("user", user.to_string()));
+    }
+    if let Some(password) = config.get_password() {
+        settings.push(("password", std::str::from_utf8(password)?.to_string()));
+    }
+    Ok(settings)
+}
+
+/// OPTIONS of the foreign server and the user mapping reaching `remote`.
+fn server_options(remote: &Config) -> anyhow::Result<(String, String)> {
+    let (mut server, mut mapping) = (Vec::new(), Vec::new());
+    for (keyword, value) in libpq_settings(remote)? {
+        let option = format!("{} {}", keyword, quote_literal(&value));
+        match keyword {
+            "user" | "password" => mapping.push(option),
+            _ => server.push(option),
+        }
+    }
+    // Starts unbatched; every load sets the batch_size it measures.
+    server.push("batch_size '1'".to_string());
+    Ok((server.join(", "), mapping.join(", ")))
+}
+
+fn print_comparison(results: &[(String, StrategyResult, i64)]) {
+    let direct = results
+        .first()
+        .filter(|(_, result, _)| result.error.is_none())
+        .map(|(_, result, _)| result.rows_per_second());
+    println!();
+    println!(
+        "{:<24} {:>10} {:>14} {:>10} {:>10}",
+        "target", "rows/s", "bytes/s", "p99 ms", "vs direct"
+    );
+    for (label, result, size) in results {
+        if let Some(e) = &result.error {
+            println!("{:<24} failed: {}", label, e);
+            continue;
+        }
+        let speed = result.rows_per_second();
+        println!(
+            "{:<24} {:>10.0} {:>14} {:>10.1} {:>10}",
+            label,
+            speed,
+            units::rate(*size as f64 / result.elapsed.as_secs_f64()),
+            result
+                .latency
+                .map_or(0.0, |latency| latency.p99.as_secs_f64() * 1000.0),
+            direct.map_or("-".to_string(), |direct| format!(
+                "{:+.1}%",
+                (speed / direct - 1.0) * 100.0
+            ))
+        );
+    }
+}
+
+pub fn fdw(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batch_count: usize,
+    args: &FdwArgs,
+) -> anyhow::Result<()> {
+    let (remote_table, foreign_table) = (&args.remote_table, &args.foreign_table);
+    let remote_config: Config = match &args.remote_dsn {
+        Some(dsn) => dsn.parse()?,
+        None => config.clone(),
+    };
+    let (server_options, mapping_options) = server_options(&remote_config)?;
+    let mut client = tls::connect(config)?;
+    let mut remote = tls::connect(&remote_config)?;
+
+    let load = |config: &Config, table_name: &str| -> anyhow::Result<StrategyResult> {
+        Ok(BenchmarkRunner::new(config.clone())
+            .with_generator(*generator)
+            .with_schema(*schema)
+            .with_config(RunConfig {
+                strategies: vec![args.strategy.clone()],
+                table_name: table_name.to_string(),
+                batch_count,
+                workers: args.workers,
+                ..RunConfig::default()
+            })
+            .run()?
+            .remove(0))
+    };
+    // The foreign table's own size is nothing; the rows land remotely.
+    let remote_size = |remote: &mut Client| -> anyhow::Result<i64> {
+        let row = remote.query_one(
+            "SELECT pg_total_relation_size($1::text::regclass)",
+            &[remote_table],
+        )?;
+        Ok(row.get(0))
+    };
+    let mut results = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        remote.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {remote_table}; {}",
+            tables::create_table(remote_table, schema)
+        ))?;
+        client
+            .batch_execute(&format!(
+                "CREATE EXTENSION IF NOT EXISTS postgres_fdw;
+                 DROP SERVER IF EXISTS {SERVER_NAME} CASCADE;
+                 CREATE SERVER {SERVER_NAME} FOREIGN DATA WRAPPER postgres_fdw
+                     OPTIONS ({server_options});
+                 CREATE USER MAPPING FOR CURRENT_USER SERVER {SERVER_NAME}
+                     OPTIONS ({mapping_options});
+                 CREATE FOREIGN TABLE {foreign_table} ({}) SERVER {SERVER_NAME}
+                     OPTIONS (table_name {});",
+                tables::column_definitions(schema),
+                quote_literal(remote_table)
+            ))
+            .map_err(|e| anyhow::anyhow!("setting up postgres_fdw: {}", e))?;
+
+        println!("Loading into {} directly", remote_table);
+        let result = load(&remote_config, remote_table)?;
+        results.push(("direct".to_string(), result, remote_size(&mut remote)?));
+
+        for batch_size in &args.batch_size {
+            remote.batch_execute(&format!("TRUNCATE {}", remote_table))?;
+            client
+                .batch_execute(&format!(
+                    "ALTER SERVER {SERVER_NAME} OPTIONS (SET batch_size '{batch_size}')"
+                ))
+                .map_err(|e| anyhow::anyhow!("setting batch_size (needs PostgreSQL 14): {}", e))?;
+            println!(
+                "Loading into {} through postgres_fdw, batch_size {}",
+                foreign_table, batch_size
+            );
+            let result = load(config, foreign_table)?;
+            let label = format!("fdw, batch_size {}", batch_size);
+            results.push((label, result, remote_size(&mut remote)?));
+        }
+        Ok(())
+    }();
+    client.batch_execute(&format!("DROP SERVER IF EXISTS {SERVER_NAME} CASCADE"))?;
+    remote.batch_execute(&format!("DROP TABLE IF EXISTS {}", remote_table))?;
+    outcome?;
+
+    print_comparison(&results);
+    Ok(())
+}
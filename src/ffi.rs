@@ -0,0 +1,145 @@
+//! C ABI over the binary COPY encoder, for ingestion services in other
+//! languages; `include/fast_generation.h` declares it.
+
+use crate::generator::{Row, Timestamp};
+use crate::pgcopy;
+use crate::schema::{SchemaConfig, TimeColumn, MAX_NUMERIC_SCALE};
+use crate::FastGenError;
+use chrono::DateTime;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+pub const FG_OK: i32 = 0;
+pub const FG_INVALID_ARGUMENT: i32 = -1;
+pub const FG_ENCODING_ERROR: i32 = -2;
+pub const FG_PANIC: i32 = -3;
+
+/// One row as C lays it out. `created` is microseconds since the Unix
+/// epoch, with `INT64_MAX` and `INT64_MIN` standing for ±infinity.
+#[repr(C)]
+pub struct FgRow {
+    pub created: i64,
+    pub sensor_id: i32,
+    pub temperature: f64,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn to_row(row: &FgRow) -> crate::Result<Row> {
+    let created = match row.created {
+        i64::MAX => Timestamp::PosInfinity,
+        i64::MIN => Timestamp::NegInfinity,
+        micros => Timestamp::Value(DateTime::from_timestamp_micros(micros).ok_or_else(|| {
+            FastGenError::Encoding(format!("timestamp {} is out of range", micros))
+        })?),
+    };
+    Ok((created, row.sensor_id, row.temperature))
+}
+
+fn encode(rows: &[FgRow], schema: &SchemaConfig) -> crate::Result<Vec<u8>> {
+    let rows = rows.iter().map(to_row).collect::<crate::Result<Vec<_>>>()?;
+    pgcopy::generate_buffer(schema, &rows)
+}
+
+/// Encodes `count` rows as one complete binary COPY stream, header to
+/// trailer. `time_column` is 0 for timestamptz, 1 for timestamp and 2 for
+/// bigint; a `numeric_scale` of -1 leaves temperatures unscaled. On success
+/// `*out` and `*out_len` receive a buffer to release with `fg_free`;
+/// otherwise the result is negative and `fg_last_error` says why.
+///
+/// # Safety
+///
+/// `rows` must point to `count` rows, or may be null if `count` is zero;
+/// `out` and `out_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encode_batch(
+    rows: *const FgRow,
+    count: usize,
+    time_column: u32,
+    numeric_scale: i32,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    let time_column = match time_column {
+        0 => TimeColumn::Timestamptz,
+        1 => TimeColumn::Timestamp,
+        2 => TimeColumn::Bigint,
+        _ => {
+            set_last_error(format!("unknown time column {}", time_column));
+            return FG_INVALID_ARGUMENT;
+        }
+    };
+    let numeric_scale = match numeric_scale {
+        -1 => None,
+        scale if (0..=MAX_NUMERIC_SCALE as i32).contains(&scale) => Some(scale as u32),
+        scale => {
+            set_last_error(format!("numeric scale {} is out of range", scale));
+            return FG_INVALID_ARGUMENT;
+        }
+    };
+    if (rows.is_null() && count > 0) || out.is_null() || out_len.is_null() {
+        set_last_error("null pointer argument".to_string());
+        return FG_INVALID_ARGUMENT;
+    }
+    let rows = if count == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(rows, count)
+    };
+    let schema = SchemaConfig {
+        time_column,
+        numeric_scale,
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| encode(rows, &schema))) {
+        Ok(Ok(buffer)) => {
+            let buffer = Box::into_raw(buffer.into_boxed_slice());
+            *out_len = buffer.len();
+            *out = buffer.cast();
+            FG_OK
+        }
+        Ok(Err(e)) => {
+            set_last_error(e.report());
+            FG_ENCODING_ERROR
+        }
+        Err(_) => {
+            set_last_error("the encoder panicked".to_string());
+            FG_PANIC
+        }
+    }
+}
+
+/// Releases a buffer returned by `fg_encode_batch`; null is ignored.
+///
+/// # Safety
+///
+/// `buffer` and `len` must be exactly as `fg_encode_batch` returned them,
+/// and the buffer must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fg_free(buffer: *mut u8, len: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
+
+/// Why the last failing call on this thread failed, or null. The string
+/// stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn fg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
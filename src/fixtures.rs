@@ -0,0 +1,377 @@
+//! The `fixtures` command: copies related tables with their keys replaced
+//! by pseudonyms and chosen columns by fakes, for seeding staging
+//! environments. Each value gets the same fake wherever it occurs, derived
+//! from it and a secret alone, so joins and foreign keys between the
+//! copies match as they did between the originals, and a later run with
+//! the same secret gives the same fakes.
+
+use crate::cli::FixturesArgs;
+use fast_generation::tls;
+use postgres::types::Type;
+use postgres::{Client, Config};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::time::Instant;
+
+/// Hex digits in the tokens text values are replaced with.
+const TOKEN_DIGITS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Treatment {
+    Keep,
+    /// A one-to-one pseudonym, so keys stay unique and references match.
+    Key,
+    /// A stable fake; numbers within the column's range.
+    Mask {
+        min: f64,
+        max: f64,
+    },
+}
+
+struct Column {
+    name: String,
+    ty: Type,
+    /// Longest text the column takes, for varchar(n) and char(n).
+    length: Option<usize>,
+    treatment: Treatment,
+}
+
+fn is_integer(ty: &Type) -> bool {
+    [Type::INT2, Type::INT4, Type::INT8].contains(ty)
+}
+
+fn is_number(ty: &Type) -> bool {
+    is_integer(ty) || [Type::NUMERIC, Type::FLOAT4, Type::FLOAT8].contains(ty)
+}
+
+fn is_text(ty: &Type) -> bool {
+    [Type::TEXT, Type::VARCHAR, Type::BPCHAR].contains(ty)
+}
+
+/// Derives fakes from the secret. Integers go through a keyed Feistel
+/// permutation, walked until it lands back in the range the value came
+/// from, so that each maps to a distinct integer of the same width
+/// whichever integer type holds it; anything else is hashed.
+struct Pseudonyms {
+    secret: Vec<u8>,
+    /// Fakes by kind of fake and original.
+    cache: HashMap<(String, Vec<u8>), Vec<u8>>,
+}
+
+impl Pseudonyms {
+    fn new(secret: &str) -> Self {
+        Pseudonyms {
+            secret: secret.as_bytes().to_vec(),
+            cache: HashMap::new(),
+        }
+    }
+
+    fn digest(&self, domain: &str, value: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update((self.secret.len() as u64).to_be_bytes());
+        hasher.update(&self.secret);
+        hasher.update(domain.as_bytes());
+        hasher.update([0]);
+        hasher.update(value);
+        hasher.finalize().into()
+    }
+
+    fn round(&self, round: u8, bits: u32, half: u64) -> u64 {
+        let digest = self.digest(&format!("feistel/{}/{}", bits, round), &half.to_be_bytes());
+        u64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
+
+    fn permute(&self, value: u64, bits: u32) -> u64 {
+        let half = bits / 2;
+        let mask = (1u64 << half) - 1;
+        let (mut left, mut right) = (value >> half, value & mask);
+        for round in 0..4 {
+            let next = left ^ (self.round(round, bits, right) & mask);
+            left = right;
+            right = next;
+        }
+        (left << half) | right
+    }
+
+    fn integer(&self, value: i64) -> i64 {
+        // Negative values mirror the non-negative ones.
+        let magnitude = if value < 0 {
+            !value as u64
+        } else {
+            value as u64
+        };
+        let (bits, low) = match magnitude {
+            0..=0x7FFF => (16, 0),
+            0x8000..=0x7FFF_FFFF => (32, 0x8000),
+            _ => (64, 0x8000_0000),
+        };
+        let high = 1u64 << (bits - 1);
+        let mut fake = magnitude;
+        loop {
+            fake = self.permute(fake, bits);
+            if (low..high).contains(&fake) {
+                break;
+            }
+        }
+        if value < 0 {
+            !(fake as i64)
+        } else {
+            fake as i64
+        }
+    }
+
+    fn token(&self, value: &[u8], length: Option<usize>) -> String {
+        let digest = self.digest("text", value);
+        let token: String = digest[..TOKEN_DIGITS / 2]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        match length {
+            Some(length) if length < TOKEN_DIGITS => token[..length].to_string(),
+            _ => token,
+        }
+    }
+
+    /// A number between `min` and `max`, with as many decimals as `value`.
+    fn number(&self, value: &str, integer: bool, min: f64, max: f64) -> String {
+        let Ok(parsed) = value.parse::<f64>() else {
+            return value.to_string();
+        };
+        if !parsed.is_finite() {
+            return value.to_string();
+        }
+        let digest = self.digest("number", value.as_bytes());
+        let unit = u64::from_be_bytes(digest[..8].try_into().unwrap()) as f64 / u64::MAX as f64;
+        let fake = min + unit * (max - min);
+        if integer {
+            return format!("{}", fake.round() as i64);
+        }
+        let decimals = value
+            .split_once('.')
+            .map_or(0, |(_, decimals)| decimals.len());
+        format!("{:.*}", decimals, fake)
+    }
+
+    /// The COPY text field for `field` of `column`.
+    fn field(&mut self, column: &Column, field: &[u8]) -> Vec<u8> {
+        if column.treatment == Treatment::Keep || field == b"\\N" {
+            return field.to_vec();
+        }
+        // Masked numbers depend on their column's range, text on its length.
+        let kind = match column.treatment {
+            Treatment::Key if is_integer(&column.ty) => "integer".to_string(),
+            Treatment::Mask { .. } if is_number(&column.ty) => format!("number/{}", column.name),
+            _ => format!("text/{:?}", column.length),
+        };
+        let key = (kind, field.to_vec());
+        if let Some(fake) = self.cache.get(&key) {
+            return fake.clone();
+        }
+        let text = String::from_utf8_lossy(field);
+        let fake = match column.treatment {
+            Treatment::Key if is_integer(&column.ty) => match text.parse() {
+                Ok(value) => self.integer(value).to_string(),
+                Err(_) => text.to_string(),
+            },
+            Treatment::Mask { min, max } if is_number(&column.ty) => {
+                self.number(&text, is_integer(&column.ty), min, max)
+            }
+            _ => self.token(field, column.length),
+        };
+        self.cache.insert(key, fake.clone().into_bytes());
+        fake.into_bytes()
+    }
+}
+
+fn columns(client: &mut Client, table: &str, args: &FixturesArgs) -> anyhow::Result<Vec<Column>> {
+    let rows = client.query(
+        "SELECT attname::text, atttypid,
+                CASE WHEN atttypid IN ('varchar'::regtype, 'bpchar'::regtype) AND atttypmod >= 4
+                     THEN atttypmod - 4 END
+           FROM pg_attribute
+          WHERE attrelid = $1::text::regclass AND attnum > 0 AND NOT attisdropped
+            AND attgenerated = ''
+          ORDER BY attnum",
+        &[&table],
+    )?;
+    rows.iter()
+        .map(|row| {
+            let name: String = row.get(0);
+            let ty = Type::from_oid(row.get(1)).unwrap_or(Type::UNKNOWN);
+            let treatment = if args.key_columns.contains(&name) {
+                if !is_integer(&ty) && !is_text(&ty) {
+                    anyhow::bail!("{}.{} is {}, not an integer or text key", table, name, ty);
+                }
+                Treatment::Key
+            } else if args.mask_columns.contains(&name) {
+                if is_number(&ty) {
+                    let range = client.query_one(
+                        &format!(
+                            "SELECT min({name}::float8), max({name}::float8) FROM {table}
+                              WHERE {name}::float8 <> 'NaN' AND abs({name}::float8) <> 'Infinity'"
+                        ),
+                        &[],
+                    )?;
+                    Treatment::Mask {
+                        min: range.get::<_, Option<f64>>(0).unwrap_or(0.0),
+                        max: range.get::<_, Option<f64>>(1).unwrap_or(0.0),
+                    }
+                } else if is_text(&ty) {
+                    Treatment::Mask { min: 0.0, max: 0.0 }
+                } else {
+                    anyhow::bail!("{}.{} is {}, which can't be masked", table, name, ty);
+                }
+            } else {
+                Treatment::Keep
+            };
+            Ok(Column {
+                name,
+                ty,
+                length: row.get::<_, Option<i32>>(2).map(|length| length as usize),
+                treatment,
+            })
+        })
+        .collect()
+}
+
+/// Streams `source` into `target` through COPY's text format, replacing
+/// fields on the way. Returns the rows copied.
+fn copy(
+    reader: &mut Client,
+    writer: &mut Client,
+    source: &str,
+    target: &str,
+    columns: &[Column],
+    pseudonyms: &mut Pseudonyms,
+) -> anyhow::Result<u64> {
+    let list = columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let input = BufReader::new(reader.copy_out(&format!("COPY {source} ({list}) TO STDOUT"))?);
+    let mut output = writer.copy_in(&format!("COPY {target} ({list}) FROM STDIN"))?;
+    let mut rows = 0;
+    // Tabs and newlines inside values are escaped in COPY text.
+    for line in input.split(b'\n') {
+        let line = line?;
+        let mut masked = Vec::with_capacity(line.len());
+        for (index, (field, column)) in line.split(|&byte| byte == b'\t').zip(columns).enumerate() {
+            if index > 0 {
+                masked.push(b'\t');
+            }
+            masked.extend(pseudonyms.field(column, field));
+        }
+        masked.push(b'\n');
+        output.write_all(&masked)?;
+        rows += 1;
+    }
+    output.finish()?;
+    Ok(rows)
+}
+
+/// Foreign keys between the given tables, as (constraint, table, columns,
+/// referenced table, referenced columns).
+type ForeignKey = (String, String, String, String, String);
+
+fn foreign_keys(client: &mut Client, tables: &[String]) -> anyhow::Result<Vec<ForeignKey>> {
+    let keys = |relation: &str, attributes: &str| {
+        format!(
+            "(SELECT string_agg(quote_ident(a.attname), ', ' ORDER BY k.i)
+                FROM unnest(c.{attributes}) WITH ORDINALITY k(n, i)
+                JOIN pg_attribute a ON a.attrelid = c.{relation} AND a.attnum = k.n)"
+        )
+    };
+    let rows = client.query(
+        &format!(
+            "SELECT c.conname::text, c.conrelid::regclass::text, {},
+                    c.confrelid::regclass::text, {}
+               FROM pg_constraint c
+              WHERE c.contype = 'f' AND c.conrelid::regclass::text = ANY($1)
+                AND c.confrelid::regclass::text = ANY($1)
+              ORDER BY c.conname",
+            keys("conrelid", "conkey"),
+            keys("confrelid", "confkey")
+        ),
+        &[&tables],
+    )?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)))
+        .collect())
+}
+
+pub fn fixtures(config: &Config, args: &FixturesArgs) -> anyhow::Result<()> {
+    let mut reader = tls::connect(config)?;
+    let mut writer = tls::connect(config)?;
+    // Named as the catalogs name them, to match foreign keys by.
+    let tables = args
+        .tables
+        .iter()
+        .map(|table| -> anyhow::Result<String> {
+            Ok(reader
+                .query_one("SELECT $1::text::regclass::text", &[table])?
+                .get(0))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let target = |table: &str| format!("{}{}", table, args.suffix);
+
+    let columns = tables
+        .iter()
+        .map(|table| columns(&mut reader, table, args))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(missing) = args
+        .key_columns
+        .iter()
+        .chain(&args.mask_columns)
+        .find(|name| !columns.iter().flatten().any(|column| &column.name == *name))
+    {
+        anyhow::bail!("none of the tables has a column {}", missing);
+    }
+
+    let mut pseudonyms = Pseudonyms::new(&args.secret);
+    for (table, columns) in tables.iter().zip(&columns) {
+        writer.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {target} CASCADE;
+             CREATE TABLE {target} (LIKE {table} INCLUDING ALL)",
+            target = target(table)
+        ))?;
+        let started = Instant::now();
+        let rows = copy(
+            &mut reader,
+            &mut writer,
+            table,
+            &target(table),
+            columns,
+            &mut pseudonyms,
+        )?;
+        println!(
+            "{:<32} {:>10} rows in {:.2}s into {}",
+            table,
+            rows,
+            started.elapsed().as_secs_f64(),
+            target(table)
+        );
+    }
+
+    // Adding them validates that the pseudonyms kept every reference.
+    let foreign_keys = foreign_keys(&mut reader, &tables)?;
+    for (name, table, columns, referenced, referenced_columns) in &foreign_keys {
+        writer.batch_execute(&format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({})",
+            target(table),
+            name,
+            columns,
+            target(referenced),
+            referenced_columns
+        ))?;
+    }
+    if !foreign_keys.is_empty() {
+        println!(
+            "Added and validated {} foreign keys between the copies",
+            foreign_keys.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! The `foreign-keys` command: loads into a scratch copy of the table that
+//! references a scratch sensors table, once with the foreign key enforced
+//! while loading and once without it, to put numbers on what checking it
+//! costs during a bulk load.
+
+use crate::cli::ForeignKeysArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::generator::MAX_SENSORS;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ForeignKey {
+    /// Checked for every row loaded
+    Enforced,
+    /// Not there at all
+    Dropped,
+    /// Added NOT VALID once loaded, then validated in one pass
+    After,
+}
+
+impl ForeignKey {
+    fn name(self) -> &'static str {
+        match self {
+            ForeignKey::Enforced => "enforced",
+            ForeignKey::Dropped => "dropped",
+            ForeignKey::After => "validated after",
+        }
+    }
+}
+
+fn constraint(scratch: &str) -> String {
+    format!(
+        "ALTER TABLE {scratch} ADD CONSTRAINT {scratch}_sensor_id_fkey \
+         FOREIGN KEY (sensor_id) REFERENCES {scratch}_sensors (id)"
+    )
+}
+
+struct Outcome {
+    foreign_key: ForeignKey,
+    result: StrategyResult,
+    /// How long VALIDATE CONSTRAINT took, for `ForeignKey::After`.
+    validation: Option<Duration>,
+}
+
+impl Outcome {
+    fn total(&self) -> Duration {
+        self.result.elapsed + self.validation.unwrap_or_default()
+    }
+}
+
+fn print_comparison(outcomes: &[Outcome]) {
+    let enforced = outcomes
+        .iter()
+        .find(|outcome| {
+            outcome.foreign_key == ForeignKey::Enforced && outcome.result.error.is_none()
+        })
+        .map(Outcome::total);
+    println!();
+    println!(
+        "{:<16} {:>10} {:>14} {:>10} {:>10} {:>10} {:>12}",
+        "foreign key", "rows/s", "bytes/s", "load s", "validate s", "total s", "vs enforced"
+    );
+    for outcome in outcomes {
+        let result = &outcome.result;
+        if let Some(e) = &result.error {
+            println!("{:<16} failed: {}", outcome.foreign_key.name(), e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        let total = outcome.total();
+        println!(
+            "{:<16} {:>10.0} {:>14} {:>10.2} {:>10} {:>10.2} {:>12}",
+            outcome.foreign_key.name(),
+            result.rows_per_second(),
+            units::rate(size / result.elapsed.as_secs_f64()),
+            result.elapsed.as_secs_f64(),
+            outcome
+                .validation
+                .map_or("-".to_string(), |validation| format!(
+                    "{:.2}",
+                    validation.as_secs_f64()
+                )),
+            total.as_secs_f64(),
+            enforced.map_or("-".to_string(), |enforced| format!(
+                "{:+.1}%",
+                (total.as_secs_f64() / enforced.as_secs_f64() - 1.0) * 100.0
+            ))
+        );
+    }
+}
+
+pub fn foreign_keys(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &ForeignKeysArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let mut client = tls::connect(config)?;
+
+    let mut outcomes = Vec::new();
+    let outcome = args
+        .modes
+        .iter()
+        .try_for_each(|&foreign_key| -> anyhow::Result<()> {
+            println!("Loading with the foreign key {}", foreign_key.name());
+            client.batch_execute(&format!(
+                "DROP TABLE IF EXISTS {scratch}, {scratch}_sensors;
+                 CREATE TABLE {scratch}_sensors (id integer PRIMARY KEY);
+                 INSERT INTO {scratch}_sensors SELECT generate_series(1, {MAX_SENSORS});
+                 CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS);"
+            ))?;
+            if foreign_key == ForeignKey::Enforced {
+                client.batch_execute(&constraint(scratch))?;
+            }
+            let result = BenchmarkRunner::new(config.clone())
+                .with_generator(*generator)
+                .with_schema(*schema)
+                .with_config(RunConfig {
+                    strategies: vec![args.strategy.clone()],
+                    table_name: scratch.clone(),
+                    batch_count,
+                    workers: args.workers,
+                    ..RunConfig::default()
+                })
+                .run()?
+                .remove(0);
+            let mut validation = None;
+            if foreign_key == ForeignKey::After && result.error.is_none() {
+                client.batch_execute(&format!("{} NOT VALID", constraint(scratch)))?;
+                let started = Instant::now();
+                client.batch_execute(&format!(
+                    "ALTER TABLE {scratch} VALIDATE CONSTRAINT {scratch}_sensor_id_fkey"
+                ))?;
+                validation = Some(started.elapsed());
+            }
+            outcomes.push(Outcome {
+                foreign_key,
+                result,
+                validation,
+            });
+            Ok(())
+        });
+    client.batch_execute(&format!(
+        "DROP TABLE IF EXISTS {scratch}, {scratch}_sensors"
+    ))?;
+    outcome?;
+
+    print_comparison(&outcomes);
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use crate::FastGenError;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// A timestamp or one of the infinities Postgres adds to its range. Mirrors
+/// `postgres_types::Timestamp`, so that generating rows doesn't need the
+/// Postgres client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Timestamp<T> {
+    PosInfinity,
+    NegInfinity,
+    Value(T),
+}
+
+impl<T> Timestamp<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Timestamp<U> {
+        match self {
+            Timestamp::PosInfinity => Timestamp::PosInfinity,
+            Timestamp::NegInfinity => Timestamp::NegInfinity,
+            Timestamp::Value(value) => Timestamp::Value(f(value)),
+        }
+    }
+}
+
+pub type Created = Timestamp<DateTime<Utc>>;
+pub type Row = (Created, i32, f64);
+
+pub const BATCH_SIZE: usize = 10_000;
+pub const MAX_SENSORS: i32 = 32;
+pub const DEFAULT_SEED: u64 = 0x5EED;
+pub const BASE_TEMP: f64 = 20.0;
+/// Decimal places temperatures are rounded to.
+pub const TEMPERATURE_SCALE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+#[serde(default)]
+pub struct GeneratorConfig {
+    /// Master seed the per-column RNG streams are derived from
+    #[cfg_attr(feature = "cli", arg(long, global = true, default_value_t = DEFAULT_SEED))]
+    pub seed: u64,
+
+    /// Timestamp of the first batch, e.g. 1970-01-01T00:00:00Z [default: eight days from now]
+    #[cfg_attr(feature = "cli", arg(long, global = true))]
+    pub start: Option<DateTime<Utc>>,
+
+    /// Time between consecutive batches in milliseconds; raise it to spread a
+    /// run over years or decades
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            global = true,
+            default_value_t = 100,
+            allow_negative_numbers = true
+        )
+    )]
+    pub batch_interval_ms: i64,
+
+    /// Fraction of rows given an infinite timestamp, and independently a NaN
+    /// temperature, to exercise consumers with special values
+    #[cfg_attr(feature = "cli", arg(long, global = true, default_value_t = 0.0))]
+    pub special_rate: f64,
+
+    /// Fewest rows in a batch; sizes are drawn uniformly between this and
+    /// --batch-size-max to emulate uneven producers [default: 10000]
+    #[cfg_attr(feature = "cli", arg(long, global = true))]
+    pub batch_size_min: Option<usize>,
+
+    /// Most rows in a batch [default: 10000]
+    #[cfg_attr(feature = "cli", arg(long, global = true))]
+    pub batch_size_max: Option<usize>,
+}
+
+impl Default for GeneratorConfig {
+    /// The same settings as the command line defaults.
+    fn default() -> Self {
+        GeneratorConfig {
+            seed: DEFAULT_SEED,
+            start: None,
+            batch_interval_ms: 100,
+            special_rate: 0.0,
+            batch_size_min: None,
+            batch_size_max: None,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    /// Truncated to microseconds, the resolution Postgres stores; otherwise
+    /// the text strategy would round what the binary one truncates.
+    pub fn start_time(&self) -> DateTime<Utc> {
+        let start = self.start.unwrap_or_else(|| Utc::now() + Duration::days(8));
+        start.trunc_subsecs(6)
+    }
+
+    /// Rows per batch, from fewest to most; only `BATCH_SIZE` unless
+    /// either bound is set.
+    pub fn batch_sizes(&self) -> RangeInclusive<usize> {
+        let min = self.batch_size_min.unwrap_or(BATCH_SIZE);
+        let max = self.batch_size_max.unwrap_or(BATCH_SIZE.max(min));
+        min.min(max)..=max
+    }
+
+    /// Rows `batch_count` batches hold on average.
+    pub fn expected_rows(&self, batch_count: usize) -> usize {
+        let sizes = self.batch_sizes();
+        batch_count * (sizes.start() + sizes.end()) / 2
+    }
+
+    /// Settings for node `node` of several loading the same table together,
+    /// with a seed of its own derived from this one so that their rows
+    /// differ. Set `start` first for the nodes to share one timeline.
+    pub fn for_node(&self, node: u64) -> GeneratorConfig {
+        GeneratorConfig {
+            seed: child_rng(self.seed, "node", node).gen(),
+            ..*self
+        }
+    }
+
+    /// Fails on options the generator can't honour, such as `batch_count`
+    /// batches starting at `start_time` running past the range of timestamps
+    /// chrono can represent.
+    pub fn validate(&self, batch_count: usize) -> crate::Result<()> {
+        if !(0.0..=1.0).contains(&self.special_rate) {
+            return Err(FastGenError::InvalidConfig(
+                "--special-rate must be between 0 and 1".to_string(),
+            ));
+        }
+        if self.batch_size_min == Some(0) {
+            return Err(FastGenError::InvalidConfig(
+                "--batch-size-min must be at least 1".to_string(),
+            ));
+        }
+        if let (Some(min), Some(max)) = (self.batch_size_min, self.batch_size_max) {
+            if min > max {
+                return Err(FastGenError::InvalidConfig(
+                    "--batch-size-min must not exceed --batch-size-max".to_string(),
+                ));
+            }
+        }
+
+        let batches = i32::try_from(batch_count).map_err(|_| {
+            FastGenError::InvalidConfig(format!(
+                "{} batches are more than the generator can space out",
+                batch_count
+            ))
+        })?;
+        let span = Duration::try_milliseconds(self.batch_interval_ms)
+            .and_then(|interval| interval.checked_mul(batches));
+        match span.and_then(|span| self.start_time().checked_add_signed(span)) {
+            Some(_) => Ok(()),
+            None => Err(FastGenError::InvalidConfig(format!(
+                "{} batches {}ms apart run past the representable time range",
+                batch_count, self.batch_interval_ms
+            ))),
+        }
+    }
+}
+
+/// Derives an independent RNG for one column (and optionally one sensor) from
+/// the master seed. All children share the ChaCha key and differ only in the
+/// stream id, which is a hash of the column name and index, so they never
+/// depend on how many other columns or sensors exist.
+pub fn child_rng(seed: u64, column: &str, index: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream_id(column, index));
+    rng
+}
+
+fn stream_id(column: &str, index: u64) -> u64 {
+    // FNV-1a, stable across platforms and releases unlike `DefaultHasher`.
+    column
+        .bytes()
+        .chain(index.to_le_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+pub struct ColumnStreams {
+    seed: u64,
+    temperature: Vec<ChaCha8Rng>,
+    special: ChaCha8Rng,
+    special_rate: f64,
+}
+
+impl ColumnStreams {
+    pub fn new(seed: u64, special_rate: f64) -> Self {
+        ColumnStreams {
+            seed,
+            temperature: Vec::new(),
+            special: child_rng(seed, "special", 0),
+            special_rate,
+        }
+    }
+
+    /// Replaces values with infinities and NaN at `special_rate`. Draws from a
+    /// stream of its own, and not at all when the rate is zero, so the regular
+    /// values are the same with or without injection.
+    fn inject_special(&mut self, row: Row) -> Row {
+        if self.special_rate <= 0.0 {
+            return row;
+        }
+        let (mut created, sensor_id, mut temperature) = row;
+        if self.special.gen_bool(self.special_rate) {
+            created = if self.special.gen() {
+                Timestamp::PosInfinity
+            } else {
+                Timestamp::NegInfinity
+            };
+        }
+        if self.special.gen_bool(self.special_rate) {
+            temperature = f64::NAN;
+        }
+        (created, sensor_id, temperature)
+    }
+
+    fn temperature(&mut self, sensor_id: i32) -> &mut ChaCha8Rng {
+        let index = sensor_id as usize;
+        while self.temperature.len() <= index {
+            let sensor = self.temperature.len() as u64;
+            self.temperature
+                .push(child_rng(self.seed, "temperature", sensor));
+        }
+        &mut self.temperature[index]
+    }
+}
+
+pub fn generate_batch(
+    streams: &mut ColumnStreams,
+    created: DateTime<Utc>,
+    sensor_id: i32,
+    base_temp: f64,
+    size: usize,
+) -> (Vec<Row>, i32) {
+    let mut current_sensor_id = sensor_id;
+    let factor = 10f64.powi(TEMPERATURE_SCALE);
+    let batch: Vec<_> = (0..size)
+        .map(|i| {
+            current_sensor_id = (current_sensor_id + (i as i32)) % MAX_SENSORS + 1;
+            let rng = streams.temperature(current_sensor_id);
+            let temperature = ((base_temp + rng.gen_range(-5.0..5.0)) * factor).round() / factor;
+            streams.inject_special((Timestamp::Value(created), current_sensor_id, temperature))
+        })
+        .collect();
+    (batch, sensor_id)
+}
+
+pub fn generate_data(
+    config: &GeneratorConfig,
+    base_temp: f64,
+    batch_count: usize,
+) -> impl Iterator<Item = (Vec<Row>, i64)> {
+    let mut streams = ColumnStreams::new(config.seed, config.special_rate);
+    let interval = Duration::milliseconds(config.batch_interval_ms);
+    let mut current_time = config.start_time();
+    let mut sensor_id = 1;
+    let mut current_tick = 0;
+    // A stream of its own, drawn from only when sizes vary, so the rows are
+    // the same as ever with fixed-size batches.
+    let sizes = config.batch_sizes();
+    let mut size_rng = child_rng(config.seed, "batch_size", 0);
+
+    (0..batch_count).flat_map(move |_| {
+        current_tick += 1;
+        current_time += interval;
+        let size = if sizes.start() == sizes.end() {
+            *sizes.start()
+        } else {
+            size_rng.gen_range(sizes.clone())
+        };
+        let (new_batch, new_sensor_id) =
+            generate_batch(&mut streams, current_time, sensor_id, base_temp, size);
+        sensor_id = new_sensor_id;
+
+        std::iter::once((new_batch, current_tick))
+    })
+}
+
+/// Batches of as many rows as `next_size` gives when each is taken, until
+/// `rows` were generated; the last is cut short to make up the total.
+pub fn generate_sized(
+    config: &GeneratorConfig,
+    base_temp: f64,
+    rows: usize,
+    mut next_size: impl FnMut() -> usize,
+) -> impl Iterator<Item = (Vec<Row>, i64)> {
+    let mut streams = ColumnStreams::new(config.seed, config.special_rate);
+    let interval = Duration::milliseconds(config.batch_interval_ms);
+    let mut current_time = config.start_time();
+    let mut sensor_id = 1;
+    let mut current_tick = 0;
+    let mut remaining = rows;
+
+    std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+        current_tick += 1;
+        current_time += interval;
+        let size = next_size().clamp(1, remaining);
+        remaining -= size;
+        let (new_batch, new_sensor_id) =
+            generate_batch(&mut streams, current_time, sensor_id, base_temp, size);
+        sensor_id = new_sensor_id;
+
+        Some((new_batch, current_tick))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn batch_counts_past_i32_are_rejected_not_truncated() {
+        let config = GeneratorConfig::default();
+        // 2^32 + 1 batches would wrap to a single one.
+        let batch_count = (1usize << 32) + 1;
+        assert!(config.validate(batch_count).is_err());
+        assert!(config.validate(1).is_ok());
+    }
+}
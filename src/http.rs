@@ -0,0 +1,206 @@
+//! `sink http`: POSTs each batch to an ingestion API, as a JSON array or
+//! as NDJSON, from several threads at once, retrying throttled and failed
+//! requests, so an application's ingest path can be load tested with the
+//! rows `run` writes to the database directly.
+
+use crate::cli::HttpArgs;
+use crate::sink::Sink;
+use clap::ValueEnum;
+use fast_generation::generator::Row;
+use fast_generation::load::BatchLatency;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::serialize;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use ureq::Agent;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum BodyFormat {
+    /// An array of objects
+    #[default]
+    Json,
+    /// An object per line
+    Ndjson,
+}
+
+impl BodyFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Requests made, how many were retried, and how long each took to
+/// succeed, retries included.
+#[derive(Default)]
+struct Requests {
+    retried: usize,
+    latencies: Vec<Duration>,
+}
+
+struct Endpoint {
+    agent: Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+    content_type: &'static str,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Endpoint {
+    /// POSTs `body`, retrying on connection errors, 429 and 5xx responses
+    /// with a pause doubling from `backoff`. Returns whether it retried.
+    fn post(&self, body: &[u8]) -> anyhow::Result<bool> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .agent
+                .post(&self.url)
+                .header("Content-Type", self.content_type);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            let failure = match request.send(body) {
+                Ok(response) if response.status().is_success() => return Ok(attempt > 0),
+                Ok(mut response) => {
+                    let status = response.status();
+                    let failure = format!(
+                        "{}: {}",
+                        status,
+                        response.body_mut().read_to_string().unwrap_or_default()
+                    );
+                    if status.as_u16() != 429 && !status.is_server_error() {
+                        anyhow::bail!("posting to {}: {}", self.url, failure);
+                    }
+                    failure
+                }
+                Err(e) => e.to_string(),
+            };
+            if attempt == self.retries {
+                anyhow::bail!(
+                    "posting to {} ({} attempts): {}",
+                    self.url,
+                    attempt + 1,
+                    failure
+                );
+            }
+            thread::sleep(self.backoff * 2u32.pow(attempt.min(16)));
+            attempt += 1;
+        }
+    }
+}
+
+pub struct HttpSink {
+    schema: SchemaConfig,
+    format: BodyFormat,
+    bodies: Option<SyncSender<Vec<u8>>>,
+    workers: Vec<JoinHandle<anyhow::Result<Requests>>>,
+    bytes: u64,
+}
+
+impl HttpSink {
+    pub fn new(args: &HttpArgs, schema: &SchemaConfig) -> anyhow::Result<HttpSink> {
+        if args.concurrency == 0 {
+            anyhow::bail!("--concurrency must be positive");
+        }
+        let agent: Agent = Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+        let endpoint = Arc::new(Endpoint {
+            agent,
+            url: args.url.clone(),
+            headers: args.headers.clone(),
+            content_type: args.format.content_type(),
+            retries: args.retries,
+            backoff: Duration::from_millis(args.retry_backoff_ms),
+        });
+        // A batch waits for a free worker rather than piling up in memory.
+        let (bodies, queue) = mpsc::sync_channel::<Vec<u8>>(0);
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..args.concurrency)
+            .map(|_| {
+                let (endpoint, queue) = (endpoint.clone(), queue.clone());
+                thread::spawn(move || {
+                    let mut requests = Requests::default();
+                    loop {
+                        let body = match queue.lock().unwrap().recv() {
+                            Ok(body) => body,
+                            Err(_) => return Ok(requests),
+                        };
+                        let started = Instant::now();
+                        requests.retried += endpoint.post(&body)? as usize;
+                        requests.latencies.push(started.elapsed());
+                    }
+                })
+            })
+            .collect();
+        Ok(HttpSink {
+            schema: *schema,
+            format: args.format,
+            bodies: Some(bodies),
+            workers,
+            bytes: 0,
+        })
+    }
+
+    /// Waits for the workers, returning the first error any of them hit.
+    fn join(&mut self) -> anyhow::Result<Requests> {
+        self.bodies = None;
+        let mut total = Requests::default();
+        for worker in self.workers.drain(..) {
+            let requests = worker
+                .join()
+                .map_err(|_| anyhow::anyhow!("an HTTP worker panicked"))??;
+            total.retried += requests.retried;
+            total.latencies.extend(requests.latencies);
+        }
+        Ok(total)
+    }
+}
+
+impl Sink for HttpSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let mut body = Vec::new();
+        match self.format {
+            BodyFormat::Json => {
+                let objects: Vec<_> = batch
+                    .iter()
+                    .map(|row| serialize::json_object(&self.schema, row))
+                    .collect();
+                body.extend_from_slice(format!("[{}]", objects.join(",")).as_bytes());
+            }
+            BodyFormat::Ndjson => serialize::write_json_lines(&mut body, &self.schema, batch)?,
+        }
+        self.bytes += body.len() as u64;
+        let sent = self.bodies.as_ref().map(|bodies| bodies.send(body));
+        if let Some(Err(_)) = sent {
+            // Every worker stopped, so at least one failed.
+            self.join()?;
+            anyhow::bail!("posting to the endpoint stopped");
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<u64> {
+        let requests = self.join()?;
+        println!(
+            "Requests: {} ({} retried)",
+            requests.latencies.len(),
+            requests.retried
+        );
+        if let Some(latency) = BatchLatency::from_samples(requests.latencies) {
+            println!(
+                " Request latency: {:.1}ms median, {:.1}ms p99, {:.1}ms max",
+                latency.p50.as_secs_f64() * 1000.0,
+                latency.p99.as_secs_f64() * 1000.0,
+                latency.max.as_secs_f64() * 1000.0
+            );
+        }
+        Ok(self.bytes)
+    }
+}
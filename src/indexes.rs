@@ -0,0 +1,117 @@
+//! The `indexes` command: repeats the same load into a scratch copy of the
+//! table with a different set of indexes each time, to weigh what each
+//! index costs on ingest against its size.
+
+use crate::cli::IndexesArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IndexSet {
+    /// No index at all
+    None,
+    /// A btree on (sensor_id, created)
+    Btree,
+    /// A BRIN index on created
+    Brin,
+    /// A hash index on sensor_id
+    Hash,
+}
+
+impl IndexSet {
+    fn name(self) -> &'static str {
+        match self {
+            IndexSet::None => "none",
+            IndexSet::Btree => "btree",
+            IndexSet::Brin => "brin",
+            IndexSet::Hash => "hash",
+        }
+    }
+
+    fn create(self, table_name: &str) -> Option<String> {
+        let definition = match self {
+            IndexSet::None => return None,
+            IndexSet::Btree => "(sensor_id, created)",
+            IndexSet::Brin => "USING brin (created)",
+            IndexSet::Hash => "USING hash (sensor_id)",
+        };
+        Some(format!(
+            "CREATE INDEX {table}_{name} ON {table} {definition}",
+            table = table_name,
+            name = self.name()
+        ))
+    }
+}
+
+fn print_matrix(results: &[(IndexSet, StrategyResult)]) {
+    println!();
+    println!(
+        "{:<8} {:>10} {:>14} {:>12} {:>12} {:>12}",
+        "indexes", "rows/s", "bytes/s", "heap", "index", "WAL"
+    );
+    for (set, result) in results {
+        if let Some(e) = &result.error {
+            println!("{:<8} failed: {}", set.name(), e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        println!(
+            "{:<8} {:>10.0} {:>14} {:>12} {:>12} {:>12}",
+            set.name(),
+            result.rows_per_second(),
+            units::rate(size / result.elapsed.as_secs_f64()),
+            units::bytes(result.after.heap as f64),
+            units::bytes(result.after.indexes as f64),
+            units::bytes(result.wal_bytes as f64)
+        );
+    }
+}
+
+pub fn indexes(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &IndexesArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let mut client = tls::connect(config)?;
+    client.batch_execute(&format!(
+        "DROP TABLE IF EXISTS {scratch};
+         CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS)"
+    ))?;
+
+    let mut results = Vec::new();
+    let outcome = args.sets.iter().try_for_each(|&set| -> anyhow::Result<()> {
+        println!("Loading with indexes: {}", set.name());
+        client.batch_execute(&format!("TRUNCATE {}", scratch))?;
+        if let Some(create) = set.create(scratch) {
+            client.batch_execute(&create)?;
+        }
+        let mut loaded = BenchmarkRunner::new(config.clone())
+            .with_generator(*generator)
+            .with_schema(*schema)
+            .with_config(RunConfig {
+                strategies: vec![args.strategy.clone()],
+                table_name: scratch.clone(),
+                batch_count,
+                workers: args.workers,
+                ..RunConfig::default()
+            })
+            .run()?;
+        results.push((set, loaded.remove(0)));
+        if set != IndexSet::None {
+            client.batch_execute(&format!("DROP INDEX {}_{}", scratch, set.name()))?;
+        }
+        Ok(())
+    });
+    client.batch_execute(&format!("DROP TABLE {}", scratch))?;
+    outcome?;
+
+    print_matrix(&results);
+    Ok(())
+}
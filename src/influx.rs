@@ -0,0 +1,106 @@
+//! `sink influx`: the rows as InfluxDB line protocol, written to a file or
+//! POSTed a batch at a time to a write endpoint, so that the same sensor
+//! simulation can feed an Influx comparison.
+
+use crate::cli::InfluxArgs;
+use crate::sink::Sink;
+use fast_generation::generator::Row;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+enum Destination {
+    File(BufWriter<File>),
+    #[cfg(feature = "http")]
+    Http {
+        url: String,
+        token: Option<String>,
+    },
+}
+
+pub struct InfluxSink {
+    destination: Destination,
+    schema: SchemaConfig,
+    measurement: String,
+    lines: Vec<u8>,
+    bytes: u64,
+}
+
+impl InfluxSink {
+    /// Points go in `measurement`; a write endpoint is sent `--token`, or
+    /// else `INFLUX_TOKEN` from the environment, if either is set.
+    pub fn new(
+        args: &InfluxArgs,
+        schema: &SchemaConfig,
+        measurement: &str,
+    ) -> anyhow::Result<InfluxSink> {
+        let destination = match &args.output {
+            Some(output) => Destination::File(BufWriter::new(
+                File::create(output)
+                    .map_err(|e| anyhow::anyhow!("creating {}: {}", output.display(), e))?,
+            )),
+            #[cfg(feature = "http")]
+            None => Destination::Http {
+                url: args.url.clone().expect("--output or --url is required"),
+                token: args
+                    .token
+                    .clone()
+                    .or_else(|| std::env::var("INFLUX_TOKEN").ok()),
+            },
+            #[cfg(not(feature = "http"))]
+            None => unreachable!("--output is required"),
+        };
+        Ok(InfluxSink {
+            destination,
+            schema: *schema,
+            measurement: measurement.to_string(),
+            lines: Vec::new(),
+            bytes: 0,
+        })
+    }
+}
+
+impl Sink for InfluxSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        self.lines.clear();
+        serialize::write_line_protocol(&mut self.lines, &self.schema, batch, &self.measurement)?;
+        self.bytes += self.lines.len() as u64;
+        match &mut self.destination {
+            Destination::File(writer) => writer.write_all(&self.lines)?,
+            #[cfg(feature = "http")]
+            Destination::Http { url, token } => {
+                let mut request = ureq::post(url.as_str())
+                    .config()
+                    .http_status_as_error(false)
+                    .build()
+                    .header("Content-Type", "text/plain; charset=utf-8");
+                if let Some(token) = token {
+                    request = request.header("Authorization", &format!("Token {}", token));
+                }
+                let mut response = request
+                    .send(&self.lines[..])
+                    .map_err(|e| anyhow::anyhow!("writing to {}: {}", url, e))?;
+                // Influx says which line it rejected and why in the body.
+                if !response.status().is_success() {
+                    anyhow::bail!(
+                        "writing to {}: {}: {}",
+                        url,
+                        response.status(),
+                        response.body_mut().read_to_string().unwrap_or_default()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        match self.destination {
+            Destination::File(mut writer) => writer.flush()?,
+            #[cfg(feature = "http")]
+            Destination::Http { .. } => {}
+        }
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,348 @@
+//! Rows read from a file, to load in place of generated ones: COPY output in
+//! any of the formats `export` writes, compressed or not, such as a sample
+//! of production data.
+
+use crate::export::{self, ExportFormat};
+use crate::generator::Row;
+use crate::pgcopy::PgCopyBinaryReader;
+use crate::schema::SchemaConfig;
+use crate::FastGenError;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+/// Rows per checksummed batch of a file, as `export` records them.
+pub const CHECKSUM_ROWS: usize = 10_000;
+
+/// CRC32 of every run of `rows` rows of a file, the last one possibly
+/// short, over their bytes as COPY wrote them before any compression; a
+/// corrupted file shows which batch differs rather than only that it does.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksums {
+    pub rows: usize,
+    pub crc32: Vec<u32>,
+}
+
+impl Checksums {
+    fn new(rows: usize) -> Checksums {
+        Checksums {
+            rows: rows.max(1),
+            crc32: Vec::new(),
+        }
+    }
+
+    /// Fails on the first batch whose checksum differs from `expected`'s.
+    pub(crate) fn verify(&self, path: &Path, expected: &Checksums) -> crate::Result<()> {
+        let batch = |index: usize| {
+            format!(
+                "{}: batch {} (rows {} to {})",
+                path.display(),
+                index + 1,
+                index * self.rows + 1,
+                (index + 1) * self.rows
+            )
+        };
+        if let Some(index) = (0..self.crc32.len().min(expected.crc32.len()))
+            .find(|&index| self.crc32[index] != expected.crc32[index])
+        {
+            return Err(FastGenError::Validation(format!(
+                "{} has CRC32 {:08x}, {:08x} was recorded",
+                batch(index),
+                self.crc32[index],
+                expected.crc32[index]
+            )));
+        }
+        if self.crc32.len() != expected.crc32.len() {
+            return Err(FastGenError::Validation(format!(
+                "{} holds {} batches of {} rows, {} were recorded",
+                path.display(),
+                self.crc32.len(),
+                self.rows,
+                expected.crc32.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Hashes rows into `Checksums` as they are read.
+pub(crate) struct Checksummer {
+    checksums: Checksums,
+    hasher: crc32fast::Hasher,
+    rows: usize,
+}
+
+impl Checksummer {
+    pub(crate) fn new(rows: usize) -> Checksummer {
+        Checksummer {
+            checksums: Checksums::new(rows),
+            hasher: crc32fast::Hasher::new(),
+            rows: 0,
+        }
+    }
+
+    pub(crate) fn row(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+        self.rows += 1;
+        if self.rows == self.checksums.rows {
+            self.end_batch();
+        }
+    }
+
+    /// A CSV or text row, hashed as if it ended in `\n` alone, as
+    /// `read_rows` reads it, so a file whose line endings were turned into
+    /// `\r\n` still matches the checksums recorded for it.
+    pub(crate) fn line(&mut self, bytes: &[u8]) {
+        match bytes.strip_suffix(b"\r\n") {
+            Some(line) => {
+                self.hasher.update(line);
+                self.row(b"\n");
+            }
+            None => self.row(bytes),
+        }
+    }
+
+    fn end_batch(&mut self) {
+        let hasher = std::mem::take(&mut self.hasher);
+        self.checksums.crc32.push(hasher.finalize());
+        self.rows = 0;
+    }
+
+    pub(crate) fn finish(mut self) -> Checksums {
+        if self.rows > 0 {
+            self.end_batch();
+        }
+        self.checksums
+    }
+}
+
+/// Keeps every byte read through it, so a binary row's bytes can be hashed
+/// once it is decoded.
+struct RecordingReader<R> {
+    inner: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Hands every row of `path`, with its bytes as COPY wrote them, to `each`.
+/// The format follows the file name the way `export` names files: `.csv`,
+/// `.tsv` for COPY's text format or `.pgcopy` for its binary one, each
+/// optionally followed by `.gz` or `.zst`. With `header`, the first line of
+/// a CSV or text file is skipped. With the `parquet` feature, a `.parquet`
+/// file is read too, its rows handed over with no bytes.
+fn read_rows(
+    path: &Path,
+    schema: &SchemaConfig,
+    header: bool,
+    mut each: impl FnMut(Row, &[u8]),
+) -> crate::Result<()> {
+    #[cfg(feature = "parquet")]
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "parquet")
+    {
+        return crate::parquet::read_rows(path, schema, |row| each(row, &[]));
+    }
+    let (format, codec) = export::file_format(path).ok_or_else(|| {
+        FastGenError::InvalidConfig(format!(
+            "can't tell the format of {} from its name; expected .csv, .tsv or .pgcopy, \
+             optionally followed by .gz or .zst{}",
+            path.display(),
+            if cfg!(feature = "parquet") {
+                ", or .parquet"
+            } else {
+                ""
+            }
+        ))
+    })?;
+    let file = File::open(path)
+        .map_err(|e| FastGenError::InvalidConfig(format!("opening {}: {}", path.display(), e)))?;
+    let reader: Box<dyn Read> = match codec {
+        Some(codec) => codec.decoder(BufReader::new(file))?,
+        None => Box::new(file),
+    };
+    let mut reader = BufReader::new(reader);
+
+    match format {
+        ExportFormat::Binary => {
+            let recording = RecordingReader {
+                inner: reader,
+                bytes: Vec::new(),
+            };
+            let mut rows = PgCopyBinaryReader::new(recording, schema)?;
+            rows.get_mut().bytes.clear();
+            while let Some(row) = rows.read_row()? {
+                each(row, &rows.get_mut().bytes);
+                rows.get_mut().bytes.clear();
+            }
+        }
+        ExportFormat::Csv | ExportFormat::Text => {
+            let separator = if format == ExportFormat::Csv {
+                ','
+            } else {
+                '\t'
+            };
+            let mut line = Vec::new();
+            let mut index = 0;
+            while reader.read_until(b'\n', &mut line)? > 0 {
+                index += 1;
+                if line.ends_with(b"\r\n") {
+                    line.remove(line.len() - 2);
+                }
+                if !(header && index == 1) {
+                    let row = std::str::from_utf8(&line)
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| {
+                            let text = text.strip_suffix('\n').unwrap_or(text);
+                            parse_line(text, separator, format, schema)
+                        })
+                        .map_err(|e| {
+                            FastGenError::Encoding(format!(
+                                "{} line {}: {}",
+                                path.display(),
+                                index,
+                                e
+                            ))
+                        })?;
+                    each(row, &line);
+                }
+                line.clear();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads every row of `path`, as `read_rows` does, into batches of
+/// `batch_size` rows, the last one possibly short. With `expected`, the
+/// file's checksums are compared with those first, and a file differing
+/// from them is an error rather than rows to load.
+pub fn read_batches(
+    path: &Path,
+    schema: &SchemaConfig,
+    header: bool,
+    batch_size: usize,
+    expected: Option<&Checksums>,
+) -> crate::Result<Vec<Vec<Row>>> {
+    let mut rows = Vec::new();
+    let mut checksummer =
+        Checksummer::new(expected.map_or(CHECKSUM_ROWS, |expected| expected.rows));
+    read_rows(path, schema, header, |row, bytes| {
+        rows.push(row);
+        if expected.is_some() {
+            checksummer.row(bytes);
+        }
+    })?;
+    if let Some(expected) = expected {
+        checksummer.finish().verify(path, expected)?;
+    }
+    if rows.is_empty() {
+        return Err(FastGenError::InvalidConfig(format!(
+            "{} holds no rows",
+            path.display()
+        )));
+    }
+    Ok(rows
+        .chunks(batch_size.max(1))
+        .map(|batch| batch.to_vec())
+        .collect())
+}
+
+/// The checksums of every `rows` rows of `path`, as `read_batches`
+/// verifies them.
+pub fn checksums(path: &Path, schema: &SchemaConfig, rows: usize) -> crate::Result<Checksums> {
+    let mut checksummer = Checksummer::new(rows);
+    read_rows(path, schema, false, |_, bytes| checksummer.row(bytes))?;
+    Ok(checksummer.finish())
+}
+
+/// A line of COPY output with the columns in table order. Values are bare
+/// numbers and timestamps, so CSV fields need no more than their quotes
+/// taken off.
+fn parse_line(
+    line: &str,
+    separator: char,
+    format: ExportFormat,
+    schema: &SchemaConfig,
+) -> Result<Row, String> {
+    let fields: Vec<&str> = line.split(separator).collect();
+    let [created, sensor_id, temperature] = fields[..] else {
+        return Err(format!("expected 3 columns, found {}", fields.len()));
+    };
+    let created = field(format, "created", created)?;
+    let sensor_id = field(format, "sensor_id", sensor_id)?;
+    let temperature = field(format, "temperature", temperature)?;
+    Ok((
+        schema
+            .time_column
+            .parse(created)
+            .ok_or_else(|| format!("invalid created {:?}", created))?,
+        sensor_id
+            .parse()
+            .map_err(|_| format!("invalid sensor_id {:?}", sensor_id))?,
+        temperature
+            .parse()
+            .map_err(|_| format!("invalid temperature {:?}", temperature))?,
+    ))
+}
+
+fn field<'a>(format: ExportFormat, column: &str, value: &'a str) -> Result<&'a str, String> {
+    let null = match format {
+        ExportFormat::Csv => value.is_empty(),
+        _ => value == "\\N",
+    };
+    if null {
+        return Err(format!("unexpected NULL in column {}", column));
+    }
+    Ok(value.trim_matches('"'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::TimeColumn;
+
+    #[test]
+    fn crlf_lines_read_and_hash_as_lf_ones() {
+        let schema = SchemaConfig {
+            time_column: TimeColumn::Timestamptz,
+            numeric_scale: None,
+        };
+        let lines = [
+            "2024-01-01 00:00:00+00,1,12.5",
+            "2024-01-01 00:00:00+00,2,-3",
+        ];
+        let mut read = Vec::new();
+        for ending in ["\n", "\r\n"] {
+            let path = std::env::temp_dir().join(format!(
+                "fast_generation-{}-{}.csv",
+                std::process::id(),
+                ending.len()
+            ));
+            std::fs::write(&path, lines.map(|line| line.to_string() + ending).concat()).unwrap();
+            let rows = read_batches(&path, &schema, false, 10, None);
+            let checksums = checksums(&path, &schema, 1);
+            std::fs::remove_file(&path).unwrap();
+            read.push((rows.unwrap(), checksums.unwrap()));
+        }
+        assert_eq!(read[0].0, read[1].0);
+        assert_eq!(read[1].0[0][1].2, -3.0);
+        assert_eq!(read[0].1, read[1].1);
+
+        let mut lf = Checksummer::new(1);
+        lf.line(b"a,b\n");
+        let mut crlf = Checksummer::new(1);
+        crlf.line(b"a,b\r\n");
+        assert_eq!(lf.finish(), crlf.finish());
+    }
+}
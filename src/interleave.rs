@@ -0,0 +1,234 @@
+//! The `interleave` command: loads with several strategies in the same
+//! time window, each into a table of its own, alternating batch by batch
+//! instead of running one strategy after the other, so that checkpoints,
+//! autovacuum and other slow background effects weigh on all of them alike.
+
+use crate::cli::InterleaveArgs;
+use crate::units;
+use fast_generation::generator::{generate_data, GeneratorConfig, Row, BASE_TEMP};
+use fast_generation::load::BatchLatency;
+use fast_generation::pool::BufferPool;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::server::{self, AutovacuumCounts, TableSizes};
+use fast_generation::strategy::{Strategy, StrategyContext, STRATEGIES};
+use fast_generation::tls;
+use postgres::{Client, Config};
+use std::time::{Duration, Instant};
+
+/// One strategy's share of the window.
+struct Lane {
+    strategy: Strategy,
+    table: String,
+    client: Client,
+    buffers: BufferPool,
+    /// Encoding and sending, summed over its batches.
+    busy: Duration,
+    latencies: Vec<Duration>,
+    rows: usize,
+    before: TableSizes,
+    autovacuum: AutovacuumCounts,
+    /// Why it stopped early; the others carry on.
+    error: Option<String>,
+}
+
+impl Lane {
+    fn load(&mut self, schema: &SchemaConfig, batch_data: &[Row]) {
+        let context = StrategyContext {
+            table_name: &self.table,
+            schema,
+            buffers: &self.buffers,
+            copy_chunk_size: None,
+        };
+        let started = Instant::now();
+        let sent = (self.strategy.encode)(&context, batch_data).and_then(|payload| {
+            let sending = Instant::now();
+            (self.strategy.send)(&mut self.client, &context, payload)?;
+            Ok(sending.elapsed())
+        });
+        match sent {
+            Ok(latency) => {
+                self.busy += started.elapsed();
+                self.latencies.push(latency);
+                self.rows += batch_data.len();
+            }
+            Err(e) => self.error = Some(e.report()),
+        }
+    }
+}
+
+/// Checkpoints the server has started, timed and requested, from
+/// pg_stat_checkpointer as of PostgreSQL 17 and pg_stat_bgwriter before.
+fn checkpoints(client: &mut Client) -> anyhow::Result<i64> {
+    let version: i32 = client
+        .query_one("SELECT current_setting('server_version_num')::int", &[])?
+        .get(0);
+    let query = match version >= 170_000 {
+        true => "SELECT num_timed + num_requested FROM pg_stat_checkpointer",
+        false => "SELECT checkpoints_timed + checkpoints_req FROM pg_stat_bgwriter",
+    };
+    Ok(client.query_one(query, &[])?.get(0))
+}
+
+fn print_lanes(lanes: &[Lane], after: &[TableSizes], autovacuum: &[AutovacuumCounts]) {
+    let first = lanes[0].strategy.name;
+    let reference = Some(&lanes[0])
+        .filter(|lane| lane.error.is_none())
+        .map(|lane| lane.rows as f64 / lane.busy.as_secs_f64());
+    println!();
+    println!(
+        "{:<12} {:>10} {:>10} {:>10} {:>10} {:>12} {:>11} {:>12}",
+        "strategy",
+        "rows/s",
+        "busy s",
+        "p50 ms",
+        "p99 ms",
+        "size",
+        "autovacuum",
+        format!("vs {}", first)
+    );
+    for ((lane, after), autovacuum) in lanes.iter().zip(after).zip(autovacuum) {
+        if let Some(e) = &lane.error {
+            println!("{:<12} failed: {}", lane.strategy.name, e);
+            continue;
+        }
+        let speed = lane.rows as f64 / lane.busy.as_secs_f64();
+        let (p50, p99) =
+            BatchLatency::from_samples(lane.latencies.clone()).map_or((0.0, 0.0), |latency| {
+                (
+                    latency.p50.as_secs_f64() * 1000.0,
+                    latency.p99.as_secs_f64() * 1000.0,
+                )
+            });
+        println!(
+            "{:<12} {:>10.0} {:>10.2} {:>10.1} {:>10.1} {:>12} {:>11} {:>12}",
+            lane.strategy.name,
+            speed,
+            lane.busy.as_secs_f64(),
+            p50,
+            p99,
+            units::bytes((after.total - lane.before.total) as f64),
+            format!("{}v {}a", autovacuum.vacuums, autovacuum.analyzes),
+            reference.map_or("-".to_string(), |reference| format!(
+                "{:+.1}%",
+                (speed / reference - 1.0) * 100.0
+            ))
+        );
+    }
+}
+
+pub fn interleave(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &InterleaveArgs,
+) -> anyhow::Result<()> {
+    generator.validate(batch_count)?;
+    let mut strategies: Vec<Strategy> = Vec::new();
+    for name in &args.strategies {
+        // Each strategy loads the table named after it, so one given twice
+        // would load the same table twice over.
+        if strategies.iter().any(|strategy| strategy.name == name) {
+            println!("{} given more than once, loading with it once", name);
+            continue;
+        }
+        strategies.push(*STRATEGIES.iter().find(|s| s.name == name).unwrap());
+    }
+    if strategies.is_empty() {
+        strategies = STRATEGIES.to_vec();
+    }
+    let tables: Vec<String> = strategies
+        .iter()
+        .map(|strategy| format!("{}_{}", args.scratch_table, strategy.name.replace('-', "_")))
+        .collect();
+
+    let mut client = tls::connect(config)?;
+    let wal = server::can_read_wal(&mut client)?;
+    let mut lanes = Vec::new();
+    let mut window = Duration::ZERO;
+    let mut wal_bytes = 0;
+    let mut checkpointed = 0;
+    let mut after = Vec::new();
+    let mut autovacuum = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        for (strategy, table) in strategies.iter().zip(&tables) {
+            client.batch_execute(&format!(
+                "DROP TABLE IF EXISTS {table};
+                 CREATE TABLE {table} (LIKE {table_name} INCLUDING DEFAULTS)"
+            ))?;
+            lanes.push(Lane {
+                strategy: *strategy,
+                table: table.clone(),
+                client: tls::connect(config)?,
+                buffers: BufferPool::new(),
+                busy: Duration::ZERO,
+                latencies: Vec::new(),
+                rows: 0,
+                before: server::table_sizes(&mut client, table)?,
+                autovacuum: server::autovacuum_counts(&mut client, table)?,
+                error: None,
+            });
+        }
+        println!(
+            "Loading {} batches alternately with {}",
+            batch_count,
+            strategies
+                .iter()
+                .map(|strategy| strategy.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let checkpoints_before = checkpoints(&mut client)?;
+        let wal_before = match wal {
+            true => server::wal_position(&mut client)?,
+            false => 0,
+        };
+        let started = Instant::now();
+        for (index, (batch_data, _)) in generate_data(generator, BASE_TEMP, batch_count).enumerate()
+        {
+            // Each batch goes first to the next strategy in turn, so none
+            // always follows another.
+            for turn in 0..lanes.len() {
+                let lane = &mut lanes[(index + turn) % strategies.len()];
+                if lane.error.is_none() {
+                    lane.load(schema, &batch_data);
+                }
+            }
+            if lanes.iter().all(|lane| lane.error.is_some()) {
+                break;
+            }
+        }
+        window = started.elapsed();
+        if wal {
+            wal_bytes = server::wal_position(&mut client)? - wal_before;
+        }
+        checkpointed = checkpoints(&mut client)? - checkpoints_before;
+        for lane in &lanes {
+            after.push(server::table_sizes(&mut client, &lane.table)?);
+            autovacuum
+                .push(server::autovacuum_counts(&mut client, &lane.table)?.since(lane.autovacuum));
+        }
+        Ok(())
+    }();
+    for table in &tables {
+        client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table))?;
+    }
+    outcome?;
+
+    println!();
+    println!(
+        "Window: {:.2}s, {} checkpoint(s), {} WAL",
+        window.as_secs_f64(),
+        checkpointed,
+        match wal {
+            true => units::bytes(wal_bytes as f64),
+            false => "unmeasured".to_string(),
+        }
+    );
+    print_lanes(&lanes, &after, &autovacuum);
+    if lanes.iter().any(|lane| lane.error.is_some()) {
+        anyhow::bail!("strategies failed while interleaved");
+    }
+    Ok(())
+}
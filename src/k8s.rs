@@ -0,0 +1,230 @@
+//! The `k8s-manifest` command: YAML for a ConfigMap holding a scenario and an
+//! indexed Job running it on several pods against one database. Each pod
+//! loads as the node its completion index names, and reads the connection
+//! string from a Secret so that it never appears in the manifest.
+
+use crate::cli::{LoadArgs, ManifestArgs};
+use chrono::SecondsFormat;
+use clap::ValueEnum;
+use fast_generation::runner::StrategyOrder;
+use fast_generation::{GeneratorConfig, SchemaConfig};
+use std::fmt::Write;
+
+/// `value` as a double-quoted YAML scalar, so that numbers and SQL alike
+/// stay strings.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The options of the scenario that take a value, as flag, ConfigMap key
+/// and value. The start is fixed here so that every pod shares one timeline.
+pub fn settings(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    load: &LoadArgs,
+) -> Vec<(&'static str, String, String)> {
+    let mut settings = vec![
+        ("seed", generator.seed.to_string()),
+        (
+            "start",
+            generator
+                .start_time()
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+        ),
+        ("batch-interval-ms", generator.batch_interval_ms.to_string()),
+        ("special-rate", generator.special_rate.to_string()),
+        (
+            "time-column",
+            schema
+                .time_column
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_string(),
+        ),
+        ("iterations", load.iterations.to_string()),
+        ("workers", load.workers.to_string()),
+        ("think-time-ms", load.think_time_ms.to_string()),
+        ("think-jitter-ms", load.think_jitter_ms.to_string()),
+        ("retries", load.retries.to_string()),
+    ];
+    for (flag, size) in [
+        ("batch-size-min", generator.batch_size_min),
+        ("batch-size-max", generator.batch_size_max),
+    ] {
+        if let Some(size) = size {
+            settings.push((flag, size.to_string()));
+        }
+    }
+    if let Some(scale) = schema.numeric_scale {
+        settings.push(("numeric-scale", scale.to_string()));
+    }
+    if let Some(size) = load.copy_chunk_size {
+        settings.push(("copy-chunk-size", size.to_string()));
+    }
+    if let Some(size) = load.max_client_memory {
+        settings.push(("max-client-memory", size.to_string()));
+    }
+    if let Some(ms) = load.target_batch_ms {
+        settings.push(("target-batch-ms", ms.to_string()));
+    }
+    if let Some(secs) = load.strategy_timeout {
+        settings.push(("strategy-timeout", secs.to_string()));
+    }
+    if let Some(rate) = load.rate {
+        settings.push(("rate", rate.to_string()));
+        settings.push((
+            "shape",
+            load.shape
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_string(),
+        ));
+        settings.push(("min-rate", load.min_rate.to_string()));
+        settings.push(("period-secs", load.period_secs.to_string()));
+    }
+    if let Some(interval) = load.chaos_interval_ms {
+        settings.push(("chaos-interval-ms", interval.to_string()));
+    }
+    if let Some(every) = load.validate_every {
+        settings.push(("validate-every", every.to_string()));
+        settings.push(("validate-sample", load.validate_sample.to_string()));
+    }
+    if load.audit_duplicates {
+        let key: Vec<_> = load
+            .audit_key
+            .iter()
+            .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+            .collect();
+        settings.push(("audit-key", key.join(",")));
+    }
+    if load.check_scale {
+        settings.push(("scale-sample", load.scale_sample.to_string()));
+    }
+    if !load.strategies.is_empty() {
+        settings.push(("strategy", load.strategies.join(",")));
+    }
+    if !load.skip.is_empty() {
+        settings.push(("skip", load.skip.join(",")));
+    }
+    if load.order != StrategyOrder::Listed {
+        settings.push((
+            "order",
+            load.order
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_string(),
+        ));
+    }
+    if let Some(reference) = &load.reference {
+        settings.push(("reference", reference.clone()));
+    }
+    let mut settings: Vec<_> = settings
+        .into_iter()
+        .map(|(flag, value)| (flag, env_name(flag), value))
+        .collect();
+
+    for (flag, statements) in [
+        ("setup-sql", &load.setup_sql),
+        ("teardown-sql", &load.teardown_sql),
+        ("session-sql", &load.session_sql),
+    ] {
+        for (index, statement) in statements.iter().enumerate() {
+            let key = format!("{}_{}", env_name(flag), index + 1);
+            settings.push((flag, key, statement.clone()));
+        }
+    }
+    settings
+}
+
+/// The options of the scenario that are set by naming them.
+pub fn switches(load: &LoadArgs) -> Vec<&'static str> {
+    [
+        ("sequential", load.sequential),
+        ("pause-autovacuum", load.pause_autovacuum),
+        ("no-reconcile", load.no_reconcile),
+        ("audit-duplicates", load.audit_duplicates),
+        ("check-scale", load.check_scale),
+        ("settle", load.settle),
+        ("pregenerate", load.pregenerate),
+    ]
+    .into_iter()
+    .filter_map(|(flag, set)| set.then_some(flag))
+    .collect()
+}
+
+fn env_name(flag: &str) -> String {
+    flag.to_uppercase().replace('-', "_")
+}
+
+pub fn manifest(generator: &GeneratorConfig, schema: &SchemaConfig, args: &ManifestArgs) -> String {
+    let settings = settings(generator, schema, &args.load);
+    let mut command_line = vec![
+        "--dsn".to_string(),
+        "$(DSN)".to_string(),
+        "run".to_string(),
+        "--node-index".to_string(),
+        "$(JOB_COMPLETION_INDEX)".to_string(),
+    ];
+    for (flag, key, _) in &settings {
+        command_line.push(format!("--{}", flag));
+        command_line.push(format!("$({})", key));
+    }
+    for flag in switches(&args.load) {
+        command_line.push(format!("--{}", flag));
+    }
+
+    let name = quote(&args.name);
+    let mut yaml = String::new();
+    writeln!(yaml, "apiVersion: v1").unwrap();
+    writeln!(yaml, "kind: ConfigMap").unwrap();
+    writeln!(yaml, "metadata:").unwrap();
+    writeln!(yaml, "  name: {}", name).unwrap();
+    writeln!(yaml, "data:").unwrap();
+    for (_, key, value) in &settings {
+        writeln!(yaml, "  {}: {}", key, quote(value)).unwrap();
+    }
+    writeln!(yaml, "---").unwrap();
+    writeln!(yaml, "apiVersion: batch/v1").unwrap();
+    writeln!(yaml, "kind: Job").unwrap();
+    writeln!(yaml, "metadata:").unwrap();
+    writeln!(yaml, "  name: {}", name).unwrap();
+    writeln!(yaml, "spec:").unwrap();
+    writeln!(yaml, "  completionMode: Indexed").unwrap();
+    writeln!(yaml, "  completions: {}", args.parallelism).unwrap();
+    writeln!(yaml, "  parallelism: {}", args.parallelism).unwrap();
+    writeln!(yaml, "  backoffLimit: 0").unwrap();
+    writeln!(yaml, "  template:").unwrap();
+    writeln!(yaml, "    spec:").unwrap();
+    writeln!(yaml, "      restartPolicy: Never").unwrap();
+    writeln!(yaml, "      containers:").unwrap();
+    writeln!(yaml, "        - name: fast-generation").unwrap();
+    writeln!(yaml, "          image: {}", quote(&args.image)).unwrap();
+    writeln!(yaml, "          command: [\"fast_generation\"]").unwrap();
+    writeln!(yaml, "          args:").unwrap();
+    for arg in &command_line {
+        writeln!(yaml, "            - {}", quote(arg)).unwrap();
+    }
+    writeln!(yaml, "          envFrom:").unwrap();
+    writeln!(yaml, "            - configMapRef:").unwrap();
+    writeln!(yaml, "                name: {}", name).unwrap();
+    writeln!(yaml, "          env:").unwrap();
+    writeln!(yaml, "            - name: DSN").unwrap();
+    writeln!(yaml, "              valueFrom:").unwrap();
+    writeln!(yaml, "                secretKeyRef:").unwrap();
+    writeln!(yaml, "                  name: {}", quote(&args.dsn_secret)).unwrap();
+    writeln!(yaml, "                  key: {}", quote(&args.dsn_key)).unwrap();
+    yaml
+}
@@ -0,0 +1,140 @@
+//! `sink kafka`: produces each generated row to a Kafka topic as an Avro
+//! message keyed by its sensor, for ingest paths running through a
+//! connector into Postgres. With a schema registry the schema is registered
+//! first and messages carry its id the way Confluent's serializers frame
+//! them, so the usual Avro converters read them as they are.
+
+use crate::cli::KafkaArgs;
+use fast_generation::avro;
+use fast_generation::generator::Row;
+use fast_generation::schema::SchemaConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::util::Timeout;
+use rdkafka::{ClientConfig, ClientContext};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long to let the producer deliver when its queue is full.
+const QUEUE_FULL_WAIT: Duration = Duration::from_millis(100);
+
+/// Counts the messages the brokers didn't take.
+#[derive(Default)]
+struct Deliveries {
+    failed: Mutex<(u64, Option<String>)>,
+}
+
+impl ClientContext for Deliveries {}
+
+impl ProducerContext for Deliveries {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            let mut failed = self.failed.lock().unwrap();
+            failed.0 += 1;
+            failed.1.get_or_insert_with(|| e.to_string());
+        }
+    }
+}
+
+/// Registers `schema` under `subject` and returns its id, the same one
+/// again if it is registered already.
+fn register(registry: &str, subject: &str, schema: &str) -> anyhow::Result<u32> {
+    let url = format!(
+        "{}/subjects/{}/versions",
+        registry.trim_end_matches('/'),
+        subject
+    );
+    let registered: serde_json::Value = ureq::post(&url)
+        .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+        .send_json(serde_json::json!({ "schema": schema }))
+        .map_err(|e| anyhow::anyhow!("registering the schema at {}: {}", url, e))?
+        .body_mut()
+        .read_json()?;
+    registered["id"]
+        .as_u64()
+        .map(|id| id as u32)
+        .ok_or_else(|| anyhow::anyhow!("{} returned no schema id: {}", url, registered))
+}
+
+pub struct KafkaSink {
+    producer: BaseProducer<Deliveries>,
+    topic: String,
+    schema: SchemaConfig,
+    schema_id: Option<u32>,
+    bytes: u64,
+}
+
+impl KafkaSink {
+    pub fn new(args: &KafkaArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<KafkaSink> {
+        let schema_id = match &args.schema_registry {
+            Some(registry) => {
+                let subject = args
+                    .subject
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-value", args.topic));
+                let id = register(registry, &subject, &avro::schema_json(schema, name))?;
+                println!("Registered schema {} as {}", id, subject);
+                Some(id)
+            }
+            None => None,
+        };
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &args.brokers);
+        for (key, value) in &args.producer_config {
+            config.set(key, value);
+        }
+        Ok(KafkaSink {
+            producer: config.create_with_context(Deliveries::default())?,
+            topic: args.topic.clone(),
+            schema: *schema,
+            schema_id,
+            bytes: 0,
+        })
+    }
+}
+
+impl crate::sink::Sink for KafkaSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        for row in batch {
+            let payload = match self.schema_id {
+                Some(id) => avro::confluent_message(id, &self.schema, row)?,
+                None => {
+                    let mut payload = Vec::new();
+                    avro::write_row(&mut payload, &self.schema, row)?;
+                    payload
+                }
+            };
+            let key = row.1.to_string();
+            self.bytes += (key.len() + payload.len()) as u64;
+            let mut record = BaseRecord::to(&self.topic).key(&key).payload(&payload);
+            loop {
+                match self.producer.send(record) {
+                    Ok(()) => break,
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                        self.producer.poll(QUEUE_FULL_WAIT);
+                        record = returned;
+                    }
+                    Err((e, _)) => anyhow::bail!("producing to {}: {}", self.topic, e),
+                }
+            }
+        }
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        self.producer.flush(Timeout::Never)?;
+        let failed = self.producer.context().failed.lock().unwrap();
+        if let (count, Some(error)) = &*failed {
+            anyhow::bail!(
+                "{} messages were not delivered to {}, the first with: {}",
+                count,
+                self.topic,
+                error
+            );
+        }
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,71 @@
+//! Generation of synthetic sensor data and benchmarks of the ways to bulk
+//! load it into Postgres. The `fast_generation` binary is built on top of
+//! this library; `BenchmarkRunner` drives the same runs programmatically.
+//! Library functions fail with a `FastGenError`.
+
+#[cfg(feature = "postgres")]
+pub mod adaptive;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+#[cfg(feature = "postgres")]
+pub mod audit;
+pub mod avro;
+pub mod bench;
+pub mod cache;
+#[cfg(feature = "cli")]
+pub mod connection;
+#[cfg(feature = "postgres")]
+pub mod dump;
+#[cfg(feature = "postgres")]
+pub mod environment;
+pub mod error;
+#[cfg(feature = "postgres")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod generator;
+#[cfg(feature = "postgres")]
+pub mod input;
+#[cfg(feature = "postgres")]
+pub mod load;
+#[cfg(feature = "postgres")]
+pub mod mapped;
+#[cfg(feature = "postgres")]
+pub mod observer;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod pgcopy;
+pub mod pool;
+pub mod protobuf;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "postgres")]
+pub mod read;
+#[cfg(feature = "postgres")]
+pub mod runner;
+pub mod schema;
+pub mod serialize;
+#[cfg(feature = "postgres")]
+pub mod server;
+#[cfg(feature = "postgres")]
+pub mod shape;
+#[cfg(feature = "postgres")]
+pub mod sql_log;
+#[cfg(feature = "postgres")]
+pub mod strategy;
+#[cfg(feature = "postgres")]
+pub mod tls;
+#[cfg(feature = "postgres")]
+pub mod usage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::{FastGenError, Result};
+pub use generator::GeneratorConfig;
+#[cfg(feature = "postgres")]
+pub use observer::Observer;
+#[cfg(feature = "postgres")]
+pub use runner::{BenchmarkRunner, RunConfig, StrategyResult};
+pub use schema::SchemaConfig;
+#[cfg(feature = "postgres")]
+pub use strategy::{Strategy, STRATEGIES};
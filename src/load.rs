@@ -0,0 +1,660 @@
+use crate::adaptive::BatchSizer;
+use crate::generator::Row;
+use crate::observer::Observer;
+use crate::schema::TimeValue;
+use crate::shape::LoadShape;
+use crate::sql_log;
+use crate::strategy::{NumericValue, Payload, Strategy, StrategyContext};
+use crate::tls;
+use crate::FastGenError;
+use postgres::{Client, Config};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+pub struct LoadOptions<'a> {
+    pub config: &'a Config,
+    pub workers: usize,
+    /// Encode each batch only after the previous one was sent.
+    pub sequential: bool,
+    /// Run on each connection a worker opens.
+    pub session_sql: &'a [String],
+    pub observer: &'a dyn Observer,
+    /// Once set, no further batches are handed out.
+    pub stop: Option<&'a AtomicBool>,
+    /// Pause before sending each batch, plus up to `think_jitter` at random.
+    pub think_time: Duration,
+    pub think_jitter: Duration,
+    /// Paces handing out batches.
+    pub shape: Option<LoadShape>,
+    /// Times to reconnect and resend a batch whose connection was lost.
+    pub retries: u32,
+    /// Terminate one of the loading backends this often, in turn.
+    pub chaos: Option<Duration>,
+    /// Read back a sample of every `every`th batch once committed.
+    pub validate: Option<Validation>,
+    /// Bytes the batches generated and not yet sent may take, with their
+    /// encoded payloads, before generating waits for some to be sent.
+    pub max_memory: Option<usize>,
+    /// Told every batch's latency, to pick the size of those to come.
+    pub sizer: Option<&'a BatchSizer>,
+    /// Stop handing out batches at this time and cancel the statements in
+    /// flight.
+    pub deadline: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Validation {
+    pub every: u64,
+    /// Rows of the batch to read back.
+    pub sample: usize,
+}
+
+impl LoadOptions<'_> {
+    fn stopped(&self) -> bool {
+        self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Whether workers register their backends, to be terminated or
+    /// cancelled.
+    fn tracks_backends(&self) -> bool {
+        self.chaos.is_some() || self.deadline.is_some()
+    }
+
+    /// Waits until `shape` allows `rows` rows since `started`, or the load
+    /// is stopped.
+    fn pace(&self, shape: &LoadShape, started: Instant, rows: usize) {
+        loop {
+            let seconds = started.elapsed().as_secs_f64();
+            let ahead = rows as f64 - shape.rows_by(seconds);
+            if ahead <= 0.0 || self.stopped() || self.expired() {
+                return;
+            }
+            let wait = (ahead / shape.rate_at(seconds)).clamp(0.001, 0.1);
+            thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+
+    /// Opens a new connection in place of a lost one, waiting a little
+    /// longer before each further attempt in case the server is restarting.
+    fn reconnect(&self, client: &mut Client, attempt: u32) -> crate::Result<()> {
+        thread::sleep(RECONNECT_BACKOFF * (attempt - 1).min(30));
+        *client = tls::connect(self.config)?;
+        execute_all(client, self.session_sql)
+    }
+
+    fn think(&self) {
+        let mut pause = self.think_time;
+        if !self.think_jitter.is_zero() {
+            pause += rand::thread_rng().gen_range(Duration::ZERO..=self.think_jitter);
+        }
+        if !pause.is_zero() {
+            thread::sleep(pause);
+        }
+    }
+}
+
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// What `load` measured, across all workers.
+#[derive(Default)]
+pub struct Loaded {
+    /// How long each batch took to send, in no particular order, including
+    /// any reconnecting and resending.
+    pub latencies: Vec<Duration>,
+    /// From each lost connection until the batch it was sending was
+    /// committed over a new one.
+    pub recoveries: Vec<Duration>,
+    /// Backends `chaos` terminated.
+    pub terminated: usize,
+}
+
+/// Lost connections and how long loading took to get going again.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Recovery {
+    /// Backends the run terminated on purpose.
+    pub terminated: usize,
+    pub reconnects: usize,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+impl Recovery {
+    pub fn new(terminated: usize, recoveries: &[Duration]) -> Recovery {
+        Recovery {
+            terminated,
+            reconnects: recoveries.len(),
+            mean: recoveries
+                .iter()
+                .sum::<Duration>()
+                .checked_div(recoveries.len() as u32)
+                .unwrap_or_default(),
+            max: recoveries.iter().copied().max().unwrap_or_default(),
+        }
+    }
+}
+
+/// Process ids of the loading backends, by worker, for `chaos` to pick from
+/// and `watchdog` to cancel.
+struct Backends(Mutex<Vec<Option<i32>>>);
+
+impl Backends {
+    fn register(&self, worker: usize, client: &mut Client) -> crate::Result<()> {
+        let pid = client.query_one("SELECT pg_backend_pid()", &[])?.get(0);
+        self.0.lock().unwrap()[worker] = Some(pid);
+        Ok(())
+    }
+}
+
+/// Terminates the loading backends in turn, one every `interval`, until
+/// `done`, and returns how many it terminated.
+fn chaos(
+    options: &LoadOptions,
+    interval: Duration,
+    backends: &Backends,
+    done: &AtomicBool,
+) -> crate::Result<usize> {
+    let mut client = tls::connect(options.config)?;
+    let mut terminated = 0;
+    let mut turn = 0;
+    loop {
+        let due = Instant::now() + interval;
+        while Instant::now() < due {
+            if done.load(Ordering::Relaxed) || options.stopped() {
+                return Ok(terminated);
+            }
+            thread::sleep((due - Instant::now()).min(Duration::from_millis(10)));
+        }
+        let pids: Vec<i32> = backends
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        if pids.is_empty() {
+            continue;
+        }
+        let pid = pids[turn % pids.len()];
+        turn += 1;
+        if client
+            .query_one("SELECT pg_terminate_backend($1)", &[&pid])?
+            .get(0)
+        {
+            terminated += 1;
+        }
+    }
+}
+
+/// Waits for `deadline`, unless loading is `done` first, then cancels what
+/// every loading backend runs until they have all stopped: again every so
+/// often, in case one was between statements. Returns whether it did.
+fn watchdog(
+    options: &LoadOptions,
+    deadline: Instant,
+    backends: &Backends,
+    done: &AtomicBool,
+) -> crate::Result<bool> {
+    let mut client = tls::connect(options.config)?;
+    while Instant::now() < deadline {
+        if done.load(Ordering::Relaxed) || options.stopped() {
+            return Ok(false);
+        }
+        thread::sleep((deadline - Instant::now()).min(Duration::from_millis(10)));
+    }
+    while !done.load(Ordering::Relaxed) {
+        let pids: Vec<i32> = backends
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        for pid in pids {
+            client.execute("SELECT pg_cancel_backend($1)", &[&pid])?;
+        }
+        thread::sleep(CANCEL_INTERVAL);
+    }
+    Ok(true)
+}
+
+const CANCEL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long the strategy's `send` took per batch, i.e. until the server
+/// had the batch committed.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct BatchLatency {
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl BatchLatency {
+    /// `None` without any samples.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<BatchLatency> {
+        samples.sort_unstable();
+        let max = *samples.last()?;
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        Some(BatchLatency {
+            p50: percentile(0.5),
+            p99: percentile(0.99),
+            max,
+        })
+    }
+}
+
+/// Runs each statement in turn, naming the one that failed.
+pub fn execute_all(client: &mut Client, statements: &[String]) -> crate::Result<()> {
+    for statement in statements {
+        sql_log::statement(statement);
+        client
+            .batch_execute(statement)
+            .map_err(|source| FastGenError::Statement {
+                statement: statement.clone(),
+                source,
+            })?;
+    }
+    Ok(())
+}
+
+/// Reads `sample` rows of a committed batch, picked at random, back from
+/// the table and fails on the first not found there as generated, so an
+/// encoder or strategy bug shows within seconds of the load starting. A
+/// temperature is compared after rounding it to the schema's numeric scale.
+fn read_back(
+    client: &mut Client,
+    context: &StrategyContext,
+    batch_data: &[Row],
+    current_tick: i64,
+    sample: usize,
+) -> crate::Result<()> {
+    let time_column = context.schema.time_column;
+    let picked: Vec<&Row> = rand::seq::index::sample(
+        &mut rand::thread_rng(),
+        batch_data.len(),
+        sample.min(batch_data.len()),
+    )
+    .into_iter()
+    .map(|index| &batch_data[index])
+    .collect();
+    let created: Vec<TimeValue> = picked.iter().map(|row| time_column.value(row.0)).collect();
+    let sensors: Vec<i32> = picked.iter().map(|row| row.1).collect();
+    let temperatures: Vec<NumericValue> =
+        picked.iter().map(|row| NumericValue::new(row.2)).collect();
+    let generated = match context.schema.numeric_scale {
+        Some(scale) => format!("round(s.temperature, {})", scale),
+        None => "s.temperature".to_string(),
+    };
+    let query = format!(
+        "SELECT s.position, (SELECT count(*) FROM {table} t \
+             WHERE t.created = s.created AND t.sensor_id = s.sensor_id) \
+         FROM unnest($1::{time_type}[], $2::integer[], $3::numeric[]) WITH ORDINALITY \
+             AS s(created, sensor_id, temperature, position) \
+         WHERE NOT EXISTS (SELECT FROM {table} t WHERE t.created = s.created \
+             AND t.sensor_id = s.sensor_id AND t.temperature = {generated}) \
+         ORDER BY s.position LIMIT 1",
+        table = context.table_name,
+        time_type = time_column.data_type(),
+    );
+    sql_log::statement(&query);
+    let Some(missing) = client.query_opt(&query, &[&created, &sensors, &temperatures])? else {
+        return Ok(());
+    };
+    let (created, sensor_id, temperature) = picked[missing.get::<_, i64>(0) as usize - 1];
+    let found: i64 = missing.get(1);
+    Err(FastGenError::Validation(format!(
+        "batch {}: sensor {} at {} was loaded with temperature {}, but none of the {} rows \
+         read back for that sensor and time has it",
+        current_tick,
+        sensor_id,
+        time_column.text(*created),
+        temperature,
+        found
+    )))
+}
+
+/// Memory taken by batches generated and not yet sent. Only the generator
+/// waits on it, so workers holding reservations always make progress.
+struct MemoryBudget {
+    limit: usize,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    fn new(limit: usize) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            in_flight: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until `bytes` more fit in the budget, or until `abandoned`,
+    /// when it returns `None`. A batch larger than the whole budget goes
+    /// once nothing else is in flight.
+    fn reserve(&self, bytes: usize, abandoned: impl Fn() -> bool) -> Option<Reservation<'_>> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        while *in_flight > 0 && *in_flight + bytes > self.limit {
+            if abandoned() {
+                return None;
+            }
+            in_flight = self
+                .released
+                .wait_timeout(in_flight, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+        *in_flight += bytes;
+        Some(Reservation {
+            budget: self,
+            bytes,
+        })
+    }
+}
+
+/// A batch's share of a `MemoryBudget`, given back on drop.
+struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: usize,
+}
+
+impl Reservation<'_> {
+    /// Adds the batch's encoded payload, without waiting: it is already
+    /// generated, and holding it back would not free anything.
+    fn grow(&mut self, bytes: usize) {
+        *self.budget.in_flight.lock().unwrap() += bytes;
+        self.bytes += bytes;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.in_flight.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// A batch on its way to a worker: its rows, generated or borrowed from
+/// batches held for the whole run, its tick and, under a memory budget, its
+/// reservation.
+type Batch<'a, 'b> = (Cow<'b, [Row]>, i64, Option<Reservation<'a>>);
+
+/// Grows a batch's reservation, if any, by its encoded payload.
+fn account(reservation: &mut Option<Reservation>, payload: &Payload) {
+    if let Some(reservation) = reservation {
+        reservation.grow(payload.bytes());
+    }
+}
+
+/// A panicked thread has already printed its message; the load reports it as
+/// interrupted rather than taking the caller down with it.
+fn joined<T>(result: thread::Result<crate::Result<T>>) -> crate::Result<T> {
+    result.unwrap_or_else(|_| {
+        Err(FastGenError::Interrupted(
+            "a loading thread panicked".to_string(),
+        ))
+    })
+}
+
+/// Feeds every batch to `strategy`. The batches are generated on this
+/// thread, in order, and handed over a bounded channel to the workers; a
+/// single worker reuses `client`, which should already have run the session
+/// SQL, more open a connection each. A worker whose connection is lost
+/// reconnects and resends the batch up to `retries` times; a batch the
+/// server committed just as the connection broke is then loaded twice.
+/// Under `max_memory`, generating the next batch waits while those in
+/// flight take more than that; borrowed batches take nothing. Past
+/// `deadline`, the load stops and fails with `FastGenError::TimedOut`,
+/// unless every batch was already loaded.
+pub fn load<'b>(
+    strategy: &Strategy,
+    client: &mut Client,
+    context: &StrategyContext,
+    batches: impl Iterator<Item = (Cow<'b, [Row]>, i64)>,
+    options: &LoadOptions,
+) -> crate::Result<Loaded> {
+    let began = Instant::now();
+    let budget = options.max_memory.map(MemoryBudget::new);
+    let (sender, receiver) = mpsc::sync_channel::<Batch>(options.workers.max(1));
+    // Each worker holds the receiver, so that sending fails once all of
+    // them have stopped.
+    let receiver = Arc::new(Mutex::new(receiver));
+    let backends = Backends(Mutex::new(vec![None; options.workers.max(1)]));
+    let done = AtomicBool::new(false);
+
+    let (loaded, terminated, cut_short, timed_out) = thread::scope(|scope| {
+        let (backends, done, budget) = (&backends, &done, &budget);
+        let terminator = options
+            .chaos
+            .map(|interval| scope.spawn(move || chaos(options, interval, backends, done)));
+        let watchdog = options
+            .deadline
+            .map(|deadline| scope.spawn(move || watchdog(options, deadline, backends, done)));
+        let handles = if options.workers <= 1 {
+            vec![scope
+                .spawn(move || worker(strategy, client, context, &receiver, options, backends, 0))]
+        } else {
+            let handles = (0..options.workers)
+                .map(|index| {
+                    let receiver = receiver.clone();
+                    scope.spawn(move || -> crate::Result<Loaded> {
+                        let mut client = tls::connect(options.config)?;
+                        execute_all(&mut client, options.session_sql)?;
+                        worker(
+                            strategy,
+                            &mut client,
+                            context,
+                            &receiver,
+                            options,
+                            backends,
+                            index,
+                        )
+                    })
+                })
+                .collect();
+            drop(receiver);
+            handles
+        };
+
+        let started = Instant::now();
+        let mut rows = 0;
+        let mut cut_short = false;
+        for batch in batches {
+            if options.expired() {
+                cut_short = true;
+                break;
+            }
+            if let Some(shape) = &options.shape {
+                options.pace(shape, started, rows);
+            }
+            rows += batch.0.len();
+            let (batch_data, current_tick) = batch;
+            let reservation = match budget {
+                Some(budget) => {
+                    let bytes = match &batch_data {
+                        Cow::Owned(rows) => rows.capacity() * std::mem::size_of::<Row>(),
+                        Cow::Borrowed(_) => 0,
+                    };
+                    // Workers that all stopped will never give any back.
+                    let abandoned = || {
+                        options.stopped()
+                            || options.expired()
+                            || handles.iter().all(|handle| handle.is_finished())
+                    };
+                    match budget.reserve(bytes, abandoned) {
+                        Some(reservation) => Some(reservation),
+                        None => {
+                            cut_short = true;
+                            break;
+                        }
+                    }
+                }
+                None => None,
+            };
+            if options.stopped()
+                || sender
+                    .send((batch_data, current_tick, reservation))
+                    .is_err()
+            {
+                cut_short = true;
+                break;
+            }
+        }
+        drop(sender);
+
+        let loaded = handles
+            .into_iter()
+            .map(|handle| joined(handle.join()))
+            .collect::<crate::Result<Vec<_>>>();
+        done.store(true, Ordering::Relaxed);
+        let terminated = terminator.map_or(Ok(0), |terminator| joined(terminator.join()));
+        let timed_out = watchdog.map_or(Ok(false), |watchdog| joined(watchdog.join()));
+        (loaded, terminated, cut_short, timed_out)
+    });
+
+    if options.stopped() {
+        return Err(FastGenError::Interrupted("stopped".to_string()));
+    }
+    if timed_out? && (cut_short || loaded.is_err()) {
+        return Err(FastGenError::TimedOut(format!(
+            "timed out after {:.1}s",
+            began.elapsed().as_secs_f64()
+        )));
+    }
+    let mut total = Loaded {
+        terminated: terminated?,
+        ..Loaded::default()
+    };
+    for loaded in loaded? {
+        total.latencies.extend(loaded.latencies);
+        total.recoveries.extend(loaded.recoveries);
+    }
+    Ok(total)
+}
+
+/// One connection's share of the load. Unless `sequential`, a companion
+/// thread encodes the next batch while this one sends the current, with a
+/// rendezvous channel so at most two batches are encoded at a time.
+fn worker(
+    strategy: &Strategy,
+    client: &mut Client,
+    context: &StrategyContext,
+    batches: &Mutex<Receiver<Batch<'_, '_>>>,
+    options: &LoadOptions,
+    backends: &Backends,
+    index: usize,
+) -> crate::Result<Loaded> {
+    let observer = options.observer;
+    if options.tracks_backends() {
+        backends.register(index, client)?;
+    }
+    let next_batch = || {
+        if options.expired() {
+            return None;
+        }
+        let batch = batches.lock().unwrap().recv().ok();
+        if let Some((_, current_tick, _)) = &batch {
+            observer.batch_started(strategy.name, *current_tick);
+        }
+        batch
+    };
+    let mut loaded = Loaded::default();
+    // A lost connection's payload is gone with it, so a resent batch is
+    // encoded again.
+    let mut send = |payload, current_tick, batch_data: &[Row]| -> crate::Result<()> {
+        sql_log::sample(strategy.name, || {
+            let comment = format!("-- the first of the batches {} loads", strategy.name);
+            match strategy.replay_sql(context, batch_data) {
+                Ok(sql) => format!("{}\n{}", comment, sql),
+                Err(e) => format!("{}, which could not be written: {}", comment, e.report()),
+            }
+        });
+        options.think();
+        let started = Instant::now();
+        let mut sent = (strategy.send)(client, context, payload);
+        let mut lost = None;
+        let mut attempt = 0;
+        while sent.as_ref().is_err_and(FastGenError::is_connection_lost)
+            && attempt < options.retries
+        {
+            lost.get_or_insert_with(Instant::now);
+            attempt += 1;
+            sent = options.reconnect(client, attempt).and_then(|()| {
+                if options.tracks_backends() {
+                    backends.register(index, client)?;
+                }
+                let payload = (strategy.encode)(context, batch_data)?;
+                (strategy.send)(client, context, payload)
+            });
+        }
+        sent?;
+        let latency = started.elapsed();
+        loaded.latencies.push(latency);
+        if let Some(sizer) = options.sizer {
+            sizer.observe(batch_data.len(), latency);
+        }
+        if let Some(lost) = lost {
+            loaded.recoveries.push(lost.elapsed());
+        }
+        if let Some(validation) = options.validate {
+            if (current_tick as u64).is_multiple_of(validation.every) {
+                read_back(client, context, batch_data, current_tick, validation.sample)?;
+            }
+        }
+        observer.batch_committed(strategy.name, current_tick, batch_data.len());
+        Ok(())
+    };
+
+    if options.sequential {
+        while let Some((batch_data, current_tick, mut reservation)) = next_batch() {
+            let payload = (strategy.encode)(context, &batch_data)?;
+            account(&mut reservation, &payload);
+            send(payload, current_tick, &batch_data)?;
+        }
+        return Ok(loaded);
+    }
+
+    thread::scope(|scope| {
+        let (sender, encoded) = mpsc::sync_channel(0);
+        let encoder = scope.spawn(move || -> crate::Result<()> {
+            while let Some((batch_data, current_tick, mut reservation)) = next_batch() {
+                let payload = (strategy.encode)(context, &batch_data)?;
+                account(&mut reservation, &payload);
+                if sender
+                    .send((payload, current_tick, batch_data, reservation))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        // Dropping `encoded` on failure stops the encoder at its next send.
+        let sent = encoded.into_iter().try_for_each(
+            |(payload, current_tick, batch_data, _reservation)| {
+                send(payload, current_tick, &batch_data)
+            },
+        );
+        let encoded = joined(encoder.join());
+        sent.and(encoded)
+    })?;
+    Ok(loaded)
+}
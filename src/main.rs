@@ -1,297 +1,1251 @@
-use byteorder::{BigEndian, WriteBytesExt};
-use chrono::{DateTime, Duration, NaiveDate, Utc};
-use postgres::{Client, NoTls};
-use postgres_types::ToSql;
-use rand::prelude::*;
-use rust_decimal::Decimal;
-use std::{
-    io::{Cursor, Write},
-    str::FromStr,
-};
-
-type Row = (DateTime<Utc>, i32, f64);
-
-use once_cell::sync::Lazy;
+#[cfg(feature = "bigquery")]
+mod bigquery;
+mod capacity;
+mod citus;
+mod cli;
+#[cfg(feature = "serve")]
+mod coordinate;
+#[cfg(feature = "cql")]
+mod cql;
+#[cfg(feature = "http")]
+mod elasticsearch;
+mod failover;
+mod fdw;
+mod fixtures;
+mod foreign_keys;
+#[cfg(feature = "http")]
+mod http;
+mod indexes;
+mod influx;
+mod interleave;
+mod k8s;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod manifest;
+#[cfg(feature = "mongodb")]
+mod mongodb;
+#[cfg(feature = "mssql")]
+mod mssql;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(all(unix, feature = "oracle"))]
+mod oracle;
+mod overhead;
+mod partitions;
+mod paths;
+mod profile;
+#[cfg(feature = "redis")]
+mod redis;
+mod replay;
+mod replication;
+mod report;
+mod repro;
+mod rls;
+mod roles;
+#[cfg(feature = "upload")]
+mod s3;
+#[cfg(feature = "serve")]
+mod serve;
+mod sink;
+mod slo;
+#[cfg(feature = "upload")]
+mod stage;
+mod storage;
+mod tables;
+mod tenants;
+mod time_zones;
+mod units;
+#[cfg(feature = "upload")]
+mod upload;
 
-static POSTGRES_EPOCH: Lazy<DateTime<Utc>> = Lazy::new(|| {
-    NaiveDate::from_ymd_opt(2000, 1, 1)
-        .unwrap()
-        .and_hms_opt(0, 0, 0)
-        .unwrap()
-        .and_utc()
-});
+use citus::{Citus, Distribution};
+use clap::{Parser, ValueEnum};
+use cli::{Cli, Command, LoadArgs, RunArgs};
+use fast_generation::environment::Environment;
+use fast_generation::export::{self, Compression, ExportFormat, ExportStats, Partitioning};
+use fast_generation::generator::{
+    generate_data, GeneratorConfig, Row, Timestamp, BASE_TEMP, BATCH_SIZE,
+};
+use fast_generation::pgcopy::{self, generate_buffer, PgCopyBinaryReader};
+use fast_generation::read::{self, ResultFormat};
+use fast_generation::runner::StrategyOrder;
+use fast_generation::schema::{self, SchemaConfig};
+use fast_generation::shape::{LoadShape, Shape};
+use fast_generation::sql_log;
+use fast_generation::strategy::Strategy;
+use fast_generation::tls;
+use fast_generation::{bench, dump, input, server, BenchmarkRunner, Observer, StrategyResult};
+use manifest::{Manifest, ManifestFile};
+use postgres::{Client, Config};
+use report::Report;
+use roles::LoadRole;
+use slo::SloViolation;
+use std::collections::HashSet;
+use std::path::Path;
 
-const BATCH_SIZE: usize = 10_000;
 const BATCH_COUNT: usize = 1_000;
-const MAX_SENSORS: i32 = 32;
+const TABLE_NAME: &str = "metrics";
+/// Heap bytes per row beyond the values: tuple header and line pointer.
+const TUPLE_OVERHEAD: f64 = 28.0;
+/// COPY bytes per row beyond the values: field count and lengths.
+const COPY_FRAMING: f64 = 14.0;
+const CONN_INFO: &str = "host=localhost dbname=postgres user=postgres password=postgres";
+
+/// Batches between progress lines.
 const REPORT_COUNT: i64 = 100;
 
-struct ExecutionContext {
-    t0: DateTime<Utc>,
-    s0: i64,
-    client: Client,
-    name: String,
-}
+/// Prints a line every `REPORT_COUNT` batches while a strategy runs.
+struct ProgressPrinter;
 
-impl ExecutionContext {
-    fn new(name: &str, conn_info: &str) -> Self {
-        let mut client = Client::connect(conn_info, NoTls).unwrap();
-        let s0 = Self::table_size(&mut client);
-        let t0 = Utc::now();
-        let name = name.to_string();
-
-        ExecutionContext {
-            t0,
-            s0,
-            client,
-            name,
+impl Observer for ProgressPrinter {
+    fn batch_committed(&self, _strategy: &str, batch: i64, _rows: usize) {
+        if batch % REPORT_COUNT == 0 {
+            println!("Copied {batch}");
         }
     }
+}
 
-    fn table_size(client: &mut Client) -> i64 {
-        let row = client
-            .query_one(
-                "SELECT pg_total_relation_size('public.metrics') as size",
-                &[],
-            )
-            .unwrap();
-        row.get("size")
+/// Prints a result as it comes in. `reference` keeps the speed of the
+/// latest `--reference` result for those that follow it.
+fn print_result(result: &StrategyResult, args: &LoadArgs, reference: &mut Option<f64>) {
+    let mut label = match (result.strategy.as_str(), args.copy_chunk_size) {
+        ("copy", Some(size)) => format!("fn copy ({}-byte writes)", size),
+        (name, _) => format!("fn {}", name),
+    };
+    if args.iterations > 1 {
+        label += &format!(" #{}", result.iteration + 1);
     }
+    let seconds = result.elapsed.as_secs_f64();
+    let size = result.after.total - result.before.total;
 
-    fn convert_bytes(bytes: f64, to: &str) -> f64 {
-        let units = ["B", "KB", "MB", "GB", "TB", "PB"];
-        let index = units
-            .iter()
-            .position(|&r| r == to.to_uppercase())
-            .unwrap_or(0);
-        bytes / (1024f64.powi(index as i32))
+    println!();
+    println!("{}:", label);
+    println!("Speed: {}", units::rate(size as f64 / seconds));
+    println!(" Data: {}", units::bytes(size as f64));
+    println!(" Time: {:.2}s", seconds);
+    for (part, before, after) in [
+        ("Heap", result.before.heap, result.after.heap),
+        ("Indexes", result.before.indexes, result.after.indexes),
+        ("TOAST", result.before.toast, result.after.toast),
+    ] {
+        println!(
+            " {}: {} -> {}",
+            part,
+            units::bytes(before as f64),
+            units::bytes(after as f64)
+        );
+    }
+    println!(" WAL: {}", units::bytes(result.wal_bytes as f64));
+    if let Some(latency) = &result.latency {
+        println!(
+            " Batch latency: {:.1}ms median, {:.1}ms p99, {:.1}ms max",
+            latency.p50.as_secs_f64() * 1000.0,
+            latency.p99.as_secs_f64() * 1000.0,
+            latency.max.as_secs_f64() * 1000.0
+        );
+    }
+    println!(
+        " Client CPU: {:.2}s, {:.0}% average, {:.0}% peak",
+        result.usage.cpu.as_secs_f64(),
+        result.usage.average_cpu * 100.0,
+        result.usage.peak_cpu * 100.0
+    );
+    println!(
+        " Client RSS: {} average, {} peak",
+        units::bytes(result.usage.average_rss as f64),
+        units::bytes(result.usage.peak_rss as f64)
+    );
+    if let Some(recovery) = &result.recovery {
+        println!(
+            " Recovery: {} backend(s) terminated, {} reconnect(s), {:.1}ms mean, {:.1}ms max",
+            recovery.terminated,
+            recovery.reconnects,
+            recovery.mean.as_secs_f64() * 1000.0,
+            recovery.max.as_secs_f64() * 1000.0
+        );
+    }
+    if let (Some(e), true) = (&result.error, result.timed_out) {
+        println!(" Stopped: {}", e);
+    } else if let Some(e) = &result.error {
+        println!(" Failed: {}", e);
+    } else if let Some(name) = &args.reference {
+        if result.strategy == *name {
+            *reference = Some(result.rows_per_second());
+        } else if let Some(speed) = reference {
+            println!(
+                " Relative: {:.1}x {}",
+                result.rows_per_second() / *speed,
+                name
+            );
+        }
+    }
+    if let (Some(discrepancy), Some(counted)) = (result.row_discrepancy(), result.counted) {
+        println!(
+            " ROW COUNT MISMATCH: the table grew by {} rows, {} were sent ({:+})",
+            counted, result.rows, discrepancy
+        );
+    }
+    if let Some(audit) = result.duplicates {
+        if audit.duplicated_keys > 0 {
+            println!(
+                " DUPLICATES: {} key(s) gained {} row(s) more than were sent",
+                audit.duplicated_keys, audit.extra_rows
+            );
+        } else {
+            println!(" Duplicates: none");
+        }
+        if audit.missing_rows > 0 {
+            println!(
+                " Missing: {} row(s) sent were not found",
+                audit.missing_rows
+            );
+        }
+    }
+    if let Some(check) = &result.scale {
+        match &check.example {
+            Some(example) => println!(
+                " SCALE MISMATCH: {} of {} sampled temperatures not at {}, e.g. {}",
+                check.mismatched, check.sampled, check.expected, example
+            ),
+            None => println!(
+                " Scale: {} sampled temperatures at {}",
+                check.sampled, check.expected
+            ),
+        }
+    }
+    if let (Some(size), Some(ms)) = (result.converged_batch_size, args.target_batch_ms) {
+        println!(
+            " Batch size: converged on {} rows for {}ms batches",
+            size, ms
+        );
+    }
+    if let Some(ran) = result.autovacuum {
+        println!(
+            " Autovacuum: {} vacuum(s), {} analyze(s) despite the pause",
+            ran.vacuums, ran.analyzes
+        );
+    }
+    if result.pool.buffers > 0 {
+        println!(
+            " Pool: {} buffers, {}, peak {} in use",
+            result.pool.buffers,
+            units::bytes(result.pool.bytes as f64),
+            result.pool.peak_in_use
+        );
     }
 }
 
-impl Drop for ExecutionContext {
-    fn drop(&mut self) {
-        let s1 = Self::table_size(&mut self.client);
-        let t1 = Utc::now();
+/// One aligned line per result, with its speed relative to the
+/// `--reference` strategy in the same iteration, or else to the slowest
+/// successful result.
+fn print_summary(results: &[StrategyResult], args: &LoadArgs) {
+    let label = |result: &StrategyResult| {
+        if args.iterations > 1 {
+            format!("{} #{}", result.strategy, result.iteration + 1)
+        } else {
+            result.strategy.clone()
+        }
+    };
+    let slowest = results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .map(StrategyResult::rows_per_second)
+        .fold(f64::INFINITY, f64::min);
+    let relative_to = |result: &StrategyResult| match &args.reference {
+        Some(name) => results
+            .iter()
+            .find(|reference| {
+                reference.strategy == *name
+                    && reference.iteration == result.iteration
+                    && reference.error.is_none()
+            })
+            .map(StrategyResult::rows_per_second),
+        None => Some(slowest),
+    };
+    let speedup = args
+        .reference
+        .as_ref()
+        .map_or("speedup".to_string(), |name| format!("vs {}", name));
+    let width = results
+        .iter()
+        .map(|result| label(result).len())
+        .max()
+        .unwrap_or(0)
+        .max("strategy".len());
+
+    println!();
+    println!(
+        "{:<width$} {:>12} {:>14} {:>12} {:>10} {:>12}",
+        "strategy", "rows/s", "bytes/s", "WAL", "p99 ms", speedup
+    );
+    for result in results {
+        if let Some(e) = &result.error {
+            match result.timed_out {
+                true => println!("{:<width$} {}", label(result), e),
+                false => println!("{:<width$} failed: {}", label(result), e),
+            }
+            continue;
+        }
+        let seconds = result.elapsed.as_secs_f64();
+        let size = (result.after.total - result.before.total) as f64;
+        let p99 = result
+            .latency
+            .map_or(0.0, |latency| latency.p99.as_secs_f64() * 1000.0);
+        let relative = relative_to(result).map_or("-".to_string(), |speed| {
+            format!("{:.2}x", result.rows_per_second() / speed)
+        });
+        println!(
+            "{:<width$} {:>12.0} {:>14} {:>12} {:>10.1} {:>12}",
+            label(result),
+            result.rows_per_second(),
+            units::rate(size / seconds),
+            units::bytes(result.wal_bytes as f64),
+            p99,
+            relative
+        );
+    }
+
+    let mismatched: Vec<_> = results
+        .iter()
+        .filter(|result| result.row_discrepancy().is_some())
+        .collect();
+    if !mismatched.is_empty() {
+        println!();
+        println!("ROW COUNT MISMATCH: the table did not grow by the rows sent");
+        for result in mismatched {
+            println!(
+                "  {:<width$} {} rows counted, {} sent ({:+})",
+                label(result),
+                result.counted.unwrap_or_default(),
+                result.rows,
+                result.row_discrepancy().unwrap_or_default()
+            );
+        }
+    }
 
-        let duration = (t1 - self.t0).num_seconds();
-        let size = s1 - self.s0;
-        let speed = (size as f64) / (duration as f64);
+    let duplicated: Vec<_> = results
+        .iter()
+        .filter_map(|result| Some((result, result.duplicates?)))
+        .filter(|(_, audit)| audit.duplicated_keys > 0)
+        .collect();
+    if !duplicated.is_empty() {
+        println!();
+        println!("DUPLICATES: keys the table holds more rows of than were sent");
+        for (result, audit) in duplicated {
+            println!(
+                "  {:<width$} {} key(s), {} extra row(s)",
+                label(result),
+                audit.duplicated_keys,
+                audit.extra_rows
+            );
+        }
+    }
 
+    let mismatched: Vec<_> = results
+        .iter()
+        .filter_map(|result| Some((result, result.scale.as_ref()?)))
+        .filter(|(_, check)| check.mismatched > 0)
+        .collect();
+    if !mismatched.is_empty() {
         println!();
-        println!("{}:", self.name);
-        println!("Speed: {:.2}MB/s", Self::convert_bytes(speed, "MB"));
-        println!(" Data: {:.2}MB", Self::convert_bytes(size as f64, "MB"));
-        println!(" Time: {:.2}s", duration);
+        println!("SCALE MISMATCH: temperatures not stored at the scale they should have");
+        for (result, check) in mismatched {
+            println!(
+                "  {:<width$} {} of {} sampled not at {}, e.g. {}",
+                label(result),
+                check.mismatched,
+                check.sampled,
+                check.expected,
+                check.example.as_deref().unwrap_or("-")
+            );
+        }
     }
 }
 
-fn generate_batch(created: DateTime<Utc>, sensor_id: i32, base_temp: f64) -> (Vec<Row>, i32) {
-    let mut rng = rand::thread_rng();
-    let mut current_sensor_id = sensor_id;
-    let batch: Vec<_> = (0..BATCH_SIZE)
-        .map(|i| {
-            current_sensor_id = (current_sensor_id + (i as i32)) % MAX_SENSORS + 1;
-            let temperature = ((base_temp + rng.gen_range(-5.0..5.0)) * 100.0).round() / 100.0;
-            (created, current_sensor_id, temperature)
-        })
+fn print_environment(environment: &Environment) {
+    let settings: Vec<_> = environment
+        .settings
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
         .collect();
-    (batch, sensor_id)
+    let mut client = format!("fast_generation {}", environment.tool_version);
+    if let Some(revision) = &environment.git_revision {
+        client += &format!(" ({})", revision);
+    }
+    for (name, version) in &environment.client_crates {
+        client += &format!(", {} {}", name, version);
+    }
+
+    println!("Server: {}", environment.server_version);
+    if let Some(address) = &environment.server_address {
+        println!(" Address: {}", address);
+    }
+    println!(" Settings: {}", settings.join(", "));
+    println!("Client: {}", client);
+    if let Some(rustc) = &environment.rustc_version {
+        println!(" Built with: {}", rustc);
+    }
+    println!(
+        " CPU: {} x {}",
+        environment.cpus,
+        environment.cpu_model.as_deref().unwrap_or("unknown model")
+    );
 }
 
-fn generate_data(
-    start_time: DateTime<Utc>,
-    base_temp: f64,
-    batch_count: usize,
-) -> impl Iterator<Item = (Vec<Row>, i64)> {
-    let mut current_time = start_time;
-    let mut sensor_id = 1;
-    let mut current_tick = 0;
-
-    (0..batch_count).flat_map(move |_| {
-        current_tick += 1;
-        current_time += Duration::milliseconds(100);
-        let (new_batch, new_sensor_id) = generate_batch(current_time, sensor_id, base_temp);
-        sensor_id = new_sensor_id;
-
-        std::iter::once((new_batch, current_tick))
-    })
+fn preview(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    rows: usize,
+    hex: bool,
+    round_trip: bool,
+) -> anyhow::Result<()> {
+    // Enough batches for `rows` however small each is; generation stops at
+    // the batch holding the last row wanted.
+    let batches = rows.div_ceil((*generator.batch_sizes().start()).max(1));
+    generator.validate(batches)?;
+    let sample: Vec<Row> = generate_data(generator, BASE_TEMP, batches)
+        .flat_map(|(batch_data, _)| batch_data)
+        .take(rows)
+        .collect();
+
+    if hex {
+        let mut header = Vec::new();
+        pgcopy::write_header(&mut header)?;
+        println!("header: {}", to_hex(&header));
+    }
+    println!("{:<35} {:>9} {:>11}", "created", "sensor_id", "temperature");
+    for row in &sample {
+        let created = match row.0 {
+            Timestamp::Value(datetime) => datetime.to_rfc3339(),
+            Timestamp::PosInfinity => "infinity".to_string(),
+            Timestamp::NegInfinity => "-infinity".to_string(),
+        };
+        println!("{:<35} {:>9} {:>11}", created, row.1, row.2);
+        if hex {
+            let mut encoded = Vec::new();
+            pgcopy::write_row(&mut encoded, schema, row)?;
+            println!("  {}", to_hex(&encoded));
+        }
+    }
+
+    if round_trip {
+        let buffer = generate_buffer(schema, &sample)?;
+        let mut decoded = 0;
+        for (expected, actual) in sample
+            .iter()
+            .zip(PgCopyBinaryReader::new(&buffer[..], schema)?)
+        {
+            let actual = actual?;
+            let same_temperature =
+                expected.2 == actual.2 || (expected.2.is_nan() && actual.2.is_nan());
+            if expected.0 != actual.0 || expected.1 != actual.1 || !same_temperature {
+                anyhow::bail!(
+                    "round trip mismatch: wrote {:?}, read {:?}",
+                    expected,
+                    actual
+                );
+            }
+            decoded += 1;
+        }
+        if decoded != sample.len() {
+            anyhow::bail!("round trip decoded {} of {} rows", decoded, sample.len());
+        }
+        println!("Round trip: {} rows decoded identically", decoded);
+    }
+
+    Ok(())
 }
 
-fn f64_to_decimal(value: f64) -> Decimal {
-    Decimal::from_str(&value.to_string()).unwrap_or_else(|_| Decimal::new(0, 0))
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-fn numeric_to_postgres_binary(value: f64) -> Vec<u8> {
-    let mut buffer = Vec::new();
-    let abs_value = value.abs();
-    let sign = if value.is_sign_negative() {
-        0x4000
-    } else {
-        0x0000
+/// Approximate table growth from loading every batch `loads` times, once
+/// per strategy in every iteration, derived from the COPY encoding of the
+/// first batch: the batches read with `--input` if given, generated ones
+/// otherwise. Indexes are not accounted for.
+fn estimate_growth(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batches: Option<&[Vec<Row>]>,
+    loads: usize,
+) -> anyhow::Result<f64> {
+    let (sample, rows) = match batches {
+        Some(batches) => (
+            batches.first().cloned().unwrap_or_default(),
+            batches.iter().map(Vec::len).sum(),
+        ),
+        None => (
+            generate_data(generator, BASE_TEMP, 1)
+                .next()
+                .map(|(sample, _)| sample)
+                .unwrap_or_default(),
+            generator.expected_rows(BATCH_COUNT),
+        ),
     };
-    let parts: Vec<String> = abs_value.to_string().split('.').map(String::from).collect();
+    if sample.is_empty() {
+        return Ok(0.0);
+    }
+    let encoded = generate_buffer(schema, &sample)?.len() as f64;
+    let row_bytes = encoded / sample.len() as f64 - COPY_FRAMING + TUPLE_OVERHEAD;
+    Ok(row_bytes * (rows * loads) as f64)
+}
 
-    let integer = &parts[0];
-    let fraction = if parts.len() > 1 {
-        parts[1].clone()
-    } else {
-        String::new()
+/// Fails if the estimated growth would take the database past `limit`.
+fn check_disk(
+    client: &mut Client,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batches: Option<&[Vec<Row>]>,
+    loads: usize,
+    limit: usize,
+) -> anyhow::Result<()> {
+    let growth = estimate_growth(generator, schema, batches, loads)?;
+    let size = server::database_size(client)? as f64;
+    println!(
+        "Disk: {} now + ~{} estimated, limit {}",
+        units::bytes(size),
+        units::bytes(growth),
+        units::bytes(limit as f64)
+    );
+    if size + growth > limit as f64 {
+        anyhow::bail!("the run would exceed --disk-limit");
+    }
+    Ok(())
+}
+
+fn dry_run(
+    client: &mut Client,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    strategies: &[Strategy],
+    args: &LoadArgs,
+) -> anyhow::Result<()> {
+    let mut problems = schema::check_table(client, schema, TABLE_NAME)?;
+    if let Err(e) = pgcopy::verify_table(client, schema, TABLE_NAME) {
+        problems.push(e.to_string());
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{}: {}", TABLE_NAME, problem);
+        }
+        anyhow::bail!("table {} is not compatible with the generator", TABLE_NAME);
+    }
+    println!("{}: schema OK", TABLE_NAME);
+
+    println!(
+        "Plan (seed {}, created as {}):",
+        generator.seed,
+        schema.time_column.data_type()
+    );
+    let sizes = generator.batch_sizes();
+    let batches = match (args.target_batch_ms, sizes.start() == sizes.end()) {
+        (Some(ms), _) => format!(
+            "{} rows in batches sized to take {}ms",
+            BATCH_COUNT * BATCH_SIZE,
+            ms
+        ),
+        (None, true) => format!("{} batches x {} rows", BATCH_COUNT, sizes.start()),
+        (None, false) => format!(
+            "{} batches x {}-{} rows",
+            BATCH_COUNT,
+            sizes.start(),
+            sizes.end()
+        ),
     };
+    for strategy in strategies {
+        println!(
+            "  fn {}: {} into {} on {} worker(s)",
+            strategy.name,
+            batches,
+            TABLE_NAME,
+            args.workers.max(1)
+        );
+    }
+    match args.order {
+        StrategyOrder::Listed => {}
+        StrategyOrder::Rotate => {
+            println!("  each iteration starting one strategy further down the list")
+        }
+        StrategyOrder::Shuffle => println!("  strategies shuffled anew every iteration"),
+    }
+    match args.run_config(TABLE_NAME, BATCH_COUNT).shape {
+        Some(LoadShape {
+            shape: Shape::Steady,
+            rate,
+            ..
+        }) => println!("  batches paced at {} rows/s", rate),
+        Some(shape) => println!(
+            "  batches paced as a {} between {} and {} rows/s over {}s periods",
+            shape.shape.to_possible_value().unwrap().get_name(),
+            shape.min_rate,
+            shape.rate,
+            shape.period_secs
+        ),
+        None => {}
+    }
+    match (args.think_time_ms, args.think_jitter_ms) {
+        (0, 0) => {}
+        (pause, 0) => println!("  {}ms pause before each batch", pause),
+        (pause, jitter) => println!("  {}-{}ms pause before each batch", pause, pause + jitter),
+    }
+    if let Some(interval) = args.chaos_interval_ms {
+        println!("  a loading backend terminated every {}ms", interval);
+    }
+    if args.retries > 0 {
+        println!(
+            "  batches resent up to {} time(s) over a new connection",
+            args.retries
+        );
+    }
+    if let Some(size) = args.copy_chunk_size {
+        println!("  COPY streams are written in {}-byte chunks", size);
+    }
+    if let Some(ms) = args.target_batch_ms {
+        println!("  batches sized to take about {}ms each", ms);
+    }
+    if let Some(secs) = args.strategy_timeout {
+        println!("  strategies stopped after {}s", secs);
+    }
+    if args.pregenerate {
+        let rows = BATCH_COUNT * (sizes.start() + sizes.end()) / 2;
+        println!(
+            "  every batch generated before timing, about {} held in memory",
+            units::bytes((rows * std::mem::size_of::<Row>()) as f64)
+        );
+    }
+    if let Some(size) = args.max_client_memory {
+        println!(
+            "  batches in flight held to {} of client memory",
+            units::bytes(size as f64)
+        );
+    }
+    for (hook, statements) in [
+        ("setup", &args.setup_sql),
+        ("session", &args.session_sql),
+        ("teardown", &args.teardown_sql),
+    ] {
+        for statement in statements {
+            println!("  {} SQL: {}", hook, statement);
+        }
+    }
+    if args.pause_autovacuum {
+        println!("  autovacuum paused on {} for the run", TABLE_NAME);
+    }
+    if args.settle && !server::can_checkpoint(client)? {
+        println!("  no CHECKPOINT between strategies: it needs a superuser or pg_checkpoint");
+    } else if args.settle {
+        let evict = if server::can_evict_buffers(client)? {
+            " and shared buffers evicted"
+        } else {
+            " (pg_buffercache_evict unavailable)"
+        };
+        println!("  CHECKPOINT before each strategy{}", evict);
+    }
+    if !server::can_read_wal(client)? {
+        println!("  WAL written is not measured: pg_current_wal_lsn is unavailable");
+    }
 
-    let weight = ((integer.len() as i16 - 1) / 4) as i16;
-    let padding = if integer.len() % 4 != 0 {
-        4 - (integer.len() % 4)
+    Ok(())
+}
+
+fn run(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    args: RunArgs,
+) -> anyhow::Result<()> {
+    let mut generator = match args.node_index {
+        Some(node) => generator.for_node(node),
+        None => *generator,
+    };
+    // The default start moves with the clock; the script has to repeat it.
+    if args.repro_dir.is_some() {
+        generator.start = Some(generator.start_time());
+    }
+    let generator = &generator;
+    // A dry run only reads, as the connecting user.
+    let load_role = if args.dry_run {
+        None
     } else {
-        0
+        LoadRole::new(config, &args.load_role, TABLE_NAME)?
     };
-    let padded_number = format!(
-        "{:0>width$}{}",
-        integer,
-        fraction,
-        width = integer.len() + padding
-    );
-
-    let digits: Vec<i16> = padded_number
-        .as_bytes()
-        .chunks(4)
-        .map(|c| {
-            let mut value = 0;
-            for (i, &digit) in c.iter().enumerate() {
-                value += (digit - b'0') as i16 * 10i16.pow(3 - i as u32);
+    let load_config = match &load_role {
+        Some(load_role) => load_role.config(config, &args.load_role),
+        None => config.clone(),
+    };
+    let batches = args
+        .input
+        .as_deref()
+        .map(|path| {
+            let recorded = manifest::recorded_checksums(path)?;
+            let batches = input::read_batches(
+                path,
+                schema,
+                args.input_header,
+                *generator.batch_sizes().end(),
+                recorded.as_ref().map(|(_, checksums)| checksums),
+            )?;
+            println!(
+                "Read {} rows in {} batches from {}",
+                batches.iter().map(Vec::len).sum::<usize>(),
+                batches.len(),
+                path.display()
+            );
+            match &recorded {
+                Some((manifest_path, checksums)) => println!(
+                    " Checksums: {} batches match {}",
+                    checksums.crc32.len(),
+                    manifest_path.display()
+                ),
+                None => println!(" Checksums: none recorded in a manifest.json above it"),
             }
-            value as i16
+            anyhow::Ok(batches)
         })
-        .collect();
-    let ndigits = digits.len() as i16;
-    let dscale = fraction.len() as i16;
+        .transpose()?;
+    let batch_count = batches.as_ref().map_or(BATCH_COUNT, Vec::len);
+    let mut reference = None;
+    let citus = match args.distributed {
+        Some(Distribution::Citus) => Some(Citus::distribute(
+            &mut tls::connect(config)?,
+            TABLE_NAME,
+            args.shard_count,
+        )?),
+        None => None,
+    };
+    let split = citus
+        .as_ref()
+        .map(|citus| move |batch: &[Row]| citus.split(batch));
+    let mut runner = BenchmarkRunner::new(load_config)
+        .with_generator(*generator)
+        .with_schema(*schema)
+        .with_config(args.load.run_config(TABLE_NAME, batch_count))
+        .observer(&ProgressPrinter)
+        .on_result(|result| print_result(result, &args.load, &mut reference));
+    if load_role.is_some() {
+        // A role granted INSERT alone can't count what it loaded.
+        runner = runner.count_as(config.clone());
+    }
+    if let Some(batches) = &batches {
+        runner = runner.with_batches(batches);
+    }
+    if let Some(split) = &split {
+        runner = runner.split_batches(split);
+    }
+    runner.validate()?;
+    #[cfg(feature = "upload")]
+    let uploader = args
+        .upload
+        .as_deref()
+        .map(upload::Uploader::new)
+        .transpose()?;
+    let baseline = args.baseline.as_deref().map(Report::load).transpose()?;
+    let environment = runner.environment()?;
+    print_environment(&environment);
+    let changed_settings = baseline
+        .as_ref()
+        .map(|baseline| environment.changed_settings(&baseline.environment))
+        .unwrap_or_default();
+    if baseline.is_some() {
+        report::print_changed_settings(&changed_settings);
+    }
 
-    buffer.write_i16::<BigEndian>(ndigits).unwrap();
-    buffer.write_i16::<BigEndian>(weight).unwrap();
-    buffer.write_i16::<BigEndian>(sign).unwrap();
-    buffer.write_i16::<BigEndian>(dscale).unwrap();
+    let mut client = tls::connect(config)?;
+    let run = args.load.run_config(TABLE_NAME, batch_count);
+    let strategies = run.resolve_strategies()?;
 
-    for digit in digits {
-        buffer.write_i16::<BigEndian>(digit).unwrap();
+    if let Some(limit) = args.disk_limit {
+        check_disk(
+            &mut client,
+            generator,
+            schema,
+            batches.as_deref(),
+            strategies.len() * run.iterations.max(1),
+            limit,
+        )?;
     }
 
-    buffer
-}
+    if args.dry_run {
+        return dry_run(&mut client, generator, schema, &strategies, &args.load);
+    }
 
-fn datetime_to_postgres_binary(datetime: DateTime<Utc>) -> i64 {
-    let time_delta = datetime - POSTGRES_EPOCH.to_utc();
-    time_delta.num_microseconds().unwrap()
+    if let Some(path) = &args.dump_sql {
+        sql_log::open(path)?;
+    }
+    if args.load.settle && !runner.can_checkpoint()? {
+        println!("CHECKPOINT needs a superuser or pg_checkpoint, not settling between strategies");
+    } else if args.load.settle && !runner.can_evict_buffers()? {
+        println!("pg_buffercache_evict is unavailable, only checkpointing between strategies");
+    }
+    if !runner.can_read_wal()? {
+        println!("pg_current_wal_lsn is unavailable, WAL written is reported as 0");
+    }
+    let results = runner.run()?;
+    let report = Report {
+        environment,
+        generator: *generator,
+        schema: *schema,
+        run: args.load.run_config(TABLE_NAME, batch_count),
+        slos: slo::evaluate(&args.slo, &results),
+        results,
+        changed_settings,
+    };
+    print_summary(&report.results, &args.load);
+    if citus.is_some() {
+        citus::print_balance(&mut client, TABLE_NAME)?;
+    }
+    if let Some(baseline) = &baseline {
+        report::print_comparison(baseline, &report);
+    }
+    if !report.slos.is_empty() {
+        slo::print_outcomes(&report.slos);
+    }
+    if let Some(path) = &args.report {
+        report.save(path)?;
+    }
+    if let Some(dir) = &args.repro_dir {
+        repro::write(
+            dir,
+            &mut client,
+            &report.environment,
+            generator,
+            schema,
+            &args.load,
+            TABLE_NAME,
+            CONN_INFO,
+        )?;
+        println!(
+            "Wrote {} and {} to {}",
+            repro::SCRIPT_FILE,
+            repro::SCENARIO_FILE,
+            dir.display()
+        );
+    }
+    #[cfg(feature = "upload")]
+    if let Some(uploader) = &uploader {
+        uploader.put("results.json", report.to_json())?;
+    }
+    let failed: Vec<_> = report
+        .results
+        .iter()
+        .filter(|result| result.error.is_some() && !result.timed_out)
+        .map(|result| result.strategy.as_str())
+        .collect();
+    let violated = report.slos.iter().filter(|outcome| !outcome.passed).count();
+    if violated > 0 {
+        return Err(SloViolation(violated).into());
+    }
+    if !failed.is_empty() && args.slo.slo_max_errors.is_none() {
+        anyhow::bail!("strategies failed: {}", failed.join(", "));
+    }
+    Ok(())
 }
 
-fn generate_buffer(batch_data: &[Row]) -> anyhow::Result<Vec<u8>> {
-    let mut buffer = Cursor::new(Vec::new());
-    buffer.write_all(b"PGCOPY\n\xff\r\n\0")?;
-    buffer.write_i32::<BigEndian>(0)?;
-    buffer.write_i32::<BigEndian>(0)?;
-
-    for row in batch_data {
-        buffer.write_i16::<BigEndian>(3)?;
+/// Returns the files written, relative to `output_dir`, manifest included.
+#[allow(clippy::too_many_arguments)]
+fn export(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    formats: &[ExportFormat],
+    output_dir: Option<&Path>,
+    partitioning: Option<(Partitioning, Option<u64>)>,
+    compression: Option<Compression>,
+    decode: bool,
+) -> anyhow::Result<Vec<String>> {
+    let mut client = tls::connect(config)?;
+    let rows: i64 = client
+        .query_one(&format!("SELECT count(*) FROM {}", TABLE_NAME), &[])?
+        .get(0);
 
-        // created
-        let micros = datetime_to_postgres_binary(row.0);
-        buffer.write_i32::<BigEndian>(8)?;
-        buffer.write_i64::<BigEndian>(micros)?;
-
-        // sensor_id
-        buffer.write_i32::<BigEndian>(4)?;
-        buffer.write_i32::<BigEndian>(row.1)?;
-
-        // temperature
-        let numeric_bytes = numeric_to_postgres_binary(row.2);
-        buffer.write_i32::<BigEndian>(numeric_bytes.len() as i32)?;
-        buffer.write_all(&numeric_bytes)?;
+    let mut files = Vec::new();
+    for &format in formats {
+        let stats = match (output_dir, partitioning) {
+            (Some(dir), Some((partitioning, max_file_size))) => export::export_partitioned(
+                &mut client,
+                schema,
+                TABLE_NAME,
+                format,
+                dir,
+                compression,
+                partitioning,
+                max_file_size,
+            )?,
+            _ => export::export(
+                &mut client,
+                schema,
+                TABLE_NAME,
+                format,
+                output_dir,
+                compression,
+                decode,
+            )?,
+        };
+        print_transfer(&format!("export {}", format.name()), &stats, rows);
+        let Some(dir) = output_dir else {
+            continue;
+        };
+        let bytes: u64 = stats.files.iter().map(|file| file.bytes).sum();
+        if partitioning.is_some() {
+            let partitions: HashSet<_> = stats
+                .files
+                .iter()
+                .filter_map(|file| file.path.parent())
+                .collect();
+            println!(
+                "Files: {} in {} partitions",
+                stats.files.len(),
+                partitions.len()
+            );
+        }
+        if let Some(compression) = compression {
+            println!(
+                " File: {} {}, {:.1}x smaller",
+                units::bytes(bytes as f64),
+                compression.codec.name(),
+                stats.bytes as f64 / bytes.max(1) as f64
+            );
+        }
+        for file in stats.files {
+            let name = file.path.strip_prefix(dir)?;
+            files.push(ManifestFile {
+                // With forward slashes wherever it is read.
+                name: name
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                format: format.name().to_string(),
+                compression: compression.map(|compression| compression.codec.name().to_string()),
+                bytes: file.bytes,
+                sha256: file.sha256,
+                // Read back once written, outside the export's timing.
+                checksums: Some(input::checksums(&file.path, schema, input::CHECKSUM_ROWS)?),
+            });
+        }
     }
 
-    buffer.write_i16::<BigEndian>(-1)?;
-    Ok(buffer.into_inner())
+    let Some(dir) = output_dir else {
+        return Ok(Vec::new());
+    };
+    let mut written: Vec<_> = files.iter().map(|file| file.name.clone()).collect();
+    Manifest {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        generator: *generator,
+        schema: *schema,
+        table_name: TABLE_NAME.to_string(),
+        rows,
+        files,
+    }
+    .save(dir)?;
+    written.push(manifest::FILE_NAME.to_string());
+    println!();
+    println!("Wrote {}", dir.join(manifest::FILE_NAME).display());
+    Ok(written)
 }
 
-fn insert_to_postgres(
-    client: &mut Client,
-    table_name: &str,
-    batch_data: &[Row],
-    current_tick: i64,
-) {
-    let mut tx = client.transaction().unwrap();
-    let stmt = tx
-        .prepare(&format!("INSERT INTO {} VALUES ($1, $2, $3)", table_name))
-        .unwrap();
+fn print_transfer(name: &str, stats: &ExportStats, rows: i64) {
+    let seconds = stats.elapsed.as_secs_f64();
 
-    for row in batch_data {
-        let params: [&(dyn ToSql + Sync); 3] = [&row.0, &row.1, &f64_to_decimal(row.2)];
-        tx.execute(&stmt, &params).unwrap();
-    }
+    println!();
+    println!("{}:", name);
+    println!("Speed: {}", units::rate(stats.bytes as f64 / seconds));
+    println!(" Rows: {:.0}/s", rows as f64 / seconds);
+    println!(" Data: {}", units::bytes(stats.bytes as f64));
+    println!(" Time: {:.2}s", seconds);
+}
 
-    tx.commit().unwrap();
+fn dump_restore(
+    config: &Config,
+    schema: &SchemaConfig,
+    format: ExportFormat,
+    dump_dir: &Path,
+    target_table: &str,
+    keep: bool,
+) -> anyhow::Result<()> {
+    let mut client = tls::connect(config)?;
+    let stats = dump::dump_restore(
+        &mut client,
+        schema,
+        TABLE_NAME,
+        target_table,
+        format,
+        dump_dir,
+        keep,
+    )?;
 
-    if current_tick % REPORT_COUNT == 0 {
-        println!("Copied {current_tick}");
-    }
+    print_transfer(&format!("dump {}", format.name()), &stats.dump, stats.rows);
+    print_transfer(
+        &format!("restore {}", format.name()),
+        &stats.restore,
+        stats.rows,
+    );
+    Ok(())
 }
 
-fn copy_to_postgres(client: &mut Client, table_name: &str, batch_data: &[Row], current_tick: i64) {
-    let buffer = generate_buffer(batch_data).unwrap();
-    let mut writer = client
-        .copy_in(&format!("COPY {} FROM STDIN WITH BINARY", table_name))
-        .unwrap();
-    writer.write_all(&buffer).unwrap();
-    writer.finish().unwrap();
+fn read(
+    config: &Config,
+    schema: &SchemaConfig,
+    formats: &[ResultFormat],
+    fetch_size: usize,
+) -> anyhow::Result<()> {
+    let mut client = tls::connect(config)?;
 
-    if current_tick % REPORT_COUNT == 0 {
-        println!("Copied {current_tick}");
+    for &format in formats {
+        let stats = read::read(&mut client, schema, TABLE_NAME, format, fetch_size)?;
+        let seconds = stats.elapsed.as_secs_f64();
+
+        println!();
+        println!("read {}:", format.name());
+        println!(" Rows: {:.0}/s", stats.rows as f64 / seconds);
+        println!(" Read: {}", stats.rows);
+        println!(" Time: {:.2}s", seconds);
     }
+
+    Ok(())
 }
 
-fn insert_to_postgres_string(
-    client: &mut Client,
-    table_name: &str,
-    batch_data: &[Row],
-    current_tick: i64,
-) {
-    let tuples = batch_data
-        .into_iter()
-        .map(|row| {
-            format!(
-                "('{}'::timestamp with time zone, {}, {}::numeric(10, 2))",
-                row.0, row.1, row.2
-            )
-        })
-        .collect::<Vec<_>>()
-        .join(",");
-    let query = format!("INSERT INTO {} VALUES {}", table_name, tuples);
-    client.execute(&query, &[]).unwrap();
+fn bench_gen(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    rows: usize,
+) -> anyhow::Result<()> {
+    let batch_count = rows.div_ceil(BATCH_SIZE);
+    generator.validate(batch_count)?;
+    let (rows, stages) = bench::bench_gen(generator, schema, batch_count)?;
 
-    if current_tick % REPORT_COUNT == 0 {
-        println!("Copied {current_tick}");
+    println!("{} rows:", rows);
+    for stage in &stages {
+        println!(
+            "{:>32}: {:>8.1}ns/row {:>8.2}s",
+            stage.stage,
+            stage.elapsed.as_nanos() as f64 / rows as f64,
+            stage.elapsed.as_secs_f64()
+        );
     }
+    Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    let conn_info = "host=localhost dbname=postgres user=postgres password=postgres";
-    let mut client = Client::connect(conn_info, NoTls).unwrap();
-
-    let pairs: Vec<(
-        fn(&mut Client, &str, &[(DateTime<Utc>, i32, f64)], i64),
-        &str,
-    )> = vec![
-        (insert_to_postgres, "insert"),
-        (insert_to_postgres_string, "insert-str"),
-        (copy_to_postgres, "copy"),
-    ];
-
-    for (f, name) in pairs {
-        let _context = ExecutionContext::new(&format!("fn {}", name), conn_info);
-
-        let start_time = Utc::now() + Duration::days(8);
-        let base_temp = 20.0;
-        for (batch_data, current_tick) in generate_data(start_time, base_temp, BATCH_COUNT) {
-            f(&mut client, "metrics", &batch_data, current_tick);
+    let outcome = run_command();
+    if let Err(e) = &outcome {
+        if e.is::<SloViolation>() {
+            eprintln!("Error: {}", e);
+            std::process::exit(slo::EXIT_CODE);
         }
     }
+    outcome
+}
 
-    Ok(())
+fn run_command() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    units::configure(cli.units);
+    let config = cli.connection.postgres_config(CONN_INFO)?;
+    #[cfg(feature = "tls")]
+    tls::configure(&cli.connection.tls_config())?;
+
+    match cli.command.unwrap_or(Command::Run(RunArgs::default())) {
+        Command::Run(args) => run(&config, &cli.generator, &cli.schema, args),
+        Command::Preview {
+            rows,
+            hex,
+            round_trip,
+        } => preview(&cli.generator, &cli.schema, rows, hex, round_trip),
+        Command::Export {
+            formats,
+            output_dir,
+            partition_by,
+            max_file_size,
+            compress,
+            compression_level,
+            decode,
+            #[cfg(feature = "upload")]
+            upload,
+        } => {
+            #[cfg(feature = "upload")]
+            let uploader = upload.as_deref().map(upload::Uploader::new).transpose()?;
+            let compression = compress.map(|codec| Compression {
+                codec,
+                level: compression_level,
+            });
+            let written = export(
+                &config,
+                &cli.generator,
+                &cli.schema,
+                &formats,
+                output_dir.as_deref(),
+                partition_by
+                    .map(|partitioning| (partitioning, max_file_size.map(|size| size as u64))),
+                compression,
+                decode,
+            )?;
+            #[cfg(feature = "upload")]
+            if let (Some(uploader), Some(dir)) = (&uploader, &output_dir) {
+                for name in &written {
+                    uploader.put_file(&dir.join(name), name)?;
+                }
+            }
+            #[cfg(not(feature = "upload"))]
+            let _ = written;
+            Ok(())
+        }
+        Command::DumpRestore {
+            format,
+            dump_dir,
+            target_table,
+            keep,
+        } => dump_restore(
+            &config,
+            &cli.schema,
+            format,
+            &dump_dir.unwrap_or_else(std::env::temp_dir),
+            &target_table,
+            keep,
+        ),
+        Command::Read {
+            formats,
+            fetch_size,
+        } => read(&config, &cli.schema, &formats, fetch_size),
+        Command::BenchGen { rows } => bench_gen(&cli.generator, &cli.schema, rows),
+        #[cfg(feature = "serve")]
+        Command::Serve { listen } => serve::serve(&config, &cli.generator, &cli.schema, &listen),
+        #[cfg(feature = "serve")]
+        Command::Coordinate { nodes, load } => coordinate::coordinate(
+            &cli.generator,
+            &cli.schema,
+            &nodes,
+            load.run_config(TABLE_NAME, BATCH_COUNT),
+            load.reference.as_deref(),
+        ),
+        Command::Capacity(args) => {
+            capacity::capacity(&config, &cli.generator, &cli.schema, TABLE_NAME, &args)
+        }
+        Command::Failover(args) => {
+            failover::failover(&config, &cli.generator, &cli.schema, TABLE_NAME, &args)
+        }
+        Command::Tenants(args) => tenants::tenants(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Tables(args) => tables::tables(&config, &cli.generator, BATCH_COUNT, &args),
+        Command::Indexes(args) => indexes::indexes(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Storage(args) => storage::storage(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Partitions(args) => partitions::partitions(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Overhead(args) => overhead::overhead(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::ForeignKeys(args) => foreign_keys::foreign_keys(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Rls(args) => rls::rls(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Fdw(args) => fdw::fdw(&config, &cli.generator, &cli.schema, BATCH_COUNT, &args),
+        Command::Replication(args) => {
+            replication::replication(&config, &cli.generator, &cli.schema, BATCH_COUNT, &args)
+        }
+        Command::Paths(args) => {
+            paths::paths(&cli.generator, &cli.schema, TABLE_NAME, BATCH_COUNT, &args)
+        }
+        Command::TimeZones(args) => {
+            time_zones::time_zones(&config, &cli.generator, &cli.schema, &args)
+        }
+        Command::Replay(args) => replay::replay(&config, &cli.schema, TABLE_NAME, &args),
+        Command::Interleave(args) => interleave::interleave(
+            &config,
+            &cli.generator,
+            &cli.schema,
+            TABLE_NAME,
+            BATCH_COUNT,
+            &args,
+        ),
+        Command::Fixtures(args) => fixtures::fixtures(&config, &args),
+        Command::Sink { rate, target } => {
+            cli.generator.validate(BATCH_COUNT)?;
+            let (rows, stats) = sink::sink(
+                &cli.generator,
+                &cli.schema,
+                BATCH_COUNT,
+                rate,
+                &target,
+                TABLE_NAME,
+            )?;
+            print_transfer(&format!("sink {}", target.name()), &stats, rows as i64);
+            Ok(())
+        }
+        #[cfg(feature = "upload")]
+        Command::Stage { warehouse } => {
+            cli.generator.validate(BATCH_COUNT)?;
+            let staged = stage::stage(
+                &cli.generator,
+                &cli.schema,
+                BATCH_COUNT,
+                &warehouse,
+                TABLE_NAME,
+            )?;
+            print_transfer("stage upload", &staged.upload, staged.rows as i64);
+            let load = ExportStats {
+                bytes: staged.upload.bytes,
+                elapsed: staged.load,
+                files: Vec::new(),
+            };
+            let name = format!("stage {} COPY", warehouse.name());
+            print_transfer(&name, &load, staged.rows as i64);
+            println!(
+                "\nTotal: {:.2}s",
+                (staged.upload.elapsed + staged.load).as_secs_f64()
+            );
+            Ok(())
+        }
+        Command::Profile(args) => profile::profile(&config, &cli.generator, &args),
+        Command::K8sManifest(args) => {
+            cli.generator.validate(BATCH_COUNT)?;
+            print!("{}", k8s::manifest(&cli.generator, &cli.schema, &args));
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,75 @@
+//! `manifest.json`, written next to exported files so that whoever loads
+//! them can check they have exactly the dataset that was generated: the
+//! generator settings, the row count and each file's size and digest, and
+//! checksums of its batches that `--input` verifies.
+
+use fast_generation::input::Checksums;
+use fast_generation::{GeneratorConfig, SchemaConfig};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const FILE_NAME: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub tool_version: String,
+    /// As given to the export, which should match those the table was
+    /// loaded with.
+    pub generator: GeneratorConfig,
+    pub schema: SchemaConfig,
+    pub table_name: String,
+    pub rows: i64,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Relative to the manifest.
+    pub name: String,
+    pub format: String,
+    /// Codec the file is compressed with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    /// Size on disk.
+    pub bytes: u64,
+    pub sha256: String,
+    /// Unset in manifests written before batches were checksummed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<Checksums>,
+}
+
+impl Manifest {
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        fs::write(dir.join(FILE_NAME), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// The checksums the nearest manifest above `path` records for it, with
+/// that manifest's path; `None` if no manifest lists them.
+pub fn recorded_checksums(path: &Path) -> anyhow::Result<Option<(PathBuf, Checksums)>> {
+    for dir in path.ancestors().skip(1) {
+        let manifest_path = dir.join(FILE_NAME);
+        if !manifest_path.is_file() {
+            continue;
+        }
+        let manifest: Manifest = serde_json::from_slice(&fs::read(&manifest_path)?)
+            .map_err(|e| anyhow::anyhow!("reading {}: {}", manifest_path.display(), e))?;
+        let name = path
+            .strip_prefix(dir)?
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let checksums = manifest
+            .files
+            .into_iter()
+            .find(|file| file.name == name)
+            .and_then(|file| file.checksums);
+        return Ok(checksums.map(|checksums| (manifest_path, checksums)));
+    }
+    Ok(None)
+}
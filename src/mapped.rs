@@ -0,0 +1,230 @@
+//! Recorded files replayed straight from memory: an uncompressed file
+//! `export` wrote is mapped, cut into batches at row boundaries, and every
+//! batch is sent to COPY as the bytes lying in the mapped pages, with no row
+//! decoded or encoded again and no buffer built for it.
+
+use crate::export::{self, ExportFormat};
+use crate::input::{Checksummer, Checksums};
+use crate::pgcopy::{FLAG_OIDS, SIGNATURE};
+use crate::sql_log;
+use crate::FastGenError;
+use memmap2::Mmap;
+use postgres::Client;
+use std::{
+    fs::File,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// Ends a binary COPY stream: a tuple of -1 fields.
+const TRAILER: [u8; 2] = [0xff, 0xff];
+
+fn be_i16(bytes: &[u8], at: usize) -> Option<i16> {
+    Some(i16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_i32(bytes: &[u8], at: usize) -> Option<i32> {
+    Some(i32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+pub struct MappedFile {
+    path: PathBuf,
+    map: Mmap,
+    format: ExportFormat,
+    /// The binary COPY header, sent ahead of every batch; empty for CSV and
+    /// text.
+    header: Range<usize>,
+    /// Where the rows start, past the binary header or a header line.
+    body: usize,
+}
+
+impl MappedFile {
+    /// Maps `path`, named the way `export` names files: `.csv`, `.tsv` or
+    /// `.pgcopy`, uncompressed. With `header`, the first line of a CSV or
+    /// text file is skipped.
+    pub fn open(path: &Path, header: bool) -> crate::Result<MappedFile> {
+        let format = match export::file_format(path) {
+            Some((format, None)) => format,
+            Some((_, Some(codec))) => {
+                return Err(FastGenError::InvalidConfig(format!(
+                    "{} is {}-compressed and can't be mapped; decompress it first",
+                    path.display(),
+                    codec.name()
+                )))
+            }
+            None => {
+                return Err(FastGenError::InvalidConfig(format!(
+                    "can't tell the format of {} from its name; expected .csv, .tsv or .pgcopy",
+                    path.display()
+                )))
+            }
+        };
+        let file = File::open(path).map_err(|e| {
+            FastGenError::InvalidConfig(format!("opening {}: {}", path.display(), e))
+        })?;
+        // SAFETY: the map is only read, and a file truncated while mapped
+        // is the caller's to avoid, as with any recorded file replayed.
+        let map = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential)?;
+
+        let (header, body) = match format {
+            ExportFormat::Binary => {
+                if !map.starts_with(SIGNATURE) {
+                    return Err(FastGenError::Encoding(format!(
+                        "{}: missing PGCOPY signature",
+                        path.display()
+                    )));
+                }
+                let at = SIGNATURE.len();
+                let (Some(flags), Some(extension_length)) =
+                    (be_i32(&map, at), be_i32(&map, at + 4))
+                else {
+                    return Err(FastGenError::Encoding(format!(
+                        "{}: truncated PGCOPY header",
+                        path.display()
+                    )));
+                };
+                if flags & FLAG_OIDS != 0 {
+                    return Err(FastGenError::Encoding(
+                        "COPY data with OIDs is not supported".to_string(),
+                    ));
+                }
+                let end = at + 8 + extension_length.max(0) as usize;
+                (0..end, end)
+            }
+            ExportFormat::Csv | ExportFormat::Text => {
+                let body = match header {
+                    true => map
+                        .iter()
+                        .position(|&byte| byte == b'\n')
+                        .map_or(map.len(), |end| end + 1),
+                    false => 0,
+                };
+                (0..0, body)
+            }
+        };
+        Ok(MappedFile {
+            path: path.to_path_buf(),
+            map,
+            format,
+            header,
+            body,
+        })
+    }
+
+    /// Bytes mapped.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Hands the bytes of every row, in order, to `each`. Binary tuples are
+    /// only walked by their field lengths; CSV and text rows keep their line
+    /// endings, `\r\n` as well as `\n`, both of which COPY accepts.
+    fn each_row(&self, mut each: impl FnMut(Range<usize>)) -> crate::Result<()> {
+        let map = &self.map[..];
+        let mut at = self.body;
+        match self.format {
+            ExportFormat::Binary => loop {
+                let start = at;
+                let truncated = || {
+                    FastGenError::Encoding(format!(
+                        "{}: tuple at byte {} is cut short",
+                        self.path.display(),
+                        start
+                    ))
+                };
+                let fields = be_i16(map, at).ok_or_else(truncated)?;
+                if fields == -1 {
+                    break;
+                }
+                if fields != 3 {
+                    return Err(FastGenError::Encoding(format!(
+                        "{}: expected 3 fields per tuple, found {} at byte {}",
+                        self.path.display(),
+                        fields,
+                        start
+                    )));
+                }
+                at += 2;
+                for _ in 0..fields {
+                    let length = be_i32(map, at).ok_or_else(truncated)?;
+                    at += 4 + length.max(0) as usize;
+                }
+                if at > map.len() {
+                    return Err(truncated());
+                }
+                each(start..at);
+            },
+            ExportFormat::Csv | ExportFormat::Text => {
+                while at < map.len() {
+                    let end = map[at..]
+                        .iter()
+                        .position(|&byte| byte == b'\n')
+                        .map_or(map.len(), |end| at + end + 1);
+                    each(at..end);
+                    at = end;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The byte ranges of consecutive batches of `rows` rows, the last one
+    /// possibly short.
+    pub fn batches(&self, rows: usize) -> crate::Result<Vec<Range<usize>>> {
+        let rows = rows.max(1);
+        let mut batches: Vec<Range<usize>> = Vec::new();
+        let mut in_batch = 0;
+        self.each_row(|row| {
+            match batches.last_mut() {
+                Some(batch) if in_batch < rows => batch.end = row.end,
+                _ => {
+                    batches.push(row);
+                    in_batch = 0;
+                }
+            }
+            in_batch += 1;
+        })?;
+        Ok(batches)
+    }
+
+    /// Fails on a run of rows whose checksum differs from the one recorded,
+    /// as `input::read_batches` does.
+    pub fn verify(&self, expected: &Checksums) -> crate::Result<()> {
+        let mut checksummer = Checksummer::new(expected.rows);
+        self.each_row(|row| match self.format {
+            ExportFormat::Binary => checksummer.row(&self.map[row]),
+            ExportFormat::Csv | ExportFormat::Text => checksummer.line(&self.map[row]),
+        })?;
+        checksummer.finish().verify(&self.path, expected)
+    }
+
+    /// Loads the rows of `batch`, one of `batches`, with one COPY, written
+    /// from the mapped pages; returns the rows COPY reports.
+    pub fn copy(
+        &self,
+        client: &mut Client,
+        table_name: &str,
+        batch: Range<usize>,
+    ) -> crate::Result<u64> {
+        let statement = format!(
+            "COPY {} FROM STDIN WITH (FORMAT {})",
+            table_name,
+            self.format.name()
+        );
+        sql_log::statement(&statement);
+        let mut writer = client.copy_in(&statement)?;
+        writer.write_all(&self.map[self.header.clone()])?;
+        writer.write_all(&self.map[batch])?;
+        if self.format == ExportFormat::Binary {
+            writer.write_all(&TRAILER)?;
+        }
+        Ok(writer.finish()?)
+    }
+}
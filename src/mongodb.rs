@@ -0,0 +1,111 @@
+//! `sink mongodb`: inserts each batch into a MongoDB collection with one
+//! insertMany, as documents with the row's columns, under the write
+//! concern asked for, so document storage can be compared with Postgres on
+//! the same rows.
+
+use crate::cli::MongodbArgs;
+use crate::sink::Sink;
+use fast_generation::generator::{Row, Timestamp};
+use fast_generation::schema::{SchemaConfig, TimeColumn};
+use fast_generation::serialize::temperature_text;
+use mongodb::bson::{self, Bson, DateTime, Decimal128, Document};
+use mongodb::options::{CollectionOptions, WriteConcern};
+use mongodb::sync::{Client, Collection};
+use std::time::Duration;
+
+pub struct MongodbSink {
+    collection: Collection<Document>,
+    ordered: bool,
+    schema: SchemaConfig,
+    bytes: u64,
+}
+
+impl MongodbSink {
+    /// Inserts into `--collection`, or else `name`.
+    pub fn new(
+        args: &MongodbArgs,
+        schema: &SchemaConfig,
+        name: &str,
+    ) -> anyhow::Result<MongodbSink> {
+        let client = Client::with_uri_str(&args.uri)
+            .map_err(|e| anyhow::anyhow!("connecting to {}: {}", args.uri, e))?;
+        let write_concern = WriteConcern::builder()
+            .w(args
+                .write_concern
+                .as_deref()
+                .map(|w| match w.parse::<u32>() {
+                    Ok(nodes) => nodes.into(),
+                    Err(_) => w.into(),
+                }))
+            .journal(args.journal.then_some(true))
+            .w_timeout(args.wtimeout_ms.map(Duration::from_millis))
+            .build();
+        let collection = client.database(&args.database).collection_with_options(
+            args.collection.as_deref().unwrap_or(name),
+            CollectionOptions::builder()
+                .write_concern(write_concern)
+                .build(),
+        );
+        Ok(MongodbSink {
+            collection,
+            ordered: !args.unordered,
+            schema: *schema,
+            bytes: 0,
+        })
+    }
+}
+
+/// A row as a document: `created` a date, which keeps milliseconds, or the
+/// microseconds of a bigint column, and `temperature` a double, or a
+/// decimal at `--numeric-scale`. Infinite timestamps are the extremes of a
+/// date, or fail in a bigint column.
+fn document(schema: &SchemaConfig, row: &Row) -> anyhow::Result<Document> {
+    let created = match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), TimeColumn::Bigint) => {
+            Bson::Int64(datetime.timestamp_micros())
+        }
+        (Timestamp::Value(datetime), _) => {
+            Bson::DateTime(DateTime::from_millis(datetime.timestamp_millis()))
+        }
+        (_, TimeColumn::Bigint) => {
+            anyhow::bail!("a bigint time column cannot store infinite timestamps")
+        }
+        (Timestamp::PosInfinity, _) => Bson::DateTime(DateTime::MAX),
+        (Timestamp::NegInfinity, _) => Bson::DateTime(DateTime::MIN),
+    };
+    let temperature = match schema.numeric_scale {
+        Some(_) => Bson::Decimal128(temperature_text(schema, row.2).parse::<Decimal128>()?),
+        None => Bson::Double(row.2),
+    };
+    let mut document = Document::new();
+    document.insert("created", created);
+    document.insert("sensor_id", row.1);
+    document.insert("temperature", temperature);
+    Ok(document)
+}
+
+impl Sink for MongodbSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let documents = batch
+            .iter()
+            .map(|row| document(&self.schema, row))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // Every document of a schema has the same fields of the same types,
+        // so the same size, not counting the `_id` the driver adds.
+        if let Some(first) = documents.first() {
+            self.bytes += (bson::to_vec(first)?.len() * documents.len()) as u64;
+        }
+        self.collection
+            .insert_many(documents)
+            .ordered(self.ordered)
+            .run()
+            .map_err(|e| {
+                anyhow::anyhow!("inserting into {}: {}", self.collection.namespace(), e)
+            })?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(self.bytes)
+    }
+}
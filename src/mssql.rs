@@ -0,0 +1,413 @@
+//! `sink mssql`: loads the rows into a SQL Server table, each batch either
+//! in a TDS bulk load, the protocol `BULK INSERT` and bcp use, as the rows
+//! of a table-valued parameter, or as multi-row parameterized INSERTs in one
+//! transaction, so a SQL Server target can be compared with Postgres on the
+//! same workload.
+
+use crate::cli::MssqlArgs;
+use crate::sink::Sink;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use clap::ValueEnum;
+use fast_generation::generator::{Row, Timestamp};
+use fast_generation::schema::{SchemaConfig, TimeColumn};
+use std::fmt::Write;
+use tiberius::numeric::Numeric;
+use tiberius::time::{Date, DateTime2, DateTimeOffset, Time};
+use tiberius::{Client, ColumnData, Config, IntoSql, Query, TokenRow};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+
+/// A statement takes 2100 parameters at most, three a row.
+const INSERT_ROWS: usize = 700;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum LoadMethod {
+    /// A bulk load of each batch
+    #[default]
+    Bulk,
+    /// INSERT ... VALUES of up to 700 rows a statement, a batch in a
+    /// transaction
+    Insert,
+    /// One INSERT ... SELECT a batch from a table variable of a table type
+    /// created for the table, filled from a single parameter: the rows as
+    /// JSON, unpacked by OPENJSON, since tiberius can't send a table-valued
+    /// parameter itself
+    Tvp,
+}
+
+pub struct MssqlSink {
+    runtime: Runtime,
+    client: Client<Compat<TcpStream>>,
+    table: String,
+    method: LoadMethod,
+    schema: SchemaConfig,
+    bytes: u64,
+}
+
+impl MssqlSink {
+    /// Loads into `--table`, or else `name`, created if it doesn't exist.
+    pub fn new(args: &MssqlArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<MssqlSink> {
+        let table = args.table.clone().unwrap_or_else(|| name.to_string());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let mut client = runtime.block_on(connect(Config::from_ado_string(&args.connection)?))?;
+        runtime
+            .block_on(client.execute(
+                format!(
+                    "IF OBJECT_ID(@P1) IS NULL CREATE TABLE {} ({})",
+                    table,
+                    column_definitions(schema)
+                ),
+                &[&table.as_str()],
+            ))
+            .map_err(|e| anyhow::anyhow!("creating {}: {}", table, e))?;
+        if let LoadMethod::Tvp = args.method {
+            let rows_type = rows_type(&table);
+            runtime
+                .block_on(client.execute(
+                    format!(
+                        "IF TYPE_ID(@P1) IS NULL CREATE TYPE {} AS TABLE ({})",
+                        rows_type,
+                        column_definitions(schema)
+                    ),
+                    &[&rows_type.as_str()],
+                ))
+                .map_err(|e| anyhow::anyhow!("creating type {}: {}", rows_type, e))?;
+        }
+        Ok(MssqlSink {
+            runtime,
+            client,
+            table,
+            method: args.method,
+            schema: *schema,
+            bytes: 0,
+        })
+    }
+}
+
+/// Connects to the server, following a redirect to another one as Azure
+/// SQL's gateway sends.
+async fn connect(mut config: Config) -> anyhow::Result<Client<Compat<TcpStream>>> {
+    let address = config.get_addr();
+    let tcp = TcpStream::connect(&address)
+        .await
+        .map_err(|e| anyhow::anyhow!("connecting to {}: {}", address, e))?;
+    tcp.set_nodelay(true)?;
+    match Client::connect(config.clone(), tcp.compat_write()).await {
+        Ok(client) => Ok(client),
+        Err(tiberius::error::Error::Routing { host, port }) => {
+            config.host(&host);
+            config.port(port);
+            let tcp = TcpStream::connect((host.as_str(), port)).await?;
+            tcp.set_nodelay(true)?;
+            Ok(Client::connect(config, tcp.compat_write()).await?)
+        }
+        Err(e) => anyhow::bail!("connecting to {}: {}", address, e),
+    }
+}
+
+/// The columns the generator produces, as SQL Server types: a timestamp
+/// with time zone a datetimeoffset, one without a datetime2. Both keep the
+/// default scale of 7, the one tiberius declares a datetimeoffset with in
+/// a bulk load.
+fn column_definitions(schema: &SchemaConfig) -> String {
+    column_types(schema)
+        .map(|(name, sql_type)| format!("{} {} not null", name, sql_type))
+        .join(", ")
+}
+
+/// The columns' names and SQL Server types, shared by the table, its
+/// table type and the `OPENJSON` that fills it.
+fn column_types(schema: &SchemaConfig) -> [(&'static str, String); 3] {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => "datetimeoffset",
+        TimeColumn::Timestamp => "datetime2",
+        TimeColumn::Bigint => "bigint",
+    };
+    let temperature = match schema.numeric_scale {
+        // Room for the integer digits of any temperature generated.
+        Some(scale) => format!("decimal({}, {})", scale + 10, scale),
+        None => "float".to_string(),
+    };
+    [
+        ("created", created.to_string()),
+        ("sensor_id", "int".to_string()),
+        ("temperature", temperature),
+    ]
+}
+
+/// The table type `LoadMethod::Tvp` fills, with the table's columns; left
+/// in place, like the table.
+fn rows_type(table: &str) -> String {
+    format!("{}_rows", table)
+}
+
+/// A row's timestamp: infinite ones the extremes of a datetime2, failing
+/// in a bigint column.
+fn datetime(schema: &SchemaConfig, row: &Row) -> anyhow::Result<DateTime<Utc>> {
+    Ok(match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), _) => datetime,
+        (_, TimeColumn::Bigint) => {
+            anyhow::bail!("a bigint time column cannot store infinite timestamps")
+        }
+        (Timestamp::PosInfinity, _) => NaiveDate::from_ymd_opt(9999, 12, 31)
+            .and_then(|date| date.and_hms_micro_opt(23, 59, 59, 999_999))
+            .map(|datetime| datetime.and_utc())
+            .expect("valid date"),
+        (Timestamp::NegInfinity, _) => {
+            DateTime::<Utc>::from_timestamp(-62_135_596_800, 0).expect("valid date")
+        }
+    })
+}
+
+/// A row's values as `column_definitions` types them; a float column
+/// cannot store NaN.
+fn values(schema: &SchemaConfig, row: &Row) -> anyhow::Result<[ColumnData<'static>; 3]> {
+    let datetime = datetime(schema, row)?;
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => {
+            ColumnData::DateTimeOffset(Some(DateTimeOffset::new(datetime2(datetime), 0)))
+        }
+        TimeColumn::Timestamp => ColumnData::DateTime2(Some(datetime2(datetime))),
+        TimeColumn::Bigint => ColumnData::I64(Some(datetime.timestamp_micros())),
+    };
+    Ok([
+        created,
+        ColumnData::I32(Some(row.1)),
+        temperature(schema, row.2)?,
+    ])
+}
+
+/// A UTC timestamp at the scale the columns are created with, in 100ns
+/// increments; a bulk load sends it as is.
+fn datetime2(datetime: DateTime<Utc>) -> DateTime2 {
+    let time = datetime.time();
+    DateTime2::new(
+        Date::new(datetime.date_naive().num_days_from_ce() as u32 - 1),
+        Time::new(
+            time.num_seconds_from_midnight() as u64 * 10_000_000 + (time.nanosecond() / 100) as u64,
+            7,
+        ),
+    )
+}
+
+fn finite(temperature: f64) -> anyhow::Result<f64> {
+    if !temperature.is_finite() {
+        anyhow::bail!(
+            "a SQL Server column cannot store a {} temperature",
+            temperature
+        )
+    }
+    Ok(temperature)
+}
+
+fn temperature(schema: &SchemaConfig, temperature: f64) -> anyhow::Result<ColumnData<'static>> {
+    let temperature = finite(temperature)?;
+    Ok(match schema.numeric_scale {
+        Some(scale) => ColumnData::Numeric(Some(Numeric::new_with_scale(
+            (temperature * 10f64.powi(scale as i32)).round() as i128,
+            scale as u8,
+        ))),
+        None => ColumnData::F64(Some(temperature)),
+    })
+}
+
+/// A batch as a JSON array of `[created, sensor_id, temperature]` arrays,
+/// timestamps as ISO 8601 strings with 7 fractional digits and decimals
+/// rounded to their scale.
+fn json_rows(schema: &SchemaConfig, batch: &[Row]) -> anyhow::Result<String> {
+    let mut json = String::with_capacity(batch.len() * 48 + 2);
+    json.push('[');
+    for (i, row) in batch.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let datetime = datetime(schema, row)?;
+        // datetime2's 100ns increments, the most a string converts.
+        let seconds = datetime.format("%Y-%m-%dT%H:%M:%S");
+        let ticks = datetime.nanosecond() % 1_000_000_000 / 100;
+        match schema.time_column {
+            TimeColumn::Timestamptz => write!(json, "[\"{}.{:07}+00:00\"", seconds, ticks),
+            TimeColumn::Timestamp => write!(json, "[\"{}.{:07}\"", seconds, ticks),
+            TimeColumn::Bigint => write!(json, "[{}", datetime.timestamp_micros()),
+        }?;
+        let temperature = finite(row.2)?;
+        match schema.numeric_scale {
+            Some(scale) => write!(json, ",{},{:.*}]", row.1, scale as usize, temperature),
+            None => write!(json, ",{},{:?}]", row.1, temperature),
+        }?;
+    }
+    json.push(']');
+    Ok(json)
+}
+
+/// Fills a table variable of `rows_type` from the JSON rows in @P1 and
+/// inserts them into `table` in one statement.
+fn tvp_insert(schema: &SchemaConfig, table: &str) -> String {
+    let with = column_types(schema)
+        .iter()
+        .enumerate()
+        .map(|(i, (name, sql_type))| format!("{} {} '$[{}]'", name, sql_type, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "DECLARE @rows {rows_type};
+         INSERT INTO @rows (created, sensor_id, temperature)
+             SELECT created, sensor_id, temperature FROM OPENJSON(@P1) WITH ({with});
+         INSERT INTO {table} (created, sensor_id, temperature)
+             SELECT created, sensor_id, temperature FROM @rows;",
+        rows_type = rows_type(table)
+    )
+}
+
+/// Bytes of a row's values as the columns store them, not counting the
+/// type and length prefixes on the wire.
+fn row_bytes(schema: &SchemaConfig) -> u64 {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => 10,
+        _ => 8,
+    };
+    let temperature = match schema.numeric_scale.map(|scale| scale + 10) {
+        Some(precision) if precision <= 19 => 9,
+        Some(precision) if precision <= 28 => 13,
+        Some(_) => 17,
+        None => 8,
+    };
+    created + 4 + temperature
+}
+
+/// A value bound as a statement parameter as is.
+struct Param(ColumnData<'static>);
+
+impl<'a> IntoSql<'a> for Param {
+    fn into_sql(self) -> ColumnData<'a> {
+        self.0
+    }
+}
+
+impl Sink for MssqlSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        if let LoadMethod::Tvp = self.method {
+            let json = json_rows(&self.schema, batch)?;
+            self.bytes += row_bytes(&self.schema) * batch.len() as u64;
+            let statement = tvp_insert(&self.schema, &self.table);
+            self.runtime
+                .block_on(self.client.execute(statement, &[&json.as_str()]))
+                .map_err(|e| anyhow::anyhow!("loading into {}: {}", self.table, e))?;
+            return Ok(());
+        }
+        let rows = batch
+            .iter()
+            .map(|row| values(&self.schema, row))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.bytes += row_bytes(&self.schema) * rows.len() as u64;
+        let client = &mut self.client;
+        let table = &self.table;
+        let method = self.method;
+        self.runtime
+            .block_on(async move {
+                match method {
+                    LoadMethod::Bulk => {
+                        let mut load = client.bulk_insert(table).await?;
+                        for values in rows {
+                            let mut row = TokenRow::with_capacity(values.len());
+                            values.into_iter().for_each(|value| row.push(value));
+                            load.send(row).await?;
+                        }
+                        load.finalize().await?;
+                    }
+                    LoadMethod::Insert => {
+                        client
+                            .simple_query("BEGIN TRANSACTION")
+                            .await?
+                            .into_results()
+                            .await?;
+                        for chunk in rows.chunks(INSERT_ROWS) {
+                            let placeholders: Vec<_> = (0..chunk.len())
+                                .map(|i| {
+                                    format!("(@P{}, @P{}, @P{})", 3 * i + 1, 3 * i + 2, 3 * i + 3)
+                                })
+                                .collect();
+                            let mut insert = Query::new(format!(
+                                "INSERT INTO {} (created, sensor_id, temperature) VALUES {}",
+                                table,
+                                placeholders.join(", ")
+                            ));
+                            for values in chunk {
+                                values
+                                    .iter()
+                                    .for_each(|value| insert.bind(Param(value.clone())));
+                            }
+                            insert.execute(client).await?;
+                        }
+                        client.simple_query("COMMIT").await?.into_results().await?;
+                    }
+                    LoadMethod::Tvp => unreachable!("sent as JSON above"),
+                }
+                tiberius::Result::Ok(())
+            })
+            .map_err(|e| anyhow::anyhow!("loading into {}: {}", table, e))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn json_rows_match_the_openjson_columns() {
+        let created = Utc.with_ymd_and_hms(2024, 2, 29, 12, 30, 5).unwrap()
+            + chrono::Duration::microseconds(250);
+        let rows = [
+            (Timestamp::Value(created), 7, 21.5),
+            (Timestamp::NegInfinity, -1, -0.125),
+        ];
+        let schema = |time_column, numeric_scale| SchemaConfig {
+            time_column,
+            numeric_scale,
+        };
+        assert_eq!(
+            json_rows(&schema(TimeColumn::Timestamptz, None), &rows).unwrap(),
+            r#"[["2024-02-29T12:30:05.0002500+00:00",7,21.5],["0001-01-01T00:00:00.0000000+00:00",-1,-0.125]]"#
+        );
+        assert_eq!(
+            json_rows(&schema(TimeColumn::Timestamp, Some(2)), &rows[..1]).unwrap(),
+            r#"[["2024-02-29T12:30:05.0002500",7,21.50]]"#
+        );
+        assert_eq!(
+            json_rows(&schema(TimeColumn::Bigint, None), &rows[..1]).unwrap(),
+            format!("[[{},7,21.5]]", created.timestamp_micros())
+        );
+        assert!(json_rows(&schema(TimeColumn::Bigint, None), &rows[1..]).is_err());
+        assert!(json_rows(
+            &schema(TimeColumn::Timestamp, None),
+            &[(Timestamp::Value(created), 1, f64::NAN)]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn openjson_columns_match_the_table() {
+        let schema = SchemaConfig {
+            time_column: TimeColumn::Timestamptz,
+            numeric_scale: Some(2),
+        };
+        assert_eq!(
+            column_definitions(&schema),
+            "created datetimeoffset not null, sensor_id int not null, \
+             temperature decimal(12, 2) not null"
+        );
+        assert!(tvp_insert(&schema, "t").contains(
+            "WITH (created datetimeoffset '$[0]', sensor_id int '$[1]', \
+             temperature decimal(12, 2) '$[2]')"
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+//! `sink nats`: publishes each row to JetStream on a subject per sensor,
+//! PREFIX.SENSOR_ID, as JSON or protobuf, and waits for a batch to be
+//! acknowledged before publishing the next.
+
+use crate::cli::NatsArgs;
+use crate::sink::Sink;
+use async_nats::jetstream::{self, stream, Context};
+use clap::ValueEnum;
+use fast_generation::generator::Row;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::{protobuf, serialize};
+use tokio::runtime::Runtime;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum MessageFormat {
+    /// An object with the row's columns
+    #[default]
+    Json,
+    /// The message `protobuf::schema_proto` declares, printed at the start
+    Protobuf,
+}
+
+pub struct NatsSink {
+    runtime: Runtime,
+    jetstream: Context,
+    prefix: String,
+    format: MessageFormat,
+    schema: SchemaConfig,
+    bytes: u64,
+}
+
+impl NatsSink {
+    /// Subjects start with `--subject-prefix`, or else `name`.
+    pub fn new(args: &NatsArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<NatsSink> {
+        let prefix = args
+            .subject_prefix
+            .clone()
+            .unwrap_or_else(|| name.to_string());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let jetstream = runtime.block_on(async {
+            let client = async_nats::connect(&args.url)
+                .await
+                .map_err(|e| anyhow::anyhow!("connecting to {}: {}", args.url, e))?;
+            let jetstream = jetstream::new(client);
+            if let Some(name) = &args.stream {
+                jetstream
+                    .get_or_create_stream(stream::Config {
+                        name: name.clone(),
+                        subjects: vec![format!("{}.>", prefix)],
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| anyhow::anyhow!("creating stream {}: {}", name, e))?;
+            }
+            anyhow::Ok(jetstream)
+        })?;
+        if let MessageFormat::Protobuf = args.format {
+            print!("{}", protobuf::schema_proto(schema, name));
+        }
+        Ok(NatsSink {
+            runtime,
+            jetstream,
+            prefix,
+            format: args.format,
+            schema: *schema,
+            bytes: 0,
+        })
+    }
+}
+
+impl Sink for NatsSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        self.runtime.block_on(async {
+            let mut acks = Vec::with_capacity(batch.len());
+            for row in batch {
+                let payload = match self.format {
+                    MessageFormat::Json => serialize::json_object(&self.schema, row).into_bytes(),
+                    MessageFormat::Protobuf => {
+                        let mut payload = Vec::new();
+                        protobuf::write_row(&mut payload, &self.schema, row)?;
+                        payload
+                    }
+                };
+                self.bytes += payload.len() as u64;
+                let subject = format!("{}.{}", self.prefix, row.1);
+                acks.push(
+                    self.jetstream
+                        .publish(subject.clone(), payload.into())
+                        .await
+                        .map_err(|e| anyhow::anyhow!("publishing to {}: {}", subject, e))?,
+                );
+            }
+            for ack in acks {
+                ack.await
+                    .map_err(|e| anyhow::anyhow!("publishing to {}: {}", self.prefix, e))?;
+            }
+            anyhow::Ok(())
+        })
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(self.bytes)
+    }
+}
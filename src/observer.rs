@@ -0,0 +1,21 @@
+use crate::runner::StrategyResult;
+use crate::FastGenError;
+
+/// Progress of a `BenchmarkRunner`, for embedding applications to show in
+/// their own UIs. Batch events arrive from the worker threads, in no
+/// particular order across workers. Every method does nothing by default.
+pub trait Observer: Sync {
+    /// A worker picked up batch `batch` (counting from 1) to encode.
+    fn batch_started(&self, _strategy: &str, _batch: i64) {}
+
+    /// The server acknowledged `rows` rows of batch `batch`.
+    fn batch_committed(&self, _strategy: &str, _batch: i64, _rows: usize) {}
+
+    fn strategy_finished(&self, _result: &StrategyResult) {}
+
+    /// The strategy stopped early; `strategy_finished` follows.
+    fn error(&self, _strategy: &str, _error: &FastGenError) {}
+}
+
+/// Observes nothing.
+impl Observer for () {}
@@ -0,0 +1,396 @@
+//! `sink oracle`: loads the rows into an Oracle table with SQL*Loader, the
+//! rows streamed as CSV into one `sqlldr` run over its direct path, so an
+//! Oracle target can be compared with Postgres on the same workload without
+//! linking Oracle's client libraries into the binary: the table is created
+//! with `sqlplus` and loaded with `sqlldr`, both from Instant Client.
+
+use crate::cli::OracleArgs;
+use crate::sink::Sink;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use fast_generation::generator::{Row, Timestamp};
+use fast_generation::schema::{SchemaConfig, TimeColumn};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use tempfile::TempDir;
+
+pub struct OracleSink {
+    sqlldr: Child,
+    /// Its input, until `finish` closes it.
+    rows: Option<BufWriter<ChildStdin>>,
+    /// A directory only the user can open, holding the parfile and the
+    /// control file, log and bad file of the run; removed once it succeeds.
+    dir: TempDir,
+    log: PathBuf,
+    _parfile: Parfile,
+    schema: SchemaConfig,
+    line: String,
+    bytes: u64,
+}
+
+impl OracleSink {
+    /// Loads into `--table`, or else `name`, created if it doesn't exist;
+    /// connects as `--userid`, or else `ORACLE_USERID` from the environment.
+    pub fn new(args: &OracleArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<OracleSink> {
+        let table = args.table.clone().unwrap_or_else(|| name.to_string());
+        let userid = args
+            .userid
+            .clone()
+            .or_else(|| std::env::var("ORACLE_USERID").ok())
+            .ok_or_else(|| anyhow::anyhow!("--userid or ORACLE_USERID is required"))?;
+        create_table(args, &userid, &table, schema)?;
+
+        let dir = tempfile::Builder::new()
+            .prefix("fast_generation-")
+            .permissions(fs::Permissions::from_mode(0o700))
+            .tempdir()?;
+        let [control, log, bad] = ["ctl", "log", "bad"]
+            .map(|extension| dir.path().join(format!("{}.{}", table, extension)));
+        create_new(&control)?
+            .write_all(control_file(&table, schema, args.conventional).as_bytes())?;
+        let parfile = Parfile::new(control.with_extension("par"), &userid)?;
+        let mut sqlldr = Command::new(&args.sqlldr)
+            .arg(format!("parfile={}", parfile.0.display()))
+            .arg(format!("control={}", control.display()))
+            .arg(format!("log={}", log.display()))
+            .arg(format!("bad={}", bad.display()))
+            .arg("silent=(header,feedback)")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("running {}: {}", args.sqlldr, e))?;
+        let rows = BufWriter::with_capacity(1 << 20, sqlldr.stdin.take().expect("piped stdin"));
+        Ok(OracleSink {
+            sqlldr,
+            rows: Some(rows),
+            dir,
+            log,
+            _parfile: parfile,
+            schema: *schema,
+            line: String::new(),
+            bytes: 0,
+        })
+    }
+
+    /// Kills and reaps `sqlldr` before its input is closed, which it would
+    /// take for the end of the load and commit the rows sent so far; keeps
+    /// its log.
+    fn abort(&mut self) {
+        let _ = self.sqlldr.kill();
+        let _ = self.sqlldr.wait();
+        self.rows = None;
+        self.dir.disable_cleanup(true);
+    }
+
+    /// Writes the whole batch to `line` before sending any of it, so a row
+    /// that can't be written fails the batch before a byte of it is sent.
+    fn write(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        self.line.clear();
+        for row in batch {
+            write_line(&mut self.line, &self.schema, row)?;
+        }
+        self.bytes += self.line.len() as u64;
+        let rows = self.rows.as_mut().expect("input open until finish");
+        // A load that failed closes its input; its log says why.
+        if let Err(e) = rows.write_all(self.line.as_bytes()) {
+            anyhow::bail!("sending rows to sqlldr: {}; see {}", e, self.log.display());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OracleSink {
+    fn drop(&mut self) {
+        if self.rows.is_some() {
+            self.abort();
+        }
+    }
+}
+
+/// A parameter file only the user can read, giving `sqlldr` the connect
+/// string, password and all, instead of its command line; removed when
+/// dropped.
+struct Parfile(PathBuf);
+
+impl Parfile {
+    fn new(path: PathBuf, userid: &str) -> anyhow::Result<Parfile> {
+        let mut file = create_new(&path)?;
+        let parfile = Parfile(path);
+        writeln!(file, "userid={}", userid)?;
+        Ok(parfile)
+    }
+}
+
+impl Drop for Parfile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Opens a file only the user can read that must not exist yet, so nothing
+/// already at `path` is written through.
+fn create_new(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+/// The columns the generator produces, as Oracle types: the timestamps to
+/// the microsecond, a bigint a NUMBER(19), and the temperature a
+/// BINARY_DOUBLE or a NUMBER with room for any temperature generated.
+fn column_definitions(schema: &SchemaConfig) -> String {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => "TIMESTAMP(6) WITH TIME ZONE",
+        TimeColumn::Timestamp => "TIMESTAMP(6)",
+        TimeColumn::Bigint => "NUMBER(19)",
+    };
+    let temperature = match schema.numeric_scale {
+        Some(scale) => format!("NUMBER({}, {})", scale + 10, scale),
+        None => "BINARY_DOUBLE".to_string(),
+    };
+    format!(
+        "created {} NOT NULL, sensor_id NUMBER(10) NOT NULL, temperature {} NOT NULL",
+        created, temperature
+    )
+}
+
+/// Runs a PL/SQL block in `sqlplus` creating `table` unless it exists
+/// already (ORA-00955), connecting from the script rather than the command
+/// line, which other users can read.
+fn create_table(
+    args: &OracleArgs,
+    userid: &str,
+    table: &str,
+    schema: &SchemaConfig,
+) -> anyhow::Result<()> {
+    let script = format!(
+        "WHENEVER SQLERROR EXIT FAILURE
+CONNECT {}
+BEGIN
+  EXECUTE IMMEDIATE 'CREATE TABLE {} ({})';
+EXCEPTION
+  WHEN OTHERS THEN
+    IF SQLCODE != -955 THEN
+      RAISE;
+    END IF;
+END;
+/
+EXIT
+",
+        userid,
+        table,
+        column_definitions(schema)
+    );
+    let mut sqlplus = Command::new(&args.sqlplus)
+        .args(["-S", "-L", "/nolog"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("running {}: {}", args.sqlplus, e))?;
+    sqlplus
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(script.as_bytes())?;
+    let output = sqlplus.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "creating {}: {}",
+            table,
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+    Ok(())
+}
+
+/// A SQL*Loader control file appending the CSV lines read from standard
+/// input to `table`, over the direct path unless `conventional`; a single
+/// rejected row fails the load.
+fn control_file(table: &str, schema: &SchemaConfig, conventional: bool) -> String {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => {
+            r#"TIMESTAMP WITH TIME ZONE "YYYY-MM-DD HH24:MI:SS.FF6 TZH:TZM""#
+        }
+        TimeColumn::Timestamp => r#"TIMESTAMP "YYYY-MM-DD HH24:MI:SS.FF6""#,
+        TimeColumn::Bigint => "INTEGER EXTERNAL",
+    };
+    let temperature = match schema.numeric_scale {
+        Some(_) => "DECIMAL EXTERNAL",
+        None => "FLOAT EXTERNAL",
+    };
+    format!(
+        "OPTIONS (DIRECT={}, ERRORS=0)
+LOAD DATA
+INFILE '/dev/stdin'
+APPEND INTO TABLE {}
+FIELDS TERMINATED BY ','
+(created {}, sensor_id INTEGER EXTERNAL, temperature {})
+",
+        if conventional { "FALSE" } else { "TRUE" },
+        table,
+        created,
+        temperature
+    )
+}
+
+/// A row as the line `control_file` reads. Infinite timestamps are the
+/// extremes of the years 1 to 9999, or fail in a bigint column, as do
+/// earlier or later ones; NaN and infinite temperatures fail too.
+fn write_line(line: &mut String, schema: &SchemaConfig, row: &Row) -> anyhow::Result<()> {
+    use std::fmt::Write;
+
+    let datetime = match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), _) => datetime,
+        (_, TimeColumn::Bigint) => {
+            anyhow::bail!("a bigint time column cannot store infinite timestamps")
+        }
+        (Timestamp::PosInfinity, _) => NaiveDate::from_ymd_opt(9999, 12, 31)
+            .and_then(|date| date.and_hms_micro_opt(23, 59, 59, 999_999))
+            .map(|datetime| datetime.and_utc())
+            .expect("valid date"),
+        (Timestamp::NegInfinity, _) => {
+            DateTime::<Utc>::from_timestamp(-62_135_596_800, 0).expect("valid date")
+        }
+    };
+    if schema.time_column != TimeColumn::Bigint && !(1..=9999).contains(&datetime.year()) {
+        anyhow::bail!("an Oracle timestamp cannot be written for {}", datetime)
+    }
+    if !row.2.is_finite() {
+        anyhow::bail!("an Oracle column cannot store a {} temperature", row.2)
+    }
+    match schema.time_column {
+        TimeColumn::Timestamptz => {
+            write!(line, "{} +00:00", datetime.format("%Y-%m-%d %H:%M:%S%.6f"))
+        }
+        TimeColumn::Timestamp => write!(line, "{}", datetime.format("%Y-%m-%d %H:%M:%S%.6f")),
+        TimeColumn::Bigint => write!(line, "{}", datetime.timestamp_micros()),
+    }?;
+    match schema.numeric_scale {
+        Some(scale) => writeln!(line, ",{},{:.*}", row.1, scale as usize, row.2),
+        None => writeln!(line, ",{},{:?}", row.1, row.2),
+    }?;
+    Ok(())
+}
+
+impl Sink for OracleSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let result = self.write(batch);
+        if result.is_err() {
+            self.abort();
+        }
+        result
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<u64> {
+        let rows = self.rows.take().expect("input open until finish");
+        // Closing its input ends the load.
+        if let Err(e) = rows.into_inner() {
+            self.abort();
+            anyhow::bail!(
+                "sending rows to sqlldr: {}; see {}",
+                e.error(),
+                self.log.display()
+            );
+        }
+        let status = self.sqlldr.wait()?;
+        if !status.success() {
+            // Kept for its log and bad file; the parfile goes regardless.
+            self.dir.disable_cleanup(true);
+            anyhow::bail!("sqlldr exited with {}; see {}", status, self.log.display());
+        }
+        Ok(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn lines_match_the_control_file_fields() {
+        let created = Utc.with_ymd_and_hms(2024, 2, 29, 12, 30, 5).unwrap()
+            + chrono::Duration::microseconds(250);
+        let schema = |time_column, numeric_scale| SchemaConfig {
+            time_column,
+            numeric_scale,
+        };
+        let line = |schema: SchemaConfig, row: Row| {
+            let mut line = String::new();
+            write_line(&mut line, &schema, &row).map(|_| line)
+        };
+        let row = (Timestamp::Value(created), 7, 21.5);
+        assert_eq!(
+            line(schema(TimeColumn::Timestamptz, None), row).unwrap(),
+            "2024-02-29 12:30:05.000250 +00:00,7,21.5\n"
+        );
+        assert_eq!(
+            line(schema(TimeColumn::Timestamp, Some(2)), row).unwrap(),
+            "2024-02-29 12:30:05.000250,7,21.50\n"
+        );
+        assert_eq!(
+            line(schema(TimeColumn::Bigint, None), row).unwrap(),
+            format!("{},7,21.5\n", created.timestamp_micros())
+        );
+        assert_eq!(
+            line(
+                schema(TimeColumn::Timestamp, None),
+                (Timestamp::PosInfinity, 1, 0.0)
+            )
+            .unwrap(),
+            "9999-12-31 23:59:59.999999,1,0.0\n"
+        );
+        assert!(line(
+            schema(TimeColumn::Bigint, None),
+            (Timestamp::NegInfinity, 1, 0.0)
+        )
+        .is_err());
+        assert!(line(
+            schema(TimeColumn::Timestamp, None),
+            (Timestamp::Value(created), 1, f64::NAN)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn a_bad_row_kills_the_load_before_it_commits() {
+        let out =
+            std::env::temp_dir().join(format!("fast_generation-{}-oracle.csv", std::process::id()));
+        // `cat` stands in for sqlldr, copying whatever reaches it before its
+        // input closes.
+        let mut cat = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(fs::File::create(&out).unwrap())
+            .spawn()
+            .unwrap();
+        let rows = BufWriter::new(cat.stdin.take().unwrap());
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().to_path_buf();
+        let mut sink = OracleSink {
+            sqlldr: cat,
+            rows: Some(rows),
+            log: dir.path().join("t.log"),
+            _parfile: Parfile::new(dir.path().join("t.par"), "bench/secret").unwrap(),
+            dir,
+            schema: SchemaConfig {
+                time_column: TimeColumn::Timestamp,
+                numeric_scale: None,
+            },
+            line: String::new(),
+            bytes: 0,
+        };
+        let row = (Timestamp::Value(Utc::now()), 1, 21.5);
+        sink.send(&[row]).unwrap();
+        assert!(sink.send(&[row, (row.0, 2, f64::NAN), row]).is_err());
+        assert!(sink.sqlldr.try_wait().unwrap().is_some());
+        drop(sink);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "");
+        assert!(!kept.join("t.par").exists());
+        fs::remove_file(&out).unwrap();
+        fs::remove_dir_all(&kept).unwrap();
+    }
+}
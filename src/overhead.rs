@@ -0,0 +1,133 @@
+//! The `overhead` command: repeats the same load into a scratch copy of the
+//! table with a stored generated column, a BEFORE INSERT trigger, both or
+//! neither, to put a number on what each costs on ingest.
+
+use crate::cli::OverheadArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TableFeatures {
+    /// The table as is
+    Plain,
+    /// A stored generated column, temperature_f, in Fahrenheit
+    Generated,
+    /// A PL/pgSQL BEFORE INSERT trigger rounding each temperature
+    Trigger,
+    /// The generated column and the trigger
+    Both,
+}
+
+impl TableFeatures {
+    fn name(self) -> &'static str {
+        match self {
+            TableFeatures::Plain => "plain",
+            TableFeatures::Generated => "generated column",
+            TableFeatures::Trigger => "trigger",
+            TableFeatures::Both => "generated column + trigger",
+        }
+    }
+
+    fn create(self, scratch: &str, table_name: &str) -> String {
+        let mut sql = format!(
+            "DROP TABLE IF EXISTS {scratch};
+             CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS);"
+        );
+        if matches!(self, TableFeatures::Generated | TableFeatures::Both) {
+            sql += &format!(
+                "ALTER TABLE {scratch} ADD COLUMN temperature_f numeric \
+                 GENERATED ALWAYS AS (temperature * 9 / 5 + 32) STORED;"
+            );
+        }
+        if matches!(self, TableFeatures::Trigger | TableFeatures::Both) {
+            sql += &format!(
+                "CREATE OR REPLACE FUNCTION {scratch}_before_insert() RETURNS trigger AS $$
+                 BEGIN
+                     NEW.temperature := round(NEW.temperature, 2);
+                     RETURN NEW;
+                 END
+                 $$ LANGUAGE plpgsql;
+                 CREATE TRIGGER {scratch}_before_insert BEFORE INSERT ON {scratch}
+                 FOR EACH ROW EXECUTE FUNCTION {scratch}_before_insert();"
+            );
+        }
+        sql
+    }
+}
+
+fn print_matrix(results: &[(TableFeatures, StrategyResult)]) {
+    let plain = results
+        .iter()
+        .find(|(features, result)| *features == TableFeatures::Plain && result.error.is_none())
+        .map(|(_, result)| result.rows_per_second());
+    println!();
+    println!(
+        "{:<28} {:>10} {:>14} {:>12} {:>12} {:>10}",
+        "table", "rows/s", "bytes/s", "heap", "WAL", "vs plain"
+    );
+    for (features, result) in results {
+        if let Some(e) = &result.error {
+            println!("{:<28} failed: {}", features.name(), e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        let speed = result.rows_per_second();
+        println!(
+            "{:<28} {:>10.0} {:>14} {:>12} {:>12} {:>10}",
+            features.name(),
+            speed,
+            units::rate(size / result.elapsed.as_secs_f64()),
+            units::bytes(result.after.heap as f64),
+            units::bytes(result.wal_bytes as f64),
+            plain.map_or("-".to_string(), |plain| format!(
+                "{:+.1}%",
+                (speed / plain - 1.0) * 100.0
+            ))
+        );
+    }
+}
+
+pub fn overhead(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &OverheadArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let mut client = tls::connect(config)?;
+
+    let mut results = Vec::new();
+    let outcome = args
+        .features
+        .iter()
+        .try_for_each(|&features| -> anyhow::Result<()> {
+            println!("Loading into a table with: {}", features.name());
+            client.batch_execute(&features.create(scratch, table_name))?;
+            let mut loaded = BenchmarkRunner::new(config.clone())
+                .with_generator(*generator)
+                .with_schema(*schema)
+                .with_config(RunConfig {
+                    strategies: vec![args.strategy.clone()],
+                    table_name: scratch.clone(),
+                    batch_count,
+                    workers: args.workers,
+                    ..RunConfig::default()
+                })
+                .run()?;
+            results.push((features, loaded.remove(0)));
+            Ok(())
+        });
+    client.batch_execute(&format!(
+        "DROP TABLE IF EXISTS {scratch};
+         DROP FUNCTION IF EXISTS {scratch}_before_insert();"
+    ))?;
+    outcome?;
+
+    print_matrix(&results);
+    Ok(())
+}
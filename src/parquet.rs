@@ -0,0 +1,413 @@
+//! Parquet files of generated rows, for bulk loads into warehouses that read
+//! them from object storage: the message type matching the table's columns,
+//! a writer buffering rows into row groups, and a reader handing back the
+//! rows of such a file, or of one another tool wrote, to load with `--input`.
+
+use crate::export::{Codec, Compression};
+use crate::generator::{Created, Row, Timestamp};
+use crate::schema::{temperature_integer_digits, SchemaConfig, TimeColumn};
+use crate::FastGenError;
+use ::parquet::basic::{self, GzipLevel, LogicalType, TimeUnit, Type as PhysicalType, ZstdLevel};
+use ::parquet::column::reader::get_typed_column_reader;
+use ::parquet::data_type::{DataType, DoubleType, Int32Type, Int64Type};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::reader::{FileReader, RowGroupReader, SerializedFileReader};
+use ::parquet::file::writer::SerializedFileWriter;
+use ::parquet::schema::parser::parse_message_type;
+use ::parquet::schema::types::ColumnDescPtr;
+use chrono::DateTime;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rows per row group: large enough for the column chunks to compress well
+/// and scans to skip, small enough to buffer.
+const ROW_GROUP_ROWS: usize = 1_000_000;
+
+impl From<ParquetError> for FastGenError {
+    fn from(e: ParquetError) -> Self {
+        FastGenError::Encoding(e.to_string())
+    }
+}
+
+/// The Parquet message type of a table named `name` with the table's
+/// columns: `created` a timestamp in microseconds, or a plain INT64 for a
+/// bigint column, and `temperature` a DOUBLE, or a DECIMAL at
+/// `--numeric-scale` stored as its unscaled INT64.
+pub fn schema_message(schema: &SchemaConfig, name: &str) -> String {
+    let created = match schema.time_column {
+        TimeColumn::Timestamptz => "INT64 created (TIMESTAMP(MICROS, true))",
+        TimeColumn::Timestamp => "INT64 created (TIMESTAMP(MICROS, false))",
+        TimeColumn::Bigint => "INT64 created",
+    };
+    let temperature = match schema.numeric_scale {
+        Some(scale) => format!(
+            "INT64 temperature (DECIMAL({}, {}))",
+            temperature_integer_digits() + scale as i32,
+            scale
+        ),
+        None => "DOUBLE temperature".to_string(),
+    };
+    format!(
+        "message {} {{ REQUIRED {}; REQUIRED INT32 sensor_id; REQUIRED {}; }}",
+        name, created, temperature
+    )
+}
+
+/// Writes a Parquet file, a row group per `ROW_GROUP_ROWS` rows, with its
+/// pages compressed by `compression` if given.
+pub struct ParquetWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    schema: SchemaConfig,
+    created: Vec<i64>,
+    sensor_ids: Vec<i32>,
+    temperatures: Vec<f64>,
+    unscaled: Vec<i64>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(
+        writer: W,
+        schema: &SchemaConfig,
+        name: &str,
+        compression: Option<Compression>,
+    ) -> crate::Result<Self> {
+        let codec = match compression {
+            None => basic::Compression::UNCOMPRESSED,
+            Some(Compression {
+                codec: Codec::Gzip,
+                level,
+            }) => basic::Compression::GZIP(GzipLevel::try_new(level.unwrap_or(6) as u32)?),
+            Some(Compression {
+                codec: Codec::Zstd,
+                level,
+            }) => basic::Compression::ZSTD(ZstdLevel::try_new(
+                level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+            )?),
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(codec)
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .build();
+        Ok(ParquetWriter {
+            writer: SerializedFileWriter::new(
+                writer,
+                Arc::new(parse_message_type(&schema_message(schema, name))?),
+                Arc::new(properties),
+            )?,
+            schema: *schema,
+            created: Vec::new(),
+            sensor_ids: Vec::new(),
+            temperatures: Vec::new(),
+            unscaled: Vec::new(),
+        })
+    }
+
+    /// Infinite timestamps are the extremes of an INT64, as Postgres stores
+    /// them; a bigint column can't hold them and a decimal has no NaN or
+    /// infinities, so those fail.
+    pub fn write_rows(&mut self, rows: &[Row]) -> crate::Result<()> {
+        for row in rows {
+            self.created.push(match (row.0, self.schema.time_column) {
+                (Timestamp::Value(datetime), _) => datetime.timestamp_micros(),
+                (_, TimeColumn::Bigint) => {
+                    return Err(FastGenError::Encoding(
+                        "a bigint time column cannot store infinite timestamps".to_string(),
+                    ))
+                }
+                (Timestamp::PosInfinity, _) => i64::MAX,
+                (Timestamp::NegInfinity, _) => i64::MIN,
+            });
+            self.sensor_ids.push(row.1);
+            match self.schema.numeric_scale {
+                Some(_) if !row.2.is_finite() => {
+                    return Err(FastGenError::Encoding(format!(
+                        "a Parquet decimal cannot store a {} temperature",
+                        row.2
+                    )))
+                }
+                Some(scale) => self
+                    .unscaled
+                    .push((row.2 * 10f64.powi(scale as i32)).round() as i64),
+                None => self.temperatures.push(row.2),
+            }
+            if self.sensor_ids.len() == ROW_GROUP_ROWS {
+                self.flush_row_group()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> crate::Result<()> {
+        if self.sensor_ids.is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        for index in 0.. {
+            let Some(mut column) = row_group.next_column()? else {
+                break;
+            };
+            match index {
+                0 => column
+                    .typed::<Int64Type>()
+                    .write_batch(&self.created, None, None)?,
+                1 => column
+                    .typed::<Int32Type>()
+                    .write_batch(&self.sensor_ids, None, None)?,
+                _ if self.schema.numeric_scale.is_some() => column
+                    .typed::<Int64Type>()
+                    .write_batch(&self.unscaled, None, None)?,
+                _ => column
+                    .typed::<DoubleType>()
+                    .write_batch(&self.temperatures, None, None)?,
+            };
+            column.close()?;
+        }
+        row_group.close()?;
+        self.created.clear();
+        self.sensor_ids.clear();
+        self.temperatures.clear();
+        self.unscaled.clear();
+        Ok(())
+    }
+
+    /// Bytes written so far; the rows of the current row group aren't yet.
+    pub fn bytes_written(&self) -> usize {
+        self.writer.bytes_written()
+    }
+
+    /// Writes the last row group and the footer and returns the writer.
+    pub fn finish(mut self) -> crate::Result<W> {
+        self.flush_row_group()?;
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+/// The index and descriptor of the leaf column `name` of `path`.
+fn column(
+    reader: &SerializedFileReader<File>,
+    path: &Path,
+    name: &str,
+) -> crate::Result<(usize, ColumnDescPtr)> {
+    let columns = reader.metadata().file_metadata().schema_descr().columns();
+    columns
+        .iter()
+        .position(|column| column.name() == name)
+        .map(|index| (index, columns[index].clone()))
+        .ok_or_else(|| FastGenError::Encoding(format!("{} has no {} column", path.display(), name)))
+}
+
+fn unreadable(path: &Path, column: &ColumnDescPtr, expected: &str) -> FastGenError {
+    FastGenError::Encoding(format!(
+        "{}: column {} is {} {:?}, expected {}",
+        path.display(),
+        column.name(),
+        column.physical_type(),
+        column.logical_type(),
+        expected
+    ))
+}
+
+/// Every value of the column at `index` in `row_group`, failing on a NULL.
+fn read_column<T: DataType>(
+    row_group: &dyn RowGroupReader,
+    path: &Path,
+    index: usize,
+) -> crate::Result<Vec<T::T>> {
+    let rows = row_group.metadata().num_rows() as usize;
+    let descriptor = row_group.metadata().column(index).column_descr();
+    let mut reader = get_typed_column_reader::<T>(row_group.get_column_reader(index)?);
+    let mut values = Vec::with_capacity(rows);
+    let mut levels = Vec::with_capacity(rows);
+    let nullable = descriptor.max_def_level() > 0;
+    reader.read_records(rows, nullable.then_some(&mut levels), None, &mut values)?;
+    if values.len() != rows {
+        return Err(FastGenError::Encoding(format!(
+            "{}: unexpected NULL in column {}",
+            path.display(),
+            descriptor.name()
+        )));
+    }
+    Ok(values)
+}
+
+/// Hands every row of the Parquet file at `path` to `each`, its columns
+/// found by name, whatever their order and alongside any others. `created`
+/// is an INT64 of microseconds since the epoch, or a timestamp of any unit,
+/// with the extremes of an INT64 infinite as `ParquetWriter` writes them;
+/// `temperature` a DOUBLE or a DECIMAL.
+pub fn read_rows(
+    path: &Path,
+    schema: &SchemaConfig,
+    mut each: impl FnMut(Row),
+) -> crate::Result<()> {
+    let file = File::open(path)
+        .map_err(|e| FastGenError::InvalidConfig(format!("opening {}: {}", path.display(), e)))?;
+    let reader = SerializedFileReader::new(file)?;
+    let (created_index, created) = column(&reader, path, "created")?;
+    // The microseconds in a unit, 0 for nanoseconds.
+    let micros_per_unit = match (created.physical_type(), created.logical_type()) {
+        (PhysicalType::INT64, None) => 1,
+        (PhysicalType::INT64, Some(LogicalType::Timestamp { unit, .. })) => match unit {
+            TimeUnit::MILLIS(_) => 1000,
+            TimeUnit::MICROS(_) => 1,
+            TimeUnit::NANOS(_) => 0,
+        },
+        _ => return Err(unreadable(path, &created, "INT64 or TIMESTAMP")),
+    };
+    let (sensor_id_index, sensor_id) = column(&reader, path, "sensor_id")?;
+    if sensor_id.physical_type() != PhysicalType::INT32 {
+        return Err(unreadable(path, &sensor_id, "INT32"));
+    }
+    let (temperature_index, temperature) = column(&reader, path, "temperature")?;
+    // The scale of a decimal, None for a DOUBLE.
+    let scale = match (temperature.physical_type(), temperature.logical_type()) {
+        (PhysicalType::DOUBLE, None) => None,
+        (PhysicalType::INT32 | PhysicalType::INT64, Some(LogicalType::Decimal { scale, .. })) => {
+            Some(scale)
+        }
+        _ => {
+            return Err(unreadable(
+                path,
+                &temperature,
+                "DOUBLE or INT32/INT64 DECIMAL",
+            ))
+        }
+    };
+
+    for group in 0..reader.num_row_groups() {
+        let row_group = reader.get_row_group(group)?;
+        let created = read_column::<Int64Type>(&*row_group, path, created_index)?;
+        let sensor_ids = read_column::<Int32Type>(&*row_group, path, sensor_id_index)?;
+        let temperatures = match scale {
+            None => read_column::<DoubleType>(&*row_group, path, temperature_index)?,
+            Some(scale) if temperature.physical_type() == PhysicalType::INT32 => {
+                read_column::<Int32Type>(&*row_group, path, temperature_index)?
+                    .into_iter()
+                    .map(|unscaled| unscaled as f64 / 10f64.powi(scale))
+                    .collect()
+            }
+            Some(scale) => read_column::<Int64Type>(&*row_group, path, temperature_index)?
+                .into_iter()
+                .map(|unscaled| unscaled as f64 / 10f64.powi(scale))
+                .collect(),
+        };
+        for ((created, sensor_id), temperature) in
+            created.into_iter().zip(sensor_ids).zip(temperatures)
+        {
+            each((
+                timestamp(created, micros_per_unit, schema, path)?,
+                sensor_id,
+                temperature,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `value` units since the epoch as a row's timestamp, the extremes of an
+/// INT64 infinite.
+fn timestamp(
+    value: i64,
+    micros_per_unit: i64,
+    schema: &SchemaConfig,
+    path: &Path,
+) -> crate::Result<Created> {
+    let infinite = match value {
+        i64::MAX => Some(Timestamp::PosInfinity),
+        i64::MIN => Some(Timestamp::NegInfinity),
+        _ => None,
+    };
+    let datetime = match micros_per_unit {
+        0 => Some(DateTime::from_timestamp_nanos(value)),
+        _ => value
+            .checked_mul(micros_per_unit)
+            .and_then(DateTime::from_timestamp_micros),
+    };
+    match (infinite, datetime) {
+        (Some(_), _) if schema.time_column == TimeColumn::Bigint => {
+            Err(FastGenError::Encoding(format!(
+                "{}: a bigint time column cannot store infinite timestamps",
+                path.display()
+            )))
+        }
+        (Some(infinite), _) => Ok(infinite),
+        (None, Some(datetime)) => Ok(Timestamp::Value(datetime)),
+        (None, None) => Err(FastGenError::Encoding(format!(
+            "{}: created {} is out of range",
+            path.display(),
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate_data, GeneratorConfig, BASE_TEMP};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn written_files_read_back_to_the_rows_written() {
+        for time_column in [
+            TimeColumn::Timestamptz,
+            TimeColumn::Timestamp,
+            TimeColumn::Bigint,
+        ] {
+            for numeric_scale in [None, Some(2)] {
+                let schema = SchemaConfig {
+                    time_column,
+                    numeric_scale,
+                };
+                let mut rows: Vec<Row> = generate_data(&GeneratorConfig::default(), BASE_TEMP, 2)
+                    .flat_map(|(batch_data, _)| batch_data)
+                    .collect();
+                let before_2000 =
+                    Timestamp::Value(Utc.with_ymd_and_hms(1969, 7, 20, 20, 17, 0).unwrap());
+                rows.push((before_2000, -1, -12.5));
+                if time_column != TimeColumn::Bigint {
+                    rows.push((Timestamp::NegInfinity, 2, 0.0));
+                    rows.push((Timestamp::PosInfinity, 3, 0.0));
+                }
+                if numeric_scale.is_none() {
+                    rows.push((before_2000, 4, f64::INFINITY));
+                }
+
+                let path = std::env::temp_dir().join(format!(
+                    "fast_generation-{}-{:?}-{:?}.parquet",
+                    std::process::id(),
+                    time_column,
+                    numeric_scale
+                ));
+                let mut writer =
+                    ParquetWriter::new(File::create(&path).unwrap(), &schema, "readings", None)
+                        .unwrap();
+                writer.write_rows(&rows).unwrap();
+                writer.finish().unwrap();
+                let mut read = Vec::new();
+                let result = read_rows(&path, &schema, |row| read.push(row));
+                std::fs::remove_file(&path).unwrap();
+                result.unwrap();
+
+                assert_eq!(read.len(), rows.len(), "{:?}", schema);
+                let scaled = |temperature: f64| match numeric_scale {
+                    Some(scale) => (temperature * 10f64.powi(scale as i32)).round(),
+                    None => temperature,
+                };
+                for (expected, actual) in rows.iter().zip(&read) {
+                    assert!(
+                        expected.0 == actual.0
+                            && expected.1 == actual.1
+                            && scaled(expected.2) == scaled(actual.2),
+                        "{:?}: wrote {:?}, read {:?}",
+                        schema,
+                        expected,
+                        actual
+                    );
+                }
+            }
+        }
+    }
+}
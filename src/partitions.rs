@@ -0,0 +1,209 @@
+//! The `partitions` command: loads the same data into a scratch table
+//! partitioned by `created` at each granularity in turn, natively or as a
+//! TimescaleDB hypertable, and reports the throughput and size of each.
+
+use crate::cli::PartitionsArgs;
+use crate::units;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Utc};
+use clap::ValueEnum;
+use fast_generation::generator::Timestamp;
+use fast_generation::schema::TimeColumn;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+
+/// More partitions than this are surely a mistake in the interval or
+/// --batch-interval-ms.
+const MAX_PARTITIONS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Granularity {
+    Hour,
+    Day,
+    /// Starting on Mondays
+    Week,
+}
+
+impl Granularity {
+    fn name(self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+        }
+    }
+
+    fn length(self) -> Duration {
+        match self {
+            Granularity::Hour => Duration::hours(1),
+            Granularity::Day => Duration::days(1),
+            Granularity::Week => Duration::weeks(1),
+        }
+    }
+
+    /// Start of the partition `time` falls into.
+    fn floor(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let day = time.duration_trunc(Duration::days(1)).unwrap();
+        match self {
+            Granularity::Hour => time.duration_trunc(Duration::hours(1)).unwrap(),
+            Granularity::Day => day,
+            Granularity::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+        }
+    }
+}
+
+/// The SQL for a scratch table partitioned by `granularity` across every
+/// timestamp the generator produces, plus a default partition for infinite
+/// ones. Returns the SQL and the number of partitions.
+fn native(
+    scratch: &str,
+    table_name: &str,
+    time_column: TimeColumn,
+    (first, last): (DateTime<Utc>, DateTime<Utc>),
+    granularity: Granularity,
+) -> anyhow::Result<(String, usize)> {
+    let mut sql = format!(
+        "DROP TABLE IF EXISTS {scratch};
+         CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS) PARTITION BY RANGE (created);"
+    );
+    let mut from = granularity.floor(first);
+    let mut partitions = 0;
+    while from <= last {
+        partitions += 1;
+        if partitions > MAX_PARTITIONS {
+            anyhow::bail!(
+                "loading would need over {} {} partitions; pick a coarser interval or fewer, closer batches",
+                MAX_PARTITIONS,
+                granularity.name()
+            );
+        }
+        let to = from + granularity.length();
+        sql += &format!(
+            "CREATE TABLE {scratch}_p{partitions} PARTITION OF {scratch} FOR VALUES FROM ({}) TO ({});",
+            time_column.literal(Timestamp::Value(from)),
+            time_column.literal(Timestamp::Value(to))
+        );
+        from = to;
+    }
+    sql += &format!("CREATE TABLE {scratch}_default PARTITION OF {scratch} DEFAULT;");
+    Ok((sql, partitions + 1))
+}
+
+/// The SQL for a scratch hypertable with chunks of `granularity`, which
+/// TimescaleDB creates as rows arrive.
+fn hypertable(
+    scratch: &str,
+    table_name: &str,
+    time_column: TimeColumn,
+    granularity: Granularity,
+) -> String {
+    let interval = match time_column {
+        TimeColumn::Bigint => granularity.length().num_microseconds().unwrap().to_string(),
+        _ => format!("INTERVAL '1 {}'", granularity.name()),
+    };
+    format!(
+        "DROP TABLE IF EXISTS {scratch};
+         CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS);
+         SELECT create_hypertable('{scratch}', 'created', chunk_time_interval => {interval});"
+    )
+}
+
+fn print_sweep(results: &[(Granularity, usize, StrategyResult)]) {
+    println!();
+    println!(
+        "{:<8} {:>10} {:>10} {:>14} {:>12} {:>12} {:>12}",
+        "interval", "partitions", "rows/s", "bytes/s", "total", "index", "WAL"
+    );
+    for (granularity, partitions, result) in results {
+        if let Some(e) = &result.error {
+            println!("{:<8} {:>10} failed: {}", granularity.name(), partitions, e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        println!(
+            "{:<8} {:>10} {:>10.0} {:>14} {:>12} {:>12} {:>12}",
+            granularity.name(),
+            partitions,
+            result.rows_per_second(),
+            units::rate(size / result.elapsed.as_secs_f64()),
+            units::bytes(result.after.total as f64),
+            units::bytes(result.after.indexes as f64),
+            units::bytes(result.wal_bytes as f64)
+        );
+    }
+}
+
+pub fn partitions(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &PartitionsArgs,
+) -> anyhow::Result<()> {
+    generator.validate(batch_count)?;
+    let scratch = &args.scratch_table;
+    // Every row of a batch shares its timestamp; fix the start so that it
+    // doesn't move between the sweep's loads.
+    let generator = GeneratorConfig {
+        start: Some(generator.start_time()),
+        ..*generator
+    };
+    let interval = Duration::milliseconds(generator.batch_interval_ms);
+    let (first, last) = (
+        generator.start_time() + interval,
+        generator.start_time() + interval * batch_count as i32,
+    );
+    let span = (first.min(last), first.max(last));
+    let mut client = tls::connect(config)?;
+
+    let mut results = Vec::new();
+    let outcome = args
+        .intervals
+        .iter()
+        .try_for_each(|&granularity| -> anyhow::Result<()> {
+            let partitions = if args.hypertable {
+                client.batch_execute(&hypertable(
+                    scratch,
+                    table_name,
+                    schema.time_column,
+                    granularity,
+                ))?;
+                None
+            } else {
+                let (sql, partitions) =
+                    native(scratch, table_name, schema.time_column, span, granularity)?;
+                client.batch_execute(&sql)?;
+                Some(partitions)
+            };
+            println!("Loading into {} partitions", granularity.name());
+            let mut loaded = BenchmarkRunner::new(config.clone())
+                .with_generator(generator)
+                .with_schema(*schema)
+                .with_config(RunConfig {
+                    strategies: vec![args.strategy.clone()],
+                    table_name: scratch.clone(),
+                    batch_count,
+                    workers: args.workers,
+                    ..RunConfig::default()
+                })
+                .run()?;
+            // Chunks only exist once loaded into.
+            let partitions = match partitions {
+                Some(partitions) => partitions,
+                None => client
+                    .query_one(
+                        "SELECT count(*) FROM pg_inherits WHERE inhparent = $1::text::regclass",
+                        &[scratch],
+                    )?
+                    .get::<_, i64>(0) as usize,
+            };
+            results.push((granularity, partitions, loaded.remove(0)));
+            Ok(())
+        });
+    client.batch_execute(&format!("DROP TABLE IF EXISTS {}", scratch))?;
+    outcome?;
+
+    print_sweep(&results);
+    Ok(())
+}
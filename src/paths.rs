@@ -0,0 +1,180 @@
+//! The `paths` command: runs the same loads into one cluster through
+//! each connection path given, e.g. straight to the primary, through
+//! pgbouncer and through HAProxy or pgcat, and reports what every hop adds
+//! to connecting, to a round trip and to each strategy's throughput.
+
+use crate::cli::PathsArgs;
+use fast_generation::load::BatchLatency;
+use fast_generation::tls;
+use fast_generation::{
+    BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult, STRATEGIES,
+};
+use postgres::Config;
+use std::time::{Duration, Instant};
+
+/// Connections opened, and `SELECT 1` round trips made, to time each path.
+const CONNECTS: usize = 10;
+const ROUND_TRIPS: usize = 200;
+
+struct Hop {
+    connect: Duration,
+    round_trip: Duration,
+}
+
+/// Median time to connect through `config` and of a round trip once
+/// connected.
+fn time_hop(config: &Config) -> anyhow::Result<Hop> {
+    let mut connects = Vec::with_capacity(CONNECTS);
+    for _ in 0..CONNECTS {
+        let started = Instant::now();
+        let client = tls::connect(config)?;
+        connects.push(started.elapsed());
+        drop(client);
+    }
+    let mut client = tls::connect(config)?;
+    let mut round_trips = Vec::with_capacity(ROUND_TRIPS);
+    for _ in 0..ROUND_TRIPS {
+        let started = Instant::now();
+        client.simple_query("SELECT 1")?;
+        round_trips.push(started.elapsed());
+    }
+    Ok(Hop {
+        connect: BatchLatency::from_samples(connects).unwrap().p50,
+        round_trip: BatchLatency::from_samples(round_trips).unwrap().p50,
+    })
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn print_hops(paths: &[(String, String)], hops: &[Hop]) {
+    println!();
+    println!(
+        "{:<16} {:>11} {:>14} {:>16}",
+        "path", "connect ms", "round trip ms", "added per trip"
+    );
+    for ((name, _), hop) in paths.iter().zip(hops) {
+        println!(
+            "{:<16} {:>11.2} {:>14.3} {:>16.3}",
+            name,
+            milliseconds(hop.connect),
+            milliseconds(hop.round_trip),
+            milliseconds(hop.round_trip) - milliseconds(hops[0].round_trip)
+        );
+    }
+}
+
+fn print_loads(paths: &[(String, String)], results: &[Vec<StrategyResult>]) {
+    let first = &paths[0].0;
+    println!();
+    println!(
+        "{:<12} {:<16} {:>10} {:>10} {:>10} {:>12}",
+        "strategy",
+        "path",
+        "rows/s",
+        "p50 ms",
+        "p99 ms",
+        format!("vs {}", first)
+    );
+    for by_path in results {
+        let reference = Some(&by_path[0])
+            .filter(|result| result.error.is_none())
+            .map(StrategyResult::rows_per_second);
+        for ((name, _), result) in paths.iter().zip(by_path) {
+            if let Some(e) = &result.error {
+                println!("{:<12} {:<16} failed: {}", result.strategy, name, e);
+                continue;
+            }
+            let speed = result.rows_per_second();
+            let (p50, p99) = result.latency.map_or((0.0, 0.0), |latency| {
+                (milliseconds(latency.p50), milliseconds(latency.p99))
+            });
+            println!(
+                "{:<12} {:<16} {:>10.0} {:>10.1} {:>10.1} {:>12}",
+                result.strategy,
+                name,
+                speed,
+                p50,
+                p99,
+                reference.map_or("-".to_string(), |reference| format!(
+                    "{:+.1}%",
+                    (speed / reference - 1.0) * 100.0
+                ))
+            );
+        }
+    }
+}
+
+pub fn paths(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &PathsArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let configs: Vec<Config> = args
+        .paths
+        .iter()
+        .map(|(name, dsn)| {
+            dsn.parse()
+                .map_err(|e| anyhow::anyhow!("--path {}: {}", name, e))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let strategies: Vec<String> = if args.strategies.is_empty() {
+        STRATEGIES
+            .iter()
+            .map(|strategy| strategy.name.to_string())
+            .collect()
+    } else {
+        args.strategies.clone()
+    };
+
+    let hops = args
+        .paths
+        .iter()
+        .zip(&configs)
+        .map(|((name, _), config)| {
+            println!("Timing connections and round trips through {}", name);
+            time_hop(config).map_err(|e| anyhow::anyhow!("--path {}: {}", name, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // All paths reach the same cluster, so the first one sets up for all.
+    let mut client = tls::connect(&configs[0])?;
+    let mut results = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        client.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {scratch};
+             CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS)"
+        ))?;
+        for strategy in &strategies {
+            let mut by_path = Vec::new();
+            for ((name, _), config) in args.paths.iter().zip(&configs) {
+                client.batch_execute(&format!("TRUNCATE {}", scratch))?;
+                println!("Loading with {} through {}", strategy, name);
+                let mut loaded = BenchmarkRunner::new(config.clone())
+                    .with_generator(*generator)
+                    .with_schema(*schema)
+                    .with_config(RunConfig {
+                        strategies: vec![strategy.clone()],
+                        table_name: scratch.clone(),
+                        batch_count,
+                        workers: args.workers,
+                        ..RunConfig::default()
+                    })
+                    .run()?;
+                by_path.push(loaded.remove(0));
+            }
+            results.push(by_path);
+        }
+        Ok(())
+    }();
+    client.batch_execute(&format!("DROP TABLE IF EXISTS {}", scratch))?;
+    outcome?;
+
+    print_hops(&args.paths, &hops);
+    print_loads(&args.paths, &results);
+    Ok(())
+}
@@ -0,0 +1,706 @@
+use crate::cache::EncodeCache;
+use crate::generator::{Created, Row, Timestamp};
+use crate::schema::{SchemaConfig, TimeColumn};
+use crate::FastGenError;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+#[cfg(feature = "postgres")]
+use postgres::Client;
+#[cfg(feature = "postgres")]
+use postgres_types::{Oid, Type};
+use std::io::{Read, Write};
+
+static POSTGRES_EPOCH: Lazy<DateTime<Utc>> = Lazy::new(|| {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+});
+
+pub(crate) const SIGNATURE: &[u8; 11] = b"PGCOPY\n\xff\r\n\0";
+/// Header flag bit announcing an OID field in every tuple.
+pub(crate) const FLAG_OIDS: i32 = 1 << 16;
+
+const NUMERIC_POS: u16 = 0x0000;
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+const NUMERIC_PINF: u16 = 0xD000;
+const NUMERIC_NINF: u16 = 0xF000;
+
+/// NaN, and on Postgres 14+ also ±Infinity, are sent as a header without
+/// digits carrying a special sign value.
+fn special_numeric_to_postgres_binary(value: f64) -> Vec<u8> {
+    let sign = if value.is_nan() {
+        NUMERIC_NAN
+    } else if value.is_sign_positive() {
+        NUMERIC_PINF
+    } else {
+        NUMERIC_NINF
+    };
+
+    let mut buffer = Vec::with_capacity(8);
+    buffer.write_i16::<BigEndian>(0).unwrap();
+    buffer.write_i16::<BigEndian>(0).unwrap();
+    buffer.write_u16::<BigEndian>(sign).unwrap();
+    buffer.write_i16::<BigEndian>(0).unwrap();
+    buffer
+}
+
+/// Powers of ten up to the largest scale the arithmetic encoder handles.
+const POW10: [u64; 16] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+    10_000_000_000,
+    100_000_000_000,
+    1_000_000_000_000,
+    10_000_000_000_000,
+    100_000_000_000_000,
+    1_000_000_000_000_000,
+];
+/// Scaled values stay below this, so that neighbouring f64s are less than
+/// one unit apart and the scaled integer is unambiguous.
+const MAX_SCALED: f64 = (1u64 << 50) as f64;
+/// Base-10000 digits of a scaled value below `MAX_SCALED` with a scale of at
+/// most 15: four for the integer part and four for the fraction.
+const MAX_FIXED_DIGITS: usize = 8;
+
+/// The smallest scale `s` and integer `n` with `n / 10^s` parsing back to
+/// exactly `abs_value`, which is the shortest decimal `Display` would print.
+/// Division of two exact f64s rounds correctly, so comparing against it
+/// matches what parsing the decimal text would give.
+fn fixed_scale(abs_value: f64) -> Option<(u64, u32)> {
+    for (scale, &power) in POW10.iter().enumerate() {
+        let power = power as f64;
+        let scaled = (abs_value * power).round();
+        if scaled >= MAX_SCALED {
+            return None;
+        }
+        if scaled / power == abs_value {
+            return Some((scaled as u64, scale as u32));
+        }
+    }
+    None
+}
+
+pub fn numeric_to_postgres_binary(value: f64) -> Vec<u8> {
+    if !value.is_finite() {
+        return special_numeric_to_postgres_binary(value);
+    }
+
+    let abs_value = value.abs();
+    let sign = if value.is_sign_negative() {
+        NUMERIC_NEG
+    } else {
+        NUMERIC_POS
+    };
+    match fixed_scale(abs_value) {
+        Some((scaled, scale)) => pack_numeric(scaled, scale, sign),
+        None => decimal_text_to_postgres_binary(abs_value, sign),
+    }
+}
+
+/// Encodes `value` rounded to `scale` decimal places, the way a
+/// numeric(p, scale) column stores it, skipping the search for the shortest
+/// decimal. Values too large to scale exactly take the general path.
+pub fn fixed_numeric_to_postgres_binary(value: f64, scale: u32) -> Vec<u8> {
+    let scaled = (value.abs() * POW10[scale as usize] as f64).round();
+    if !value.is_finite() || scaled >= MAX_SCALED {
+        return numeric_to_postgres_binary(value);
+    }
+    let sign = if value.is_sign_negative() {
+        NUMERIC_NEG
+    } else {
+        NUMERIC_POS
+    };
+    pack_numeric(scaled as u64, scale, sign)
+}
+
+/// Encodes `scaled / 10^scale` in the same layout as the text encoder: the
+/// integer part in whole groups of four digits, at least one, then the
+/// fraction left-aligned in groups.
+fn pack_numeric(scaled: u64, scale: u32, sign: u16) -> Vec<u8> {
+    let mut digits = [0i16; MAX_FIXED_DIGITS];
+    let mut ndigits = 0;
+    let mut integer = scaled / POW10[scale as usize];
+    let fraction = scaled % POW10[scale as usize];
+
+    loop {
+        digits[ndigits] = (integer % 10_000) as i16;
+        ndigits += 1;
+        integer /= 10_000;
+        if integer == 0 {
+            break;
+        }
+    }
+    digits[..ndigits].reverse();
+    let weight = ndigits as i16 - 1;
+
+    let fraction_digits = scale.div_ceil(4) as usize;
+    let mut fraction = fraction * POW10[fraction_digits * 4 - scale as usize];
+    for i in (0..fraction_digits).rev() {
+        digits[ndigits + i] = (fraction % 10_000) as i16;
+        fraction /= 10_000;
+    }
+    ndigits += fraction_digits;
+
+    let mut buffer = Vec::with_capacity(8 + 2 * ndigits);
+    buffer.write_i16::<BigEndian>(ndigits as i16).unwrap();
+    buffer.write_i16::<BigEndian>(weight).unwrap();
+    buffer.write_u16::<BigEndian>(sign).unwrap();
+    buffer.write_i16::<BigEndian>(scale as i16).unwrap();
+    for &digit in &digits[..ndigits] {
+        buffer.write_i16::<BigEndian>(digit).unwrap();
+    }
+
+    buffer
+}
+
+/// Encodes a temperature with the schema's fixed scale, if it declares one.
+pub fn temperature_to_postgres_binary(schema: &SchemaConfig, value: f64) -> Vec<u8> {
+    match schema.numeric_scale {
+        Some(scale) => fixed_numeric_to_postgres_binary(value, scale),
+        None => numeric_to_postgres_binary(value),
+    }
+}
+
+/// Encodes `abs_value` from its shortest decimal text, for values outside
+/// the fixed-scale range.
+fn decimal_text_to_postgres_binary(abs_value: f64, sign: u16) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let parts: Vec<String> = abs_value.to_string().split('.').map(String::from).collect();
+
+    let integer = &parts[0];
+    let fraction = if parts.len() > 1 {
+        parts[1].clone()
+    } else {
+        String::new()
+    };
+
+    let weight = (integer.len() as i16 - 1) / 4;
+    let padding = if !integer.len().is_multiple_of(4) {
+        4 - (integer.len() % 4)
+    } else {
+        0
+    };
+    let padded_number = format!(
+        "{:0>width$}{}",
+        integer,
+        fraction,
+        width = integer.len() + padding
+    );
+
+    let digits: Vec<i16> = padded_number
+        .as_bytes()
+        .chunks(4)
+        .map(|c| {
+            let mut value = 0;
+            for (i, &digit) in c.iter().enumerate() {
+                value += (digit - b'0') as i16 * 10i16.pow(3 - i as u32);
+            }
+            value
+        })
+        .collect();
+    let ndigits = digits.len() as i16;
+    let dscale = fraction.len() as i16;
+
+    buffer.write_i16::<BigEndian>(ndigits).unwrap();
+    buffer.write_i16::<BigEndian>(weight).unwrap();
+    buffer.write_u16::<BigEndian>(sign).unwrap();
+    buffer.write_i16::<BigEndian>(dscale).unwrap();
+
+    for digit in digits {
+        buffer.write_i16::<BigEndian>(digit).unwrap();
+    }
+
+    buffer
+}
+
+/// Earliest timestamp Postgres accepts, 4714-11-24 00:00:00 BC, in
+/// microseconds relative to `POSTGRES_EPOCH`.
+const MIN_TIMESTAMP: i64 = -211_813_488_000_000_000;
+/// First timestamp past the supported range, 294277-01-01 00:00:00.
+const END_TIMESTAMP: i64 = 9_223_371_331_200_000_000;
+
+/// Whether Postgres accepts a timestamp `micros` from `POSTGRES_EPOCH`. The
+/// upper bound is past what chrono represents, so only checked here.
+fn in_postgres_range(micros: i64) -> bool {
+    (MIN_TIMESTAMP..END_TIMESTAMP).contains(&micros)
+}
+
+/// Microseconds since `POSTGRES_EPOCH`, negative for earlier dates. Values
+/// chrono can represent but Postgres can't (roughly before 4713 BC or after
+/// 294276 AD) are rejected here instead of by the server mid-COPY.
+pub fn datetime_to_postgres_binary(datetime: DateTime<Utc>) -> crate::Result<i64> {
+    // Both operands are within ±2^63 for every date chrono supports.
+    let micros = datetime.timestamp_micros() - POSTGRES_EPOCH.timestamp_micros();
+    if !in_postgres_range(micros) {
+        return Err(FastGenError::Encoding(format!(
+            "timestamp {} is outside the Postgres range",
+            datetime
+        )));
+    }
+    Ok(micros)
+}
+
+/// Column types in the order `write_row` encodes them.
+#[cfg(feature = "postgres")]
+pub fn field_types(schema: &SchemaConfig) -> [Type; 3] {
+    [schema.time_column.pg_type(), Type::INT4, Type::NUMERIC]
+}
+
+#[cfg(feature = "postgres")]
+fn type_name(oid: Oid) -> String {
+    Type::from_oid(oid)
+        .map(|ty| ty.name().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Checks that the table's columns line up with `field_types`. The server
+/// only validates binary COPY data once it reaches a mismatching row, with an
+/// error that doesn't name the column, so this runs before any data is sent.
+/// Generated columns are skipped, as no strategy writes them.
+#[cfg(feature = "postgres")]
+pub fn verify_table(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+) -> crate::Result<()> {
+    let field_types = field_types(schema);
+    let rows = client.query(
+        "SELECT attname::text AS name, atttypid AS oid
+           FROM pg_attribute
+          WHERE attrelid = $1::text::regclass AND attnum > 0 AND NOT attisdropped
+            AND attgenerated = ''
+          ORDER BY attnum",
+        &[&table_name],
+    )?;
+
+    if rows.len() != field_types.len() {
+        return Err(FastGenError::SchemaMismatch(format!(
+            "{} has {} columns, binary COPY encodes {}",
+            table_name,
+            rows.len(),
+            field_types.len()
+        )));
+    }
+
+    for (position, (row, expected)) in rows.iter().zip(field_types.iter()).enumerate() {
+        let name: String = row.get("name");
+        let oid: Oid = row.get("oid");
+        if oid != expected.oid() {
+            return Err(FastGenError::SchemaMismatch(format!(
+                "column {} ({}) of {} is {} (oid {}), binary COPY encodes {} (oid {})",
+                position + 1,
+                name,
+                table_name,
+                type_name(oid),
+                oid,
+                expected.name(),
+                expected.oid()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_header<W: Write>(buffer: &mut W) -> std::io::Result<()> {
+    buffer.write_all(SIGNATURE)?;
+    buffer.write_i32::<BigEndian>(0)?;
+    buffer.write_i32::<BigEndian>(0)
+}
+
+/// Both timestamp types are microseconds since the Postgres epoch on the
+/// wire, with `i64::MAX` and `i64::MIN` standing for ±infinity; `Bigint`
+/// stores microseconds since the Unix epoch instead and has no infinities.
+pub fn time_to_postgres_binary(time_column: TimeColumn, created: Created) -> crate::Result<i64> {
+    match (time_column, created) {
+        (TimeColumn::Bigint, Timestamp::Value(datetime)) => Ok(datetime.timestamp_micros()),
+        (TimeColumn::Bigint, _) => Err(FastGenError::Encoding(
+            "a bigint time column cannot store infinite timestamps".to_string(),
+        )),
+        (_, Timestamp::Value(datetime)) => datetime_to_postgres_binary(datetime),
+        (_, Timestamp::PosInfinity) => Ok(i64::MAX),
+        (_, Timestamp::NegInfinity) => Ok(i64::MIN),
+    }
+}
+
+/// Encodes rows, keeping the encoded `created` and `sensor_id` fields of
+/// every distinct value seen, since a batch repeats one timestamp for all of
+/// its rows and cycles through a few dozen sensors.
+pub struct RowEncoder<'a> {
+    schema: &'a SchemaConfig,
+    created: EncodeCache<Created, [u8; 12]>,
+    sensor_ids: EncodeCache<i32, [u8; 8]>,
+}
+
+impl<'a> RowEncoder<'a> {
+    pub fn new(schema: &'a SchemaConfig) -> Self {
+        RowEncoder {
+            schema,
+            created: EncodeCache::new(),
+            sensor_ids: EncodeCache::new(),
+        }
+    }
+
+    pub fn write_row<W: Write>(&mut self, buffer: &mut W, row: &Row) -> crate::Result<()> {
+        buffer.write_i16::<BigEndian>(3)?;
+
+        // created
+        let time_column = self.schema.time_column;
+        let created = self.created.get_or_try_insert(row.0, |created| {
+            let mut field = [0; 12];
+            field[..4].copy_from_slice(&8i32.to_be_bytes());
+            field[4..]
+                .copy_from_slice(&time_to_postgres_binary(time_column, created)?.to_be_bytes());
+            Ok::<_, FastGenError>(field)
+        })?;
+        buffer.write_all(created)?;
+
+        // sensor_id
+        let sensor_id = self.sensor_ids.get_or_try_insert(row.1, |sensor_id| {
+            let mut field = [0; 8];
+            field[..4].copy_from_slice(&4i32.to_be_bytes());
+            field[4..].copy_from_slice(&sensor_id.to_be_bytes());
+            Ok::<_, FastGenError>(field)
+        })?;
+        buffer.write_all(sensor_id)?;
+
+        // temperature
+        let numeric_bytes = temperature_to_postgres_binary(self.schema, row.2);
+        buffer.write_i32::<BigEndian>(numeric_bytes.len() as i32)?;
+        buffer.write_all(&numeric_bytes)?;
+        Ok(())
+    }
+}
+
+pub fn write_row<W: Write>(buffer: &mut W, schema: &SchemaConfig, row: &Row) -> crate::Result<()> {
+    RowEncoder::new(schema).write_row(buffer, row)
+}
+
+pub fn write_trailer<W: Write>(buffer: &mut W) -> std::io::Result<()> {
+    buffer.write_i16::<BigEndian>(-1)
+}
+
+/// Encodes a whole batch, header to trailer, into `buffer`, so callers can
+/// reuse one allocation across batches.
+pub fn write_buffer(
+    buffer: &mut Vec<u8>,
+    schema: &SchemaConfig,
+    batch_data: &[Row],
+) -> crate::Result<()> {
+    write_header(buffer)?;
+
+    let mut encoder = RowEncoder::new(schema);
+    for row in batch_data {
+        encoder.write_row(buffer, row)?;
+    }
+
+    write_trailer(buffer)?;
+    Ok(())
+}
+
+pub fn generate_buffer(schema: &SchemaConfig, batch_data: &[Row]) -> crate::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_buffer(&mut buffer, schema, batch_data)?;
+    Ok(buffer)
+}
+
+/// Inverse of `numeric_to_postgres_binary`. The digits are rendered as decimal
+/// text and parsed, so a value the writer produced from `f64::to_string`
+/// comes back as the identical `f64`.
+pub fn numeric_from_postgres_binary(mut bytes: &[u8]) -> crate::Result<f64> {
+    let ndigits = bytes.read_i16::<BigEndian>()? as i32;
+    let weight = bytes.read_i16::<BigEndian>()? as i32;
+    let sign = bytes.read_u16::<BigEndian>()?;
+    let dscale = bytes.read_i16::<BigEndian>()? as usize;
+
+    match sign {
+        NUMERIC_NAN => return Ok(f64::NAN),
+        NUMERIC_PINF => return Ok(f64::INFINITY),
+        NUMERIC_NINF => return Ok(f64::NEG_INFINITY),
+        NUMERIC_POS | NUMERIC_NEG => {}
+        _ => {
+            return Err(FastGenError::Encoding(format!(
+                "invalid numeric sign {:#06x}",
+                sign
+            )))
+        }
+    }
+
+    let digits = (0..ndigits)
+        .map(|_| bytes.read_i16::<BigEndian>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let digit = |i: i32| {
+        if (0..ndigits).contains(&i) {
+            digits[i as usize]
+        } else {
+            0
+        }
+    };
+
+    let mut text = String::new();
+    if sign == NUMERIC_NEG {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for i in 1..=weight {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if dscale > 0 {
+        let groups = dscale.div_ceil(4) as i32;
+        let mut fraction: String = (weight + 1..weight + 1 + groups)
+            .map(|i| format!("{:04}", digit(i)))
+            .collect();
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+
+    text.parse()
+        .map_err(|e| FastGenError::Encoding(format!("numeric {}: {}", text, e)))
+}
+
+/// Inverse of `time_to_postgres_binary`.
+pub fn time_from_postgres_binary(time_column: TimeColumn, micros: i64) -> crate::Result<Created> {
+    let datetime = match (time_column, micros) {
+        (TimeColumn::Bigint, _) => DateTime::from_timestamp_micros(micros),
+        (_, i64::MAX) => return Ok(Timestamp::PosInfinity),
+        (_, i64::MIN) => return Ok(Timestamp::NegInfinity),
+        (_, _) => POSTGRES_EPOCH.checked_add_signed(Duration::microseconds(micros)),
+    };
+    datetime
+        .map(Timestamp::Value)
+        .ok_or_else(|| FastGenError::Encoding(format!("timestamp {} is out of range", micros)))
+}
+
+/// Parses the binary COPY format produced by `generate_buffer`, or by
+/// `COPY ... TO STDOUT WITH BINARY` on a table `verify_table` accepts, back
+/// into rows.
+pub struct PgCopyBinaryReader<R> {
+    reader: R,
+    time_column: TimeColumn,
+    field: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> PgCopyBinaryReader<R> {
+    pub fn new(mut reader: R, schema: &SchemaConfig) -> crate::Result<Self> {
+        let mut signature = [0; SIGNATURE.len()];
+        reader.read_exact(&mut signature)?;
+        if &signature != SIGNATURE {
+            return Err(FastGenError::Encoding(
+                "missing PGCOPY signature".to_string(),
+            ));
+        }
+
+        let flags = reader.read_i32::<BigEndian>()?;
+        if flags & FLAG_OIDS != 0 {
+            return Err(FastGenError::Encoding(
+                "COPY data with OIDs is not supported".to_string(),
+            ));
+        }
+        let extension_length = reader.read_i32::<BigEndian>()?;
+        std::io::copy(
+            &mut (&mut reader).take(extension_length as u64),
+            &mut std::io::sink(),
+        )?;
+
+        Ok(PgCopyBinaryReader {
+            reader,
+            time_column: schema.time_column,
+            field: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// The reader rows are decoded from.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    fn read_field(&mut self, column: &str) -> crate::Result<&[u8]> {
+        let length = self.reader.read_i32::<BigEndian>()?;
+        if length < 0 {
+            return Err(FastGenError::Encoding(format!(
+                "unexpected NULL in column {}",
+                column
+            )));
+        }
+        self.field.resize(length as usize, 0);
+        self.reader.read_exact(&mut self.field)?;
+        Ok(&self.field)
+    }
+
+    /// Returns the next row, or `None` once the trailer has been read.
+    pub fn read_row(&mut self) -> crate::Result<Option<Row>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let field_count = self.reader.read_i16::<BigEndian>()?;
+        if field_count == -1 {
+            self.finished = true;
+            return Ok(None);
+        }
+        if field_count != 3 {
+            return Err(FastGenError::Encoding(format!(
+                "expected 3 fields per tuple, found {}",
+                field_count
+            )));
+        }
+
+        let time_column = self.time_column;
+        let micros = self.read_field("created")?.read_i64::<BigEndian>()?;
+        let created = time_from_postgres_binary(time_column, micros)?;
+        let sensor_id = self.read_field("sensor_id")?.read_i32::<BigEndian>()?;
+        let temperature = numeric_from_postgres_binary(self.read_field("temperature")?)?;
+
+        Ok(Some((created, sensor_id, temperature)))
+    }
+}
+
+impl<R: Read> Iterator for PgCopyBinaryReader<R> {
+    type Item = crate::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.read_row();
+        if row.is_err() {
+            self.finished = true;
+        }
+        row.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate_data, GeneratorConfig, BASE_TEMP};
+    use chrono::TimeZone;
+
+    fn utc(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn timestamps_before_2000_are_negative() {
+        assert_eq!(datetime_to_postgres_binary(utc(2000, 1, 1)).unwrap(), 0);
+        assert_eq!(
+            datetime_to_postgres_binary(utc(1970, 1, 1)).unwrap(),
+            -946_684_800_000_000
+        );
+        assert_eq!(
+            datetime_to_postgres_binary(utc(1999, 12, 31) + Duration::microseconds(86_399_999_999))
+                .unwrap(),
+            -1
+        );
+    }
+
+    #[test]
+    fn bc_years_round_trip() {
+        // 44 BC is year -43 in chrono's proleptic count.
+        let datetime = utc(-43, 3, 15) + Duration::microseconds(1);
+        let micros = datetime_to_postgres_binary(datetime).unwrap();
+        assert_eq!(
+            time_from_postgres_binary(TimeColumn::Timestamp, micros).unwrap(),
+            Timestamp::Value(datetime)
+        );
+    }
+
+    #[test]
+    fn earliest_timestamp_is_accepted_and_one_before_it_rejected() {
+        let earliest = utc(-4713, 11, 24);
+        assert_eq!(
+            datetime_to_postgres_binary(earliest).unwrap(),
+            MIN_TIMESTAMP
+        );
+        assert!(datetime_to_postgres_binary(earliest - Duration::microseconds(1)).is_err());
+    }
+
+    #[test]
+    fn upper_bound_is_exclusive() {
+        assert!(in_postgres_range(END_TIMESTAMP - 1));
+        assert!(!in_postgres_range(END_TIMESTAMP));
+        assert!(in_postgres_range(MIN_TIMESTAMP));
+        assert!(!in_postgres_range(MIN_TIMESTAMP - 1));
+        // chrono ends before Postgres does, so its latest time is accepted.
+        assert!(datetime_to_postgres_binary(DateTime::<Utc>::MAX_UTC).is_ok());
+    }
+
+    /// Generated rows, some of them infinite or NaN, followed by every
+    /// special value, each on rows of their own; no infinite timestamps
+    /// where the column can't hold them.
+    fn rows(time_column: TimeColumn) -> Vec<Row> {
+        let generator = GeneratorConfig {
+            special_rate: match time_column {
+                TimeColumn::Bigint => 0.0,
+                _ => 0.1,
+            },
+            ..GeneratorConfig::default()
+        };
+        let mut rows: Vec<Row> = generate_data(&generator, BASE_TEMP, 2)
+            .flat_map(|(batch_data, _)| batch_data)
+            .collect();
+        let finite = Timestamp::Value(utc(1999, 12, 31));
+        let (past, future) = match time_column {
+            TimeColumn::Bigint => (finite, finite),
+            _ => (Timestamp::NegInfinity, Timestamp::PosInfinity),
+        };
+        rows.extend([
+            (past, 1, f64::NAN),
+            (future, 2, f64::INFINITY),
+            (finite, 3, f64::NEG_INFINITY),
+            (finite, -4, -12.5),
+        ]);
+        rows
+    }
+
+    #[test]
+    fn generated_buffers_decode_to_the_rows_encoded() {
+        for time_column in [
+            TimeColumn::Timestamptz,
+            TimeColumn::Timestamp,
+            TimeColumn::Bigint,
+        ] {
+            for numeric_scale in [None, Some(2), Some(5)] {
+                let schema = SchemaConfig {
+                    time_column,
+                    numeric_scale,
+                };
+                let rows = rows(time_column);
+                let buffer = generate_buffer(&schema, &rows).unwrap();
+                let decoded: Vec<Row> = PgCopyBinaryReader::new(&buffer[..], &schema)
+                    .unwrap()
+                    .collect::<crate::Result<_>>()
+                    .unwrap();
+                assert_eq!(decoded.len(), rows.len(), "{:?}", schema);
+                for (expected, actual) in rows.iter().zip(&decoded) {
+                    let same_temperature = expected.2.to_bits() == actual.2.to_bits()
+                        || (expected.2.is_nan() && actual.2.is_nan());
+                    assert!(
+                        expected.0 == actual.0 && expected.1 == actual.1 && same_temperature,
+                        "{:?}: wrote {:?}, read {:?}",
+                        schema,
+                        expected,
+                        actual
+                    );
+                }
+                assert!(decoded.iter().any(|row| row.2.is_nan()));
+            }
+        }
+    }
+}
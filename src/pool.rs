@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Reusable serialization buffers shared by the workers of one strategy.
+/// Buffers are never freed while the pool lives, so once every worker has
+/// grown one to batch size no further allocations happen, and the memory the
+/// pool holds is also its peak.
+#[derive(Default)]
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    bytes: AtomicUsize,
+    buffers: AtomicUsize,
+    in_use: AtomicUsize,
+    peak_in_use: AtomicUsize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Capacity of all buffers the pool owns.
+    pub bytes: usize,
+    /// Buffers the pool has allocated.
+    pub buffers: usize,
+    /// Most buffers checked out at the same time.
+    pub peak_in_use: usize,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hands out an empty buffer, reusing a returned one when available.
+    pub fn take(&self) -> PooledBuffer<'_> {
+        let buffer = match self.free.lock().unwrap().pop() {
+            Some(buffer) => buffer,
+            None => {
+                self.buffers.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+        };
+        let in_use = self.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_in_use.fetch_max(in_use, Ordering::Relaxed);
+
+        PooledBuffer {
+            capacity: buffer.capacity(),
+            buffer,
+            pool: self,
+        }
+    }
+
+    fn give_back(&self, mut buffer: Vec<u8>, capacity: usize) {
+        self.bytes
+            .fetch_add(buffer.capacity() - capacity, Ordering::Relaxed);
+        self.in_use.fetch_sub(1, Ordering::Relaxed);
+        buffer.clear();
+        self.free.lock().unwrap().push(buffer);
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            buffers: self.buffers.load(Ordering::Relaxed),
+            peak_in_use: self.peak_in_use.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A buffer checked out of a `BufferPool`; it returns itself on drop.
+pub struct PooledBuffer<'a> {
+    buffer: Vec<u8>,
+    /// Capacity when taken, to account for growth while checked out.
+    capacity: usize,
+    pool: &'a BufferPool,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        let buffer = std::mem::take(&mut self.buffer);
+        self.pool.give_back(buffer, self.capacity);
+    }
+}
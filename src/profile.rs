@@ -0,0 +1,362 @@
+//! The `profile` command: samples an existing table with the generator's
+//! columns and fits the generator settings to it, so that generated data
+//! resembles it without copying any of it: the span and cadence of the
+//! timestamps, the batch sizes, the rate of special values and the column
+//! types. What the generator can't vary, the sensors and the spread of
+//! temperatures, is printed next to what it generates instead.
+
+use crate::cli::ProfileArgs;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use clap::ValueEnum;
+use fast_generation::generator::{BASE_TEMP, MAX_SENSORS};
+use fast_generation::schema::{SchemaConfig, TimeColumn, MAX_NUMERIC_SCALE};
+use fast_generation::tls;
+use fast_generation::{pgcopy, GeneratorConfig};
+use postgres::{Client, Config};
+use serde::Serialize;
+use std::fs;
+
+/// Half the width of the uniform spread of generated temperatures.
+const TEMPERATURE_SPREAD: f64 = 5.0;
+
+/// What the fitted settings are written as, the same shape the generator
+/// and schema take in a `serve` run request.
+#[derive(Serialize)]
+struct Profile {
+    generator: GeneratorConfig,
+    schema: SchemaConfig,
+}
+
+/// The layout options matching the table's column types.
+fn detect_schema(client: &mut Client, table: &str) -> anyhow::Result<SchemaConfig> {
+    let rows = client.query(
+        "SELECT attname::text, format_type(atttypid, NULL),
+                CASE WHEN atttypid = 'numeric'::regtype AND atttypmod >= 4
+                     THEN (atttypmod - 4) & 65535 END
+           FROM pg_attribute
+          WHERE attrelid = $1::text::regclass AND attname IN ('created', 'temperature')
+            AND NOT attisdropped",
+        &[&table],
+    )?;
+    let mut schema = SchemaConfig::default();
+    let mut found = false;
+    for row in rows {
+        let (name, data_type, scale): (String, String, Option<i32>) =
+            (row.get(0), row.get(1), row.get(2));
+        if name == "created" {
+            found = true;
+            schema.time_column = [
+                TimeColumn::Timestamptz,
+                TimeColumn::Timestamp,
+                TimeColumn::Bigint,
+            ]
+            .into_iter()
+            .find(|column| column.data_type() == data_type)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}.created is {}, which the generator can't fill",
+                    table,
+                    data_type
+                )
+            })?;
+        } else {
+            schema.numeric_scale = scale
+                .map(|scale| scale as u32)
+                .filter(|&scale| scale <= MAX_NUMERIC_SCALE);
+        }
+    }
+    if !found {
+        anyhow::bail!("{} has no created column", table);
+    }
+    // Anything else about the table the strategies couldn't load into.
+    pgcopy::verify_table(client, &schema, table)?;
+    Ok(schema)
+}
+
+struct Stats {
+    rows: i64,
+    infinite: i64,
+    nan: i64,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    timestamps: i64,
+    sensors: i64,
+    sensor_range: (Option<i32>, Option<i32>),
+    temperature_mean: Option<f64>,
+    temperature_stddev: Option<f64>,
+    temperature_range: (Option<f64>, Option<f64>),
+    batch_sizes: (Option<i64>, Option<i64>),
+}
+
+fn sample(
+    client: &mut Client,
+    table: &str,
+    time_column: TimeColumn,
+    percent: f64,
+    seed: u64,
+) -> anyhow::Result<Stats> {
+    let sample = if percent < 100.0 {
+        format!(
+            "TABLESAMPLE BERNOULLI ({}) REPEATABLE ({})",
+            percent, seed as i64
+        )
+    } else {
+        String::new()
+    };
+    let created = match time_column {
+        TimeColumn::Timestamptz => "created",
+        TimeColumn::Timestamp => "created AT TIME ZONE 'UTC'",
+        TimeColumn::Bigint => "to_timestamp(created / 1000000.0)",
+    };
+    let row = client.query_one(
+        &format!(
+            "WITH sample AS (
+                 SELECT {created} AS created, sensor_id, temperature::float8 AS temperature
+                   FROM {table} {sample}
+             ), batches AS (
+                 SELECT count(*) AS size FROM sample WHERE isfinite(created) GROUP BY created
+             )
+             SELECT count(*),
+                    count(*) FILTER (WHERE NOT isfinite(created)),
+                    count(*) FILTER (WHERE temperature = 'NaN'),
+                    min(created) FILTER (WHERE isfinite(created)),
+                    max(created) FILTER (WHERE isfinite(created)),
+                    count(DISTINCT created) FILTER (WHERE isfinite(created)),
+                    count(DISTINCT sensor_id),
+                    min(sensor_id),
+                    max(sensor_id),
+                    avg(temperature) FILTER (WHERE temperature <> 'NaN'),
+                    stddev_pop(temperature) FILTER (WHERE temperature <> 'NaN'),
+                    min(temperature) FILTER (WHERE temperature <> 'NaN'),
+                    max(temperature) FILTER (WHERE temperature <> 'NaN'),
+                    (SELECT min(size) FROM batches),
+                    (SELECT max(size) FROM batches)
+               FROM sample"
+        ),
+        &[],
+    )?;
+    Ok(Stats {
+        rows: row.get(0),
+        infinite: row.get(1),
+        nan: row.get(2),
+        first: row.get(3),
+        last: row.get(4),
+        timestamps: row.get(5),
+        sensors: row.get(6),
+        sensor_range: (row.get(7), row.get(8)),
+        temperature_mean: row.get(9),
+        temperature_stddev: row.get(10),
+        temperature_range: (row.get(11), row.get(12)),
+        batch_sizes: (row.get(13), row.get(14)),
+    })
+}
+
+/// Rows sharing a timestamp in the table, from those in the sample.
+fn batch_size(sampled: Option<i64>, fraction: f64) -> Option<usize> {
+    sampled.map(|size| ((size as f64 / fraction).round() as usize).max(1))
+}
+
+/// Settings generating about as many rows per timestamp, as often, from
+/// the same start, with special values as often as the table has them.
+fn fit(stats: &Stats, fraction: f64, seed: u64) -> GeneratorConfig {
+    // Each batch shares one timestamp, and the first one comes an interval
+    // after the start.
+    let interval_ms = match (stats.first, stats.last) {
+        (Some(first), Some(last)) if stats.timestamps > 1 => {
+            (last - first).num_milliseconds() / (stats.timestamps - 1)
+        }
+        _ => GeneratorConfig::default().batch_interval_ms,
+    };
+    let (batch_size_min, batch_size_max) = (
+        batch_size(stats.batch_sizes.0, fraction),
+        batch_size(stats.batch_sizes.1, fraction),
+    );
+    GeneratorConfig {
+        seed,
+        start: stats
+            .first
+            .map(|first| first - Duration::milliseconds(interval_ms)),
+        batch_interval_ms: interval_ms,
+        // One rate draws both, independently.
+        special_rate: ((stats.infinite + stats.nan) as f64 / 2.0 / stats.rows as f64 * 1e6).round()
+            / 1e6,
+        batch_size_min,
+        batch_size_max,
+    }
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or("-".to_string(), |value| value.to_string())
+}
+
+fn print_profile(table: &str, stats: &Stats, fraction: f64) {
+    let percent = |count: i64| format!("{:.3}%", count as f64 / stats.rows as f64 * 100.0);
+    let time = |time: Option<DateTime<Utc>>| {
+        or_dash(time.map(|time| time.to_rfc3339_opts(SecondsFormat::Micros, true)))
+    };
+    let float = |value: Option<f64>| or_dash(value.map(|value| format!("{:.2}", value)));
+    let uniform_stddev = 2.0 * TEMPERATURE_SPREAD / 12f64.sqrt();
+
+    println!();
+    println!(
+        "Sampled {} rows of {} ({:.2}%)",
+        stats.rows,
+        table,
+        fraction * 100.0
+    );
+    println!();
+    println!("{:<24} {:>32} {:>32}", "", "table", "generated");
+    for (name, table, generated) in [
+        (
+            "first created",
+            time(stats.first),
+            "--start + interval".to_string(),
+        ),
+        ("last created", time(stats.last), "-".to_string()),
+        (
+            "distinct created",
+            stats.timestamps.to_string(),
+            "one per batch".to_string(),
+        ),
+        (
+            "rows per created",
+            format!(
+                "{}-{}",
+                or_dash(batch_size(stats.batch_sizes.0, fraction)),
+                or_dash(batch_size(stats.batch_sizes.1, fraction))
+            ),
+            "batch size".to_string(),
+        ),
+        (
+            "infinite created",
+            percent(stats.infinite),
+            "--special-rate".to_string(),
+        ),
+        (
+            "sensors",
+            format!(
+                "{} in {}-{}",
+                stats.sensors,
+                or_dash(stats.sensor_range.0),
+                or_dash(stats.sensor_range.1)
+            ),
+            format!("{} in 1-{}", MAX_SENSORS, MAX_SENSORS),
+        ),
+        (
+            "temperature mean",
+            float(stats.temperature_mean),
+            format!("{:.2}", BASE_TEMP),
+        ),
+        (
+            "temperature stddev",
+            float(stats.temperature_stddev),
+            format!("{:.2}", uniform_stddev),
+        ),
+        (
+            "temperature range",
+            format!(
+                "{} to {}",
+                float(stats.temperature_range.0),
+                float(stats.temperature_range.1)
+            ),
+            format!(
+                "{:.2} to {:.2}",
+                BASE_TEMP - TEMPERATURE_SPREAD,
+                BASE_TEMP + TEMPERATURE_SPREAD
+            ),
+        ),
+        (
+            "NaN temperature",
+            percent(stats.nan),
+            "--special-rate".to_string(),
+        ),
+    ] {
+        println!("{:<24} {:>32} {:>32}", name, table, generated);
+    }
+    if fraction < 1.0 {
+        println!();
+        println!("Rows per created are scaled up from the sample, and less certain for it.");
+    }
+}
+
+/// The fitted settings as command line flags.
+fn flags(generator: &GeneratorConfig, schema: &SchemaConfig) -> String {
+    let mut flags = vec![
+        format!("--seed {}", generator.seed),
+        format!("--batch-interval-ms {}", generator.batch_interval_ms),
+        format!("--special-rate {}", generator.special_rate),
+        format!(
+            "--time-column {}",
+            schema.time_column.to_possible_value().unwrap().get_name()
+        ),
+    ];
+    if let Some(start) = generator.start {
+        flags.push(format!(
+            "--start {}",
+            start.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ));
+    }
+    if let Some(size) = generator.batch_size_min {
+        flags.push(format!("--batch-size-min {}", size));
+    }
+    if let Some(size) = generator.batch_size_max {
+        flags.push(format!("--batch-size-max {}", size));
+    }
+    if let Some(scale) = schema.numeric_scale {
+        flags.push(format!("--numeric-scale {}", scale));
+    }
+    flags.join(" ")
+}
+
+pub fn profile(
+    config: &Config,
+    generator: &GeneratorConfig,
+    args: &ProfileArgs,
+) -> anyhow::Result<()> {
+    let table = &args.table;
+    let mut client = tls::connect(config)?;
+    let schema = detect_schema(&mut client, table)?;
+
+    let estimate: f64 = client
+        .query_one(
+            "SELECT greatest(reltuples, 0)::float8 FROM pg_class WHERE oid = $1::text::regclass",
+            &[table],
+        )?
+        .get(0);
+    // Never analyzed, or small enough to read whole.
+    let percent = if estimate > args.sample_rows as f64 {
+        args.sample_rows as f64 / estimate * 100.0
+    } else {
+        100.0
+    };
+    let stats = sample(
+        &mut client,
+        table,
+        schema.time_column,
+        percent,
+        generator.seed,
+    )?;
+    if stats.rows == 0 {
+        anyhow::bail!("{} has no rows to profile", table);
+    }
+
+    let fraction = percent / 100.0;
+    print_profile(table, &stats, fraction);
+    let fitted = fit(&stats, fraction, generator.seed);
+    fitted.validate(stats.timestamps.max(1) as usize)?;
+    println!();
+    println!("Generator settings:");
+    println!("{}", flags(&fitted, &schema));
+    if let Some(path) = &args.output {
+        fs::write(
+            path,
+            serde_json::to_vec_pretty(&Profile {
+                generator: fitted,
+                schema,
+            })?,
+        )?;
+        println!();
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Protocol Buffers encoding of generated rows, for message buses and APIs
+//! that take protobuf: the `.proto` declaration of a message with the
+//! table's columns, and the encoding of a row as that message.
+
+use crate::generator::{Row, Timestamp};
+use crate::schema::{SchemaConfig, TimeColumn};
+use crate::serialize::temperature_text;
+use crate::FastGenError;
+
+/// Wire types of the fields used.
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+
+/// The `.proto` (proto3) declaration of a message named `name` with the
+/// table's columns: `created` in microseconds since the Unix epoch,
+/// whatever the time column, and `temperature` a double, or a decimal
+/// string at `--numeric-scale`.
+pub fn schema_proto(schema: &SchemaConfig, name: &str) -> String {
+    let temperature = if schema.numeric_scale.is_some() {
+        "string"
+    } else {
+        "double"
+    };
+    format!(
+        "syntax = \"proto3\";\n\nmessage {} {{\n  int64 created = 1;\n  int32 sensor_id = 2;\n  {} temperature = 3;\n}}\n",
+        name, temperature
+    )
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_key(out: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(out, field << 3 | wire_type);
+}
+
+/// Appends the encoding of `row` to `out`, as the message `schema_proto`
+/// declares. Infinite timestamps are the extremes of an int64, as Postgres
+/// stores them, except in a bigint column, which can't hold them.
+pub fn write_row(out: &mut Vec<u8>, schema: &SchemaConfig, row: &Row) -> crate::Result<()> {
+    let created = match (row.0, schema.time_column) {
+        (Timestamp::Value(datetime), _) => datetime.timestamp_micros(),
+        (_, TimeColumn::Bigint) => {
+            return Err(FastGenError::Encoding(
+                "a bigint time column cannot store infinite timestamps".to_string(),
+            ))
+        }
+        (Timestamp::PosInfinity, _) => i64::MAX,
+        (Timestamp::NegInfinity, _) => i64::MIN,
+    };
+    write_key(out, 1, VARINT);
+    write_varint(out, created as u64);
+    write_key(out, 2, VARINT);
+    write_varint(out, row.1 as i64 as u64);
+    if schema.numeric_scale.is_some() {
+        let text = temperature_text(schema, row.2);
+        write_key(out, 3, LENGTH_DELIMITED);
+        write_varint(out, text.len() as u64);
+        out.extend_from_slice(text.as_bytes());
+    } else {
+        write_key(out, 3, FIXED64);
+        out.extend_from_slice(&row.2.to_le_bytes());
+    }
+    Ok(())
+}
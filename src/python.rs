@@ -0,0 +1,137 @@
+//! Python bindings to the seeded generator and the binary COPY encoder, so
+//! that a notebook gets the same rows and bytes as a benchmark run with the
+//! same settings. Built as the `fast_generation` extension module.
+
+use crate::generator::{generate_data, GeneratorConfig, Timestamp, BASE_TEMP, DEFAULT_SEED};
+use crate::pgcopy;
+use crate::schema::{SchemaConfig, TimeColumn, MAX_NUMERIC_SCALE};
+use crate::FastGenError;
+use chrono::{DateTime, Utc};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+impl From<FastGenError> for PyErr {
+    fn from(e: FastGenError) -> Self {
+        let message = e.report();
+        match e {
+            FastGenError::Encoding(_)
+            | FastGenError::SchemaMismatch(_)
+            | FastGenError::InvalidConfig(_) => PyValueError::new_err(message),
+            FastGenError::SinkError(_) => PyIOError::new_err(message),
+            _ => PyRuntimeError::new_err(message),
+        }
+    }
+}
+
+/// A timestamp as Python sees it; infinities, which `datetime` lacks, are
+/// the strings Postgres prints for them.
+#[derive(IntoPyObject)]
+enum PyCreated {
+    Value(DateTime<Utc>),
+    Infinity(&'static str),
+}
+
+fn generator_config(
+    seed: u64,
+    start: Option<DateTime<Utc>>,
+    batch_interval_ms: i64,
+    special_rate: f64,
+    batch_count: usize,
+) -> crate::Result<GeneratorConfig> {
+    let config = GeneratorConfig {
+        seed,
+        start,
+        batch_interval_ms,
+        special_rate,
+        ..GeneratorConfig::default()
+    };
+    config.validate(batch_count)?;
+    Ok(config)
+}
+
+fn time_column(name: &str) -> crate::Result<TimeColumn> {
+    match name {
+        "timestamptz" => Ok(TimeColumn::Timestamptz),
+        "timestamp" => Ok(TimeColumn::Timestamp),
+        "bigint" => Ok(TimeColumn::Bigint),
+        _ => Err(FastGenError::InvalidConfig(format!(
+            "unknown time column {:?}",
+            name
+        ))),
+    }
+}
+
+/// Rows of `batch_count` batches as `(created, sensor_id, temperature)`
+/// tuples, one list per batch. Without `start` the first batch is eight days
+/// from now, so pass it to get the same rows on every call.
+#[pyfunction]
+#[pyo3(signature = (batch_count, *, seed = DEFAULT_SEED, start = None, batch_interval_ms = 100, special_rate = 0.0))]
+fn generate(
+    batch_count: usize,
+    seed: u64,
+    start: Option<DateTime<Utc>>,
+    batch_interval_ms: i64,
+    special_rate: f64,
+) -> PyResult<Vec<Vec<(PyCreated, i32, f64)>>> {
+    let config = generator_config(seed, start, batch_interval_ms, special_rate, batch_count)?;
+    Ok(generate_data(&config, BASE_TEMP, batch_count)
+        .map(|(batch, _)| {
+            batch
+                .into_iter()
+                .map(|(created, sensor_id, temperature)| {
+                    let created = match created {
+                        Timestamp::Value(datetime) => PyCreated::Value(datetime),
+                        Timestamp::PosInfinity => PyCreated::Infinity("infinity"),
+                        Timestamp::NegInfinity => PyCreated::Infinity("-infinity"),
+                    };
+                    (created, sensor_id, temperature)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// The same batches as `generate`, each encoded as a complete binary COPY
+/// stream for `COPY ... FROM STDIN WITH BINARY`.
+#[pyfunction]
+#[pyo3(signature = (batch_count, *, seed = DEFAULT_SEED, start = None, batch_interval_ms = 100, special_rate = 0.0, time_column = "timestamptz", numeric_scale = None))]
+#[allow(clippy::too_many_arguments)]
+fn encode<'py>(
+    py: Python<'py>,
+    batch_count: usize,
+    seed: u64,
+    start: Option<DateTime<Utc>>,
+    batch_interval_ms: i64,
+    special_rate: f64,
+    time_column: &str,
+    numeric_scale: Option<u32>,
+) -> PyResult<Vec<Bound<'py, PyBytes>>> {
+    let config = generator_config(seed, start, batch_interval_ms, special_rate, batch_count)?;
+    if numeric_scale.is_some_and(|scale| scale > MAX_NUMERIC_SCALE) {
+        return Err(PyValueError::new_err(format!(
+            "numeric_scale must be at most {}",
+            MAX_NUMERIC_SCALE
+        )));
+    }
+    let schema = SchemaConfig {
+        time_column: self::time_column(time_column)?,
+        numeric_scale,
+    };
+    let mut buffer = Vec::new();
+    generate_data(&config, BASE_TEMP, batch_count)
+        .map(|(batch, _)| {
+            buffer.clear();
+            pgcopy::write_buffer(&mut buffer, &schema, &batch)?;
+            Ok(PyBytes::new(py, &buffer))
+        })
+        .collect()
+}
+
+#[pymodule]
+fn fast_generation(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("BATCH_SIZE", crate::generator::BATCH_SIZE)?;
+    module.add_function(wrap_pyfunction!(generate, module)?)?;
+    module.add_function(wrap_pyfunction!(encode, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use crate::generator::{Created, Row, Timestamp};
+use crate::pgcopy::{numeric_from_postgres_binary, time_from_postgres_binary};
+use crate::schema::{SchemaConfig, TimeColumn};
+use crate::FastGenError;
+use chrono::{Datelike, NaiveDateTime};
+use postgres::{Client, SimpleQueryMessage};
+use postgres_types::{FromSql, Type};
+use std::{
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ResultFormat {
+    /// Extended protocol, binary result columns
+    Binary,
+    /// Simple protocol, text result columns
+    Text,
+}
+
+impl ResultFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResultFormat::Binary => "binary",
+            ResultFormat::Text => "text",
+        }
+    }
+}
+
+pub struct ReadStats {
+    pub rows: u64,
+    pub elapsed: Duration,
+}
+
+/// Result columns use the same binary encodings as COPY, so the extended
+/// protocol path shares the `pgcopy` decoders.
+struct CreatedField(Created);
+
+impl<'a> FromSql<'a> for CreatedField {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let time_column = if *ty == Type::INT8 {
+            TimeColumn::Bigint
+        } else {
+            TimeColumn::Timestamptz
+        };
+        let micros = i64::from_sql(ty, raw)?;
+        Ok(CreatedField(time_from_postgres_binary(
+            time_column,
+            micros,
+        )?))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        [Type::TIMESTAMPTZ, Type::TIMESTAMP, Type::INT8].contains(ty)
+    }
+}
+
+struct TemperatureField(f64);
+
+impl<'a> FromSql<'a> for TemperatureField {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(TemperatureField(numeric_from_postgres_binary(raw)?))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+/// Parses a timestamp as printed with `TimeZone = 'UTC'`, including
+/// infinities and BC dates.
+fn parse_created(time_column: TimeColumn, text: &str) -> crate::Result<Created> {
+    match text {
+        "infinity" => return Ok(Timestamp::PosInfinity),
+        "-infinity" => return Ok(Timestamp::NegInfinity),
+        _ => {}
+    }
+    if time_column == TimeColumn::Bigint {
+        return time_from_postgres_binary(time_column, parse_field(text)?);
+    }
+
+    let (text, bc) = match text.strip_suffix(" BC") {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let text = text.strip_suffix("+00").unwrap_or(text);
+    let mut datetime = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+        .map_err(|e| FastGenError::Encoding(format!("timestamp {:?}: {}", text, e)))?;
+    if bc {
+        datetime = datetime
+            .with_year(1 - datetime.year())
+            .ok_or_else(|| FastGenError::Encoding(format!("invalid BC timestamp {}", text)))?;
+    }
+    Ok(Timestamp::Value(datetime.and_utc()))
+}
+
+fn parse_text_row(time_column: TimeColumn, row: &postgres::SimpleQueryRow) -> crate::Result<Row> {
+    let field = |index: usize| {
+        row.get(index).ok_or_else(|| {
+            FastGenError::Encoding(format!("unexpected NULL in column {}", index + 1))
+        })
+    };
+    Ok((
+        parse_created(time_column, field(0)?)?,
+        parse_field(field(1)?)?,
+        parse_field(field(2)?)?,
+    ))
+}
+
+fn parse_field<T: FromStr>(text: &str) -> crate::Result<T>
+where
+    T::Err: Display,
+{
+    text.parse()
+        .map_err(|e| FastGenError::Encoding(format!("{:?}: {}", text, e)))
+}
+
+/// Reads the whole table through a cursor `fetch_size` rows at a time and
+/// deserializes every row, timing the transfer and the client-side parsing.
+pub fn read(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+    format: ResultFormat,
+    fetch_size: usize,
+) -> crate::Result<ReadStats> {
+    let started = Instant::now();
+    let mut tx = client.transaction()?;
+    tx.batch_execute(&format!(
+        "SET LOCAL TimeZone = 'UTC';
+         DECLARE reader NO SCROLL CURSOR FOR SELECT created, sensor_id, temperature FROM {}",
+        table_name
+    ))?;
+    let fetch = format!("FETCH {} FROM reader", fetch_size);
+
+    let mut rows = 0;
+    loop {
+        let fetched = match format {
+            ResultFormat::Binary => {
+                let batch = tx.query(&fetch, &[])?;
+                for row in &batch {
+                    let _: Row = (
+                        row.try_get::<_, CreatedField>(0)?.0,
+                        row.try_get(1)?,
+                        row.try_get::<_, TemperatureField>(2)?.0,
+                    );
+                }
+                batch.len()
+            }
+            ResultFormat::Text => {
+                let mut fetched = 0;
+                for message in tx.simple_query(&fetch)? {
+                    if let SimpleQueryMessage::Row(row) = message {
+                        parse_text_row(schema.time_column, &row)?;
+                        fetched += 1;
+                    }
+                }
+                fetched
+            }
+        };
+
+        rows += fetched as u64;
+        if fetched < fetch_size {
+            break;
+        }
+    }
+    tx.commit()?;
+
+    Ok(ReadStats {
+        rows,
+        elapsed: started.elapsed(),
+    })
+}
@@ -0,0 +1,89 @@
+//! `sink redis`: adds the rows to a Redis stream with XADD, or to a
+//! RedisTimeSeries series per sensor with TS.ADD, a pipeline per batch, for
+//! benchmarking architectures where consumers move the rows on to Postgres.
+
+use crate::cli::RedisArgs;
+use crate::sink::Sink;
+use fast_generation::generator::{Row, Timestamp};
+use fast_generation::schema::SchemaConfig;
+use fast_generation::serialize::temperature_text;
+use redis::Connection;
+
+pub struct RedisSink {
+    connection: Connection,
+    schema: SchemaConfig,
+    key: String,
+    timeseries: bool,
+    max_len: Option<u64>,
+    bytes: u64,
+}
+
+impl RedisSink {
+    /// Streams and series are named after `--key`, or else `name`.
+    pub fn new(args: &RedisArgs, schema: &SchemaConfig, name: &str) -> anyhow::Result<RedisSink> {
+        let connection = redis::Client::open(args.url.as_str())?
+            .get_connection()
+            .map_err(|e| anyhow::anyhow!("connecting to {}: {}", args.url, e))?;
+        Ok(RedisSink {
+            connection,
+            schema: *schema,
+            key: args.key.clone().unwrap_or_else(|| name.to_string()),
+            timeseries: args.timeseries,
+            max_len: args.max_len,
+            bytes: 0,
+        })
+    }
+}
+
+impl Sink for RedisSink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let mut pipeline = redis::pipe();
+        for row in batch {
+            let temperature = temperature_text(&self.schema, row.2);
+            if self.timeseries {
+                // One value per sensor and millisecond; the last one wins.
+                let Timestamp::Value(created) = row.0 else {
+                    anyhow::bail!("RedisTimeSeries has no infinite timestamps");
+                };
+                let key = format!("{}:{}", self.key, row.1);
+                self.bytes += (key.len() + temperature.len() + 8) as u64;
+                pipeline
+                    .cmd("TS.ADD")
+                    .arg(key)
+                    .arg(created.timestamp_millis())
+                    .arg(temperature)
+                    .arg("ON_DUPLICATE")
+                    .arg("LAST")
+                    .arg("LABELS")
+                    .arg("sensor_id")
+                    .arg(row.1)
+                    .ignore();
+            } else {
+                let created = self.schema.time_column.text(row.0);
+                let sensor_id = row.1.to_string();
+                self.bytes += (created.len() + sensor_id.len() + temperature.len()) as u64;
+                let command = pipeline.cmd("XADD").arg(&self.key);
+                if let Some(max_len) = self.max_len {
+                    command.arg("MAXLEN").arg("~").arg(max_len);
+                }
+                command
+                    .arg("*")
+                    .arg("created")
+                    .arg(created)
+                    .arg("sensor_id")
+                    .arg(sensor_id)
+                    .arg("temperature")
+                    .arg(temperature)
+                    .ignore();
+            }
+        }
+        pipeline
+            .query::<()>(&mut self.connection)
+            .map_err(|e| anyhow::anyhow!("adding to {}: {}", self.key, e))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(self.bytes)
+    }
+}
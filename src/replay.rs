@@ -0,0 +1,72 @@
+//! The `replay` command: loads a recorded file with COPY straight from its
+//! mapped pages, so the client does no more than hand the server bytes,
+//! and reports it the way `run` reports a strategy.
+
+use crate::cli::ReplayArgs;
+use crate::manifest;
+use crate::units;
+use fast_generation::load::BatchLatency;
+use fast_generation::mapped::MappedFile;
+use fast_generation::pgcopy;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::tls;
+use postgres::Config;
+use std::time::Instant;
+
+pub fn replay(
+    config: &Config,
+    schema: &SchemaConfig,
+    table_name: &str,
+    args: &ReplayArgs,
+) -> anyhow::Result<()> {
+    let file = MappedFile::open(&args.input, args.input_header)?;
+    let batches = file.batches(args.batch_rows)?;
+    if batches.is_empty() {
+        anyhow::bail!("{} holds no rows", args.input.display());
+    }
+    println!(
+        "Mapped {} of {} in {} batches",
+        units::bytes(file.len() as f64),
+        args.input.display(),
+        batches.len()
+    );
+    match manifest::recorded_checksums(&args.input)? {
+        Some((manifest_path, checksums)) => {
+            file.verify(&checksums)?;
+            println!(
+                " Checksums: {} batches match {}",
+                checksums.crc32.len(),
+                manifest_path.display()
+            );
+        }
+        None => println!(" Checksums: none recorded in a manifest.json above it"),
+    }
+
+    let mut client = tls::connect(config)?;
+    pgcopy::verify_table(&mut client, schema, table_name)?;
+    let mut rows = 0;
+    let mut bytes = 0;
+    let mut latencies = Vec::with_capacity(batches.len());
+    let started = Instant::now();
+    for batch in batches {
+        let sending = Instant::now();
+        bytes += batch.len();
+        rows += file.copy(&mut client, table_name, batch)?;
+        latencies.push(sending.elapsed());
+    }
+    let seconds = started.elapsed().as_secs_f64();
+
+    println!();
+    println!("replay:");
+    println!("Speed: {}", units::rate(bytes as f64 / seconds));
+    println!(" Rows: {:.0}/s", rows as f64 / seconds);
+    if let Some(latency) = BatchLatency::from_samples(latencies) {
+        println!(
+            " Batch latency: p50 {:.1}ms, p99 {:.1}ms",
+            latency.p50.as_secs_f64() * 1000.0,
+            latency.p99.as_secs_f64() * 1000.0
+        );
+    }
+    println!(" Time: {:.2}s", seconds);
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//! The `replication` command: publishes a scratch copy of the table to a
+//! subscriber database over logical replication, loads it with each
+//! strategy and times how long the subscriber takes to apply the rows,
+//! since the replica's apply rate is often the real ceiling on ingest.
+
+use crate::cli::ReplicationArgs;
+use crate::fdw::libpq_settings;
+use crate::tables;
+use crate::units;
+use fast_generation::tls;
+use fast_generation::{
+    BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult, STRATEGIES,
+};
+use postgres::{Client, Config};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The publication created on the connected database.
+const PUBLICATION: &str = "fast_generation_pub";
+/// The subscription created on the subscriber, and its slot on the
+/// publisher.
+const SUBSCRIPTION: &str = "fast_generation_sub";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Applied {
+    result: StrategyResult,
+    /// From the start of the load until the subscriber held every row.
+    applied: Duration,
+}
+
+/// A keyword=value connection string for the subscriber to reach the
+/// publisher `config` connects to.
+fn conninfo(config: &Config) -> anyhow::Result<String> {
+    let settings: Vec<_> = libpq_settings(config)?
+        .into_iter()
+        .map(|(keyword, value)| {
+            let value = value.replace('\\', "\\\\").replace('\'', "\\'");
+            format!("{}='{}'", keyword, value)
+        })
+        .collect();
+    Ok(settings.join(" "))
+}
+
+/// The host, port and database `config` connects to.
+fn location(config: &Config) -> anyhow::Result<Vec<(&'static str, String)>> {
+    let mut settings = libpq_settings(config)?;
+    settings.retain(|(keyword, _)| ["host", "port", "dbname"].contains(keyword));
+    Ok(settings)
+}
+
+/// Polls the subscriber until its copy of `table_name` has `rows` rows.
+fn wait_for_rows(
+    subscriber: &mut Client,
+    table_name: &str,
+    rows: i64,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let count = format!("SELECT count(*) FROM {}", table_name);
+    loop {
+        let applied: i64 = subscriber.query_one(&count, &[])?.get(0);
+        if applied >= rows {
+            return Ok(());
+        }
+        if started.elapsed() > timeout {
+            anyhow::bail!(
+                "the subscriber applied {} of {} rows in {}s",
+                applied,
+                rows,
+                timeout.as_secs()
+            );
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Drops the subscription's slot on the publisher, once the walsender that
+/// may still hold it a moment after the subscription went lets go.
+fn drop_slot(client: &mut Client) -> anyhow::Result<()> {
+    let started = Instant::now();
+    loop {
+        let active: Option<bool> = client
+            .query_opt(
+                "SELECT active FROM pg_replication_slots WHERE slot_name = $1",
+                &[&SUBSCRIPTION],
+            )?
+            .map(|row| row.get(0));
+        match active {
+            None => return Ok(()),
+            Some(false) => {
+                client.execute("SELECT pg_drop_replication_slot($1)", &[&SUBSCRIPTION])?;
+                return Ok(());
+            }
+            Some(true) if started.elapsed() < Duration::from_secs(10) => {
+                thread::sleep(POLL_INTERVAL)
+            }
+            Some(true) => anyhow::bail!("replication slot {} is still in use", SUBSCRIPTION),
+        }
+    }
+}
+
+fn print_comparison(results: &[Applied]) {
+    println!();
+    println!(
+        "{:<16} {:>10} {:>12} {:>12} {:>10} {:>12}",
+        "strategy", "rows", "load rows/s", "apply rows/s", "lag s", "WAL"
+    );
+    for Applied { result, applied } in results {
+        if let Some(e) = &result.error {
+            println!("{:<16} failed: {}", result.strategy, e);
+            continue;
+        }
+        println!(
+            "{:<16} {:>10} {:>12.0} {:>12.0} {:>10.2} {:>12}",
+            result.strategy,
+            result.rows,
+            result.rows_per_second(),
+            result.rows as f64 / applied.as_secs_f64(),
+            applied.saturating_sub(result.elapsed).as_secs_f64(),
+            units::bytes(result.wal_bytes as f64)
+        );
+    }
+}
+
+pub fn replication(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batch_count: usize,
+    args: &ReplicationArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let subscriber_config: Config = args.subscriber_dsn.parse()?;
+    if location(config)? == location(&subscriber_config)? {
+        anyhow::bail!("--subscriber-dsn must point to a database other than the loaded one");
+    }
+    let publisher_conninfo = match &args.publisher_conninfo {
+        Some(conninfo) => conninfo.clone(),
+        None => conninfo(config)?,
+    };
+    let mut client = tls::connect(config)?;
+    let mut subscriber = tls::connect(&subscriber_config)?;
+    let wal_level: String = client.query_one("SHOW wal_level", &[])?.get(0);
+    if wal_level != "logical" {
+        anyhow::bail!(
+            "the publisher runs with wal_level = {}; replication needs logical",
+            wal_level
+        );
+    }
+    let strategies: Vec<String> = if args.strategies.is_empty() {
+        STRATEGIES
+            .iter()
+            .map(|strategy| strategy.name.to_string())
+            .collect()
+    } else {
+        args.strategies.clone()
+    };
+
+    let mut results = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        let create = format!(
+            "DROP TABLE IF EXISTS {scratch}; {}",
+            tables::create_table(scratch, schema)
+        );
+        client.batch_execute(&create)?;
+        subscriber.batch_execute(&create)?;
+        // Only inserts, so truncating between strategies stays on each side.
+        client.batch_execute(&format!(
+            "DROP PUBLICATION IF EXISTS {PUBLICATION};
+             CREATE PUBLICATION {PUBLICATION} FOR TABLE {scratch} WITH (publish = 'insert')"
+        ))?;
+        drop_slot(&mut client)?;
+        // Created apart from the subscription, which would otherwise wait
+        // forever for its own transaction when both are on one server.
+        client.execute(
+            "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+            &[&SUBSCRIPTION],
+        )?;
+        subscriber
+            .batch_execute(&format!(
+                "CREATE SUBSCRIPTION {SUBSCRIPTION} CONNECTION '{}' PUBLICATION {PUBLICATION}
+                     WITH (create_slot = false, slot_name = '{SUBSCRIPTION}', copy_data = false)",
+                publisher_conninfo.replace('\'', "''")
+            ))
+            .map_err(|e| anyhow::anyhow!("creating the subscription: {}", e))?;
+
+        for strategy in &strategies {
+            let truncate = format!("TRUNCATE {}", scratch);
+            client.batch_execute(&truncate)?;
+            subscriber.batch_execute(&truncate)?;
+            println!("Loading with {} and waiting for the subscriber", strategy);
+            let started = Instant::now();
+            let result = BenchmarkRunner::new(config.clone())
+                .with_generator(*generator)
+                .with_schema(*schema)
+                .with_config(RunConfig {
+                    strategies: vec![strategy.clone()],
+                    table_name: scratch.clone(),
+                    batch_count,
+                    workers: args.workers,
+                    ..RunConfig::default()
+                })
+                .run()?
+                .remove(0);
+            if result.error.is_none() {
+                let timeout = Duration::from_secs(args.apply_timeout);
+                wait_for_rows(&mut subscriber, scratch, result.rows as i64, timeout)?;
+            }
+            let applied = started.elapsed();
+            results.push(Applied { result, applied });
+        }
+        Ok(())
+    }();
+
+    // Detached from its slot first, so dropping it does not reach back to
+    // the publisher.
+    subscriber.batch_execute(&format!(
+        "DO $$ BEGIN
+             IF EXISTS (SELECT FROM pg_subscription WHERE subname = '{SUBSCRIPTION}') THEN
+                 ALTER SUBSCRIPTION {SUBSCRIPTION} DISABLE;
+                 ALTER SUBSCRIPTION {SUBSCRIPTION} SET (slot_name = NONE);
+                 DROP SUBSCRIPTION {SUBSCRIPTION};
+             END IF;
+         END $$;
+         DROP TABLE IF EXISTS {scratch}"
+    ))?;
+    drop_slot(&mut client)?;
+    client.batch_execute(&format!(
+        "DROP PUBLICATION IF EXISTS {PUBLICATION}; DROP TABLE IF EXISTS {scratch}"
+    ))?;
+    outcome?;
+
+    print_comparison(&results);
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! The JSON report of a run: what `--report` writes and `--upload` uploads,
+//! and what `--baseline` compares a new run against.
+
+use crate::slo::SloOutcome;
+use fast_generation::environment::{Environment, SettingChange};
+use fast_generation::{GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct Report {
+    pub environment: Environment,
+    pub generator: GeneratorConfig,
+    pub schema: SchemaConfig,
+    pub run: RunConfig,
+    pub results: Vec<StrategyResult>,
+    /// Server settings that differ from those of the baseline, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_settings: Vec<SettingChange>,
+    /// The objectives the run was held to, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slos: Vec<SloOutcome>,
+}
+
+impl Report {
+    pub fn load(path: &Path) -> anyhow::Result<Report> {
+        let file =
+            File::open(path).map_err(|e| anyhow::anyhow!("opening {}: {}", path.display(), e))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow::anyhow!("reading {}: {}", path.display(), e))
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&self.to_json())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn print_changed_settings(changes: &[SettingChange]) {
+    if changes.is_empty() {
+        println!("Settings: unchanged since the baseline");
+        return;
+    }
+    println!("Settings changed since the baseline:");
+    for change in changes {
+        println!(
+            " {}: {} -> {}",
+            change.name,
+            change.before.as_deref().unwrap_or("(not recorded)"),
+            change.after.as_deref().unwrap_or("(not recorded)")
+        );
+    }
+}
+
+/// Rows per second of each successful result next to the baseline's for
+/// the same strategy and iteration, followed by the changed settings that
+/// might explain a difference.
+pub fn print_comparison(baseline: &Report, report: &Report) {
+    println!();
+    println!("Against the baseline:");
+    for result in report
+        .results
+        .iter()
+        .filter(|result| result.error.is_none())
+    {
+        let before = baseline.results.iter().find(|before| {
+            before.strategy == result.strategy
+                && before.iteration == result.iteration
+                && before.error.is_none()
+        });
+        let speed = result.rows_per_second();
+        match before {
+            Some(before) => {
+                let before = before.rows_per_second();
+                println!(
+                    " fn {} #{}: {:.0} rows/s, baseline {:.0} rows/s ({:+.1}%)",
+                    result.strategy,
+                    result.iteration + 1,
+                    speed,
+                    before,
+                    (speed / before - 1.0) * 100.0
+                );
+            }
+            None => println!(
+                " fn {} #{}: {:.0} rows/s, not in the baseline",
+                result.strategy,
+                result.iteration + 1,
+                speed
+            ),
+        }
+    }
+    print_changed_settings(&report.changed_settings);
+}
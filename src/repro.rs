@@ -0,0 +1,136 @@
+//! `--repro-dir`: a scenario file with every setting of the run and a script
+//! that creates the table as it was and runs the same load with them, so the
+//! run can be repeated elsewhere with one command.
+
+use crate::cli::LoadArgs;
+use crate::k8s;
+use chrono::{SecondsFormat, Utc};
+use fast_generation::environment::Environment;
+use fast_generation::{GeneratorConfig, SchemaConfig};
+use postgres::Client;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+pub const SCENARIO_FILE: &str = "scenario.env";
+pub const SCRIPT_FILE: &str = "repro.sh";
+
+/// `value` single-quoted for sh.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The table and its indexes as they stand, as statements that leave an
+/// existing table alone.
+fn table_ddl(client: &mut Client, table_name: &str) -> anyhow::Result<Vec<String>> {
+    let columns: String = client
+        .query_one(
+            "SELECT string_agg(quote_ident(attname) || ' ' || format_type(atttypid, atttypmod)
+                 || coalesce(' DEFAULT ' || pg_get_expr(adbin, adrelid), '')
+                 || CASE WHEN attnotnull THEN ' NOT NULL' ELSE '' END, ', ' ORDER BY attnum)
+             FROM pg_attribute LEFT JOIN pg_attrdef ON adrelid = attrelid AND adnum = attnum
+             WHERE attrelid = $1::text::regclass AND attnum > 0 AND NOT attisdropped",
+            &[&table_name],
+        )?
+        .get(0);
+    let mut ddl = vec![format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        table_name, columns
+    )];
+    for row in client.query(
+        "SELECT pg_get_indexdef(indexrelid) FROM pg_index WHERE indrelid = $1::text::regclass",
+        &[&table_name],
+    )? {
+        let index: String = row.get(0);
+        ddl.push(
+            index
+                .replacen("CREATE INDEX ", "CREATE INDEX IF NOT EXISTS ", 1)
+                .replacen(
+                    "CREATE UNIQUE INDEX ",
+                    "CREATE UNIQUE INDEX IF NOT EXISTS ",
+                    1,
+                ),
+        );
+    }
+    Ok(ddl)
+}
+
+/// Writes the scenario file and the script into `dir`, creating it.
+#[allow(clippy::too_many_arguments)]
+pub fn write(
+    dir: &Path,
+    client: &mut Client,
+    environment: &Environment,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    load: &LoadArgs,
+    table_name: &str,
+    conn_info: &str,
+) -> anyhow::Result<()> {
+    let settings = k8s::settings(generator, schema, load);
+    let mut scenario = String::new();
+    for (_, key, value) in &settings {
+        writeln!(scenario, "{}={}", key, quote(value)).unwrap();
+    }
+
+    let mut command_line = vec!["fast_generation --dsn \"$DSN\" run".to_string()];
+    for (flag, key, _) in &settings {
+        command_line.push(format!("--{} \"${}\"", flag, key));
+    }
+    for flag in k8s::switches(load) {
+        command_line.push(format!("--{}", flag));
+    }
+
+    let mut tool = format!("fast_generation {}", environment.tool_version);
+    if let Some(revision) = &environment.git_revision {
+        write!(tool, " ({})", revision).unwrap();
+    }
+    let mut script = String::new();
+    writeln!(script, "#!/bin/sh").unwrap();
+    writeln!(
+        script,
+        "# Repeats a run of {} at {},",
+        tool,
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    )
+    .unwrap();
+    writeln!(script, "# against {}.", environment.server_version).unwrap();
+    writeln!(
+        script,
+        "# Usage: {} [connection string]; needs psql and fast_generation on the PATH.",
+        SCRIPT_FILE
+    )
+    .unwrap();
+    writeln!(script, "set -eu").unwrap();
+    writeln!(script, "cd \"$(dirname \"$0\")\"").unwrap();
+    writeln!(script, "DSN=\"${{1:-{}}}\"", without_password(conn_info)).unwrap();
+    writeln!(script, ". ./{}", SCENARIO_FILE).unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "psql \"$DSN\" -v ON_ERROR_STOP=1 <<'SQL'").unwrap();
+    for statement in table_ddl(client, table_name)? {
+        writeln!(script, "{};", statement).unwrap();
+    }
+    writeln!(script, "SQL").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "exec {}", command_line.join(" \\\n    ")).unwrap();
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(SCENARIO_FILE), scenario)?;
+    let path = dir.join(SCRIPT_FILE);
+    fs::write(&path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// The password is left to PGPASSWORD or ~/.pgpass.
+fn without_password(conn_info: &str) -> String {
+    conn_info
+        .split_whitespace()
+        .filter(|pair| !pair.starts_with("password="))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
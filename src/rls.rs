@@ -0,0 +1,135 @@
+//! The `rls` command: loads into a scratch copy of the table as the
+//! connecting user without row-level security, then as a non-superuser role
+//! with a policy checking every row, to put a number on what RLS costs on
+//! the write path.
+
+use crate::cli::RlsArgs;
+use crate::units;
+use fast_generation::generator::MAX_SENSORS;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+
+fn print_comparison(results: &[(String, StrategyResult)]) {
+    let unrestricted = results
+        .first()
+        .filter(|(_, result)| result.error.is_none())
+        .map(|(_, result)| result.rows_per_second());
+    println!();
+    println!(
+        "{:<40} {:>10} {:>14} {:>10} {:>16}",
+        "load", "rows/s", "bytes/s", "p99 ms", "vs unrestricted"
+    );
+    for (label, result) in results {
+        if let Some(e) = &result.error {
+            println!("{:<40} failed: {}", label, e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        let speed = result.rows_per_second();
+        println!(
+            "{:<40} {:>10.0} {:>14} {:>10.1} {:>16}",
+            label,
+            speed,
+            units::rate(size / result.elapsed.as_secs_f64()),
+            result
+                .latency
+                .map_or(0.0, |latency| latency.p99.as_secs_f64() * 1000.0),
+            unrestricted.map_or("-".to_string(), |unrestricted| format!(
+                "{:+.1}%",
+                (speed / unrestricted - 1.0) * 100.0
+            ))
+        );
+        if let Some(discrepancy) = result.row_discrepancy() {
+            println!(
+                "  ROW COUNT MISMATCH: the table grew by {} rows, {} were sent ({:+})",
+                result.counted.unwrap_or_default(),
+                result.rows,
+                discrepancy
+            );
+        }
+    }
+}
+
+pub fn rls(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &RlsArgs,
+) -> anyhow::Result<()> {
+    let (scratch, role) = (&args.scratch_table, &args.role);
+    let mut client = tls::connect(config)?;
+    // A role that already exists is left as it is, bar the grants.
+    let created = client
+        .query_opt("SELECT 1 FROM pg_roles WHERE rolname = $1", &[role])?
+        .is_none();
+    if created {
+        let password = args
+            .role_password
+            .as_ref()
+            .map_or(String::new(), |password| {
+                format!(" PASSWORD '{}'", password.replace('\'', "''"))
+            });
+        client
+            .batch_execute(&format!(
+                "CREATE ROLE {role} LOGIN NOSUPERUSER NOBYPASSRLS{password}"
+            ))
+            .map_err(|e| anyhow::anyhow!("creating role {} (needs CREATEROLE): {}", role, e))?;
+    }
+    let mut restricted = config.clone();
+    restricted.user(role);
+    if let Some(password) = &args.role_password {
+        restricted.password(password);
+    }
+
+    // Rows are counted as the connecting user, whom the policy doesn't hide
+    // them from.
+    let load = |connection: &Config| -> anyhow::Result<StrategyResult> {
+        Ok(BenchmarkRunner::new(connection.clone())
+            .count_as(config.clone())
+            .with_generator(*generator)
+            .with_schema(*schema)
+            .with_config(RunConfig {
+                strategies: vec![args.strategy.clone()],
+                table_name: scratch.clone(),
+                batch_count,
+                workers: args.workers,
+                ..RunConfig::default()
+            })
+            .run()?
+            .remove(0))
+    };
+    let mut results = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        client.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {scratch};
+             CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS);"
+        ))?;
+        println!("Loading as the connecting user, without RLS");
+        results.push(("connecting user, RLS disabled".to_string(), load(config)?));
+
+        client.batch_execute(&format!(
+            "TRUNCATE {scratch};
+             GRANT USAGE ON SCHEMA public TO {role};
+             GRANT SELECT, INSERT ON {scratch} TO {role};
+             ALTER TABLE {scratch} ENABLE ROW LEVEL SECURITY;
+             CREATE POLICY {scratch}_sensors ON {scratch} FOR INSERT TO {role}
+                 WITH CHECK (sensor_id BETWEEN 1 AND {MAX_SENSORS});"
+        ))?;
+        println!("Loading as {}, with RLS", role);
+        results.push((format!("{}, RLS enabled", role), load(&restricted)?));
+        Ok(())
+    }();
+    client.batch_execute(&format!("DROP TABLE IF EXISTS {}", scratch))?;
+    if created {
+        client.batch_execute(&format!(
+            "REVOKE ALL ON SCHEMA public FROM {role}; DROP ROLE {role}"
+        ))?;
+    }
+    outcome?;
+
+    print_comparison(&results);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! A dedicated role for loading with no more privileges than it needs, so
+//! that benchmarks don't have to run as a superuser.
+
+use crate::cli::LoadRoleArgs;
+use clap::ValueEnum;
+use fast_generation::tls;
+use postgres::{Client, Config};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Privileges {
+    /// INSERT on the table only
+    #[default]
+    Insert,
+    /// INSERT and SELECT, to read back what was loaded
+    InsertSelect,
+}
+
+impl Privileges {
+    fn grants(self) -> &'static str {
+        match self {
+            Privileges::Insert => "INSERT",
+            Privileges::InsertSelect => "INSERT, SELECT",
+        }
+    }
+}
+
+/// The role loading into a table, granted what it needs; a role created
+/// for the run is dropped with its grants once this goes, while an existing
+/// one keeps them.
+pub struct LoadRole {
+    client: Client,
+    role: String,
+    created: bool,
+}
+
+impl LoadRole {
+    pub fn new(
+        config: &Config,
+        args: &LoadRoleArgs,
+        table_name: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(role) = &args.load_role else {
+            return Ok(None);
+        };
+        let mut client = tls::connect(config)?;
+        let created = client
+            .query_opt("SELECT 1 FROM pg_roles WHERE rolname = $1", &[role])?
+            .is_none();
+        if created {
+            let password = args
+                .load_role_password
+                .as_ref()
+                .map_or(String::new(), |password| {
+                    format!(" PASSWORD '{}'", password.replace('\'', "''"))
+                });
+            client
+                .batch_execute(&format!(
+                    "CREATE ROLE {role} LOGIN NOSUPERUSER NOCREATEDB NOCREATEROLE{password}"
+                ))
+                .map_err(|e| anyhow::anyhow!("creating role {} (needs CREATEROLE): {}", role, e))?;
+        }
+        let mut load_role = LoadRole {
+            client,
+            role: role.clone(),
+            created,
+        };
+        load_role.client.batch_execute(&format!(
+            "GRANT USAGE ON SCHEMA public TO {role};
+             GRANT {} ON {table_name} TO {role};",
+            args.load_role_privileges.grants()
+        ))?;
+        Ok(Some(load_role))
+    }
+
+    /// `config` connecting as the load role instead.
+    pub fn config(&self, config: &Config, args: &LoadRoleArgs) -> Config {
+        let mut config = config.clone();
+        config.user(&self.role);
+        if let Some(password) = &args.load_role_password {
+            config.password(password);
+        }
+        config
+    }
+}
+
+impl Drop for LoadRole {
+    fn drop(&mut self) {
+        if !self.created {
+            return;
+        }
+        let statement = format!("DROP OWNED BY {role}; DROP ROLE {role}", role = self.role);
+        if let Err(e) = self.client.batch_execute(&statement) {
+            eprintln!("could not drop role {}: {}", self.role, e);
+        }
+    }
+}
@@ -0,0 +1,807 @@
+use crate::adaptive::BatchSizer;
+use crate::audit::{AuditColumn, DuplicateAudit, KeyCounts, ScaleCheck};
+use crate::environment::Environment;
+use crate::generator::{
+    child_rng, generate_data, generate_sized, GeneratorConfig, Row, BASE_TEMP, BATCH_SIZE,
+};
+use crate::load::{self, BatchLatency, LoadOptions, Recovery, Validation};
+use crate::observer::Observer;
+use crate::pgcopy;
+use crate::pool::{BufferPool, PoolStats};
+use crate::schema::{SchemaConfig, TimeColumn};
+use crate::server::{self, AutovacuumCounts, AutovacuumPause, TableSizes};
+use crate::shape::LoadShape;
+use crate::strategy::{Strategy, StrategyContext, STRATEGIES};
+use crate::tls;
+use crate::usage::{ClientUsage, UsageSampler};
+use crate::FastGenError;
+use chrono::{DateTime, Utc};
+use postgres::Config;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// What one strategy did in one iteration of a run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StrategyResult {
+    pub strategy: String,
+    /// Zero-based.
+    pub iteration: usize,
+    /// Rows the strategy was given; all of them were loaded unless `error`.
+    pub rows: usize,
+    /// Wall-clock time loading began, to line up results from several hosts.
+    pub started_at: DateTime<Utc>,
+    pub elapsed: Duration,
+    pub before: TableSizes,
+    pub after: TableSizes,
+    pub usage: ClientUsage,
+    pub pool: PoolStats,
+    /// WAL the server wrote while the strategy ran, by any session; 0 where
+    /// the WAL position can't be read.
+    #[serde(default)]
+    pub wal_bytes: i64,
+    /// Unset when the strategy failed.
+    #[serde(default)]
+    pub latency: Option<BatchLatency>,
+    /// Set when backends were terminated or connections lost and regained.
+    #[serde(default)]
+    pub recovery: Option<Recovery>,
+    /// Set when autovacuum was paused, counting any that ran regardless.
+    pub autovacuum: Option<AutovacuumCounts>,
+    /// How many rows `count(*)` of the table grew by while the strategy
+    /// ran; unset where not counted.
+    #[serde(default)]
+    pub counted: Option<i64>,
+    /// Set when keys were audited for duplicates.
+    #[serde(default)]
+    pub duplicates: Option<DuplicateAudit>,
+    /// Set when the scale of loaded temperatures was checked.
+    #[serde(default)]
+    pub scale: Option<ScaleCheck>,
+    /// Set under a batch latency target: the median of the batch sizes
+    /// last picked to hold it.
+    #[serde(default)]
+    pub converged_batch_size: Option<usize>,
+    /// Whether the strategy was stopped at the strategy timeout; `error`
+    /// then says after how long.
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Why loading stopped early, with its causes; the run carries on with
+    /// the next strategy.
+    pub error: Option<String>,
+}
+
+impl StrategyResult {
+    pub fn rows_per_second(&self) -> f64 {
+        self.rows as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// How many more rows the table grew by than a successful strategy
+    /// sent, or fewer when negative; `None` when they agree or weren't
+    /// counted.
+    pub fn row_discrepancy(&self) -> Option<i64> {
+        let counted = self.counted.filter(|_| self.error.is_none())?;
+        Some(counted - self.rows as i64).filter(|&discrepancy| discrepancy != 0)
+    }
+}
+
+/// The order strategies run in within each iteration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum StrategyOrder {
+    /// As listed, every iteration
+    #[default]
+    Listed,
+    /// Each iteration starting one strategy further down the list
+    Rotate,
+    /// At random, anew every iteration, drawn from the seed
+    Shuffle,
+}
+
+impl StrategyOrder {
+    /// `strategies` in the order iteration `iteration` runs them in.
+    pub fn arrange<T: Clone>(self, strategies: &[T], iteration: usize, seed: u64) -> Vec<T> {
+        let mut arranged = strategies.to_vec();
+        match self {
+            StrategyOrder::Listed => {}
+            StrategyOrder::Rotate if arranged.is_empty() => {}
+            StrategyOrder::Rotate => arranged.rotate_left(iteration % strategies.len()),
+            StrategyOrder::Shuffle => {
+                arranged.shuffle(&mut child_rng(seed, "strategy_order", iteration as u64))
+            }
+        }
+        arranged
+    }
+}
+
+/// Everything about a run besides the connection, generator and schema, in
+/// a form that can be saved and loaded again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunConfig {
+    /// Names of the `STRATEGIES` to run, in order; all of them when empty.
+    pub strategies: Vec<String>,
+    /// Names of strategies to leave out.
+    pub skip: Vec<String>,
+    /// How the order strategies run in changes between iterations.
+    pub order: StrategyOrder,
+    pub table_name: String,
+    /// Batches each strategy loads per iteration, of `BATCH_SIZE` rows
+    /// unless the generator varies their size.
+    pub batch_count: usize,
+    /// Times every strategy is run, cycling through all of them each time.
+    pub iterations: usize,
+    pub workers: usize,
+    /// Encode each batch only after the previous one was sent.
+    pub sequential: bool,
+    pub copy_chunk_size: Option<usize>,
+    /// SQL run on a connection of its own before each strategy.
+    pub setup_sql: Vec<String>,
+    /// SQL run after each strategy, even one that failed.
+    pub teardown_sql: Vec<String>,
+    /// SQL run on every loading connection once opened.
+    pub session_sql: Vec<String>,
+    pub pause_autovacuum: bool,
+    /// CHECKPOINT, and evict shared buffers where possible, before each
+    /// strategy; nothing where the user may not checkpoint.
+    pub settle: bool,
+    /// Pause before sending each batch, as a producer doing other work.
+    pub think_time_ms: u64,
+    /// Up to this much more pause, drawn at random for every batch.
+    pub think_jitter_ms: u64,
+    /// Hand out batches at this target rate, instead of as fast as the
+    /// workers take them.
+    pub shape: Option<LoadShape>,
+    /// Times to reconnect and resend a batch whose connection was lost.
+    pub retries: u32,
+    /// Terminate one of the loading backends this often, to exercise
+    /// reconnecting.
+    pub chaos_interval_ms: Option<u64>,
+    /// Read a sample of every this many batches back once committed, and
+    /// fail the strategy on a row not loaded as generated.
+    pub validate_every: Option<u64>,
+    /// Rows of each validated batch to read back.
+    pub validate_sample: usize,
+    /// Count the table's rows before and after each strategy, to check
+    /// that it grew by as many rows as were sent.
+    pub reconcile: bool,
+    /// Columns to audit the table's rows for duplicates by after each
+    /// strategy; no audit when empty.
+    pub audit_key: Vec<AuditColumn>,
+    /// Temperatures to sample after each strategy, to check the scale they
+    /// were stored at; no check when unset.
+    pub scale_sample: Option<usize>,
+    /// Bytes the batches generated and not yet sent may take, with their
+    /// encoded payloads; generating waits while over it. Unbounded when
+    /// unset.
+    pub max_client_memory: Option<usize>,
+    /// Size batches to take about this long each, from how long those
+    /// committed took, instead of as the generator is set to; as many rows
+    /// are loaded as `batch_count` batches of `BATCH_SIZE` hold.
+    pub target_batch_ms: Option<u64>,
+    /// Stop a strategy still loading after this many seconds, cancelling
+    /// what it was running, record it as timed out and go on to the next.
+    pub strategy_timeout_secs: Option<u64>,
+    /// Generate every batch before the first strategy starts and have each
+    /// load those, so that no strategy's time includes generating rows.
+    /// They're all held in memory for the run.
+    pub pregenerate: bool,
+}
+
+impl Default for RunConfig {
+    /// Every built-in strategy, once, loading 1000 batches into `metrics`
+    /// over one connection.
+    fn default() -> Self {
+        RunConfig {
+            strategies: Vec::new(),
+            skip: Vec::new(),
+            order: StrategyOrder::Listed,
+            table_name: "metrics".to_string(),
+            batch_count: 1_000,
+            iterations: 1,
+            workers: 1,
+            sequential: false,
+            copy_chunk_size: None,
+            setup_sql: Vec::new(),
+            teardown_sql: Vec::new(),
+            session_sql: Vec::new(),
+            pause_autovacuum: false,
+            settle: false,
+            think_time_ms: 0,
+            think_jitter_ms: 0,
+            shape: None,
+            retries: 0,
+            chaos_interval_ms: None,
+            validate_every: None,
+            validate_sample: 10,
+            reconcile: true,
+            audit_key: Vec::new(),
+            scale_sample: None,
+            max_client_memory: None,
+            target_batch_ms: None,
+            strategy_timeout_secs: None,
+            pregenerate: false,
+        }
+    }
+}
+
+impl RunConfig {
+    /// The built-in strategies named in `strategies`, or all of them, less
+    /// those in `skip`.
+    pub fn resolve_strategies(&self) -> crate::Result<Vec<Strategy>> {
+        let find = |name: &String| {
+            STRATEGIES
+                .iter()
+                .find(|strategy| strategy.name == name)
+                .copied()
+                .ok_or_else(|| FastGenError::InvalidConfig(format!("unknown strategy {:?}", name)))
+        };
+        let mut strategies = match self.strategies.is_empty() {
+            true => STRATEGIES.to_vec(),
+            false => self
+                .strategies
+                .iter()
+                .map(find)
+                .collect::<crate::Result<_>>()?,
+        };
+        for name in &self.skip {
+            let skipped = find(name)?;
+            strategies.retain(|strategy| strategy.name != skipped.name);
+        }
+        if strategies.is_empty() && !self.skip.is_empty() {
+            return Err(FastGenError::InvalidConfig(
+                "--skip leaves no strategy to run".to_string(),
+            ));
+        }
+        Ok(strategies)
+    }
+}
+
+type ResultCallback<'a> = Box<dyn FnMut(&StrategyResult) + Send + 'a>;
+type BatchSplitter<'a> = &'a (dyn Fn(&[Row]) -> Vec<Vec<Row>> + Sync);
+
+/// Drives benchmark runs for embedding applications; the binary's `run`
+/// command is a thin wrapper around it.
+///
+/// ```no_run
+/// use fast_generation::{BenchmarkRunner, GeneratorConfig, STRATEGIES};
+///
+/// let config = "host=localhost user=postgres".parse()?;
+/// let results = BenchmarkRunner::new(config)
+///     .with_generator(GeneratorConfig::default())
+///     .with_strategy(STRATEGIES[2])
+///     .iterations(3)
+///     .on_result(|result| println!("{}: {:?}", result.strategy, result.elapsed))
+///     .run()?;
+/// # Ok::<(), fast_generation::FastGenError>(())
+/// ```
+pub struct BenchmarkRunner<'a> {
+    connection: Config,
+    generator: GeneratorConfig,
+    schema: SchemaConfig,
+    run: RunConfig,
+    /// Loaded in place of generated batches when set.
+    batches: Option<&'a [Vec<Row>]>,
+    /// Splits every batch before it's handed to a worker.
+    split: Option<BatchSplitter<'a>>,
+    /// Counts the table's rows instead of `connection` when set.
+    counting: Option<Config>,
+    /// Strategies beyond the built-in ones, run after those named in `run`.
+    custom_strategies: Vec<Strategy>,
+    on_result: Option<ResultCallback<'a>>,
+    observer: &'a dyn Observer,
+    stop: Option<&'a AtomicBool>,
+}
+
+impl<'a> BenchmarkRunner<'a> {
+    /// A run with the defaults of `RunConfig`, `GeneratorConfig` and
+    /// `SchemaConfig`.
+    pub fn new(connection: Config) -> Self {
+        BenchmarkRunner {
+            connection,
+            generator: GeneratorConfig::default(),
+            schema: SchemaConfig::default(),
+            run: RunConfig::default(),
+            batches: None,
+            split: None,
+            counting: None,
+            custom_strategies: Vec::new(),
+            on_result: None,
+            observer: &(),
+            stop: None,
+        }
+    }
+
+    pub fn with_generator(mut self, generator: GeneratorConfig) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    pub fn with_schema(mut self, schema: SchemaConfig) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Has every strategy load these batches, e.g. read from a file with
+    /// `input::read_batches`, instead of generated ones; the generator and
+    /// `batch_count` then go unused.
+    pub fn with_batches(mut self, batches: &'a [Vec<Row>]) -> Self {
+        self.batches = Some(batches);
+        self
+    }
+
+    /// Splits every batch, generated or given, into the batches `split`
+    /// returns before handing them to the workers, e.g. into the rows of
+    /// each node of a distributed table. They keep the batch's number.
+    pub fn split_batches(mut self, split: BatchSplitter<'a>) -> Self {
+        self.split = Some(split);
+        self
+    }
+
+    /// Counts the table's rows for reconciling and auditing keys over a
+    /// connection of `connection`, such as the table owner's, when loading
+    /// as a role that row-level security or a missing SELECT grant keeps
+    /// from seeing them.
+    pub fn count_as(mut self, connection: Config) -> Self {
+        self.counting = Some(connection);
+        self
+    }
+
+    /// Replaces every run setting at once, e.g. with a saved one.
+    pub fn with_config(mut self, run: RunConfig) -> Self {
+        self.run = run;
+        self
+    }
+
+    pub fn config(&self) -> &RunConfig {
+        &self.run
+    }
+
+    /// Adds a strategy to run, in order. Built-in strategies are recorded by
+    /// name in the `RunConfig`; others only live in the runner. Without any,
+    /// all of `STRATEGIES` run.
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        if STRATEGIES
+            .iter()
+            .any(|builtin| builtin.name == strategy.name)
+        {
+            self.run.strategies.push(strategy.name.to_string());
+        } else {
+            self.custom_strategies.push(strategy);
+        }
+        self
+    }
+
+    pub fn table(mut self, table_name: &str) -> Self {
+        self.run.table_name = table_name.to_string();
+        self
+    }
+
+    pub fn batch_count(mut self, batch_count: usize) -> Self {
+        self.run.batch_count = batch_count;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.run.iterations = iterations;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.run.workers = workers;
+        self
+    }
+
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.run.sequential = sequential;
+        self
+    }
+
+    pub fn copy_chunk_size(mut self, copy_chunk_size: Option<usize>) -> Self {
+        self.run.copy_chunk_size = copy_chunk_size;
+        self
+    }
+
+    pub fn setup_sql(mut self, statements: Vec<String>) -> Self {
+        self.run.setup_sql = statements;
+        self
+    }
+
+    pub fn teardown_sql(mut self, statements: Vec<String>) -> Self {
+        self.run.teardown_sql = statements;
+        self
+    }
+
+    pub fn session_sql(mut self, statements: Vec<String>) -> Self {
+        self.run.session_sql = statements;
+        self
+    }
+
+    pub fn pause_autovacuum(mut self, pause_autovacuum: bool) -> Self {
+        self.run.pause_autovacuum = pause_autovacuum;
+        self
+    }
+
+    pub fn settle(mut self, settle: bool) -> Self {
+        self.run.settle = settle;
+        self
+    }
+
+    pub fn think_time(mut self, think_time_ms: u64, think_jitter_ms: u64) -> Self {
+        self.run.think_time_ms = think_time_ms;
+        self.run.think_jitter_ms = think_jitter_ms;
+        self
+    }
+
+    pub fn shape(mut self, shape: Option<LoadShape>) -> Self {
+        self.run.shape = shape;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.run.retries = retries;
+        self
+    }
+
+    /// Terminates one loading backend every `interval_ms`, in turn.
+    pub fn chaos(mut self, interval_ms: Option<u64>) -> Self {
+        self.run.chaos_interval_ms = interval_ms;
+        self
+    }
+
+    /// Called with each result as soon as its strategy finishes.
+    pub fn on_result(mut self, on_result: impl FnMut(&StrategyResult) + Send + 'a) -> Self {
+        self.on_result = Some(Box::new(on_result));
+        self
+    }
+
+    /// Receives batch progress, results and errors as they happen.
+    pub fn observer(mut self, observer: &'a dyn Observer) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Lets another thread stop the run by setting `stop`: the strategy
+    /// loading at the time fails with `FastGenError::Interrupted` within a
+    /// few batches, and no further ones start.
+    pub fn stop_flag(mut self, stop: &'a AtomicBool) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Fails on a configuration the generator can't load, without touching
+    /// the database.
+    pub fn validate(&self) -> crate::Result<()> {
+        match (self.batches, self.run.target_batch_ms) {
+            (Some(_), _) => {}
+            (None, Some(ms)) => {
+                let sizer = BatchSizer::new(Duration::from_millis(ms), &self.generator);
+                self.generator
+                    .validate(sizer.most_batches(self.run.batch_count * BATCH_SIZE))?;
+            }
+            (None, None) => self.generator.validate(self.run.batch_count)?,
+        }
+        if let Some(shape) = &self.run.shape {
+            shape.validate()?;
+        }
+        if self.generator.special_rate > 0.0 && self.schema.time_column == TimeColumn::Bigint {
+            return Err(FastGenError::InvalidConfig(
+                "--special-rate injects infinite timestamps, which a bigint time column cannot store"
+                    .to_string(),
+            ));
+        }
+        if self.run.target_batch_ms.is_some() && self.batches.is_some() {
+            return Err(FastGenError::InvalidConfig(
+                "--target-batch-ms sizes generated batches, not those read from a file".to_string(),
+            ));
+        }
+        if self.run.target_batch_ms.is_some() && self.run.pregenerate {
+            return Err(FastGenError::InvalidConfig(
+                "--target-batch-ms sizes batches as they're loaded, which --pregenerate generates beforehand"
+                    .to_string(),
+            ));
+        }
+        if self.run.target_batch_ms == Some(0) {
+            return Err(FastGenError::InvalidConfig(
+                "--target-batch-ms must be positive".to_string(),
+            ));
+        }
+        if self.run.strategy_timeout_secs == Some(0) {
+            return Err(FastGenError::InvalidConfig(
+                "--strategy-timeout must be positive".to_string(),
+            ));
+        }
+        if self.run.chaos_interval_ms == Some(0) {
+            return Err(FastGenError::InvalidConfig(
+                "--chaos-interval-ms must be positive".to_string(),
+            ));
+        }
+        if self.run.copy_chunk_size == Some(0) {
+            return Err(FastGenError::InvalidConfig(
+                "--copy-chunk-size must be positive".to_string(),
+            ));
+        }
+        self.run.resolve_strategies()?;
+        Ok(())
+    }
+
+    /// Whether `settle` can evict buffers on the server, or only checkpoint.
+    pub fn can_evict_buffers(&self) -> crate::Result<bool> {
+        server::can_evict_buffers(&mut tls::connect(&self.connection)?)
+    }
+
+    /// Whether `settle` can checkpoint at all; it does nothing otherwise.
+    pub fn can_checkpoint(&self) -> crate::Result<bool> {
+        server::can_checkpoint(&mut tls::connect(&self.connection)?)
+    }
+
+    /// Whether the WAL a strategy writes can be measured; `wal_bytes` is 0
+    /// otherwise.
+    pub fn can_read_wal(&self) -> crate::Result<bool> {
+        server::can_read_wal(&mut tls::connect(&self.connection)?)
+    }
+
+    /// The server, its settings and this client, to report with the results.
+    pub fn environment(&self) -> crate::Result<Environment> {
+        Environment::capture(&mut tls::connect(&self.connection)?)
+    }
+
+    /// Runs every iteration, or until stopped, and returns all results, failed
+    /// ones included. Errors are reserved for problems outside a strategy,
+    /// like an invalid configuration or failing hook SQL.
+    pub fn run(mut self) -> crate::Result<Vec<StrategyResult>> {
+        self.validate()?;
+        let mut strategies = if self.run.strategies.is_empty() && !self.custom_strategies.is_empty()
+        {
+            Vec::new()
+        } else {
+            self.run.resolve_strategies()?
+        };
+        strategies.append(&mut self.custom_strategies);
+        let run = &self.run;
+
+        let mut client = tls::connect(&self.connection)?;
+        pgcopy::verify_table(&mut client, &self.schema, &run.table_name)?;
+        load::execute_all(&mut client, &run.session_sql)?;
+        // Hooks get a connection of their own so that their session settings
+        // don't leak into the loading one.
+        let mut hooks = tls::connect(&self.connection)?;
+        let _autovacuum = run
+            .pause_autovacuum
+            .then(|| AutovacuumPause::new(&self.connection, &run.table_name))
+            .transpose()?;
+        let checkpoint = run.settle && server::can_checkpoint(&mut hooks)?;
+        let evict = checkpoint && server::can_evict_buffers(&mut hooks)?;
+        let wal = server::can_read_wal(&mut hooks)?;
+        let wal_position = |hooks: &mut postgres::Client| match wal {
+            true => server::wal_position(hooks),
+            false => Ok(0),
+        };
+        let mut counter = match &self.counting {
+            Some(connection) if run.reconcile || !run.audit_key.is_empty() => {
+                Some(tls::connect(connection)?)
+            }
+            _ => None,
+        };
+
+        let options = LoadOptions {
+            config: &self.connection,
+            workers: run.workers,
+            sequential: run.sequential,
+            session_sql: &run.session_sql,
+            observer: self.observer,
+            stop: self.stop,
+            think_time: Duration::from_millis(run.think_time_ms),
+            think_jitter: Duration::from_millis(run.think_jitter_ms),
+            shape: run.shape,
+            retries: run.retries,
+            chaos: run.chaos_interval_ms.map(Duration::from_millis),
+            validate: run.validate_every.map(|every| Validation {
+                every: every.max(1),
+                sample: run.validate_sample,
+            }),
+            max_memory: run.max_client_memory,
+            sizer: None,
+            deadline: None,
+        };
+        // Batches given are in memory already.
+        let pregenerated: Option<Vec<Vec<Row>>> =
+            (run.pregenerate && self.batches.is_none()).then(|| {
+                generate_data(&self.generator, BASE_TEMP, run.batch_count)
+                    .map(|(batch_data, _)| batch_data)
+                    .collect()
+            });
+        let given = self.batches.or(pregenerated.as_deref());
+        let stopped = || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed));
+        let mut results = Vec::new();
+        let mut lost = false;
+        'iterations: for iteration in 0..run.iterations.max(1) {
+            for strategy in &run
+                .order
+                .arrange(&strategies, iteration, self.generator.seed)
+            {
+                if stopped() {
+                    break 'iterations;
+                }
+                // The previous strategy may have lost the connection for good.
+                if lost || client.is_closed() {
+                    client = tls::connect(&self.connection)?;
+                    load::execute_all(&mut client, &run.session_sql)?;
+                }
+                let buffers = BufferPool::new();
+                let context = StrategyContext {
+                    table_name: &run.table_name,
+                    schema: &self.schema,
+                    buffers: &buffers,
+                    copy_chunk_size: run.copy_chunk_size,
+                };
+
+                load::execute_all(&mut hooks, &run.setup_sql)?;
+                if checkpoint {
+                    server::settle(&mut hooks, evict)?;
+                }
+                let autovacuum = server::autovacuum_counts(&mut hooks, &run.table_name)?;
+                let before = server::table_sizes(&mut hooks, &run.table_name)?;
+                let wal_before = wal_position(&mut hooks)?;
+                let count_before = run
+                    .reconcile
+                    .then(|| {
+                        server::row_count(counter.as_mut().unwrap_or(&mut hooks), &run.table_name)
+                    })
+                    .transpose()?;
+                let keys_before = (!run.audit_key.is_empty())
+                    .then(|| {
+                        KeyCounts::of_table(
+                            counter.as_mut().unwrap_or(&mut hooks),
+                            &run.table_name,
+                            &run.audit_key,
+                            &self.schema,
+                        )
+                    })
+                    .transpose()?;
+                let mut keys_sent = KeyCounts::default();
+                let scale_start = run
+                    .scale_sample
+                    .map(|_| ScaleCheck::start(&mut hooks))
+                    .transpose()?;
+
+                let sampler = UsageSampler::start();
+                let started_at = Utc::now();
+                let started = Instant::now();
+                let mut rows = 0;
+                let sizer = run
+                    .target_batch_ms
+                    .map(|ms| BatchSizer::new(Duration::from_millis(ms), &self.generator));
+                let options = LoadOptions {
+                    sizer: sizer.as_ref(),
+                    deadline: run
+                        .strategy_timeout_secs
+                        .map(|secs| started + Duration::from_secs(secs)),
+                    ..options
+                };
+                // Batches held for the run are lent to the workers, not
+                // copied, so loading them costs no allocation either.
+                let batches: Box<dyn Iterator<Item = (Cow<[Row]>, i64)>> = match (given, &sizer) {
+                    (Some(batches), _) => Box::new(
+                        batches
+                            .iter()
+                            .map(|batch_data| Cow::Borrowed(&batch_data[..]))
+                            .zip(1..),
+                    ),
+                    (None, Some(sizer)) => Box::new(
+                        generate_sized(
+                            &self.generator,
+                            BASE_TEMP,
+                            run.batch_count * BATCH_SIZE,
+                            || sizer.next_size(),
+                        )
+                        .map(|(batch_data, batch)| (Cow::Owned(batch_data), batch)),
+                    ),
+                    (None, None) => Box::new(
+                        generate_data(&self.generator, BASE_TEMP, run.batch_count)
+                            .map(|(batch_data, batch)| (Cow::Owned(batch_data), batch)),
+                    ),
+                };
+                let batches: Box<dyn Iterator<Item = (Cow<[Row]>, i64)>> = match self.split {
+                    Some(split) => Box::new(batches.flat_map(move |(batch_data, batch)| {
+                        split(&batch_data)
+                            .into_iter()
+                            .map(move |part| (Cow::Owned(part), batch))
+                    })),
+                    None => batches,
+                };
+                let batches = batches.inspect(|(batch_data, _)| {
+                    rows += batch_data.len();
+                    if keys_before.is_some() {
+                        keys_sent.add(&run.audit_key, &self.schema, batch_data);
+                    }
+                });
+                let loaded = load::load(strategy, &mut client, &context, batches, &options);
+                let elapsed = started.elapsed();
+                let usage = sampler.finish();
+
+                let after = server::table_sizes(&mut hooks, &run.table_name)?;
+                let wal_bytes = wal_position(&mut hooks)? - wal_before;
+                let counted = match count_before {
+                    Some(before) => Some(
+                        server::row_count(counter.as_mut().unwrap_or(&mut hooks), &run.table_name)?
+                            - before,
+                    ),
+                    None => None,
+                };
+                let duplicates = match &keys_before {
+                    Some(before) => {
+                        let after = KeyCounts::of_table(
+                            counter.as_mut().unwrap_or(&mut hooks),
+                            &run.table_name,
+                            &run.audit_key,
+                            &self.schema,
+                        )?;
+                        Some(DuplicateAudit::new(before, &after, &keys_sent))
+                    }
+                    None => None,
+                };
+                let scale = match (scale_start, run.scale_sample) {
+                    (Some(start), Some(sample)) => Some(ScaleCheck::run(
+                        &mut hooks,
+                        &run.table_name,
+                        &self.schema,
+                        strategy.name,
+                        start,
+                        sample,
+                    )?),
+                    _ => None,
+                };
+                let autovacuum = if run.pause_autovacuum {
+                    Some(server::autovacuum_counts(&mut hooks, &run.table_name)?.since(autovacuum))
+                } else {
+                    None
+                };
+                load::execute_all(&mut hooks, &run.teardown_sql)?;
+
+                if let Err(e) = &loaded {
+                    self.observer.error(strategy.name, e);
+                }
+                lost = loaded.as_ref().is_err_and(FastGenError::is_connection_lost);
+                let recovery = loaded.as_ref().ok().and_then(|loaded| {
+                    (run.chaos_interval_ms.is_some() || !loaded.recoveries.is_empty())
+                        .then(|| Recovery::new(loaded.terminated, &loaded.recoveries))
+                });
+                let loaded = loaded.map(|loaded| BatchLatency::from_samples(loaded.latencies));
+                let result = StrategyResult {
+                    strategy: strategy.name.to_string(),
+                    iteration,
+                    rows,
+                    started_at,
+                    elapsed,
+                    before,
+                    after,
+                    usage,
+                    pool: buffers.stats(),
+                    wal_bytes,
+                    latency: loaded.as_ref().ok().copied().flatten(),
+                    recovery,
+                    autovacuum,
+                    counted,
+                    duplicates,
+                    scale,
+                    converged_batch_size: sizer.and_then(|sizer| sizer.converged()),
+                    timed_out: matches!(loaded, Err(FastGenError::TimedOut(_))),
+                    error: loaded.err().map(|e| e.report()),
+                };
+                self.observer.strategy_finished(&result);
+                if let Some(on_result) = &mut self.on_result {
+                    on_result(&result);
+                }
+                results.push(result);
+            }
+        }
+
+        Ok(results)
+    }
+}
@@ -0,0 +1,152 @@
+//! `sink s3`: streams the rows as CSV or Parquet files to S3 or GCS in
+//! multipart uploads, staging nothing on disk, as bulk-load inputs for
+//! Redshift COPY or Snowflake stages made from the same rows `run` loads.
+
+use crate::cli::S3Args;
+use crate::sink::Sink;
+use crate::upload::{ObjectWriter, Uploader};
+use clap::ValueEnum;
+use fast_generation::export::{Compression, Encoder};
+use fast_generation::generator::Row;
+use fast_generation::parquet::ParquetWriter;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::serialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ObjectFormat {
+    /// CSV as COPY ... WITH (FORMAT csv) writes it
+    #[default]
+    Csv,
+    /// Parquet, in row groups of a million rows
+    Parquet,
+}
+
+/// Only one is open at a time, so the size of either doesn't matter.
+#[allow(clippy::large_enum_variant)]
+enum Object {
+    Csv(Encoder<ObjectWriter>),
+    Parquet(ParquetWriter<ObjectWriter>),
+}
+
+impl Object {
+    /// Bytes uploaded or uploading; a compressor or the current row group
+    /// may hold more.
+    fn written(&self) -> u64 {
+        match self {
+            Object::Csv(Encoder::Plain(writer)) => writer.bytes(),
+            Object::Csv(Encoder::Gzip(encoder)) => encoder.get_ref().bytes(),
+            Object::Csv(Encoder::Zstd(encoder)) => encoder.get_ref().bytes(),
+            Object::Parquet(writer) => writer.bytes_written() as u64,
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<u64> {
+        match self {
+            Object::Csv(encoder) => encoder.finish()?.finish(),
+            Object::Parquet(writer) => writer.finish()?.finish(),
+        }
+    }
+}
+
+pub struct S3Sink {
+    uploader: Uploader,
+    format: ObjectFormat,
+    compression: Option<Compression>,
+    header: bool,
+    max_file_size: Option<u64>,
+    schema: SchemaConfig,
+    name: String,
+    current: Option<Object>,
+    files: usize,
+    bytes: u64,
+}
+
+impl S3Sink {
+    /// Objects are named after the table, `name`, numbered from 00000.
+    pub fn new(args: &S3Args, schema: &SchemaConfig, name: &str) -> anyhow::Result<S3Sink> {
+        let compression = args.compress.map(|codec| Compression {
+            codec,
+            level: args.compression_level,
+        });
+        if let Some(compression) = &compression {
+            compression.validate()?;
+        }
+        Ok(S3Sink {
+            uploader: Uploader::new(&args.uri)?,
+            format: args.format,
+            compression,
+            header: args.header,
+            max_file_size: args.max_file_size.map(|size| size as u64),
+            schema: *schema,
+            name: name.to_string(),
+            current: None,
+            files: 0,
+            bytes: 0,
+        })
+    }
+
+    pub fn uploader(&self) -> &Uploader {
+        &self.uploader
+    }
+
+    fn open(&mut self) -> anyhow::Result<Object> {
+        let mut file_name = format!("{}-{:05}", self.name, self.files);
+        self.files += 1;
+        Ok(match self.format {
+            ObjectFormat::Csv => {
+                file_name.push_str(".csv");
+                if let Some(compression) = &self.compression {
+                    file_name.push('.');
+                    file_name.push_str(compression.codec.extension());
+                }
+                let writer = self.uploader.writer(&file_name)?;
+                let mut encoder = match &self.compression {
+                    Some(compression) => compression.encoder(writer)?,
+                    None => Encoder::Plain(writer),
+                };
+                if self.header {
+                    serialize::write_csv(&mut encoder, &self.schema, &[], true)?;
+                }
+                Object::Csv(encoder)
+            }
+            ObjectFormat::Parquet => {
+                file_name.push_str(".parquet");
+                Object::Parquet(ParquetWriter::new(
+                    self.uploader.writer(&file_name)?,
+                    &self.schema,
+                    &self.name,
+                    self.compression,
+                )?)
+            }
+        })
+    }
+}
+
+impl Sink for S3Sink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        let mut object = match self.current.take() {
+            Some(object) => object,
+            None => self.open()?,
+        };
+        match &mut object {
+            Object::Csv(encoder) => serialize::write_csv(encoder, &self.schema, batch, false)?,
+            Object::Parquet(writer) => writer.write_rows(batch)?,
+        }
+        if self
+            .max_file_size
+            .is_some_and(|max| object.written() >= max)
+        {
+            self.bytes += object.finish()?;
+        } else {
+            self.current = Some(object);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<u64> {
+        if let Some(object) = self.current.take() {
+            self.bytes += object.finish()?;
+        }
+        Ok(self.bytes)
+    }
+}
@@ -0,0 +1,395 @@
+use crate::generator::BASE_TEMP;
+#[cfg(feature = "postgres")]
+use crate::generator::TEMPERATURE_SCALE;
+use crate::generator::{Created, Timestamp};
+#[cfg(feature = "postgres")]
+use bytes::BytesMut;
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+#[cfg(feature = "postgres")]
+use postgres::Client;
+#[cfg(feature = "postgres")]
+use postgres_types::{to_sql_checked, IsNull, ToSql, Type};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "postgres")]
+use std::error::Error;
+
+/// Storage format of the `created` column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum TimeColumn {
+    #[default]
+    Timestamptz,
+    Timestamp,
+    /// Microseconds since the Unix epoch
+    Bigint,
+}
+
+impl TimeColumn {
+    pub fn data_type(self) -> &'static str {
+        match self {
+            TimeColumn::Timestamptz => "timestamp with time zone",
+            TimeColumn::Timestamp => "timestamp without time zone",
+            TimeColumn::Bigint => "bigint",
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    pub fn pg_type(self) -> Type {
+        match self {
+            TimeColumn::Timestamptz => Type::TIMESTAMPTZ,
+            TimeColumn::Timestamp => Type::TIMESTAMP,
+            TimeColumn::Bigint => Type::INT8,
+        }
+    }
+
+    /// The value as Postgres prints and parses it for this column type.
+    pub fn text(self, created: Created) -> String {
+        match created {
+            Timestamp::Value(datetime) if self == TimeColumn::Bigint => {
+                datetime.timestamp_micros().to_string()
+            }
+            Timestamp::Value(datetime) if self == TimeColumn::Timestamptz => {
+                timestamp_text(datetime, "+00")
+            }
+            Timestamp::Value(datetime) => timestamp_text(datetime, ""),
+            Timestamp::PosInfinity => "infinity".to_string(),
+            Timestamp::NegInfinity => "-infinity".to_string(),
+        }
+    }
+
+    /// Reads back a value as Postgres prints it for this column type, in any
+    /// session time zone; `None` if it isn't one.
+    pub fn parse(self, text: &str) -> Option<Created> {
+        match text {
+            "infinity" => return Some(Timestamp::PosInfinity),
+            "-infinity" => return Some(Timestamp::NegInfinity),
+            _ => {}
+        }
+        if self == TimeColumn::Bigint {
+            return DateTime::from_timestamp_micros(text.parse().ok()?).map(Timestamp::Value);
+        }
+        let (text, bc) = match text.strip_suffix(" BC") {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+        // chrono reads years of more than four digits only with a sign.
+        let signed;
+        let text = match text.find('-') {
+            Some(dash) if dash > 4 => {
+                signed = format!("+{}", text);
+                &signed
+            }
+            _ => text,
+        };
+        let datetime = if self == TimeColumn::Timestamptz {
+            DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+                .ok()?
+                .naive_utc()
+        } else {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").ok()?
+        };
+        let datetime = if bc {
+            datetime.with_year(1 - datetime.year())?
+        } else {
+            datetime
+        };
+        Some(Timestamp::Value(datetime.and_utc()))
+    }
+
+    /// SQL literal for the string-building strategy.
+    pub fn literal(self, created: Created) -> String {
+        match created {
+            Timestamp::Value(_) if self == TimeColumn::Bigint => self.text(created),
+            // Infinities in a bigint column fail with the server's own message.
+            _ => format!("'{}'::{}", self.text(created), self.data_type()),
+        }
+    }
+
+    /// Bind parameter for the prepared-statement strategy.
+    #[cfg(feature = "postgres")]
+    pub fn value(self, created: Created) -> TimeValue {
+        TimeValue(self, created)
+    }
+}
+
+/// Formats a timestamp the way Postgres parses it. chrono's `Display` writes
+/// years outside 0000..=9999 with a sign, which Postgres rejects; it expects
+/// unsigned years and a `BC` suffix instead (year 0 being 1 BC), which goes
+/// after any `offset`, as Postgres prints it.
+fn timestamp_text(created: DateTime<Utc>, offset: &str) -> String {
+    let year = created.year();
+    let (year, era) = if year <= 0 {
+        (1 - year, " BC")
+    } else {
+        (year, "")
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}{}{}",
+        year,
+        created.month(),
+        created.day(),
+        created.hour(),
+        created.minute(),
+        created.second(),
+        created.timestamp_subsec_micros(),
+        offset,
+        era
+    )
+}
+
+#[cfg(feature = "postgres")]
+fn postgres_timestamp<T>(timestamp: Timestamp<T>) -> postgres_types::Timestamp<T> {
+    match timestamp {
+        Timestamp::PosInfinity => postgres_types::Timestamp::PosInfinity,
+        Timestamp::NegInfinity => postgres_types::Timestamp::NegInfinity,
+        Timestamp::Value(value) => postgres_types::Timestamp::Value(value),
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[derive(Debug)]
+pub struct TimeValue(TimeColumn, Created);
+
+#[cfg(feature = "postgres")]
+impl ToSql for TimeValue {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match (self.0, self.1) {
+            (TimeColumn::Timestamptz, created) => postgres_timestamp(created).to_sql(ty, out),
+            (TimeColumn::Timestamp, created) => {
+                postgres_timestamp(created.map(|datetime| datetime.naive_utc())).to_sql(ty, out)
+            }
+            (TimeColumn::Bigint, Timestamp::Value(datetime)) => {
+                datetime.timestamp_micros().to_sql(ty, out)
+            }
+            (TimeColumn::Bigint, _) => {
+                Err("a bigint time column cannot store infinite timestamps".into())
+            }
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        [Type::TIMESTAMPTZ, Type::TIMESTAMP, Type::INT8].contains(ty)
+    }
+
+    to_sql_checked!();
+}
+
+/// Largest `--numeric-scale` the fixed-scale encoder handles.
+pub const MAX_NUMERIC_SCALE: u32 = 15;
+
+/// Table layout options shared by every strategy and the encoder.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+#[serde(default)]
+pub struct SchemaConfig {
+    /// Type of the `created` column
+    #[cfg_attr(feature = "cli", arg(long, global = true, value_enum, default_value_t))]
+    pub time_column: TimeColumn,
+
+    /// Scale s of a numeric(p, s) temperature column; COPY then encodes every
+    /// value at that scale directly instead of finding its shortest decimal
+    #[cfg_attr(feature = "cli", arg(long, global = true, value_parser = clap::value_parser!(u32).range(0..=MAX_NUMERIC_SCALE as i64)))]
+    pub numeric_scale: Option<u32>,
+}
+
+/// A column the generator produces, in the order the strategies write them.
+pub struct ColumnSpec {
+    pub name: &'static str,
+    pub data_type: &'static str,
+}
+
+pub fn columns(schema: &SchemaConfig) -> [ColumnSpec; 3] {
+    [
+        ColumnSpec {
+            name: "created",
+            data_type: schema.time_column.data_type(),
+        },
+        ColumnSpec {
+            name: "sensor_id",
+            data_type: "integer",
+        },
+        ColumnSpec {
+            name: "temperature",
+            data_type: "numeric",
+        },
+    ]
+}
+
+#[cfg(feature = "postgres")]
+struct TableColumn {
+    name: String,
+    data_type: String,
+    numeric_precision: Option<i32>,
+    numeric_scale: Option<i32>,
+}
+
+#[cfg(feature = "postgres")]
+fn table_columns(client: &mut Client, table_name: &str) -> crate::Result<Vec<TableColumn>> {
+    let rows = client.query(
+        "SELECT column_name::text AS name,
+                data_type::text AS data_type,
+                numeric_precision::int AS numeric_precision,
+                numeric_scale::int AS numeric_scale
+           FROM information_schema.columns
+          WHERE table_schema = current_schema() AND table_name = $1
+            AND is_generated = 'NEVER'
+          ORDER BY ordinal_position",
+        &[&table_name],
+    )?;
+
+    Ok(rows
+        .iter()
+        .map(|row| TableColumn {
+            name: row.get("name"),
+            data_type: row.get("data_type"),
+            numeric_precision: row.get("numeric_precision"),
+            numeric_scale: row.get("numeric_scale"),
+        })
+        .collect())
+}
+
+/// Integer digits needed for the largest temperature the generator emits.
+pub(crate) fn temperature_integer_digits() -> i32 {
+    let max = BASE_TEMP.abs() + 5.0;
+    (max.log10().floor() as i32 + 1).max(1)
+}
+
+/// Compares the target table with the generator's columns and returns a
+/// description of every incompatibility found; an empty list means the
+/// strategies can load into the table as is.
+#[cfg(feature = "postgres")]
+pub fn check_table(
+    client: &mut Client,
+    schema: &SchemaConfig,
+    table_name: &str,
+) -> crate::Result<Vec<String>> {
+    let expected_columns = columns(schema);
+    let columns = table_columns(client, table_name)?;
+    if columns.is_empty() {
+        return Ok(vec![format!("table {} does not exist", table_name)]);
+    }
+
+    let mut problems = Vec::new();
+    if columns.len() != expected_columns.len() {
+        problems.push(format!(
+            "expected {} columns, table has {}",
+            expected_columns.len(),
+            columns.len()
+        ));
+    }
+
+    for (expected, actual) in expected_columns.iter().zip(&columns) {
+        if expected.name != actual.name {
+            problems.push(format!(
+                "expected column {}, found {}",
+                expected.name, actual.name
+            ));
+        }
+        if expected.data_type != actual.data_type {
+            problems.push(format!(
+                "column {}: expected {}, found {}",
+                actual.name, expected.data_type, actual.data_type
+            ));
+        }
+        if let Some(declared) = schema.numeric_scale {
+            if actual.data_type == "numeric" && actual.numeric_scale != Some(declared as i32) {
+                problems.push(format!(
+                    "column {}: --numeric-scale {} given, but the column's scale is {}",
+                    actual.name,
+                    declared,
+                    actual
+                        .numeric_scale
+                        .map_or("unconstrained".to_string(), |scale| scale.to_string())
+                ));
+            }
+        }
+        if let (Some(precision), Some(scale)) = (actual.numeric_precision, actual.numeric_scale) {
+            if actual.data_type != "numeric" {
+                continue;
+            }
+            if scale < TEMPERATURE_SCALE {
+                problems.push(format!(
+                    "column {}: scale {} would round the generated {} decimal places",
+                    actual.name, scale, TEMPERATURE_SCALE
+                ));
+            }
+            if precision - scale < temperature_integer_digits() {
+                problems.push(format!(
+                    "column {}: numeric({}, {}) cannot hold values up to {}",
+                    actual.name,
+                    precision,
+                    scale,
+                    BASE_TEMP + 5.0
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, SubsecRound, TimeZone};
+
+    fn utc(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    fn round_trip(column: TimeColumn, datetime: DateTime<Utc>) -> String {
+        let text = column.text(Timestamp::Value(datetime));
+        assert_eq!(column.parse(&text), Some(Timestamp::Value(datetime)));
+        text
+    }
+
+    #[test]
+    fn pre_2000_timestamps_print_and_parse() {
+        let datetime = utc(1969, 7, 20) + Duration::microseconds(123_456);
+        assert_eq!(
+            round_trip(TimeColumn::Timestamp, datetime),
+            "1969-07-20 00:00:00.123456"
+        );
+        assert_eq!(
+            round_trip(TimeColumn::Timestamptz, datetime),
+            "1969-07-20 00:00:00.123456+00"
+        );
+        assert_eq!(round_trip(TimeColumn::Bigint, datetime), "-14255999876544");
+    }
+
+    #[test]
+    fn bc_years_print_with_an_era() {
+        assert_eq!(
+            round_trip(TimeColumn::Timestamp, utc(0, 1, 1)),
+            "0001-01-01 00:00:00.000000 BC"
+        );
+        assert_eq!(
+            round_trip(TimeColumn::Timestamptz, utc(-43, 3, 15)),
+            "0044-03-15 00:00:00.000000+00 BC"
+        );
+    }
+
+    #[test]
+    fn range_bounds_print_and_parse() {
+        assert_eq!(
+            round_trip(TimeColumn::Timestamp, utc(-4713, 11, 24)),
+            "4714-11-24 00:00:00.000000 BC"
+        );
+        let latest = DateTime::<Utc>::MAX_UTC.trunc_subsecs(6);
+        round_trip(TimeColumn::Timestamp, latest);
+    }
+
+    #[test]
+    fn infinities_parse_in_timestamp_columns() {
+        for column in [TimeColumn::Timestamptz, TimeColumn::Timestamp] {
+            assert_eq!(column.parse("infinity"), Some(Timestamp::PosInfinity));
+            assert_eq!(column.parse("-infinity"), Some(Timestamp::NegInfinity));
+        }
+        assert_eq!(TimeColumn::Timestamp.parse("2024-13-01 00:00:00"), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! Client-side text serializations of generated rows, for consumers that
+//! aren't Postgres: CSV that `COPY ... WITH (FORMAT csv)` loads unchanged,
+//! JSON Lines, and InfluxDB's line protocol.
+
+use crate::generator::{Row, Timestamp};
+use crate::schema::{columns, SchemaConfig};
+use crate::FastGenError;
+use chrono::SecondsFormat;
+use std::io::Write;
+
+/// A temperature the way the binary encoder sends it: shortest round-trip
+/// decimal, or rounded to `numeric_scale` places when one is set.
+pub fn temperature_text(schema: &SchemaConfig, value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        format!("{}Infinity", if value > 0.0 { "" } else { "-" })
+    } else if let Some(scale) = schema.numeric_scale {
+        format!("{:.*}", scale as usize, value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes `rows` as CSV, preceded by a line of column names if `header`.
+/// No field needs quoting.
+pub fn write_csv<W: Write>(
+    writer: &mut W,
+    schema: &SchemaConfig,
+    rows: &[Row],
+    header: bool,
+) -> crate::Result<()> {
+    if header {
+        let names = columns(schema).map(|column| column.name);
+        writeln!(writer, "{}", names.join(","))?;
+    }
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{}",
+            schema.time_column.text(row.0),
+            row.1,
+            temperature_text(schema, row.2)
+        )?;
+    }
+    Ok(())
+}
+
+/// A row as a JSON object. Timestamps are RFC 3339 whatever the time
+/// column, and values JSON has no number for are strings: "infinity" and
+/// "-infinity" timestamps, "NaN" and "Infinity" temperatures.
+pub fn json_object(schema: &SchemaConfig, row: &Row) -> String {
+    let created = match row.0 {
+        Timestamp::Value(datetime) => datetime.to_rfc3339_opts(SecondsFormat::Micros, true),
+        Timestamp::PosInfinity => "infinity".to_string(),
+        Timestamp::NegInfinity => "-infinity".to_string(),
+    };
+    let temperature = temperature_text(schema, row.2);
+    let temperature = if row.2.is_finite() {
+        temperature
+    } else {
+        format!("\"{}\"", temperature)
+    };
+    format!(
+        r#"{{"created":"{}","sensor_id":{},"temperature":{}}}"#,
+        created, row.1, temperature
+    )
+}
+
+/// Writes one `json_object` per line.
+pub fn write_json_lines<W: Write>(
+    writer: &mut W,
+    schema: &SchemaConfig,
+    rows: &[Row],
+) -> crate::Result<()> {
+    for row in rows {
+        writeln!(writer, "{}", json_object(schema, row))?;
+    }
+    Ok(())
+}
+
+/// Writes one point of InfluxDB line protocol per row, in `measurement`,
+/// tagged with its sensor and with the temperature as a field, timestamped
+/// in nanoseconds. Influx stores no NaN or infinite field, nor a timestamp
+/// outside the years 1677 to 2262, so those fail.
+pub fn write_line_protocol<W: Write>(
+    writer: &mut W,
+    schema: &SchemaConfig,
+    rows: &[Row],
+    measurement: &str,
+) -> crate::Result<()> {
+    for row in rows {
+        let created = match row.0 {
+            Timestamp::Value(datetime) => datetime.timestamp_nanos_opt(),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            FastGenError::Encoding(format!(
+                "line protocol has no timestamp for {}",
+                schema.time_column.text(row.0)
+            ))
+        })?;
+        if !row.2.is_finite() {
+            return Err(FastGenError::Encoding(format!(
+                "line protocol has no {} field value",
+                row.2
+            )));
+        }
+        writeln!(
+            writer,
+            "{},sensor_id={} temperature={} {}",
+            measurement,
+            row.1,
+            temperature_text(schema, row.2),
+            created
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! The `serve` command: a small JSON API over HTTP to start, stop and query
+//! benchmark runs on this host, one run at a time.
+//!
+//! - `POST /runs` with a `RunRequest` body starts a run and returns its id
+//! - `GET /runs` lists every run since the server started
+//! - `GET /runs/{id}` returns a run with the results so far
+//! - `POST /runs/{id}/stop` stops a run within a few batches
+
+use fast_generation::environment::Environment;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Settings of a run; each part falls back to its defaults, the generator
+/// and schema to those given on the command line.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunRequest {
+    pub run: RunConfig,
+    pub generator: Option<GeneratorConfig>,
+    pub schema: Option<SchemaConfig>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Running,
+    Finished,
+    Stopped,
+    Failed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RunStatus {
+    pub id: usize,
+    pub state: RunState,
+    pub run: RunConfig,
+    pub generator: GeneratorConfig,
+    pub schema: SchemaConfig,
+    pub results: Vec<StrategyResult>,
+    /// Captured as the run starts.
+    pub environment: Option<Environment>,
+    /// Why the run as a whole failed, as opposed to a single strategy.
+    pub error: Option<String>,
+}
+
+struct Run {
+    status: RunStatus,
+    stop: Arc<AtomicBool>,
+}
+
+type Runs = Arc<Mutex<Vec<Run>>>;
+
+struct Defaults<'a> {
+    config: &'a Config,
+    generator: &'a GeneratorConfig,
+    schema: &'a SchemaConfig,
+}
+
+pub fn serve(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    listen: &str,
+) -> anyhow::Result<()> {
+    let server =
+        Server::http(listen).map_err(|e| anyhow::anyhow!("listening on {}: {}", listen, e))?;
+    println!("Listening on http://{}", server.server_addr());
+    let defaults = Defaults {
+        config,
+        generator,
+        schema,
+    };
+    let runs = Runs::default();
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = match handle(&mut request, &defaults, &runs) {
+            Ok((status, body)) => (status, body),
+            Err((status, message)) => (status, serde_json::json!({ "error": message })),
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+        if let Err(e) = request.respond(response) {
+            eprintln!("could not respond: {}", e);
+        }
+    }
+    Ok(())
+}
+
+type Reply = Result<(u16, serde_json::Value), (u16, String)>;
+
+fn handle(request: &mut Request, defaults: &Defaults, runs: &Runs) -> Reply {
+    let path: Vec<_> = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let id = |segment: &str| {
+        segment
+            .parse::<usize>()
+            .ok()
+            .filter(|id| *id < runs.lock().unwrap().len())
+            .ok_or((404, format!("no run {}", segment)))
+    };
+
+    match (request.method(), path.as_slice()) {
+        (Method::Post, ["runs"]) => {
+            let mut body = String::new();
+            request
+                .as_reader()
+                .read_to_string(&mut body)
+                .map_err(|e| (400, e.to_string()))?;
+            let run_request: RunRequest = if body.trim().is_empty() {
+                RunRequest::default()
+            } else {
+                serde_json::from_str(&body).map_err(|e| (400, e.to_string()))?
+            };
+            start(run_request, defaults, runs).map(|id| (202, serde_json::json!({ "id": id })))
+        }
+        (Method::Get, ["runs"]) => {
+            let statuses: Vec<_> = runs
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|run| run.status.clone())
+                .collect();
+            Ok((200, serde_json::to_value(statuses).unwrap()))
+        }
+        (Method::Get, ["runs", id_segment]) => {
+            let id = id(id_segment)?;
+            let status = runs.lock().unwrap()[id].status.clone();
+            Ok((200, serde_json::to_value(status).unwrap()))
+        }
+        (Method::Post, ["runs", id_segment, "stop"]) => {
+            let id = id(id_segment)?;
+            runs.lock().unwrap()[id].stop.store(true, Ordering::Relaxed);
+            Ok((202, serde_json::json!({ "id": id })))
+        }
+        _ => Err((
+            404,
+            format!("no route for {} {}", request.method(), request.url()),
+        )),
+    }
+}
+
+fn start(request: RunRequest, defaults: &Defaults, runs: &Runs) -> Result<usize, (u16, String)> {
+    let generator = request.generator.unwrap_or(*defaults.generator);
+    let schema = request.schema.unwrap_or(*defaults.schema);
+    let config = defaults.config.clone();
+    let run = request.run;
+    let runner = move || {
+        BenchmarkRunner::new(config.clone())
+            .with_generator(generator)
+            .with_schema(schema)
+            .with_config(run.clone())
+    };
+    runner().validate().map_err(|e| (400, e.report()))?;
+
+    let mut all = runs.lock().unwrap();
+    if all.iter().any(|run| run.status.state == RunState::Running) {
+        return Err((409, "a run is in progress".to_string()));
+    }
+    let id = all.len();
+    let stop = Arc::new(AtomicBool::new(false));
+    all.push(Run {
+        status: RunStatus {
+            id,
+            state: RunState::Running,
+            run: runner().config().clone(),
+            generator,
+            schema,
+            results: Vec::new(),
+            environment: None,
+            error: None,
+        },
+        stop: stop.clone(),
+    });
+    drop(all);
+
+    let runs = runs.clone();
+    thread::spawn(move || {
+        // A server that can't be reached fails the run below.
+        runs.lock().unwrap()[id].status.environment = runner().environment().ok();
+        let results = runs.clone();
+        let outcome = runner()
+            .stop_flag(&stop)
+            .on_result(move |result| {
+                results.lock().unwrap()[id]
+                    .status
+                    .results
+                    .push(result.clone());
+            })
+            .run();
+
+        let status = &mut runs.lock().unwrap()[id].status;
+        status.state = match &outcome {
+            Err(_) => RunState::Failed,
+            Ok(_) if stop.load(Ordering::Relaxed) => RunState::Stopped,
+            Ok(_) => RunState::Finished,
+        };
+        status.error = outcome.err().map(|e| e.report());
+    });
+    Ok(id)
+}
@@ -0,0 +1,192 @@
+use crate::sql_log;
+use crate::tls;
+use crate::FastGenError;
+use postgres::{Client, Config};
+use serde::{Deserialize, Serialize};
+
+/// How often the server vacuumed and analyzed a table on its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutovacuumCounts {
+    pub vacuums: i64,
+    pub analyzes: i64,
+}
+
+impl AutovacuumCounts {
+    pub fn since(self, earlier: AutovacuumCounts) -> AutovacuumCounts {
+        AutovacuumCounts {
+            vacuums: self.vacuums - earlier.vacuums,
+            analyzes: self.analyzes - earlier.analyzes,
+        }
+    }
+}
+
+/// The cumulative counters lag by up to a second, as backends flush their
+/// statistics in the background.
+pub fn autovacuum_counts(client: &mut Client, table_name: &str) -> crate::Result<AutovacuumCounts> {
+    let row = client.query_opt(
+        "SELECT autovacuum_count, autoanalyze_count FROM pg_stat_user_tables WHERE relid = $1::text::regclass",
+        &[&table_name],
+    )?;
+    Ok(
+        row.map_or_else(AutovacuumCounts::default, |row| AutovacuumCounts {
+            vacuums: row.get(0),
+            analyzes: row.get(1),
+        }),
+    )
+}
+
+/// Keeps autovacuum off for a table while alive, then puts its previous
+/// `autovacuum_enabled` setting back, even if the run bails out early.
+pub struct AutovacuumPause {
+    client: Client,
+    table_name: String,
+    previous: Option<String>,
+}
+
+impl AutovacuumPause {
+    pub fn new(config: &Config, table_name: &str) -> crate::Result<Self> {
+        let mut client = tls::connect(config)?;
+        let previous = client
+            .query_opt(
+                "SELECT option_value FROM pg_class, pg_options_to_table(reloptions) \
+                 WHERE oid = $1::text::regclass AND option_name = 'autovacuum_enabled'",
+                &[&table_name],
+            )?
+            .map(|row| row.get(0));
+        let statement = format!(
+            "ALTER TABLE {} SET (autovacuum_enabled = false)",
+            table_name
+        );
+        sql_log::statement(&statement);
+        client.batch_execute(&statement)?;
+
+        Ok(AutovacuumPause {
+            client,
+            table_name: table_name.to_string(),
+            previous,
+        })
+    }
+}
+
+impl Drop for AutovacuumPause {
+    fn drop(&mut self) {
+        let statement = match &self.previous {
+            Some(value) => format!(
+                "ALTER TABLE {} SET (autovacuum_enabled = {})",
+                self.table_name, value
+            ),
+            None => format!("ALTER TABLE {} RESET (autovacuum_enabled)", self.table_name),
+        };
+        sql_log::statement(&statement);
+        if let Err(e) = self.client.batch_execute(&statement) {
+            eprintln!("could not restore autovacuum on {}: {}", self.table_name, e);
+        }
+    }
+}
+
+/// `pg_buffercache_evict` arrived in PostgreSQL 17 and needs the extension
+/// and a superuser.
+pub fn can_evict_buffers(client: &mut Client) -> crate::Result<bool> {
+    let row = client.query_one(
+        "SELECT current_setting('server_version_num')::int >= 170000 \
+         AND EXISTS (SELECT FROM pg_extension WHERE extname = 'pg_buffercache') \
+         AND (SELECT rolsuper FROM pg_roles WHERE rolname = current_user)",
+        &[],
+    )?;
+    Ok(row.get(0))
+}
+
+/// CHECKPOINT needs a superuser or, from PostgreSQL 15, a member of
+/// pg_checkpoint, which managed services seldom grant.
+pub fn can_checkpoint(client: &mut Client) -> crate::Result<bool> {
+    let row = client.query_one(
+        "SELECT rolsuper OR CASE WHEN current_setting('server_version_num')::int >= 150000 \
+             THEN pg_has_role(current_user, 'pg_checkpoint', 'MEMBER') ELSE false END \
+         FROM pg_roles WHERE rolname = current_user",
+        &[],
+    )?;
+    Ok(row.get(0))
+}
+
+/// Flushes dirty pages with a CHECKPOINT and optionally evicts every shared
+/// buffer, so a strategy neither pays for the previous one's deferred writes
+/// nor profits from the pages it left cached. Returns the buffers evicted.
+pub fn settle(client: &mut Client, evict: bool) -> crate::Result<i64> {
+    sql_log::statement("CHECKPOINT");
+    client.batch_execute("CHECKPOINT")?;
+    if !evict {
+        return Ok(0);
+    }
+    let row = client.query_one(
+        "SELECT count(*) FILTER (WHERE pg_buffercache_evict(bufferid)) \
+         FROM pg_buffercache WHERE relfilenode IS NOT NULL",
+        &[],
+    )?;
+    Ok(row.get(0))
+}
+
+/// Bytes of WAL written since the cluster was initialised, so that the
+/// difference between two readings is what every session wrote in between.
+/// Fails on a standby, and on Aurora, whose storage layer replaces the WAL.
+pub fn wal_position(client: &mut Client) -> crate::Result<i64> {
+    let row = client.query_one(
+        "SELECT pg_wal_lsn_diff(pg_current_wal_lsn(), '0/0')::bigint",
+        &[],
+    )?;
+    Ok(row.get(0))
+}
+
+/// Whether `wal_position` works on this server; a lost connection still
+/// fails.
+pub fn can_read_wal(client: &mut Client) -> crate::Result<bool> {
+    match wal_position(client) {
+        Ok(_) => Ok(true),
+        Err(FastGenError::Connection(e)) if e.as_db_error().is_some() => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn database_size(client: &mut Client) -> crate::Result<i64> {
+    let row = client.query_one("SELECT pg_database_size(current_database())", &[])?;
+    Ok(row.get(0))
+}
+
+/// Where a table's storage goes, in bytes, summed over its partitions and
+/// other inheritance children such as hypertable chunks. `total` also counts
+/// the free space and visibility maps, so it exceeds the sum of the parts.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct TableSizes {
+    pub heap: i64,
+    pub indexes: i64,
+    /// The TOAST table and its index.
+    pub toast: i64,
+    pub total: i64,
+}
+
+/// The rows in the table, partitions included; a scan of all of them.
+pub fn row_count(client: &mut Client, table_name: &str) -> crate::Result<i64> {
+    let statement = format!("SELECT count(*) FROM {}", table_name);
+    sql_log::statement(&statement);
+    Ok(client.query_one(&statement, &[])?.get(0))
+}
+
+pub fn table_sizes(client: &mut Client, table_name: &str) -> crate::Result<TableSizes> {
+    let row = client.query_one(
+        "WITH RECURSIVE tree(oid) AS ( \
+             SELECT $1::text::regclass::oid \
+             UNION ALL \
+             SELECT inhrelid FROM pg_inherits JOIN tree ON inhparent = tree.oid \
+         ) \
+         SELECT sum(pg_relation_size(oid))::bigint, sum(pg_indexes_size(oid))::bigint, \
+         sum(coalesce(pg_total_relation_size(nullif(reltoastrelid, 0)), 0))::bigint, \
+         sum(pg_total_relation_size(oid))::bigint \
+         FROM tree JOIN pg_class USING (oid)",
+        &[&table_name],
+    )?;
+    Ok(TableSizes {
+        heap: row.get(0),
+        indexes: row.get(1),
+        toast: row.get(2),
+        total: row.get(3),
+    })
+}
@@ -0,0 +1,89 @@
+//! Target ingestion rates that change over time, to watch how checkpoints
+//! and autovacuum respond to ramps and spikes instead of to a flat-out load.
+
+use crate::FastGenError;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Shape {
+    /// The full rate throughout
+    #[default]
+    Steady,
+    /// From the lowest rate up to the full one over a period, then steady
+    Ramp,
+    /// Alternating half periods at the full and at the lowest rate
+    Burst,
+    /// A sine wave between the lowest and the full rate
+    Sine,
+}
+
+/// A target rate in rows per second across all workers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadShape {
+    pub shape: Shape,
+    pub rate: u64,
+    /// Where ramps start and the troughs of bursts and sines lie.
+    pub min_rate: u64,
+    pub period_secs: u64,
+}
+
+impl LoadShape {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.rate == 0 || self.period_secs == 0 {
+            return Err(FastGenError::InvalidConfig(
+                "--rate and --period-secs must be positive".to_string(),
+            ));
+        }
+        if self.min_rate > self.rate {
+            return Err(FastGenError::InvalidConfig(
+                "--min-rate must not exceed --rate".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The target rate `seconds` into the load.
+    pub fn rate_at(&self, seconds: f64) -> f64 {
+        let (max, min, period) = (
+            self.rate as f64,
+            self.min_rate as f64,
+            self.period_secs as f64,
+        );
+        match self.shape {
+            Shape::Steady => max,
+            Shape::Ramp => min + (max - min) * (seconds / period).min(1.0),
+            Shape::Burst if seconds % period < period / 2.0 => max,
+            Shape::Burst => min,
+            Shape::Sine => (max + min) / 2.0 + (max - min) / 2.0 * (TAU * seconds / period).sin(),
+        }
+    }
+
+    /// Rows the target rate allows in the first `seconds` of the load.
+    pub fn rows_by(&self, seconds: f64) -> f64 {
+        let (max, min, period) = (
+            self.rate as f64,
+            self.min_rate as f64,
+            self.period_secs as f64,
+        );
+        match self.shape {
+            Shape::Steady => max * seconds,
+            Shape::Ramp if seconds < period => {
+                min * seconds + (max - min) * seconds * seconds / (2.0 * period)
+            }
+            Shape::Ramp => (max + min) / 2.0 * period + max * (seconds - period),
+            Shape::Burst => {
+                let half = period / 2.0;
+                let periods = (seconds / period).floor();
+                let into = seconds - periods * period;
+                periods * (max + min) * half + max * into.min(half) + min * (into - half).max(0.0)
+            }
+            Shape::Sine => {
+                (max + min) / 2.0 * seconds
+                    + (max - min) / 2.0 * period / TAU * (1.0 - (TAU * seconds / period).cos())
+            }
+        }
+    }
+}
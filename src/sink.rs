@@ -0,0 +1,116 @@
+//! The `sink` command: the workload `run` loads, sent somewhere other than
+//! Postgres instead and timed the way `export` times COPY, so that other
+//! ingest paths can be compared on exactly the same rows.
+
+use crate::cli::SinkTarget;
+use fast_generation::avro::AvroFileWriter;
+use fast_generation::export::ExportStats;
+use fast_generation::generator::{generate_data, GeneratorConfig, Row, BASE_TEMP};
+use fast_generation::schema::SchemaConfig;
+use std::fs::File;
+use std::io::BufWriter;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Somewhere rows can be sent, a batch at a time.
+pub trait Sink {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()>;
+
+    /// Waits for everything sent to arrive and returns the bytes sent.
+    fn finish(self: Box<Self>) -> anyhow::Result<u64>;
+}
+
+impl Sink for AvroFileWriter<BufWriter<File>> {
+    fn send(&mut self, batch: &[Row]) -> anyhow::Result<()> {
+        Ok(self.write_rows(batch)?)
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<u64> {
+        Ok(AvroFileWriter::finish(*self)?)
+    }
+}
+
+fn open(target: &SinkTarget, schema: &SchemaConfig, name: &str) -> anyhow::Result<Box<dyn Sink>> {
+    Ok(match target {
+        SinkTarget::Avro { output } => {
+            let file = File::create(output)
+                .map_err(|e| anyhow::anyhow!("creating {}: {}", output.display(), e))?;
+            Box::new(AvroFileWriter::new(BufWriter::new(file), schema, name)?)
+        }
+        #[cfg(feature = "kafka")]
+        SinkTarget::Kafka(args) => Box::new(crate::kafka::KafkaSink::new(args, schema, name)?),
+        SinkTarget::Influx(args) => Box::new(crate::influx::InfluxSink::new(args, schema, name)?),
+        #[cfg(feature = "redis")]
+        SinkTarget::Redis(args) => Box::new(crate::redis::RedisSink::new(args, schema, name)?),
+        #[cfg(feature = "nats")]
+        SinkTarget::Nats(args) => Box::new(crate::nats::NatsSink::new(args, schema, name)?),
+        #[cfg(feature = "http")]
+        SinkTarget::Http(args) => Box::new(crate::http::HttpSink::new(args, schema)?),
+        #[cfg(feature = "upload")]
+        SinkTarget::S3(args) => Box::new(crate::s3::S3Sink::new(args, schema, name)?),
+        #[cfg(feature = "bigquery")]
+        SinkTarget::Bigquery(args) => {
+            Box::new(crate::bigquery::BigquerySink::new(args, schema, name)?)
+        }
+        #[cfg(feature = "mongodb")]
+        SinkTarget::Mongodb(args) => {
+            Box::new(crate::mongodb::MongodbSink::new(args, schema, name)?)
+        }
+        #[cfg(feature = "cql")]
+        SinkTarget::Cql(args) => Box::new(crate::cql::CqlSink::new(args, schema, name)?),
+        #[cfg(feature = "http")]
+        SinkTarget::Elasticsearch(args) => Box::new(crate::elasticsearch::ElasticsearchSink::new(
+            args, schema, name,
+        )?),
+        #[cfg(feature = "mssql")]
+        SinkTarget::Mssql(args) => Box::new(crate::mssql::MssqlSink::new(args, schema, name)?),
+        #[cfg(all(unix, feature = "oracle"))]
+        SinkTarget::Oracle(args) => Box::new(crate::oracle::OracleSink::new(args, schema, name)?),
+    })
+}
+
+/// Sends `batch_count` generated batches to `target`, the rows of a table
+/// named `name`, no faster than `rate` rows per second if given, and
+/// returns the rows sent and the time and bytes it took.
+pub fn sink(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batch_count: usize,
+    rate: Option<u64>,
+    target: &SinkTarget,
+    name: &str,
+) -> anyhow::Result<(usize, ExportStats)> {
+    if rate == Some(0) {
+        anyhow::bail!("--rate must be positive");
+    }
+    send_all(generator, batch_count, rate, open(target, schema, name)?)
+}
+
+/// Sends `batch_count` generated batches to an open `sink`, as `sink` does.
+pub fn send_all(
+    generator: &GeneratorConfig,
+    batch_count: usize,
+    rate: Option<u64>,
+    mut sink: Box<dyn Sink>,
+) -> anyhow::Result<(usize, ExportStats)> {
+    let started = Instant::now();
+    let mut rows = 0;
+    for (batch, _) in generate_data(generator, BASE_TEMP, batch_count) {
+        if let Some(rate) = rate {
+            // Until the rows sent so far are due.
+            let due = Duration::from_secs_f64(rows as f64 / rate as f64);
+            thread::sleep(due.saturating_sub(started.elapsed()));
+        }
+        sink.send(&batch)?;
+        rows += batch.len();
+    }
+    let bytes = sink.finish()?;
+    Ok((
+        rows,
+        ExportStats {
+            bytes,
+            elapsed: started.elapsed(),
+            files: Vec::new(),
+        },
+    ))
+}
@@ -0,0 +1,100 @@
+//! `--slo-*`: objectives a run passes or fails as a whole, so that nightly
+//! runs can gate deploys on the exit code.
+
+use crate::cli::SloArgs;
+use fast_generation::StrategyResult;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The exit code of a run that failed an objective.
+pub const EXIT_CODE: i32 = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SloOutcome {
+    /// E.g. "p99 batch latency <= 250ms".
+    pub objective: String,
+    pub passed: bool,
+    /// The worst value measured, and where.
+    pub detail: String,
+}
+
+/// The run failed these many objectives.
+#[derive(Debug)]
+pub struct SloViolation(pub usize);
+
+impl fmt::Display for SloViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} SLO(s) failed", self.0)
+    }
+}
+
+impl std::error::Error for SloViolation {}
+
+fn label(result: &StrategyResult) -> String {
+    format!("{} #{}", result.strategy, result.iteration + 1)
+}
+
+pub fn evaluate(args: &SloArgs, results: &[StrategyResult]) -> Vec<SloOutcome> {
+    let succeeded: Vec<_> = results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .collect();
+    let mut outcomes = Vec::new();
+
+    if let Some(max_ms) = args.slo_p99_ms {
+        let worst = succeeded
+            .iter()
+            .filter_map(|result| Some((result, result.latency?.p99.as_secs_f64() * 1000.0)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        outcomes.push(SloOutcome {
+            objective: format!("p99 batch latency <= {}ms", max_ms),
+            passed: worst.is_none_or(|(_, p99)| p99 <= max_ms as f64),
+            detail: worst.map_or("no batches".to_string(), |(result, p99)| {
+                format!("{:.1}ms at worst, {}", p99, label(result))
+            }),
+        });
+    }
+    if let Some(min_rate) = args.slo_min_rows_per_sec {
+        let worst = succeeded
+            .iter()
+            .map(|result| (result, result.rows_per_second()))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        outcomes.push(SloOutcome {
+            objective: format!("throughput >= {} rows/s", min_rate),
+            passed: worst.is_none_or(|(_, rate)| rate >= min_rate as f64),
+            detail: worst.map_or("no results".to_string(), |(result, rate)| {
+                format!("{:.0} rows/s at worst, {}", rate, label(result))
+            }),
+        });
+    }
+    if let Some(max_errors) = args.slo_max_errors {
+        let failed: Vec<_> = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .map(label)
+            .collect();
+        outcomes.push(SloOutcome {
+            objective: format!("failed strategies <= {}", max_errors),
+            passed: failed.len() <= max_errors,
+            detail: if failed.is_empty() {
+                "none failed".to_string()
+            } else {
+                format!("{} failed: {}", failed.len(), failed.join(", "))
+            },
+        });
+    }
+    outcomes
+}
+
+pub fn print_outcomes(outcomes: &[SloOutcome]) {
+    println!();
+    println!("SLOs:");
+    for outcome in outcomes {
+        println!(
+            " {}: {} ({})",
+            outcome.objective,
+            if outcome.passed { "pass" } else { "FAIL" },
+            outcome.detail
+        );
+    }
+}
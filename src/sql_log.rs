@@ -0,0 +1,64 @@
+//! A copy of the statements a run issues, written as a script psql can
+//! replay: session settings, hooks and other SQL in full, and the first
+//! batch each strategy loads as a sample of the rest.
+
+use crate::FastGenError;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+struct SqlLog {
+    file: BufWriter<File>,
+    sampled: HashSet<String>,
+}
+
+static LOG: OnceLock<Mutex<SqlLog>> = OnceLock::new();
+
+/// Starts writing statements to `path`, replacing it; only the first call
+/// has an effect.
+pub fn open(path: &Path) -> crate::Result<()> {
+    let file = File::create(path)
+        .map_err(|e| FastGenError::InvalidConfig(format!("creating {}: {}", path.display(), e)))?;
+    let _ = LOG.set(Mutex::new(SqlLog {
+        file: BufWriter::new(file),
+        sampled: HashSet::new(),
+    }));
+    Ok(())
+}
+
+fn write(text: &str) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+    let mut log = log.lock().unwrap();
+    let terminator = if text.trim_end().ends_with(';') || text.ends_with("\\.") {
+        "\n"
+    } else {
+        ";\n"
+    };
+    // Losing the log is no reason to fail the run.
+    if let Err(e) =
+        write!(log.file, "{}{}", text.trim_end(), terminator).and_then(|()| log.file.flush())
+    {
+        eprintln!("could not write the SQL log: {}", e);
+    }
+}
+
+/// Logs `statement` as issued.
+pub fn statement(statement: &str) {
+    write(statement);
+}
+
+/// Logs what `render` returns the first time it's called for `key`, e.g. a
+/// strategy name, and nothing after; `render` only runs then.
+pub fn sample(key: &str, render: impl FnOnce() -> String) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+    if !log.lock().unwrap().sampled.insert(key.to_string()) {
+        return;
+    }
+    write(&render());
+}
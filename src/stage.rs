@@ -0,0 +1,339 @@
+//! `stage`: uploads the rows to object storage as `sink s3` does, then has
+//! a cloud warehouse load them with its own COPY, timing both phases, so
+//! that warehouse ingest can be compared with `run` on the same rows.
+
+use crate::cli::{RedshiftArgs, S3Args, Warehouse};
+use crate::s3::{ObjectFormat, S3Sink};
+use crate::sink;
+use crate::upload::Uploader;
+use fast_generation::export::{Codec, ExportStats};
+use fast_generation::generator::GeneratorConfig;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::tls;
+use postgres::{Client, Config};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "http")]
+pub use snowflake::TokenType;
+
+/// The rows staged, and what uploading and loading them took.
+pub struct Staged {
+    pub rows: usize,
+    pub upload: ExportStats,
+    pub load: Duration,
+}
+
+/// A warehouse, connected before anything is uploaded so that bad settings
+/// fail early.
+enum Loader {
+    Redshift {
+        client: Client,
+        table: String,
+        iam_role: String,
+        options: String,
+    },
+    #[cfg(feature = "http")]
+    Snowflake(snowflake::Api),
+}
+
+impl Loader {
+    /// The statement loading the files `uploader` uploads.
+    fn statement(&self, uploader: &Uploader) -> String {
+        match self {
+            Loader::Redshift {
+                table,
+                iam_role,
+                options,
+                ..
+            } => format!(
+                "COPY {} FROM '{}/' IAM_ROLE '{}' {}",
+                table,
+                uploader.directory(),
+                iam_role,
+                options
+            ),
+            #[cfg(feature = "http")]
+            Loader::Snowflake(api) => {
+                // The stage's URL is --uri, so the run's directory is the
+                // last part of its URI.
+                let directory = uploader.directory();
+                api.statement(directory.rsplit('/').next().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Runs `statement` and returns the rows it loaded.
+    fn load(&mut self, statement: &str) -> anyhow::Result<u64> {
+        match self {
+            Loader::Redshift { client, .. } => {
+                client.batch_execute(statement)?;
+                let loaded: i64 = client.query_one("SELECT pg_last_copy_count()", &[])?.get(0);
+                Ok(loaded as u64)
+            }
+            #[cfg(feature = "http")]
+            Loader::Snowflake(api) => api.copy_into(statement),
+        }
+    }
+}
+
+/// The options of a Redshift COPY reading the files as written.
+fn redshift_options(files: &S3Args) -> String {
+    match files.format {
+        ObjectFormat::Parquet => "FORMAT AS PARQUET".to_string(),
+        ObjectFormat::Csv => {
+            let mut options = "FORMAT AS CSV TIMEFORMAT 'auto'".to_string();
+            if files.header {
+                options.push_str(" IGNOREHEADER 1");
+            }
+            match files.compress {
+                Some(Codec::Gzip) => options.push_str(" GZIP"),
+                Some(Codec::Zstd) => options.push_str(" ZSTD"),
+                None => {}
+            }
+            options
+        }
+    }
+}
+
+fn connect_redshift(args: &RedshiftArgs) -> anyhow::Result<Loader> {
+    if !args.files.uri.starts_with("s3://") {
+        anyhow::bail!(
+            "Redshift loads from s3:// URIs only, not {:?}",
+            args.files.uri
+        );
+    }
+    let config: Config = args.dsn.parse()?;
+    Ok(Loader::Redshift {
+        client: tls::connect(&config)?,
+        table: args.table.clone(),
+        iam_role: args.iam_role.clone(),
+        options: redshift_options(&args.files),
+    })
+}
+
+/// Uploads `batch_count` generated batches for `warehouse`, the rows of a
+/// table named `name`, and has it load them.
+pub fn stage(
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    batch_count: usize,
+    warehouse: &Warehouse,
+    name: &str,
+) -> anyhow::Result<Staged> {
+    let (mut loader, files) = match warehouse {
+        Warehouse::Redshift(args) => (connect_redshift(args)?, &args.files),
+        #[cfg(feature = "http")]
+        Warehouse::Snowflake(args) => (
+            Loader::Snowflake(snowflake::Api::new(args, schema)?),
+            &args.files,
+        ),
+    };
+    let sink = S3Sink::new(files, schema, name)?;
+    let statement = loader.statement(sink.uploader());
+    let (rows, upload) = sink::send_all(generator, batch_count, None, Box::new(sink))?;
+
+    println!("{}", statement);
+    let started = Instant::now();
+    let loaded = loader.load(&statement)?;
+    let load = started.elapsed();
+    if loaded != rows as u64 {
+        anyhow::bail!(
+            "{} loaded {} of the {} rows uploaded",
+            warehouse.name(),
+            loaded,
+            rows
+        );
+    }
+    Ok(Staged { rows, upload, load })
+}
+
+#[cfg(feature = "http")]
+mod snowflake {
+    use crate::cli::{S3Args, SnowflakeArgs};
+    use crate::s3::ObjectFormat;
+    use clap::ValueEnum;
+    use fast_generation::export::Codec;
+    use fast_generation::schema::{SchemaConfig, TimeColumn};
+    use serde_json::{json, Value};
+    use std::thread;
+    use std::time::Duration;
+    use ureq::Agent;
+
+    /// How often to ask whether a statement still running is done.
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    #[derive(Clone, Copy, Debug, Default, ValueEnum)]
+    pub enum TokenType {
+        /// An OAuth access token
+        #[default]
+        Oauth,
+        /// A JWT signed with the user's key pair
+        KeypairJwt,
+        /// A programmatic access token
+        ProgrammaticAccessToken,
+    }
+
+    impl TokenType {
+        fn header(self) -> &'static str {
+            match self {
+                TokenType::Oauth => "OAUTH",
+                TokenType::KeypairJwt => "KEYPAIR_JWT",
+                TokenType::ProgrammaticAccessToken => "PROGRAMMATIC_ACCESS_TOKEN",
+            }
+        }
+    }
+
+    /// Runs statements through the SQL API.
+    pub struct Api {
+        agent: Agent,
+        url: String,
+        token: String,
+        token_type: TokenType,
+        stage: String,
+        table: String,
+        file_format: String,
+        context: Value,
+    }
+
+    impl Api {
+        pub fn new(args: &SnowflakeArgs, schema: &SchemaConfig) -> anyhow::Result<Api> {
+            let token = match &args.token {
+                Some(token) => token.clone(),
+                None => std::env::var("SNOWFLAKE_TOKEN")
+                    .map_err(|_| anyhow::anyhow!("--token or SNOWFLAKE_TOKEN is required"))?,
+            };
+            let mut context = json!({});
+            for (key, value) in [
+                ("warehouse", &args.warehouse),
+                ("database", &args.database),
+                ("schema", &args.schema),
+                ("role", &args.role),
+            ] {
+                if let Some(value) = value {
+                    context[key] = json!(value);
+                }
+            }
+            Ok(Api {
+                agent: Agent::config_builder()
+                    .http_status_as_error(false)
+                    .build()
+                    .into(),
+                url: args.account_url.trim_end_matches('/').to_string(),
+                token,
+                token_type: args.token_type,
+                stage: args.stage.trim_start_matches('@').to_string(),
+                table: args.table.clone(),
+                file_format: file_format(&args.files, schema),
+                context,
+            })
+        }
+
+        /// Runs `statement`, waiting for it to finish, and returns the
+        /// result's first partition.
+        fn execute(&self, statement: &str) -> anyhow::Result<Value> {
+            let mut body = self.context.clone();
+            body["statement"] = json!(statement);
+            body["timeout"] = json!(0);
+            let mut response = self
+                .agent
+                .post(&format!("{}/api/v2/statements", self.url))
+                .header("Authorization", &format!("Bearer {}", self.token))
+                .header(
+                    "X-Snowflake-Authorization-Token-Type",
+                    self.token_type.header(),
+                )
+                .header("Accept", "application/json")
+                .send_json(body)?;
+            loop {
+                let status = response.status();
+                let result: Value = response.body_mut().read_json().unwrap_or_default();
+                match status.as_u16() {
+                    200 => return Ok(result),
+                    202 => {
+                        let Some(status_url) = result["statementStatusUrl"].as_str() else {
+                            anyhow::bail!(
+                                "{} returned no statement to wait for: {}",
+                                self.url,
+                                result
+                            );
+                        };
+                        thread::sleep(POLL_INTERVAL);
+                        response = self
+                            .agent
+                            .get(&format!("{}{}", self.url, status_url))
+                            .header("Authorization", &format!("Bearer {}", self.token))
+                            .header(
+                                "X-Snowflake-Authorization-Token-Type",
+                                self.token_type.header(),
+                            )
+                            .header("Accept", "application/json")
+                            .call()?;
+                    }
+                    _ => anyhow::bail!(
+                        "running {:?} on {}: {}: {}",
+                        statement,
+                        self.url,
+                        status,
+                        result["message"].as_str().unwrap_or_default()
+                    ),
+                }
+            }
+        }
+
+        /// COPY INTO the table of the files of the run directory `run`
+        /// under the stage.
+        pub fn statement(&self, run: &str) -> String {
+            format!(
+                "COPY INTO {} FROM @{}/{}/ {} ON_ERROR = ABORT_STATEMENT",
+                self.table, self.stage, run, self.file_format
+            )
+        }
+
+        /// Runs a COPY INTO and returns the rows it loaded.
+        pub fn copy_into(&self, statement: &str) -> anyhow::Result<u64> {
+            let result = self.execute(statement)?;
+            // A row per file loaded, or a single status if there were none.
+            let columns = result["resultSetMetaData"]["rowType"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            let Some(column) = columns
+                .iter()
+                .position(|column| column["name"].as_str() == Some("rows_loaded"))
+            else {
+                return Ok(0);
+            };
+            Ok(result["data"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|row| row[column].as_str()?.parse::<u64>().ok())
+                .sum())
+        }
+    }
+
+    /// The file format and options reading the files as written.
+    fn file_format(files: &S3Args, schema: &SchemaConfig) -> String {
+        match files.format {
+            ObjectFormat::Parquet => {
+                "FILE_FORMAT = (TYPE = PARQUET) MATCH_BY_COLUMN_NAME = CASE_INSENSITIVE".to_string()
+            }
+            ObjectFormat::Csv => {
+                let compression = match files.compress {
+                    Some(Codec::Gzip) => "GZIP",
+                    Some(Codec::Zstd) => "ZSTD",
+                    None => "NONE",
+                };
+                let timestamp_format = match schema.time_column {
+                    TimeColumn::Timestamptz => " TIMESTAMP_FORMAT = 'YYYY-MM-DD HH24:MI:SS.FF6TZH'",
+                    TimeColumn::Timestamp => " TIMESTAMP_FORMAT = 'YYYY-MM-DD HH24:MI:SS.FF6'",
+                    TimeColumn::Bigint => "",
+                };
+                format!(
+                    "FILE_FORMAT = (TYPE = CSV SKIP_HEADER = {} COMPRESSION = {}{})",
+                    files.header as u8, compression, timestamp_format
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! The `storage` command: repeats the same load into a fresh scratch copy of
+//! the table for every combination of the storage parameters given, to
+//! automate storage tuning experiments.
+
+use crate::cli::StorageArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, StrategyResult};
+use postgres::Config;
+
+/// How the server compresses the values of `temperature` it TOASTs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    Pglz,
+    /// Needs a server built with lz4
+    Lz4,
+}
+
+impl Compression {
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Pglz => "pglz",
+            Compression::Lz4 => "lz4",
+        }
+    }
+}
+
+/// One combination; `None` leaves the server's default.
+#[derive(Clone, Copy, Debug)]
+struct StorageParams {
+    fillfactor: Option<u32>,
+    toast_tuple_target: Option<u32>,
+    compression: Option<Compression>,
+}
+
+impl StorageParams {
+    fn create(&self, scratch: &str, table_name: &str) -> String {
+        let options: Vec<_> = [
+            ("fillfactor", self.fillfactor),
+            ("toast_tuple_target", self.toast_tuple_target),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{} = {}", name, value?)))
+        .collect();
+        let mut sql = format!(
+            "DROP TABLE IF EXISTS {scratch};
+             CREATE TABLE {scratch} (LIKE {table_name} INCLUDING DEFAULTS)"
+        );
+        if !options.is_empty() {
+            sql += &format!(" WITH ({})", options.join(", "));
+        }
+        if let Some(compression) = self.compression {
+            sql += &format!(
+                "; ALTER TABLE {} ALTER COLUMN temperature SET COMPRESSION {}",
+                scratch,
+                compression.name()
+            );
+        }
+        sql
+    }
+}
+
+fn or_default<T: ToString>(value: Option<T>) -> String {
+    value.map_or("default".to_string(), |value| value.to_string())
+}
+
+/// Every combination of the values given, with `None` alone for a
+/// parameter given none.
+fn combinations(args: &StorageArgs) -> Vec<StorageParams> {
+    fn values<T: Copy>(given: &[T]) -> Vec<Option<T>> {
+        if given.is_empty() {
+            vec![None]
+        } else {
+            given.iter().copied().map(Some).collect()
+        }
+    }
+    let mut combinations = Vec::new();
+    for fillfactor in values(&args.fillfactor) {
+        for toast_tuple_target in values(&args.toast_tuple_target) {
+            for compression in values(&args.compression) {
+                combinations.push(StorageParams {
+                    fillfactor,
+                    toast_tuple_target,
+                    compression,
+                });
+            }
+        }
+    }
+    combinations
+}
+
+fn print_matrix(results: &[(StorageParams, StrategyResult)]) {
+    println!();
+    println!(
+        "{:>10} {:>12} {:>11} {:>10} {:>14} {:>12} {:>12} {:>12}",
+        "fillfactor", "toast target", "compression", "rows/s", "bytes/s", "heap", "TOAST", "WAL"
+    );
+    for (params, result) in results {
+        let labels = format!(
+            "{:>10} {:>12} {:>11}",
+            or_default(params.fillfactor),
+            or_default(params.toast_tuple_target),
+            or_default(params.compression.map(Compression::name))
+        );
+        if let Some(e) = &result.error {
+            println!("{} failed: {}", labels, e);
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        println!(
+            "{} {:>10.0} {:>14} {:>12} {:>12} {:>12}",
+            labels,
+            result.rows_per_second(),
+            units::rate(size / result.elapsed.as_secs_f64()),
+            units::bytes(result.after.heap as f64),
+            units::bytes(result.after.toast as f64),
+            units::bytes(result.wal_bytes as f64)
+        );
+    }
+}
+
+pub fn storage(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &StorageArgs,
+) -> anyhow::Result<()> {
+    let scratch = &args.scratch_table;
+    let mut client = tls::connect(config)?;
+
+    let mut results = Vec::new();
+    let outcome = combinations(args)
+        .into_iter()
+        .try_for_each(|params| -> anyhow::Result<()> {
+            println!(
+                "Loading with fillfactor {}, toast_tuple_target {}, compression {}",
+                or_default(params.fillfactor),
+                or_default(params.toast_tuple_target),
+                or_default(params.compression.map(Compression::name))
+            );
+            client.batch_execute(&params.create(scratch, table_name))?;
+            let mut loaded = BenchmarkRunner::new(config.clone())
+                .with_generator(*generator)
+                .with_schema(*schema)
+                .with_config(RunConfig {
+                    strategies: vec![args.strategy.clone()],
+                    table_name: scratch.clone(),
+                    batch_count,
+                    workers: args.workers,
+                    ..RunConfig::default()
+                })
+                .run()?;
+            results.push((params, loaded.remove(0)));
+            Ok(())
+        });
+    client.batch_execute(&format!("DROP TABLE IF EXISTS {}", scratch))?;
+    outcome?;
+
+    print_matrix(&results);
+    Ok(())
+}
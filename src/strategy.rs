@@ -0,0 +1,308 @@
+use crate::cache::EncodeCache;
+use crate::generator::Row;
+use crate::pgcopy::{self, write_buffer};
+use crate::pool::{BufferPool, PooledBuffer};
+use crate::schema::{SchemaConfig, TimeValue};
+use crate::FastGenError;
+use bytes::BytesMut;
+use postgres::Client;
+use postgres_types::{to_sql_checked, IsNull, ToSql, Type};
+use rust_decimal::Decimal;
+use std::{error::Error, fmt::Write as _, io::Write, str::FromStr};
+
+/// Everything a strategy needs besides its connection and the batch; shared
+/// by all workers running the strategy.
+pub struct StrategyContext<'a> {
+    pub table_name: &'a str,
+    pub schema: &'a SchemaConfig,
+    pub buffers: &'a BufferPool,
+    /// Bytes per `CopyData` message; the whole batch goes as one if unset.
+    pub copy_chunk_size: Option<usize>,
+}
+
+/// A batch serialized for one strategy, ready to be sent.
+pub enum Payload<'a> {
+    /// Bind parameters for the prepared INSERT, converted up front.
+    Params(Vec<(TimeValue, i32, NumericValue)>),
+    /// A complete multi-row INSERT statement.
+    Query(String),
+    /// A complete binary COPY stream.
+    Copy(PooledBuffer<'a>),
+}
+
+impl Payload<'_> {
+    /// Memory the payload takes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Payload::Params(params) => {
+                params.capacity() * std::mem::size_of::<(TimeValue, i32, NumericValue)>()
+            }
+            Payload::Query(query) => query.capacity(),
+            Payload::Copy(buffer) => buffer.capacity(),
+        }
+    }
+}
+
+/// A loading strategy, split so that encoding the next batch can overlap
+/// with sending the current one.
+#[derive(Clone, Copy)]
+pub struct Strategy {
+    pub name: &'static str,
+    pub encode: for<'a> fn(&StrategyContext<'a>, &[Row]) -> crate::Result<Payload<'a>>,
+    pub send: fn(&mut Client, &StrategyContext, Payload) -> crate::Result<()>,
+}
+
+impl Strategy {
+    /// SQL psql can run to load `batch_data` the way this strategy does;
+    /// COPY and other strategies are written as a text COPY.
+    pub fn replay_sql(
+        &self,
+        context: &StrategyContext,
+        batch_data: &[Row],
+    ) -> crate::Result<String> {
+        let time_column = context.schema.time_column;
+        let mut sql = String::new();
+        match self.name {
+            "insert" => {
+                sql.push_str("BEGIN;\n");
+                for row in batch_data {
+                    writeln!(
+                        sql,
+                        "INSERT INTO {} VALUES ({}, {}, {});",
+                        context.table_name,
+                        time_column.literal(row.0),
+                        row.1,
+                        numeric_literal(row.2)
+                    )
+                    .unwrap();
+                }
+                sql.push_str("COMMIT;");
+            }
+            "insert-str" => {
+                let Payload::Query(query) = encode_query(context, batch_data)? else {
+                    unreachable!("insert-str is always encoded as a query")
+                };
+                sql = query;
+            }
+            name => {
+                writeln!(
+                    sql,
+                    "-- {} sends its batches in its own format; this one as text:",
+                    name
+                )
+                .unwrap();
+                writeln!(sql, "COPY {} FROM STDIN;", context.table_name).unwrap();
+                for row in batch_data {
+                    let temperature = match row.2 {
+                        value if value.is_infinite() && value > 0.0 => "Infinity".to_string(),
+                        value if value.is_infinite() => "-Infinity".to_string(),
+                        value => value.to_string(),
+                    };
+                    writeln!(
+                        sql,
+                        "{}\t{}\t{}",
+                        time_column.text(row.0),
+                        row.1,
+                        temperature
+                    )
+                    .unwrap();
+                }
+                sql.push_str("\\.");
+            }
+        }
+        Ok(sql)
+    }
+}
+
+pub const STRATEGIES: [Strategy; 3] = [
+    Strategy {
+        name: "insert",
+        encode: encode_params,
+        send: insert_to_postgres,
+    },
+    Strategy {
+        name: "insert-str",
+        encode: encode_query,
+        send: insert_to_postgres_string,
+    },
+    Strategy {
+        name: "copy",
+        encode: encode_copy,
+        send: copy_to_postgres,
+    },
+];
+
+fn f64_to_decimal(value: f64) -> Decimal {
+    Decimal::from_str(&value.to_string()).unwrap_or_else(|_| Decimal::new(0, 0))
+}
+
+/// `Decimal` has no NaN or infinity, which `f64_to_decimal` would silently
+/// turn into zero; those are sent with the COPY encoder's bytes instead.
+#[derive(Debug)]
+pub enum NumericValue {
+    Decimal(Decimal),
+    Special(f64),
+}
+
+impl NumericValue {
+    pub(crate) fn new(value: f64) -> Self {
+        if value.is_finite() {
+            NumericValue::Decimal(f64_to_decimal(value))
+        } else {
+            NumericValue::Special(value)
+        }
+    }
+}
+
+impl ToSql for NumericValue {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self {
+            NumericValue::Decimal(decimal) => decimal.to_sql(ty, out),
+            NumericValue::Special(value) => {
+                out.extend_from_slice(&pgcopy::numeric_to_postgres_binary(*value));
+                Ok(IsNull::No)
+            }
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+
+    to_sql_checked!();
+}
+
+fn numeric_literal(value: f64) -> String {
+    if value.is_nan() {
+        "'NaN'".to_string()
+    } else if value.is_infinite() {
+        format!("'{}Infinity'", if value > 0.0 { "" } else { "-" })
+    } else {
+        value.to_string()
+    }
+}
+
+fn encode_params<'a>(
+    context: &StrategyContext<'a>,
+    batch_data: &[Row],
+) -> crate::Result<Payload<'a>> {
+    Ok(Payload::Params(
+        batch_data
+            .iter()
+            .map(|row| {
+                (
+                    context.schema.time_column.value(row.0),
+                    row.1,
+                    NumericValue::new(row.2),
+                )
+            })
+            .collect(),
+    ))
+}
+
+/// Timestamp and sensor id literals are formatted once per distinct value,
+/// as every row of a batch shares one timestamp and the sensors repeat.
+fn encode_query<'a>(
+    context: &StrategyContext<'a>,
+    batch_data: &[Row],
+) -> crate::Result<Payload<'a>> {
+    let time_column = context.schema.time_column;
+    let mut literals = EncodeCache::new();
+    let mut sensor_ids = EncodeCache::new();
+    let mut query = format!("INSERT INTO {} VALUES ", context.table_name);
+    for (i, row) in batch_data.iter().enumerate() {
+        let created = literals.get_or_try_insert(row.0, |created| {
+            Ok::<_, FastGenError>(time_column.literal(created))
+        })?;
+        let sensor_id = sensor_ids.get_or_try_insert(row.1, |sensor_id| {
+            Ok::<_, FastGenError>(sensor_id.to_string())
+        })?;
+        if i > 0 {
+            query.push(',');
+        }
+        write!(
+            query,
+            "({}, {}, {}::numeric(10, 2))",
+            created,
+            sensor_id,
+            numeric_literal(row.2)
+        )
+        .unwrap();
+    }
+    Ok(Payload::Query(query))
+}
+
+fn encode_copy<'a>(
+    context: &StrategyContext<'a>,
+    batch_data: &[Row],
+) -> crate::Result<Payload<'a>> {
+    let mut buffer = context.buffers.take();
+    write_buffer(&mut buffer, context.schema, batch_data)?;
+    Ok(Payload::Copy(buffer))
+}
+
+pub fn insert_to_postgres(
+    client: &mut Client,
+    context: &StrategyContext,
+    payload: Payload,
+) -> crate::Result<()> {
+    let Payload::Params(rows) = payload else {
+        unreachable!("insert is always encoded as bind parameters")
+    };
+    let mut tx = client.transaction()?;
+    let stmt = tx.prepare(&format!(
+        "INSERT INTO {} VALUES ($1, $2, $3)",
+        context.table_name
+    ))?;
+
+    for row in &rows {
+        let params: [&(dyn ToSql + Sync); 3] = [&row.0, &row.1, &row.2];
+        tx.execute(&stmt, &params)?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn copy_to_postgres(
+    client: &mut Client,
+    context: &StrategyContext,
+    payload: Payload,
+) -> crate::Result<()> {
+    let Payload::Copy(buffer) = payload else {
+        unreachable!("copy is always encoded as a COPY stream")
+    };
+    let mut writer = client.copy_in(&format!(
+        "COPY {} FROM STDIN WITH BINARY",
+        context.table_name
+    ))?;
+    match context.copy_chunk_size {
+        // The writer coalesces small writes, so flush to send each chunk as
+        // a message of its own.
+        Some(size) => {
+            for chunk in buffer.chunks(size) {
+                writer.write_all(chunk)?;
+                writer.flush()?;
+            }
+        }
+        None => writer.write_all(&buffer)?,
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn insert_to_postgres_string(
+    client: &mut Client,
+    _context: &StrategyContext,
+    payload: Payload,
+) -> crate::Result<()> {
+    let Payload::Query(query) = payload else {
+        unreachable!("insert-str is always encoded as a query")
+    };
+    client.execute(&query, &[])?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! The `tables` command: loads into several tables at once, each with a
+//! layout, strategy and workers of its own, the way an application writes
+//! to several tables, and reports the throughput of each and of all.
+
+use crate::cli::TablesArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::schema::{self, SchemaConfig, TimeColumn, MAX_NUMERIC_SCALE};
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, StrategyResult, STRATEGIES};
+use postgres::Config;
+use std::{thread, time::Instant};
+
+/// One `--table`: a name followed by optional `key=value` settings.
+#[derive(Clone, Debug)]
+pub struct TableSpec {
+    pub name: String,
+    pub schema: SchemaConfig,
+    pub strategy: Option<String>,
+    pub workers: Option<usize>,
+}
+
+/// Parses e.g. "events,time-column=bigint,numeric-scale=2,strategy=insert,workers=2".
+pub fn parse_table_spec(text: &str) -> Result<TableSpec, String> {
+    let mut parts = text.split(',').map(str::trim);
+    let name = parts.next().filter(|name| !name.is_empty());
+    let mut spec = TableSpec {
+        name: name.ok_or("a table name comes first")?.to_string(),
+        schema: SchemaConfig::default(),
+        strategy: None,
+        workers: None,
+    };
+    for part in parts {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, found {:?}", part))?;
+        match key {
+            "time-column" => spec.schema.time_column = TimeColumn::from_str(value, true)?,
+            "numeric-scale" => {
+                let scale = value
+                    .parse()
+                    .ok()
+                    .filter(|&scale| scale <= MAX_NUMERIC_SCALE)
+                    .ok_or_else(|| {
+                        format!("numeric-scale must be between 0 and {}", MAX_NUMERIC_SCALE)
+                    })?;
+                spec.schema.numeric_scale = Some(scale);
+            }
+            "strategy" => {
+                if !STRATEGIES.iter().any(|strategy| strategy.name == value) {
+                    return Err(format!("unknown strategy {:?}", value));
+                }
+                spec.strategy = Some(value.to_string());
+            }
+            "workers" => {
+                let workers = value.parse().ok().filter(|&workers| workers >= 1);
+                spec.workers = Some(workers.ok_or("workers must be at least 1")?);
+            }
+            key => return Err(format!("unknown setting {:?}", key)),
+        }
+    }
+    Ok(spec)
+}
+
+/// The columns the generator produces for `schema`, as in CREATE TABLE.
+pub fn column_definitions(schema: &SchemaConfig) -> String {
+    let columns: Vec<_> = schema::columns(schema)
+        .iter()
+        .map(|column| match (column.name, schema.numeric_scale) {
+            // Room for the integer digits of any temperature generated.
+            ("temperature", Some(scale)) => {
+                format!("temperature numeric({}, {}) not null", scale + 10, scale)
+            }
+            (name, _) => format!("{} {} not null", name, column.data_type),
+        })
+        .collect();
+    columns.join(", ")
+}
+
+/// A table with the columns the generator produces for `schema`.
+pub fn create_table(name: &str, schema: &SchemaConfig) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        name,
+        column_definitions(schema)
+    )
+}
+
+fn print_tables(specs: &[TableSpec], results: &[StrategyResult], args: &TablesArgs) {
+    println!();
+    println!(
+        "{:<24} {:<12} {:>7} {:>10} {:>10} {:>14} {:>10}",
+        "table", "strategy", "workers", "rows", "rows/s", "bytes/s", "p99 ms"
+    );
+    for (spec, result) in specs.iter().zip(results) {
+        let workers = spec.workers.unwrap_or(args.workers);
+        if let Some(e) = &result.error {
+            println!(
+                "{:<24} {:<12} {:>7} failed: {}",
+                spec.name, result.strategy, workers, e
+            );
+            continue;
+        }
+        let size = (result.after.total - result.before.total) as f64;
+        println!(
+            "{:<24} {:<12} {:>7} {:>10} {:>10.0} {:>14} {:>10.1}",
+            spec.name,
+            result.strategy,
+            workers,
+            result.rows,
+            result.rows_per_second(),
+            units::rate(size / result.elapsed.as_secs_f64()),
+            result
+                .latency
+                .map_or(0.0, |latency| latency.p99.as_secs_f64() * 1000.0)
+        );
+    }
+}
+
+pub fn tables(
+    config: &Config,
+    generator: &GeneratorConfig,
+    batch_count: usize,
+    args: &TablesArgs,
+) -> anyhow::Result<()> {
+    let mut client = tls::connect(config)?;
+    for spec in &args.tables {
+        client.batch_execute(&create_table(&spec.name, &spec.schema))?;
+    }
+    // Each table gets rows of its own, as from a different source.
+    let runners: Vec<_> = args
+        .tables
+        .iter()
+        .enumerate()
+        .map(|(index, spec)| {
+            let strategy = spec.strategy.as_deref().unwrap_or(&args.strategy);
+            let runner = BenchmarkRunner::new(config.clone())
+                .with_generator(generator.for_node(index as u64))
+                .with_schema(spec.schema)
+                .with_config(RunConfig {
+                    strategies: vec![strategy.to_string()],
+                    table_name: spec.name.clone(),
+                    batch_count,
+                    workers: spec.workers.unwrap_or(args.workers),
+                    ..RunConfig::default()
+                });
+            runner.validate()?;
+            Ok(runner)
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let started = Instant::now();
+    let results = thread::scope(|scope| {
+        let handles: Vec<_> = runners
+            .into_iter()
+            .map(|runner| scope.spawn(|| runner.run()))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| Ok(handle.join().unwrap()?.remove(0)))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+    let elapsed = started.elapsed();
+
+    print_tables(&args.tables, &results, args);
+    let rows: usize = results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .map(|result| result.rows)
+        .sum();
+    println!();
+    println!(
+        "All tables: {} rows in {:.2}s, {:.0} rows/s",
+        rows,
+        elapsed.as_secs_f64(),
+        rows as f64 / elapsed.as_secs_f64()
+    );
+    let failed: Vec<_> = args
+        .tables
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.error.is_some())
+        .map(|(spec, _)| spec.name.as_str())
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!("tables failed: {}", failed.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! The `tenants` command: benchmarks schema-per-tenant and table-per-tenant
+//! designs by creating a copy of the table for each tenant and loading into
+//! all of them at once, with batches spread over the tenants along a Zipf
+//! distribution, as tenants are rarely equally busy.
+
+use crate::cli::TenantsArgs;
+use crate::units;
+use clap::ValueEnum;
+use fast_generation::generator::{child_rng, generate_data, GeneratorConfig, Row, BASE_TEMP};
+use fast_generation::pool::BufferPool;
+use fast_generation::schema::SchemaConfig;
+use fast_generation::strategy::{StrategyContext, STRATEGIES};
+use fast_generation::tls;
+use postgres::{Client, Config};
+use rand::distributions::{Distribution, WeightedIndex};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Tenants listed one by one; the rest are summed up.
+const LISTED_TENANTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// A schema per tenant, e.g. tenant_3.metrics
+    #[default]
+    Schema,
+    /// A table per tenant, e.g. metrics_tenant_3
+    Table,
+}
+
+impl Layout {
+    fn table(self, table_name: &str, tenant: u32) -> String {
+        match self {
+            Layout::Schema => format!("tenant_{}.{}", tenant, table_name),
+            Layout::Table => format!("{}_tenant_{}", table_name, tenant),
+        }
+    }
+
+    /// Replaces any previous tenant of the same number.
+    fn create(self, table_name: &str, tenant: u32) -> String {
+        let table = self.table(table_name, tenant);
+        match self {
+            Layout::Schema => format!(
+                "DROP SCHEMA IF EXISTS tenant_{tenant} CASCADE;
+                 CREATE SCHEMA tenant_{tenant};
+                 CREATE TABLE {table} (LIKE {table_name} INCLUDING ALL);"
+            ),
+            Layout::Table => format!(
+                "DROP TABLE IF EXISTS {table};
+                 CREATE TABLE {table} (LIKE {table_name} INCLUDING ALL);"
+            ),
+        }
+    }
+
+    fn drop(self, table_name: &str, tenant: u32) -> String {
+        match self {
+            Layout::Schema => format!("DROP SCHEMA tenant_{} CASCADE;", tenant),
+            Layout::Table => format!("DROP TABLE {};", self.table(table_name, tenant)),
+        }
+    }
+}
+
+/// Loads `batch_count` batches, each into a tenant drawn by `skew`, and
+/// returns how long that took and the rows each tenant got.
+fn load(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    tables: &[String],
+    batch_count: usize,
+    args: &TenantsArgs,
+) -> anyhow::Result<(Duration, Vec<usize>)> {
+    let strategy = STRATEGIES
+        .iter()
+        .find(|strategy| strategy.name == args.strategy)
+        .unwrap();
+    let buffers = BufferPool::new();
+    let contexts: Vec<_> = tables
+        .iter()
+        .map(|table_name| StrategyContext {
+            table_name,
+            schema,
+            buffers: &buffers,
+            copy_chunk_size: None,
+        })
+        .collect();
+    let tenants =
+        WeightedIndex::new((1..=tables.len()).map(|rank| 1.0 / (rank as f64).powf(args.skew)))?;
+    let mut rng = child_rng(generator.seed, "tenant", 0);
+    let rows: Vec<_> = tables.iter().map(|_| AtomicUsize::new(0)).collect();
+
+    let (sender, receiver) = mpsc::sync_channel::<(usize, Vec<Row>)>(args.workers.max(1));
+    let receiver = Arc::new(Mutex::new(receiver));
+    let started = Instant::now();
+    thread::scope(|scope| {
+        let (contexts, rows) = (&contexts, &rows);
+        let handles: Vec<_> = (0..args.workers.max(1))
+            .map(|_| {
+                let receiver = receiver.clone();
+                scope.spawn(move || -> anyhow::Result<()> {
+                    let mut client = tls::connect(config)?;
+                    loop {
+                        let Ok((tenant, batch_data)) = receiver.lock().unwrap().recv() else {
+                            return Ok(());
+                        };
+                        let payload = (strategy.encode)(&contexts[tenant], &batch_data)?;
+                        (strategy.send)(&mut client, &contexts[tenant], payload)?;
+                        rows[tenant].fetch_add(batch_data.len(), Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        // The workers alone hold the receiver, so sending fails once all of
+        // them have stopped.
+        drop(receiver);
+
+        for (batch_data, _) in generate_data(generator, BASE_TEMP, batch_count) {
+            if sender.send((tenants.sample(&mut rng), batch_data)).is_err() {
+                break;
+            }
+        }
+        drop(sender);
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().unwrap())
+    })?;
+
+    Ok((
+        started.elapsed(),
+        rows.into_iter().map(AtomicUsize::into_inner).collect(),
+    ))
+}
+
+fn print_tenants(
+    client: &mut Client,
+    tables: &[String],
+    elapsed: Duration,
+    rows: &[usize],
+    args: &TenantsArgs,
+) -> anyhow::Result<()> {
+    let total: usize = rows.iter().sum();
+    println!(
+        "Loaded {} rows into {} tenants in {:.2}s: {:.0} rows/s with {} on {} worker(s)",
+        total,
+        tables.len(),
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64(),
+        args.strategy,
+        args.workers.max(1)
+    );
+    println!();
+    println!(
+        "{:<24} {:>10} {:>8} {:>12}",
+        "tenant", "rows", "share", "size"
+    );
+    for (table, &tenant_rows) in tables.iter().zip(rows).take(LISTED_TENANTS) {
+        let size: i64 = client
+            .query_one(
+                "SELECT pg_total_relation_size($1::text::regclass)",
+                &[table],
+            )?
+            .get(0);
+        println!(
+            "{:<24} {:>10} {:>7.1}% {:>12}",
+            table,
+            tenant_rows,
+            tenant_rows as f64 / total.max(1) as f64 * 100.0,
+            units::bytes(size as f64)
+        );
+    }
+    if tables.len() > LISTED_TENANTS {
+        let rest: usize = rows[LISTED_TENANTS..].iter().sum();
+        println!(
+            "{:<24} {:>10} {:>7.1}%",
+            format!("{} more", tables.len() - LISTED_TENANTS),
+            rest,
+            rest as f64 / total.max(1) as f64 * 100.0
+        );
+    }
+    Ok(())
+}
+
+pub fn tenants(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    table_name: &str,
+    batch_count: usize,
+    args: &TenantsArgs,
+) -> anyhow::Result<()> {
+    if !(args.skew >= 0.0 && args.skew.is_finite()) {
+        anyhow::bail!("--skew must be a finite number of at least 0");
+    }
+    generator.validate(batch_count)?;
+    let mut client = tls::connect(config)?;
+    let numbers = 1..=args.tenants;
+    let tables: Vec<_> = numbers
+        .clone()
+        .map(|tenant| args.layout.table(table_name, tenant))
+        .collect();
+    let create: String = numbers
+        .clone()
+        .map(|tenant| args.layout.create(table_name, tenant))
+        .collect();
+    client.batch_execute(&create)?;
+
+    let outcome = load(config, generator, schema, &tables, batch_count, args)
+        .and_then(|(elapsed, rows)| print_tenants(&mut client, &tables, elapsed, &rows, args));
+    if !args.keep {
+        let drop: String = numbers
+            .map(|tenant| args.layout.drop(table_name, tenant))
+            .collect();
+        client.batch_execute(&drop)?;
+    }
+    outcome
+}
@@ -0,0 +1,224 @@
+//! The `time-zones` command: loads a few batches with each strategy under
+//! each client `TimeZone`, reads them back as the server prints them in
+//! that zone and compares every timestamp with the UTC one generated, to
+//! show the timestamptz encoders aren't off by an offset.
+
+use crate::cli::TimeZonesArgs;
+use crate::tables;
+use fast_generation::generator::{generate_data, Created, Timestamp, BASE_TEMP};
+use fast_generation::schema::TimeColumn;
+use fast_generation::tls;
+use fast_generation::{BenchmarkRunner, GeneratorConfig, RunConfig, SchemaConfig, STRATEGIES};
+use postgres::{Client, Config};
+use std::collections::HashMap;
+
+struct Checked {
+    zone: String,
+    strategy: String,
+    /// The first timestamp not read back as generated, described, or why
+    /// the load failed.
+    outcome: Result<Option<String>, String>,
+}
+
+fn set_time_zone(zone: &str) -> String {
+    format!("SET TimeZone = '{}'", zone.replace('\'', "''"))
+}
+
+/// A signed difference of microseconds as hours, minutes and seconds.
+fn offset(micros: i64) -> String {
+    let sign = if micros < 0 { '-' } else { '+' };
+    let micros = micros.unsigned_abs();
+    let seconds = micros / 1_000_000;
+    format!(
+        "{}{:02}:{:02}:{:02}.{:06}",
+        sign,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        micros % 1_000_000
+    )
+}
+
+/// Orders timestamps the way the server does, infinities at either end.
+fn order(created: &Created) -> (u8, i64) {
+    match created {
+        Timestamp::NegInfinity => (0, 0),
+        Timestamp::Value(datetime) => (1, datetime.timestamp_micros()),
+        Timestamp::PosInfinity => (2, 0),
+    }
+}
+
+/// Every distinct timestamp, in order, with its rows.
+fn sorted(counts: HashMap<Created, i64>) -> Vec<(Created, i64)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by_key(|(created, _)| order(created));
+    counts
+}
+
+/// Reads the scratch table back in `zone` and pairs its timestamps, in
+/// order, with those generated: an offset shifts every one alike, so the
+/// first pair differing shows by how much.
+fn compare(
+    client: &mut Client,
+    scratch: &str,
+    time_column: TimeColumn,
+    zone: &str,
+    generated: &[(Created, i64)],
+) -> anyhow::Result<Option<String>> {
+    client.batch_execute(&set_time_zone(zone))?;
+    let mut read = HashMap::new();
+    for row in client.query(
+        &format!("SELECT created::text, count(*) FROM {} GROUP BY 1", scratch),
+        &[],
+    )? {
+        let text: &str = row.get(0);
+        let Some(created) = time_column.parse(text) else {
+            return Ok(Some(format!(
+                "read back {:?}, not a {}",
+                text,
+                time_column.data_type()
+            )));
+        };
+        // Kept as printed, to show it in the zone it was read in.
+        read.insert(created, (text.to_string(), row.get::<_, i64>(1)));
+    }
+    let mut read: Vec<_> = read.into_iter().collect();
+    read.sort_by_key(|(created, _)| order(created));
+
+    for (&(expected, rows), (created, (text, count))) in generated.iter().zip(&read) {
+        if expected == *created && rows == *count {
+            continue;
+        }
+        let offset = match (expected, created) {
+            (Timestamp::Value(expected), Timestamp::Value(created)) => {
+                format!(
+                    ", off by {}",
+                    offset(created.timestamp_micros() - expected.timestamp_micros())
+                )
+            }
+            _ => String::new(),
+        };
+        return Ok(Some(format!(
+            "generated {} ({} rows), read back {} ({} rows){}",
+            time_column.text(expected),
+            rows,
+            text,
+            count,
+            offset
+        )));
+    }
+    if generated.len() != read.len() {
+        return Ok(Some(format!(
+            "generated {} distinct timestamps, read back {}",
+            generated.len(),
+            read.len()
+        )));
+    }
+    Ok(None)
+}
+
+fn print_checks(checks: &[Checked]) {
+    println!();
+    println!("{:<24} {:<12} result", "time zone", "strategy");
+    for check in checks {
+        let result = match &check.outcome {
+            Ok(None) => "ok".to_string(),
+            Ok(Some(mismatch)) => format!("MISMATCH: {}", mismatch),
+            Err(e) => format!("failed: {}", e),
+        };
+        println!("{:<24} {:<12} {}", check.zone, check.strategy, result);
+    }
+}
+
+pub fn time_zones(
+    config: &Config,
+    generator: &GeneratorConfig,
+    schema: &SchemaConfig,
+    args: &TimeZonesArgs,
+) -> anyhow::Result<()> {
+    if schema.time_column == TimeColumn::Bigint {
+        anyhow::bail!("created is a bigint of microseconds, which no time zone applies to");
+    }
+    generator.validate(args.batches)?;
+    // Fixed, so that every load generates the timestamps compared against.
+    let generator = &GeneratorConfig {
+        start: Some(generator.start_time()),
+        ..*generator
+    };
+    let scratch = &args.scratch_table;
+    let strategies: Vec<String> = if args.strategies.is_empty() {
+        STRATEGIES
+            .iter()
+            .map(|strategy| strategy.name.to_string())
+            .collect()
+    } else {
+        args.strategies.clone()
+    };
+    let mut generated = HashMap::new();
+    for (batch_data, _) in generate_data(generator, BASE_TEMP, args.batches) {
+        for row in batch_data {
+            *generated.entry(row.0).or_insert(0) += 1;
+        }
+    }
+    let generated = sorted(generated);
+
+    let mut client = tls::connect(config)?;
+    let mut checks = Vec::new();
+    let outcome = || -> anyhow::Result<()> {
+        client.batch_execute(&format!(
+            "DROP TABLE IF EXISTS {scratch}; {}",
+            tables::create_table(scratch, schema)
+        ))?;
+        for zone in &args.time_zones {
+            for strategy in &strategies {
+                client.batch_execute(&format!("TRUNCATE {}", scratch))?;
+                println!("Loading with {} under TimeZone {}", strategy, zone);
+                let result = BenchmarkRunner::new(config.clone())
+                    .with_generator(*generator)
+                    .with_schema(*schema)
+                    .with_config(RunConfig {
+                        strategies: vec![strategy.clone()],
+                        table_name: scratch.clone(),
+                        batch_count: args.batches,
+                        session_sql: vec![set_time_zone(zone)],
+                        ..RunConfig::default()
+                    })
+                    .run()
+                    .map_err(|e| anyhow::anyhow!("TimeZone {}: {}", zone, e))?
+                    .remove(0);
+                let outcome = match result.error {
+                    Some(e) => Err(e),
+                    None => Ok(compare(
+                        &mut client,
+                        scratch,
+                        schema.time_column,
+                        zone,
+                        &generated,
+                    )?),
+                };
+                checks.push(Checked {
+                    zone: zone.clone(),
+                    strategy: strategy.clone(),
+                    outcome,
+                });
+            }
+        }
+        Ok(())
+    }();
+    client.batch_execute(&format!("DROP TABLE IF EXISTS {}", scratch))?;
+    outcome?;
+
+    print_checks(&checks);
+    let mismatched = checks
+        .iter()
+        .filter(|check| !matches!(check.outcome, Ok(None)))
+        .count();
+    if mismatched > 0 {
+        anyhow::bail!(
+            "{} of {} loads did not read back as generated",
+            mismatched,
+            checks.len()
+        );
+    }
+    Ok(())
+}
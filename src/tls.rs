@@ -0,0 +1,89 @@
+//! Opening connections. Every connection the library and the binary open
+//! goes through `connect`, so that with the `tls` feature the TLS settings
+//! given once at startup apply to all of them; without it, connections are
+//! in plain text.
+//!
+//! `sslmode` and `channel_binding` in the connection string decide whether
+//! TLS and SCRAM channel binding are tried, preferred or required, as with
+//! libpq.
+
+use postgres::{Client, Config};
+
+#[cfg(feature = "tls")]
+pub use self::native::{configure, TlsConfig};
+
+/// Connects with `config`, over TLS if the server and `sslmode` agree to it
+/// and the `tls` feature is on.
+pub fn connect(config: &Config) -> Result<Client, postgres::Error> {
+    #[cfg(feature = "tls")]
+    return config.connect(native::connector().clone());
+    #[cfg(not(feature = "tls"))]
+    config.connect(postgres::NoTls)
+}
+
+#[cfg(feature = "tls")]
+mod native {
+    use crate::FastGenError;
+    use native_tls::{Certificate, Identity, TlsConnector};
+    use postgres_native_tls::MakeTlsConnector;
+    use std::{fs, path::Path, path::PathBuf, sync::OnceLock};
+
+    static CONNECTOR: OnceLock<MakeTlsConnector> = OnceLock::new();
+
+    /// Certificates to connect with; files are PEM.
+    #[derive(Clone, Debug, Default)]
+    pub struct TlsConfig {
+        /// CA certificate the server's certificate and host name are
+        /// verified against. Without one, any certificate is accepted, as
+        /// libpq does below sslmode=verify-ca.
+        pub root_cert: Option<PathBuf>,
+        /// Client certificate, for servers authenticating with `cert`.
+        pub cert: Option<PathBuf>,
+        /// PKCS#8 private key of the client certificate.
+        pub key: Option<PathBuf>,
+    }
+
+    fn read(path: &Path) -> crate::Result<Vec<u8>> {
+        fs::read(path)
+            .map_err(|e| FastGenError::InvalidConfig(format!("reading {}: {}", path.display(), e)))
+    }
+
+    fn build(tls: &TlsConfig) -> crate::Result<MakeTlsConnector> {
+        let invalid = |e: native_tls::Error| FastGenError::InvalidConfig(e.to_string());
+        let mut builder = TlsConnector::builder();
+        match &tls.root_cert {
+            Some(path) => {
+                builder
+                    .disable_built_in_roots(true)
+                    .add_root_certificate(Certificate::from_pem(&read(path)?).map_err(invalid)?);
+            }
+            None => {
+                builder.danger_accept_invalid_certs(true);
+            }
+        }
+        match (&tls.cert, &tls.key) {
+            (Some(cert), Some(key)) => {
+                builder.identity(Identity::from_pkcs8(&read(cert)?, &read(key)?).map_err(invalid)?);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(FastGenError::InvalidConfig(
+                    "a client certificate needs its key, and a key its certificate".to_string(),
+                ))
+            }
+        }
+        Ok(MakeTlsConnector::new(builder.build().map_err(invalid)?))
+    }
+
+    /// Sets the certificates every connection uses from then on; only the
+    /// first call has an effect.
+    pub fn configure(tls: &TlsConfig) -> crate::Result<()> {
+        let connector = build(tls)?;
+        let _ = CONNECTOR.set(connector);
+        Ok(())
+    }
+
+    pub(super) fn connector() -> &'static MakeTlsConnector {
+        CONNECTOR.get_or_init(|| build(&TlsConfig::default()).expect("default TLS connector"))
+    }
+}
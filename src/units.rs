@@ -0,0 +1,60 @@
+//! How reports print byte sizes and rates: scaled to each value in powers
+//! of 1024 or of 1000, with the unit that goes with it, and always with a
+//! `.` decimal point and no digit grouping, so output parses the same
+//! wherever it was produced.
+
+use clap::ValueEnum;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Units {
+    /// Powers of 1024: KiB, MiB, GiB and on
+    #[default]
+    Binary,
+    /// Powers of 1000: kB, MB, GB and on
+    Si,
+}
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Units::Binary => 1024.0,
+            Units::Si => 1000.0,
+        }
+    }
+
+    fn names(self) -> [&'static str; 6] {
+        match self {
+            Units::Binary => ["B", "KiB", "MiB", "GiB", "TiB", "PiB"],
+            Units::Si => ["B", "kB", "MB", "GB", "TB", "PB"],
+        }
+    }
+}
+
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+/// Sets the units every report uses; only the first call has an effect.
+pub fn configure(units: Units) {
+    let _ = UNITS.set(units);
+}
+
+/// `bytes` in the largest unit it holds at least one of, e.g. "1.50 MiB".
+pub fn bytes(bytes: f64) -> String {
+    let units = UNITS.get().copied().unwrap_or_default();
+    let names = units.names();
+    let mut scaled = bytes;
+    let mut index = 0;
+    while scaled.abs() >= units.base() && index < names.len() - 1 {
+        scaled /= units.base();
+        index += 1;
+    }
+    match index {
+        0 => format!("{:.0} {}", scaled, names[0]),
+        _ => format!("{:.2} {}", scaled, names[index]),
+    }
+}
+
+/// A rate of `bytes_per_second`, e.g. "1.50 MiB/s".
+pub fn rate(bytes_per_second: f64) -> String {
+    format!("{}/s", bytes(bytes_per_second))
+}
@@ -0,0 +1,186 @@
+//! `--upload`: copies the report of a run, or the files an export wrote, to
+//! S3 or GCS once the command is done, for bench hosts whose disks don't
+//! outlive them. Credentials come from the usual `AWS_*` and `GOOGLE_*`
+//! environment variables.
+
+use chrono::Utc;
+use object_store::{parse_url_opts, path::Path as ObjectPath, ObjectStore, WriteMultipart};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+use tokio::runtime::{Handle, Runtime};
+use url::Url;
+
+/// Parts in flight at once while streaming a file.
+const MAX_CONCURRENCY: usize = 4;
+/// Bytes read from a file per write into the upload.
+const READ_SIZE: usize = 1 << 20;
+
+/// Uploads into a directory of its own under a URI, named after the time
+/// it was created so that runs sharing a prefix don't overwrite each other.
+pub struct Uploader {
+    runtime: Runtime,
+    store: Box<dyn ObjectStore>,
+    url: Url,
+    prefix: ObjectPath,
+}
+
+impl Uploader {
+    /// Fails on a URI that isn't `s3://` or `gs://`, or on missing settings
+    /// such as a region, before anything runs.
+    pub fn new(uri: &str) -> anyhow::Result<Uploader> {
+        let url = Url::parse(uri).map_err(|e| anyhow::anyhow!("invalid URI {:?}: {}", uri, e))?;
+        if !matches!(url.scheme(), "s3" | "s3a" | "gs") {
+            anyhow::bail!("unsupported upload URI {:?}, expected s3:// or gs://", uri);
+        }
+        let options = std::env::vars()
+            .filter(|(key, _)| key.starts_with("AWS_") || key.starts_with("GOOGLE_"))
+            .map(|(key, value)| (key.to_ascii_lowercase(), value));
+        let (store, prefix) = parse_url_opts(&url, options)?;
+        // Threads of its own, so that parts upload while `ObjectWriter`s are
+        // being written to.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()?;
+        Ok(Uploader {
+            runtime,
+            store,
+            url,
+            prefix: prefix.child(Utc::now().format("%Y%m%dT%H%M%SZ").to_string()),
+        })
+    }
+
+    /// The URI of the directory uploads go into.
+    pub fn directory(&self) -> String {
+        self.location(&self.prefix)
+    }
+
+    fn location(&self, path: &ObjectPath) -> String {
+        format!(
+            "{}://{}/{}",
+            self.url.scheme(),
+            self.url.host_str().unwrap_or_default(),
+            path
+        )
+    }
+
+    /// Uploads `data` as `name`.
+    pub fn put(&self, name: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        let path = self.prefix.child(name);
+        self.runtime
+            .block_on(self.store.put(&path, data.into()))
+            .map_err(|e| anyhow::anyhow!("uploading {}: {}", self.location(&path), e))?;
+        println!("Uploaded {}", self.location(&path));
+        Ok(())
+    }
+
+    /// A writer streaming what's written to it up as `name`, in parts, with
+    /// nothing staged on disk.
+    pub fn writer(&self, name: &str) -> anyhow::Result<ObjectWriter> {
+        let path = self.prefix.child(name);
+        let location = self.location(&path);
+        let upload = self
+            .runtime
+            .block_on(self.store.put_multipart(&path))
+            .map_err(|e| anyhow::anyhow!("uploading {}: {}", location, e))?;
+        Ok(ObjectWriter {
+            runtime: self.runtime.handle().clone(),
+            location,
+            upload: Some(WriteMultipart::new(upload)),
+            bytes: 0,
+        })
+    }
+
+    /// Streams the file at `file` up as `name`, a path relative to the
+    /// prefix with forward slashes between directories, in parts.
+    pub fn put_file(&self, file: &Path, name: &str) -> anyhow::Result<()> {
+        let path = name
+            .split('/')
+            .fold(self.prefix.clone(), |path, part| path.child(part));
+        let mut reader = File::open(file)?;
+        self.runtime
+            .block_on(async {
+                let mut upload = WriteMultipart::new(self.store.put_multipart(&path).await?);
+                let mut buffer = vec![0; READ_SIZE];
+                let written = async {
+                    loop {
+                        let read = reader.read(&mut buffer)?;
+                        if read == 0 {
+                            return anyhow::Ok(());
+                        }
+                        upload.wait_for_capacity(MAX_CONCURRENCY).await?;
+                        upload.write(&buffer[..read]);
+                    }
+                }
+                .await;
+                // Parts of an upload that is never completed are kept, and
+                // billed, until aborted.
+                if let Err(e) = written {
+                    upload.abort().await.ok();
+                    return Err(e);
+                }
+                upload.finish().await?;
+                anyhow::Ok(())
+            })
+            .map_err(|e| anyhow::anyhow!("uploading {}: {}", self.location(&path), e))?;
+        println!("Uploaded {}", self.location(&path));
+        Ok(())
+    }
+}
+
+/// An object being uploaded; the upload is aborted unless `finish`ed.
+pub struct ObjectWriter {
+    runtime: Handle,
+    location: String,
+    upload: Option<WriteMultipart>,
+    bytes: u64,
+}
+
+impl ObjectWriter {
+    /// Bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Waits for the parts still uploading and completes the upload.
+    pub fn finish(mut self) -> anyhow::Result<u64> {
+        let upload = self.upload.take().expect("finished once");
+        self.runtime
+            .block_on(upload.finish())
+            .map_err(|e| anyhow::anyhow!("uploading {}: {}", self.location, e))?;
+        println!("Uploaded {}", self.location);
+        Ok(self.bytes)
+    }
+}
+
+impl Write for ObjectWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let upload = self.upload.as_mut().expect("not finished");
+        let runtime = &self.runtime;
+        runtime
+            .block_on(upload.wait_for_capacity(MAX_CONCURRENCY))
+            .map_err(io::Error::other)?;
+        // Full parts are handed to the runtime's threads.
+        let _context = runtime.enter();
+        upload.write(buf);
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ObjectWriter {
+    fn drop(&mut self) {
+        // Parts of an upload that is never completed are kept, and billed,
+        // until aborted.
+        if let Some(upload) = self.upload.take() {
+            self.runtime.block_on(upload.abort()).ok();
+        }
+    }
+}
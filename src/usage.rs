@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// CPU time and resident memory of this process over one strategy, to tell
+/// client-bound strategies from server-bound ones.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ClientUsage {
+    /// User plus system time of all threads.
+    pub cpu: Duration,
+    /// CPU time per wall-clock second, 1.0 being one core fully busy.
+    pub average_cpu: f64,
+    pub peak_cpu: f64,
+    pub average_rss: u64,
+    pub peak_rss: u64,
+}
+
+/// Samples this process's usage on a background thread until `finish`.
+pub struct UsageSampler {
+    stop: Sender<()>,
+    handle: JoinHandle<ClientUsage>,
+}
+
+impl UsageSampler {
+    pub fn start() -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+            let cpu_started = cpu_time();
+            let (mut last, mut cpu_last) = (started, cpu_started);
+            let mut usage = ClientUsage::default();
+            let mut rss_total = 0;
+            let mut samples = 0;
+
+            loop {
+                let now = Instant::now();
+                let cpu = cpu_time();
+                let wall = (now - last).as_secs_f64();
+                if wall > 0.0 {
+                    usage.peak_cpu = usage.peak_cpu.max((cpu - cpu_last).as_secs_f64() / wall);
+                }
+                (last, cpu_last) = (now, cpu);
+
+                let rss = resident_memory();
+                usage.peak_rss = usage.peak_rss.max(rss);
+                rss_total += rss;
+                samples += 1;
+
+                if let Ok(()) | Err(RecvTimeoutError::Disconnected) =
+                    stopped.recv_timeout(SAMPLE_INTERVAL)
+                {
+                    break;
+                }
+            }
+
+            usage.cpu = cpu_time() - cpu_started;
+            usage.average_cpu = usage.cpu.as_secs_f64() / started.elapsed().as_secs_f64();
+            usage.average_rss = rss_total / samples;
+            usage
+        });
+
+        UsageSampler { stop, handle }
+    }
+
+    pub fn finish(self) -> ClientUsage {
+        let _ = self.stop.send(());
+        self.handle.join().unwrap()
+    }
+}
+
+fn cpu_time() -> Duration {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: getrusage only writes into the struct it is given.
+    let usage = unsafe {
+        libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr());
+        usage.assume_init()
+    };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1_000);
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+/// Current resident set size in bytes; zero where /proc is unavailable.
+fn resident_memory() -> u64 {
+    let pages = std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .unwrap_or(0);
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    pages * page_size.max(0) as u64
+}
@@ -0,0 +1,51 @@
+//! JavaScript bindings for live previews of the generated data, e.g.
+//! `wasm-pack build --no-default-features --features wasm`.
+
+use crate::generator::{generate_data, GeneratorConfig, Row, BASE_TEMP, BATCH_SIZE};
+use crate::schema::SchemaConfig;
+use crate::serialize;
+use chrono::{DateTime, Utc};
+use wasm_bindgen::prelude::*;
+
+/// The first `rows` rows a run with `seed` would load, starting at `start`
+/// (RFC 3339), or eight days from now without one.
+fn sample(rows: usize, seed: u64, start: Option<String>) -> Result<Vec<Row>, JsError> {
+    let config = GeneratorConfig {
+        seed,
+        start: start
+            .map(|start| start.parse::<DateTime<Utc>>())
+            .transpose()?,
+        ..GeneratorConfig::default()
+    };
+    let batch_count = rows.div_ceil(BATCH_SIZE);
+    config.validate(batch_count)?;
+    Ok(generate_data(&config, BASE_TEMP, batch_count)
+        .flat_map(|(batch, _)| batch)
+        .take(rows)
+        .collect())
+}
+
+/// CSV with a header line, as `COPY ... WITH (FORMAT csv, HEADER)` loads it.
+#[wasm_bindgen(js_name = previewCsv)]
+pub fn preview_csv(rows: usize, seed: u64, start: Option<String>) -> Result<String, JsError> {
+    let mut csv = Vec::new();
+    serialize::write_csv(
+        &mut csv,
+        &SchemaConfig::default(),
+        &sample(rows, seed, start)?,
+        true,
+    )?;
+    Ok(String::from_utf8(csv)?)
+}
+
+/// JSON Lines, one object per row.
+#[wasm_bindgen(js_name = previewJson)]
+pub fn preview_json(rows: usize, seed: u64, start: Option<String>) -> Result<String, JsError> {
+    let mut json = Vec::new();
+    serialize::write_json_lines(
+        &mut json,
+        &SchemaConfig::default(),
+        &sample(rows, seed, start)?,
+    )?;
+    Ok(String::from_utf8(json)?)
+}